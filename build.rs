@@ -21,10 +21,25 @@ fn main() {
     let mut version_str = String::from_utf8(version.stdout).unwrap();
     version_str.pop();
 
+    let mut cmd = Command::new("git");
+    cmd.arg("rev-parse").arg("--short").arg("HEAD");
+
+    let hash = cmd.output().unwrap();
+
+    if !hash.status.success() {
+        panic!("Failed to get commit hash from git");
+    }
+
+    let mut hash_str = String::from_utf8(hash.stdout).unwrap();
+    hash_str.pop();
+
     f.write_all(
-        format!("const VERSION: &str = \"{}\";", version_str)
-            .into_bytes()
-            .as_slice(),
+        format!(
+            "const VERSION: &str = \"{}\";\nconst GIT_HASH: &str = \"{}\";",
+            version_str, hash_str
+        )
+        .into_bytes()
+        .as_slice(),
     )
     .unwrap();
 }
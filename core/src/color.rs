@@ -1,11 +1,10 @@
 use std::collections::HashMap;
 
-use gtk::glib;
-
 #[derive(Hash, PartialEq, Eq)]
 pub enum HlGroup {
     Pmenu,
     PmenuSel,
+    PmenuMatch,
 
     Tabline,
     TablineSel,
@@ -18,6 +17,12 @@ pub enum HlGroup {
     WildmenuSel,
 
     MsgSeparator,
+
+    ModeMsg,
+    MoreMsg,
+    WarningMsg,
+    ErrorMsg,
+    Visual,
 }
 
 #[derive(Default)]
@@ -29,6 +34,10 @@ pub struct HlDefs {
     pub default_fg: Color,
     pub default_bg: Color,
     pub default_sp: Color,
+
+    /// Font family for highlights with `altfont`. If `None`, they're drawn
+    /// with the grid's font.
+    pub altfont: Option<String>,
 }
 
 impl HlDefs {
@@ -44,6 +53,15 @@ impl HlDefs {
         self.hl_defs.insert(id, hl)
     }
 
+    /// Number of highlight definitions.
+    pub fn len(&self) -> usize {
+        self.hl_defs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hl_defs.is_empty()
+    }
+
     pub fn set_hl_group(&mut self, group: HlGroup, id: u64) -> Option<u64> {
         self.hl_groups.insert(group, id)
     }
@@ -68,6 +86,11 @@ pub struct Highlight {
     pub bold: bool,
     pub underline: bool,
     pub undercurl: bool,
+    /// Draw with the alternative font (`HlDefs::altfont`).
+    pub altfont: bool,
+    /// Don't combine with the highlight underneath, e.g. the cell's
+    /// highlight under the cursor.
+    pub nocombine: bool,
 
     /// The blend value in range of 0..1.
     pub blend: f64,
@@ -110,7 +133,7 @@ impl Highlight {
             weight = weight,
             fontstyle = fontstyle,
             underline = underline,
-            text = glib::markup_escape_text(text)
+            text = markup_escape(text)
         )
     }
 
@@ -121,6 +144,23 @@ impl Highlight {
     }
 }
 
+/// Escapes `text` for pango markup.
+pub fn markup_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\'' => escaped.push_str("&apos;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Color {
     pub r: f64,
@@ -195,4 +235,13 @@ mod tests {
 
         assert_eq!(c.as_rgba(0.4), "rgba(255, 0, 255, 0.6)");
     }
+
+    #[test]
+    fn test_markup_escape() {
+        assert_eq!(
+            markup_escape("a < b && 'c' > \"d\""),
+            "a &lt; b &amp;&amp; &apos;c&apos; &gt; &quot;d&quot;"
+        );
+        assert_eq!(markup_escape("äö"), "äö");
+    }
 }
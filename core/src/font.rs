@@ -0,0 +1,180 @@
+use std::fmt;
+use std::fmt::Display;
+
+/// Font height, when `guifont` doesn't have one.
+pub const DEFAULT_HEIGHT: f32 = 14.0;
+
+pub enum FontUnit {
+    #[allow(dead_code)]
+    Pixel,
+    Point,
+}
+
+impl Display for FontUnit {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontUnit::Pixel => write!(fmt, "px"),
+            FontUnit::Point => write!(fmt, "pt"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Font {
+    name: String,
+    pub height: f32,
+}
+
+impl Font {
+    /// Parses nvim `guifont` option.
+    ///
+    /// If invalid height is specified, defaults to `DEFAULT_HEIGHT`.
+    #[allow(clippy::result_unit_err)]
+    pub fn from_guifont(guifont: &str) -> Result<Self, ()> {
+        let mut parts = guifont.split(':');
+
+        let name = parts.next().ok_or(())?;
+
+        if name.is_empty() {
+            return Err(());
+        }
+
+        let mut font = Font {
+            name: name.to_string(),
+            height: DEFAULT_HEIGHT,
+        };
+
+        for part in parts {
+            let mut chars = part.chars();
+            if let Some(ch) = chars.next() {
+                match ch {
+                    'h' => {
+                        let rest = chars.collect::<String>();
+                        let h = rest.parse::<f32>().or(Err(()))?;
+                        if h <= 0.0 {
+                            // Ignore zero sized font.
+                            continue;
+                        }
+                        font.height = h;
+                    }
+                    _ => {
+                        println!("Not supported guifont option: {}", part);
+                    }
+                }
+            }
+        }
+
+        Ok(font)
+    }
+
+    /// Font family name.
+    pub fn family(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns self in nvim's `guifont` format.
+    pub fn to_guifont(&self) -> String {
+        format!("{}:h{}", self.name, self.height)
+    }
+
+    /// Returns a copy of self with height changed by `delta`. The height
+    /// is kept at one or above.
+    pub fn resized(&self, delta: f32) -> Self {
+        Font {
+            name: self.name.clone(),
+            height: (self.height + delta).max(1.0),
+        }
+    }
+
+    /// Returns a CSS representation of self for a wild (`*`) CSS selector.
+    /// On gtk version below 3.20 unit needs to be `FontUnit::Pixel` and
+    /// with version 3.20 and up, unit needs to be `FontUnit::Point`. This is
+    /// to work around some gtk issues on versions before 3.20.
+    pub fn as_wild_css(&self, unit: FontUnit) -> String {
+        format!(
+            "* {{ \
+             font-family: \"{font_family}\"; \
+             font-size: {font_size}{font_unit}; \
+             }}",
+            font_family = self.name,
+            font_size = self.height,
+            font_unit = unit,
+        )
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font {
+            name: String::from("Monospace"),
+            height: DEFAULT_HEIGHT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_wild_css() {
+        let font = Font {
+            name: "foo".to_string(),
+            height: 10.0,
+        };
+
+        assert_eq!(
+            font.as_wild_css(FontUnit::Point),
+            "* { \
+             font-family: \"foo\"; \
+             font-size: 10pt; \
+             }"
+        );
+
+        assert_eq!(
+            font.as_wild_css(FontUnit::Pixel),
+            "* { \
+             font-family: \"foo\"; \
+             font-size: 10px; \
+             }"
+        );
+    }
+
+    #[test]
+    fn test_from_guifont() {
+        // Font with proper height.
+        let f = Font::from_guifont("monospace:h11").unwrap();
+        assert_eq!(f.name, "monospace");
+        assert_eq!(f.height, 11.0);
+
+        // Font with invalid height.
+        let f = Font::from_guifont("font:h");
+        assert!(f.is_err());
+        let f = Font::from_guifont("font:hn");
+        assert!(f.is_err());
+
+        // Font with height zero.
+        let f = Font::from_guifont("foo:h0").unwrap();
+        assert_eq!(f.name, "foo");
+        assert_eq!(f.height, DEFAULT_HEIGHT);
+
+        // Font with negative height.
+        let f = Font::from_guifont("font:h-1").unwrap();
+        assert_eq!(f.name, "font");
+        assert_eq!(f.height, DEFAULT_HEIGHT);
+
+        // Font with no height.
+        let f = Font::from_guifont("bar").unwrap();
+        assert_eq!(f.name, "bar");
+        assert_eq!(f.height, DEFAULT_HEIGHT);
+    }
+
+    #[test]
+    fn test_to_guifont() {
+        let f = Font::from_guifont("Fira Code:h11.5").unwrap();
+        assert_eq!(f.to_guifont(), "Fira Code:h11.5");
+
+        assert_eq!(f.resized(1.0).to_guifont(), "Fira Code:h12.5");
+        assert_eq!(f.resized(-20.0).to_guifont(), "Fira Code:h1");
+    }
+}
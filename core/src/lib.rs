@@ -0,0 +1,11 @@
+//! Gnvim's display agnostic parts: the grid model, colors and fonts, and
+//! how a piece of highlighted text is drawn. The drawing itself is done by
+//! a `render::Backend`, so the grid logic can be reused (and tested)
+//! without gtk.
+#![cfg_attr(feature = "unstable", feature(test))]
+
+pub mod color;
+pub mod font;
+pub mod lru;
+pub mod render;
+pub mod row;
@@ -0,0 +1,89 @@
+//! Least recently used cache.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Cache that holds up to `capacity` values, dropping the least recently
+/// used one when full.
+pub struct Lru<K, V> {
+    capacity: usize,
+    /// The values, and the time they were last used.
+    entries: HashMap<K, (V, u64)>,
+    /// Keys by the time they were last used.
+    used: BTreeMap<u64, K>,
+    time: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> Lru<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Lru {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            used: BTreeMap::new(),
+            time: 0,
+        }
+    }
+
+    /// Returns the value for `key`, computing it with `f` if it isn't
+    /// cached.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &V
+    where
+        F: FnOnce() -> V,
+    {
+        self.time += 1;
+
+        if let Some((_, used)) = self.entries.get_mut(&key) {
+            self.used.remove(used);
+            *used = self.time;
+        } else {
+            if self.entries.len() >= self.capacity {
+                let oldest = self.used.keys().next().copied();
+                if let Some(key) = oldest.and_then(|t| self.used.remove(&t)) {
+                    self.entries.remove(&key);
+                }
+            }
+
+            self.entries.insert(key.clone(), (f(), self.time));
+        }
+
+        self.used.insert(self.time, key.clone());
+        &self.entries[&key].0
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::new(2);
+        assert_eq!(*lru.get_or_insert_with("a", || 1), 1);
+        assert_eq!(*lru.get_or_insert_with("b", || 2), 2);
+        // Cached.
+        assert_eq!(*lru.get_or_insert_with("a", || 10), 1);
+
+        // "b" is the least recently used one.
+        assert_eq!(*lru.get_or_insert_with("c", || 3), 3);
+        assert_eq!(lru.len(), 2);
+        assert_eq!(*lru.get_or_insert_with("a", || 10), 1);
+        assert_eq!(*lru.get_or_insert_with("b", || 20), 20);
+
+        lru.clear();
+        assert!(lru.is_empty());
+        assert_eq!(*lru.get_or_insert_with("a", || 100), 100);
+    }
+}
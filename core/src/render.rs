@@ -0,0 +1,708 @@
+use crate::color::{Color, Highlight, HlDefs};
+use crate::font::Font;
+use crate::row::Segment;
+
+/// Rectangle, in pixels.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    /// Returns the area that is in both `self` and `other`, if any.
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let width = (self.x + self.width).min(other.x + other.width) - x;
+        let height = (self.y + self.height).min(other.y + other.height) - y;
+
+        if width > 0.0 && height > 0.0 {
+            Some(Rect {
+                x,
+                y,
+                width,
+                height,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the smallest area that has both `self` and `other` in it.
+    pub fn union(&self, other: &Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// How the width of the cells is determined.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CellWidth {
+    /// The font's character width. For monospace fonts.
+    #[default]
+    Font,
+    /// The width of the font's widest (printable ASCII) character, for fonts
+    /// that aren't strictly monospace. Narrower glyphs are centered in their
+    /// cells.
+    Widest,
+    /// Width in pixels. Glyphs are centered in their cells.
+    Fixed(f64),
+}
+
+impl CellWidth {
+    /// Parses the cell width from `widest`, `font` or a width in pixels.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "font" => Some(CellWidth::Font),
+            "widest" => Some(CellWidth::Widest),
+            _ => value
+                .parse::<f64>()
+                .ok()
+                .filter(|width| *width > 0.0)
+                .map(CellWidth::Fixed),
+        }
+    }
+
+    /// If the glyphs are drawn one cell at a time, centered in their cells
+    /// (instead of laid out one after another).
+    pub fn centers_glyphs(&self) -> bool {
+        *self != CellWidth::Font
+    }
+}
+
+/// Size of the cells relative to the size from the font, e.g. to tighten or
+/// loosen the spacing of the characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellScale {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Default for CellScale {
+    fn default() -> Self {
+        CellScale {
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+impl CellScale {
+    /// Creates the scale from percents (e.g. `90` for 90%).
+    pub fn from_percents(width: Option<u32>, height: Option<u32>) -> Self {
+        let scale = |percent: Option<u32>| {
+            percent
+                .filter(|percent| *percent > 0)
+                .map(|percent| f64::from(percent) / 100.0)
+                .unwrap_or(1.0)
+        };
+
+        CellScale {
+            width: scale(width),
+            height: scale(height),
+        }
+    }
+}
+
+/// What to do with glyphs that don't fit in their cells (e.g. icons and
+/// some CJK characters).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum GlyphOverflow {
+    /// Let the glyphs overflow to the neighbouring cells.
+    #[default]
+    Overflow,
+    /// Clip the glyphs to their cells.
+    Clip,
+    /// Scale the glyphs down to fit their cells.
+    Shrink,
+}
+
+impl GlyphOverflow {
+    /// Parses the policy from `overflow`, `clip` or `shrink`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "overflow" => Some(GlyphOverflow::Overflow),
+            "clip" => Some(GlyphOverflow::Clip),
+            "shrink" => Some(GlyphOverflow::Shrink),
+            _ => None,
+        }
+    }
+}
+
+/// Options of how the text is drawn in the cells, from the config file.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RenderOptions {
+    /// If the text is drawn without the font's ligatures.
+    pub disable_ligatures: bool,
+    /// If characters whose glyphs are missing from the fonts are drawn as
+    /// the fonts draw them, instead of as boxes with their code points.
+    pub disable_hex_boxes: bool,
+    /// What to do with glyphs that don't fit in their cells.
+    pub glyph_overflow: GlyphOverflow,
+}
+
+/// Cell metrics tells the size (and other metrics) of the cells in a grid.
+#[derive(Default, Debug, Clone)]
+pub struct CellMetrics {
+    pub height: f64,
+    pub width: f64,
+    pub ascent: f64,
+    pub decent: f64,
+    pub underline_thickness: f64,
+    pub underline_position: f64,
+
+    pub line_space: i64,
+    pub font: Font,
+    /// Font for double width cells ('guifontwide'). Only the family is used,
+    /// the size is `font`'s.
+    pub font_wide: Option<Font>,
+    pub cell_width: CellWidth,
+    /// Scale of the cells. Doesn't apply to `CellWidth::Fixed`.
+    pub cell_scale: CellScale,
+    pub options: RenderOptions,
+}
+
+impl CellMetrics {
+    /// If the glyphs are drawn one cell at a time, centered in their cells,
+    /// as the cells are not as wide as the font's characters.
+    pub fn centers_glyphs(&self) -> bool {
+        self.cell_width.centers_glyphs() || self.cell_scale.width != 1.0
+    }
+}
+
+/// Drawing primitives that the grids are drawn with.
+pub trait Backend {
+    type Error;
+
+    /// Fills `rect` with `color`.
+    fn fill_rect(
+        &mut self,
+        rect: Rect,
+        color: &Color,
+    ) -> Result<(), Self::Error>;
+
+    /// Draws `text` with `hl`'s font attributes (e.g. bold) in `color`, to
+    /// the cells of `area`, with the baseline at `y`. `altfont` is the font
+    /// family for `hl.altfont`, if any.
+    fn draw_text(
+        &mut self,
+        text: &str,
+        area: Rect,
+        y: f64,
+        hl: &Highlight,
+        altfont: Option<&str>,
+        color: &Color,
+    ) -> Result<(), Self::Error>;
+
+    /// Draws a curly underline, filling `rect`, in `color`.
+    fn draw_undercurl(
+        &mut self,
+        rect: Rect,
+        color: &Color,
+    ) -> Result<(), Self::Error>;
+
+    /// Called by `render_segments` before `segment` is rendered, for the
+    /// backends that need to know which cells the text is from (e.g. to
+    /// shape the whole row at once).
+    fn begin_segment(&mut self, _segment: &Segment) {}
+}
+
+/// Returns the foreground and background colors `hl` is drawn with.
+pub fn hl_colors(hl: &Highlight, hl_defs: &HlDefs) -> (Color, Color) {
+    if hl.reverse {
+        (
+            hl.background.unwrap_or(hl_defs.default_bg),
+            hl.foreground.unwrap_or(hl_defs.default_fg),
+        )
+    } else {
+        (
+            hl.foreground.unwrap_or(hl_defs.default_fg),
+            hl.background.unwrap_or(hl_defs.default_bg),
+        )
+    }
+}
+
+/// Returns the highlight for drawing the cell (with `cell_hl`) under the
+/// cursor. Colors missing from the cursor's highlight (`cursor_hl`), or
+/// all of them if it's `None`, are taken from the cell's reversed colors.
+/// The other attributes are the cell's, unless the cursor's highlight has
+/// `nocombine`.
+pub fn cursor_highlight(
+    cell_hl: &Highlight,
+    cursor_hl: Option<&Highlight>,
+    hl_defs: &HlDefs,
+) -> Highlight {
+    let (fg, bg) = hl_colors(cell_hl, hl_defs);
+    let (cursor_fg, cursor_bg) = match cursor_hl {
+        Some(hl) if hl.reverse => (hl.background, hl.foreground),
+        Some(hl) => (hl.foreground, hl.background),
+        None => (None, None),
+    };
+
+    let attrs = match cursor_hl {
+        Some(hl) if hl.nocombine => hl,
+        _ => cell_hl,
+    };
+
+    Highlight {
+        foreground: Some(cursor_fg.unwrap_or(bg)),
+        background: Some(cursor_bg.unwrap_or(fg)),
+        reverse: false,
+        ..*attrs
+    }
+}
+
+/// Renders text with `backend`.
+///
+/// * `backend` - The backend to render with.
+/// * `cm` - Cell metrics to use for text placement.
+/// * `hl` - The highlighting to use.
+/// * `hl_defs` - Global hl defs. Used to get default values.
+/// * `text` - The text to render.
+/// * `pos` - Target position.
+pub fn render_text<B: Backend>(
+    backend: &mut B,
+    cm: &CellMetrics,
+    hl: &Highlight,
+    hl_defs: &HlDefs,
+    text: &str,
+    pos: Rect,
+) -> Result<(), B::Error> {
+    let Rect {
+        x,
+        y,
+        width: w,
+        height: h,
+    } = pos;
+
+    let (fg, bg) = hl_colors(hl, hl_defs);
+    backend.fill_rect(pos, &bg)?;
+
+    let altfont = hl_defs.altfont.as_deref().filter(|_| hl.altfont);
+    backend.draw_text(text, pos, y + cm.ascent, hl, altfont, &fg)?;
+
+    let sp = hl.special.unwrap_or(hl_defs.default_sp);
+    if hl.undercurl {
+        let rect = Rect {
+            x,
+            y: y + h + cm.underline_position - cm.underline_thickness,
+            width: w,
+            height: cm.underline_thickness * 2.0,
+        };
+        backend.draw_undercurl(rect, &sp)?;
+    }
+    if hl.underline {
+        let rect = Rect {
+            x,
+            y: y + h + cm.underline_position,
+            width: w,
+            height: cm.underline_thickness,
+        };
+        backend.fill_rect(rect, &sp)?;
+    }
+
+    Ok(())
+}
+
+/// Renders `segments` of `row` with `backend`. Returns the areas that were
+/// drawn to.
+pub fn render_segments<B: Backend>(
+    backend: &mut B,
+    cm: &CellMetrics,
+    hl_defs: &HlDefs,
+    segments: Vec<Segment>,
+    row: usize,
+) -> Result<Vec<Rect>, B::Error> {
+    let cw = cm.width;
+    let ch = cm.height;
+
+    let mut areas = vec![];
+    for seg in segments {
+        let hl = hl_defs.get(&seg.hl_id).unwrap();
+
+        let pos = Rect {
+            x: (seg.start as f64 * cw).floor(),
+            y: (row as f64 * ch).floor(),
+            width: (seg.len as f64 * cw).ceil(),
+            height: ch.ceil(),
+        };
+
+        backend.begin_segment(&seg);
+        render_text(backend, cm, hl, hl_defs, &seg.text, pos)?;
+        areas.push(pos);
+    }
+
+    Ok(areas)
+}
+
+pub fn get_rect(
+    col_h: f64,
+    col_w: f64,
+    top: f64,
+    bot: f64,
+    left: f64,
+    right: f64,
+) -> (f64, f64, f64, f64) {
+    let (x1, y1) = get_coords(col_h, col_w, top, left);
+    let (x2, y2) = get_coords(col_h, col_w, bot, right);
+    (x1, y1, x2, y2)
+}
+
+pub fn get_coords(h: f64, w: f64, row: f64, col: f64) -> (f64, f64) {
+    let x = col * w;
+    let y = row * h;
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Op {
+        Fill(Rect, Color),
+        Text(String, f64, f64, Option<String>, Color),
+        Undercurl(Rect, Color),
+        Segment(usize, usize),
+    }
+
+    /// Backend that records what's drawn.
+    #[derive(Default)]
+    struct Recorder {
+        ops: Vec<Op>,
+    }
+
+    impl Backend for Recorder {
+        type Error = ();
+
+        fn fill_rect(&mut self, rect: Rect, color: &Color) -> Result<(), ()> {
+            self.ops.push(Op::Fill(rect, *color));
+            Ok(())
+        }
+
+        fn draw_text(
+            &mut self,
+            text: &str,
+            area: Rect,
+            y: f64,
+            _hl: &Highlight,
+            altfont: Option<&str>,
+            color: &Color,
+        ) -> Result<(), ()> {
+            self.ops.push(Op::Text(
+                text.to_string(),
+                area.x,
+                y,
+                altfont.map(String::from),
+                *color,
+            ));
+            Ok(())
+        }
+
+        fn draw_undercurl(
+            &mut self,
+            rect: Rect,
+            color: &Color,
+        ) -> Result<(), ()> {
+            self.ops.push(Op::Undercurl(rect, *color));
+            Ok(())
+        }
+
+        fn begin_segment(&mut self, segment: &Segment) {
+            self.ops.push(Op::Segment(segment.start, segment.len));
+        }
+    }
+
+    fn cell_metrics() -> CellMetrics {
+        CellMetrics {
+            height: 10.0,
+            width: 5.0,
+            ascent: 8.0,
+            decent: 2.0,
+            underline_thickness: 1.0,
+            underline_position: -2.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hl_colors() {
+        let mut hl_defs = HlDefs::default();
+        hl_defs.default_fg = Color::from_u64(0xffffff);
+        let red = Color::from_u64(0xff0000);
+        let hl = Highlight {
+            foreground: Some(red),
+            ..Default::default()
+        };
+
+        assert_eq!(hl_colors(&hl, &hl_defs), (red, hl_defs.default_bg));
+        let hl = Highlight {
+            reverse: true,
+            ..hl
+        };
+        assert_eq!(hl_colors(&hl, &hl_defs), (hl_defs.default_bg, red));
+    }
+
+    #[test]
+    fn test_cursor_highlight() {
+        let hl_defs = HlDefs::default();
+        let red = Color::from_u64(0xff0000);
+        let blue = Color::from_u64(0x0000ff);
+        let cell_hl = Highlight {
+            foreground: Some(red),
+            background: Some(blue),
+            bold: true,
+            ..Default::default()
+        };
+
+        // Without a cursor highlight, the cell's colors are reversed.
+        let hl = cursor_highlight(&cell_hl, None, &hl_defs);
+        assert_eq!((hl.foreground, hl.background), (Some(blue), Some(red)));
+        assert!(hl.bold);
+
+        // Nocombine leaves out the cell's attributes.
+        let cursor_hl = Highlight {
+            nocombine: true,
+            italic: true,
+            ..Default::default()
+        };
+        let hl = cursor_highlight(&cell_hl, Some(&cursor_hl), &hl_defs);
+        assert!(!hl.bold);
+        assert!(hl.italic);
+    }
+
+    #[test]
+    fn test_render_text() {
+        let mut hl_defs = HlDefs::default();
+        hl_defs.altfont = Some(String::from("Alt"));
+        let sp = Color::from_u64(0x00ff00);
+        let hl = Highlight {
+            underline: true,
+            undercurl: true,
+            altfont: true,
+            special: Some(sp),
+            ..Default::default()
+        };
+        let pos = Rect {
+            x: 10.0,
+            y: 20.0,
+            width: 15.0,
+            height: 10.0,
+        };
+
+        let mut recorder = Recorder::default();
+        render_text(&mut recorder, &cell_metrics(), &hl, &hl_defs, "foo", pos)
+            .unwrap();
+
+        assert_eq!(
+            recorder.ops,
+            vec![
+                Op::Fill(pos, hl_defs.default_bg),
+                Op::Text(
+                    String::from("foo"),
+                    10.0,
+                    28.0,
+                    Some(String::from("Alt")),
+                    hl_defs.default_fg
+                ),
+                Op::Undercurl(
+                    Rect {
+                        x: 10.0,
+                        y: 27.0,
+                        width: 15.0,
+                        height: 2.0,
+                    },
+                    sp
+                ),
+                Op::Fill(
+                    Rect {
+                        x: 10.0,
+                        y: 28.0,
+                        width: 15.0,
+                        height: 1.0,
+                    },
+                    sp
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let mut hl_defs = HlDefs::default();
+        hl_defs.insert(0, Highlight::default());
+        let segments = vec![
+            Segment {
+                text: String::from("ab"),
+                hl_id: 0,
+                start: 0,
+                len: 2,
+            },
+            Segment {
+                text: String::from("c"),
+                hl_id: 0,
+                start: 2,
+                len: 1,
+            },
+        ];
+
+        let mut recorder = Recorder::default();
+        let areas = render_segments(
+            &mut recorder,
+            &cell_metrics(),
+            &hl_defs,
+            segments,
+            3,
+        )
+        .unwrap();
+
+        assert_eq!(
+            areas,
+            vec![
+                Rect {
+                    x: 0.0,
+                    y: 30.0,
+                    width: 10.0,
+                    height: 10.0,
+                },
+                Rect {
+                    x: 10.0,
+                    y: 30.0,
+                    width: 5.0,
+                    height: 10.0,
+                },
+            ]
+        );
+
+        let segments = recorder
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Segment(start, len) => Some((*start, *len)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(segments, vec![(0, 2), (2, 1)]);
+        // The segment is started before its text is drawn.
+        assert_eq!(recorder.ops[0], Op::Segment(0, 2));
+    }
+
+    #[test]
+    fn test_rect_intersection() {
+        let rect = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 10.0,
+            height: 10.0,
+        };
+
+        assert_eq!(
+            rect.intersection(&Rect {
+                x: 5.0,
+                y: -5.0,
+                width: 10.0,
+                height: 10.0,
+            }),
+            Some(Rect {
+                x: 5.0,
+                y: 0.0,
+                width: 5.0,
+                height: 5.0,
+            })
+        );
+        assert_eq!(rect.intersection(&rect), Some(rect));
+        assert_eq!(
+            rect.intersection(&Rect {
+                x: 10.0,
+                y: 0.0,
+                width: 10.0,
+                height: 10.0,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rect_union() {
+        let a = Rect {
+            x: 0.0,
+            y: 5.0,
+            width: 10.0,
+            height: 10.0,
+        };
+        let b = Rect {
+            x: 20.0,
+            y: 0.0,
+            width: 5.0,
+            height: 10.0,
+        };
+
+        assert_eq!(
+            a.union(&b),
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 25.0,
+                height: 15.0,
+            }
+        );
+        assert_eq!(a.union(&a), a);
+    }
+
+    #[test]
+    fn test_cell_width_parse() {
+        assert_eq!(CellWidth::parse("font"), Some(CellWidth::Font));
+        assert_eq!(CellWidth::parse("widest"), Some(CellWidth::Widest));
+        assert_eq!(CellWidth::parse("9"), Some(CellWidth::Fixed(9.0)));
+        assert_eq!(CellWidth::parse("8.5"), Some(CellWidth::Fixed(8.5)));
+        assert_eq!(CellWidth::parse("0"), None);
+        assert_eq!(CellWidth::parse("wide"), None);
+
+        assert!(!CellWidth::Font.centers_glyphs());
+        assert!(CellWidth::Widest.centers_glyphs());
+    }
+
+    #[test]
+    fn test_cell_scale() {
+        assert_eq!(
+            CellScale::from_percents(Some(90), None),
+            CellScale {
+                width: 0.9,
+                height: 1.0,
+            }
+        );
+        assert_eq!(
+            CellScale::from_percents(Some(0), Some(120)),
+            CellScale {
+                width: 1.0,
+                height: 1.2,
+            }
+        );
+
+        let mut cm = CellMetrics::default();
+        assert!(!cm.centers_glyphs());
+        cm.cell_scale = CellScale::from_percents(Some(110), None);
+        assert!(cm.centers_glyphs());
+    }
+
+    #[test]
+    fn test_glyph_overflow_parse() {
+        assert_eq!(
+            GlyphOverflow::parse("overflow"),
+            Some(GlyphOverflow::Overflow)
+        );
+        assert_eq!(GlyphOverflow::parse("clip"), Some(GlyphOverflow::Clip));
+        assert_eq!(GlyphOverflow::parse("shrink"), Some(GlyphOverflow::Shrink));
+        assert_eq!(GlyphOverflow::parse("scale"), None);
+    }
+}
@@ -1,7 +1,11 @@
-use crate::nvim_bridge::GridLineSegment;
-
-#[cfg(test)]
-use crate::nvim_bridge;
+/// Cell(s) of nvim's `grid_line` event.
+#[derive(Debug, PartialEq)]
+pub struct LineCell {
+    pub text: String,
+    pub hl_id: u64,
+    pub repeat: u64,
+    pub double_width: bool,
+}
 
 #[derive(Clone)]
 pub struct Cell {
@@ -18,6 +22,45 @@ pub struct Segment {
     pub len: usize,
 }
 
+/// If `text` has emoji, or sequences of them (joined with zero width joiners
+/// or with variation selectors). Fonts often draw them at a different width
+/// than nvim gives their cells.
+pub fn is_emoji(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(c,
+            // Zero width joiner and the variation selectors.
+            '\u{200d}' | '\u{fe0e}' | '\u{fe0f}'
+            // Miscellaneous symbols and dingbats.
+            | '\u{2600}'..='\u{27bf}'
+            // Emoji, including the regional indicators and skin tones.
+            | '\u{1f000}'..='\u{1faff}'
+            // Tags (of flag sequences).
+            | '\u{e0020}'..='\u{e007f}')
+    })
+}
+
+/// If `text` has characters of the scripts whose shaping depends on the
+/// neighbouring characters, e.g. joined Arabic letters and Indic conjuncts,
+/// which might span highlight segments. Box drawing, symbols and the other
+/// scripts are shaped the same a segment at a time.
+pub fn is_complex_script(text: &str) -> bool {
+    text.chars().any(|c| {
+        matches!(c,
+            // Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan, Mandaic and
+            // the Arabic supplements.
+            '\u{0590}'..='\u{08ff}'
+            // Indic scripts, Thai, Lao, Tibetan and Myanmar.
+            | '\u{0900}'..='\u{109f}'
+            // Hangul jamo (conjoining).
+            | '\u{1100}'..='\u{11ff}'
+            // Khmer and Mongolian.
+            | '\u{1780}'..='\u{18af}'
+            // Arabic presentation forms.
+            | '\u{fb1d}'..='\u{fdff}'
+            | '\u{fe70}'..='\u{feff}')
+    })
+}
+
 /// Row, as in one row in a grid.
 #[derive(Clone)]
 pub struct Row {
@@ -56,11 +99,20 @@ impl Row {
         self.cells.get(at)
     }
 
-    #[allow(unused)] // Not used currently, but tested.
     pub fn len(&self) -> usize {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// If the row has text that needs to be shaped as a whole (see
+    /// `is_complex_script` and `Backend::begin_segment`).
+    pub fn has_complex_script(&self) -> bool {
+        self.cells.iter().any(|cell| is_complex_script(&cell.text))
+    }
+
     /// Clears (resets) the row.
     pub fn clear(&mut self) {
         self.cells = Row::create_empty_cells(self.len).into_boxed_slice();
@@ -103,11 +155,13 @@ impl Row {
         assert_eq!(self.cells.len(), self.len);
     }
 
-    /// Updates row. `line` should be coming straight from nvim's 'grid_line'.
-    /// event.
-    pub fn update(&mut self, line: GridLineSegment) -> Vec<Segment> {
-        let col_start = line.col_start as usize;
-
+    /// Updates row with `cells`, starting from `col_start`. The cells should
+    /// be coming straight from nvim's 'grid_line' event.
+    pub fn update(
+        &mut self,
+        col_start: usize,
+        cells: Vec<LineCell>,
+    ) -> Vec<Segment> {
         // Check where the segment at col_start starts and use that when checking
         // for affected segments. This is so that if col_start is in middle of a
         // ligature, we'll render the whole segment where the ligature might have
@@ -120,7 +174,7 @@ impl Row {
             };
 
         let mut offset = col_start;
-        for cell in line.cells.iter() {
+        for cell in cells.iter() {
             for r in 0..cell.repeat as usize {
                 self.cells[offset + r] = Cell {
                     // TODO(ville): Avoid clone here?
@@ -267,25 +321,23 @@ mod benches {
         );
 
         b.iter(move || {
-            row.clone().update(GridLineSegment {
-                grid: 0,
-                row: 0,
-                col_start: 3,
-                cells: vec![
-                    nvim_bridge::Cell {
+            row.clone().update(
+                3,
+                vec![
+                    LineCell {
                         text: String::from("1"),
                         hl_id: 1,
                         repeat: 3,
                         double_width: false,
                     },
-                    nvim_bridge::Cell {
+                    LineCell {
                         text: String::from("1"),
                         hl_id: 1,
                         repeat: 3,
                         double_width: false,
                     },
                 ],
-            });
+            );
         });
     }
 
@@ -349,25 +401,23 @@ mod benches {
         );
 
         b.iter(move || {
-            row.update(GridLineSegment {
-                grid: 0,
-                row: 0,
-                col_start: 3,
-                cells: vec![
-                    nvim_bridge::Cell {
+            row.update(
+                3,
+                vec![
+                    LineCell {
                         text: String::from("1"),
                         hl_id: 1,
                         repeat: 3,
                         double_width: false,
                     },
-                    nvim_bridge::Cell {
+                    LineCell {
                         text: String::from("1"),
                         hl_id: 2,
                         repeat: 3,
                         double_width: false,
                     },
                 ],
-            });
+            );
         });
     }
 
@@ -535,25 +585,23 @@ mod tests {
             ],
         );
 
-        row.update(GridLineSegment {
-            grid: 0,
-            row: 0,
-            col_start: 3,
-            cells: vec![
-                nvim_bridge::Cell {
+        row.update(
+            3,
+            vec![
+                LineCell {
                     text: String::from("1"),
                     hl_id: 1,
                     repeat: 3,
                     double_width: false,
                 },
-                nvim_bridge::Cell {
+                LineCell {
                     text: String::from("2"),
                     hl_id: 1,
                     repeat: 3,
                     double_width: false,
                 },
             ],
-        });
+        );
 
         assert_eq!(
             row.cells.iter().map(|c| c.text.clone()).collect::<String>(),
@@ -595,17 +643,15 @@ mod tests {
             ],
         );
 
-        let segments = row.update(GridLineSegment {
-            grid: 0,
-            row: 0,
-            col_start: 4,
-            cells: vec![nvim_bridge::Cell {
+        let segments = row.update(
+            4,
+            vec![LineCell {
                 text: String::from(" "),
                 hl_id: 2,
                 repeat: 1,
                 double_width: false,
             }],
-        });
+        );
 
         assert_eq!(
             row.cells.iter().map(|c| c.text.clone()).collect::<String>(),
@@ -631,6 +677,56 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_row_has_complex_script() {
+        let mut row = Row::new(3);
+        assert!(!row.has_complex_script());
+
+        // E.g. the vertical separator of split windows.
+        row.insert_at(
+            0,
+            vec![Cell {
+                text: "│".to_string(),
+                hl_id: 0,
+                double_width: false,
+            }],
+        );
+        assert!(!row.has_complex_script());
+
+        row.insert_at(
+            1,
+            vec![Cell {
+                text: "क्ष".to_string(),
+                hl_id: 0,
+                double_width: false,
+            }],
+        );
+        assert!(row.has_complex_script());
+    }
+
+    #[test]
+    fn test_is_complex_script() {
+        assert!(is_complex_script("क्ष"));
+        assert!(is_complex_script("سلام"));
+        assert!(is_complex_script("ก"));
+        assert!(!is_complex_script("a"));
+        assert!(!is_complex_script("·"));
+        assert!(!is_complex_script("漢"));
+        assert!(!is_complex_script("\u{f07c}"));
+    }
+
+    #[test]
+    fn test_is_emoji() {
+        assert!(is_emoji("😀"));
+        assert!(is_emoji("❤\u{fe0f}"));
+        assert!(is_emoji("👩\u{200d}💻"));
+        assert!(is_emoji("🇫🇮"));
+        assert!(!is_emoji("a"));
+        assert!(!is_emoji("ä"));
+        assert!(!is_emoji("漢"));
+        assert!(!is_emoji(""));
+    }
+
     #[test]
     fn test_row_copy_range() {
         let mut row = Row::new(30);
@@ -21,3 +21,130 @@ function! gnvim#enable_ext_popupmenu(enable)
                 \ 'EnableExtPopupmenu',
                 \ a:enable)
 endfunction
+
+function! gnvim#enable_ext_messages(enable)
+    return rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'EnableExtMessages',
+                \ a:enable)
+endfunction
+
+function! gnvim#cmdline_history()
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'CmdlineHistory')
+    return ''
+endfunction
+
+function! gnvim#request(method, ...)
+    return call('rpcrequest', [g:gnvim_channel_id, 'Gnvim', a:method] + a:000)
+endfunction
+
+function! gnvim#metrics()
+    return gnvim#request('GetMetrics')
+endfunction
+
+function! gnvim#version()
+    return gnvim#request('GetVersion')
+endfunction
+
+function! gnvim#capabilities()
+    return gnvim#request('GetCapabilities')
+endfunction
+
+function! gnvim#clipboard_get(register)
+    return gnvim#request('ClipboardGet', a:register)
+endfunction
+
+function! gnvim#clipboard_set(register, lines, regtype)
+    return gnvim#request('ClipboardSet', a:register, a:lines, a:regtype)
+endfunction
+
+function! gnvim#file_dialog(save)
+    return gnvim#request('FileDialog', a:save)
+endfunction
+
+function! gnvim#set_fullscreen(enable)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetFullscreen', a:enable)
+endfunction
+
+function! gnvim#set_opacity(opacity)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetOpacity', a:opacity)
+endfunction
+
+function! gnvim#set_title_prefix(prefix)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetTitlePrefix', a:prefix)
+endfunction
+
+function! gnvim#set_progress(progress)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetProgress', a:progress)
+endfunction
+
+function! gnvim#set_urgent(urgent)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetUrgent', a:urgent)
+endfunction
+
+function! gnvim#resize_window(width, height)
+    call rpcnotify(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'ResizeWindow',
+                \ a:width,
+                \ a:height)
+endfunction
+
+function! gnvim#font_complete(arglead, cmdline, cursorpos)
+    let fonts = gnvim#request('ListFonts')
+    let lead = tolower(a:arglead)
+    return filter(fonts, {_, font -> stridx(tolower(font), lead) == 0})
+endfunction
+
+function! gnvim#log_level_complete(arglead, cmdline, cursorpos)
+    let levels = ['off', 'error', 'warn', 'info', 'debug', 'trace']
+    return filter(levels, {_, level -> stridx(level, a:arglead) == 0})
+endfunction
+
+function! gnvim#set_accels(action, accels)
+    call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetAccels', a:action, a:accels)
+endfunction
+
+" Returns the visually selected text, or an empty string if not in visual
+" mode. Used by gnvim's copy action.
+function! gnvim#selection()
+    let mode = mode()
+    if mode !~# "^[vV\<C-v>]"
+        return ''
+    endif
+
+    let [l1, c1] = getpos('v')[1:2]
+    let [l2, c2] = getpos('.')[1:2]
+    if l1 > l2 || (l1 == l2 && c1 > c2)
+        let [l1, c1, l2, c2] = [l2, c2, l1, c1]
+    endif
+
+    let lines = getline(l1, l2)
+    if mode ==# 'V'
+        return join(lines, "\n") . "\n"
+    elseif mode ==# 'v'
+        let lines[-1] = lines[-1][: s:char_end(lines[-1], c2)]
+        let lines[0] = lines[0][c1 - 1 :]
+        return join(lines, "\n")
+    endif
+
+    " Blockwise.
+    let [c1, c2] = [min([c1, c2]), max([c1, c2])]
+    call map(lines, {_, line -> line[c1 - 1 : s:char_end(line, c2)]})
+    return join(lines, "\n")
+endfunction
+
+" Index of the last byte of the (multibyte) character at column `col`.
+function! s:char_end(line, col)
+    return a:col - 2 + max([len(matchstr(a:line, '.', a:col - 1)), 1])
+endfunction
+
+" Makes `win` the current window when the mouse pointer enters it, like
+" 'mousefocus' does in vim's GUI. Floating windows are skipped.
+function! gnvim#mouse_focus(win)
+    if mode() ==# 'n' && nvim_win_get_config(a:win).relative ==# ''
+        call nvim_set_current_win(a:win)
+    endif
+endfunction
@@ -0,0 +1,7 @@
+function! gnvim#command#complete(arglead, cmdline, cursorpos)
+    return rpcrequest(
+                \ g:gnvim_channel_id,
+                \ 'Gnvim',
+                \ 'CompleteCommand',
+                \ strpart(a:cmdline, 0, a:cursorpos))
+endfunction
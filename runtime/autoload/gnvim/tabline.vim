@@ -0,0 +1,22 @@
+" Returns the labels of `tabs` (tabpage handles), formatted with
+" g:gnvim_tabline_format, or v:null if it's not set.
+function! gnvim#tabline#labels(tabs)
+    if !exists('g:gnvim_tabline_format')
+        return v:null
+    endif
+
+    return map(
+                \ copy(a:tabs),
+                \ {_, tab -> call(g:gnvim_tabline_format, [nvim_tabpage_get_number(tab)])})
+endfunction
+
+" Returns the details of `tab` (tabpage handle) for its tooltip.
+function! gnvim#tabline#tooltip(tab)
+    let nr = nvim_tabpage_get_number(a:tab)
+    let name = bufname(winbufnr(nvim_tabpage_get_win(a:tab)))
+    return {
+                \ 'path': empty(name) ? '' : fnamemodify(name, ':p'),
+                \ 'cwd': getcwd(-1, nr),
+                \ 'windows': tabpagewinnr(nr, '$'),
+                \ }
+endfunction
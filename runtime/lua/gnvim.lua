@@ -0,0 +1,70 @@
+-- Lua interface for gnvim. Wraps the rpc protocol that gnvim uses, see
+-- `:help gnvim-lua`.
+
+local M = {}
+
+--- Sends a gnvim event.
+function M.notify(event, ...)
+    vim.rpcnotify(vim.g.gnvim_channel_id, 'Gnvim', event, ...)
+end
+
+--- Makes a gnvim request, and returns the response.
+function M.request(method, ...)
+    return vim.rpcrequest(vim.g.gnvim_channel_id, 'Gnvim', method, ...)
+end
+
+--- Sets the font, e.g. `set_font('Monospace:h12')`.
+function M.set_font(font)
+    vim.o.guifont = font
+end
+
+--- Sets the line space (in pixels).
+function M.set_linespace(space)
+    vim.o.linespace = space
+end
+
+function M.set_fullscreen(enable)
+    M.notify('SetFullscreen', enable)
+end
+
+function M.set_opacity(opacity)
+    M.notify('SetOpacity', opacity)
+end
+
+function M.set_title_prefix(prefix)
+    M.notify('SetTitlePrefix', prefix)
+end
+
+function M.set_progress(progress)
+    M.notify('SetProgress', progress)
+end
+
+function M.set_urgent(urgent)
+    M.notify('SetUrgent', urgent)
+end
+
+function M.resize_window(width, height)
+    M.notify('ResizeWindow', width, height)
+end
+
+function M.metrics()
+    return M.request('GetMetrics')
+end
+
+function M.version()
+    return M.request('GetVersion')
+end
+
+function M.capabilities()
+    return M.request('GetCapabilities')
+end
+
+function M.file_dialog(save)
+    return M.request('FileDialog', save or false)
+end
+
+M.cursor = require('gnvim.cursor')
+M.ext = require('gnvim.ext')
+M.popupmenu = require('gnvim.popupmenu')
+
+return M
@@ -0,0 +1,7 @@
+local M = {}
+
+function M.enable_animations(enable)
+    require('gnvim').notify('SetCursorAnimations', enable)
+end
+
+return M
@@ -0,0 +1,21 @@
+-- Toggles for the externalized UI components.
+
+local M = {}
+
+function M.enable_tabline(enable)
+    require('gnvim').notify('EnableExtTabline', enable and 1 or 0)
+end
+
+function M.enable_cmdline(enable)
+    require('gnvim').notify('EnableExtCmdline', enable and 1 or 0)
+end
+
+function M.enable_popupmenu(enable)
+    require('gnvim').notify('EnableExtPopupmenu', enable and 1 or 0)
+end
+
+function M.enable_messages(enable)
+    require('gnvim').notify('EnableExtMessages', enable and 1 or 0)
+end
+
+return M
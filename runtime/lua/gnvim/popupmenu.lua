@@ -0,0 +1,47 @@
+local M = {}
+
+--- Configures the popupmenu. Accepts a table with any of the following keys:
+---
+--- * `width` - width (in pixels) when the details view is not open, or the
+---   maximum width if `min_width` is set
+--- * `width_details` - width (in pixels) when the details view is open
+--- * `min_width` - minimum width (in pixels), 0 for fixed width
+--- * `max_items` - maximum number of visible items, 0 for no limit
+--- * `show_menu` - show `menu` of the completion items
+--- * `show_menu_on_all_items` - show `menu` for all completion items
+function M.configure(opts)
+    local gnvim = require('gnvim')
+
+    if opts.width ~= nil then
+        gnvim.notify('PopupmenuSetWidth', opts.width)
+    end
+
+    if opts.width_details ~= nil then
+        gnvim.notify('PopupmenuSetWidthDetails', opts.width_details)
+    end
+
+    if opts.min_width ~= nil then
+        gnvim.notify('PopupmenuSetMinWidth', opts.min_width)
+    end
+
+    if opts.max_items ~= nil then
+        gnvim.notify('PopupmenuSetMaxItems', opts.max_items)
+    end
+
+    if opts.show_menu ~= nil then
+        gnvim.notify('PopupmenuShowMenu', opts.show_menu)
+    end
+
+    if opts.show_menu_on_all_items ~= nil then
+        gnvim.notify(
+            'PopupmenuShowMenuOnAllItems',
+            opts.show_menu_on_all_items and 1 or 0
+        )
+    end
+end
+
+function M.toggle_details()
+    require('gnvim').notify('CompletionMenuToggleInfo')
+end
+
+return M
@@ -6,5 +6,80 @@ let g:gnvim_runtime_loaded = 1
 
 inoremap <expr> <C-s> gnvim#popupmenu#toggle_details()
 
+command! -nargs=+ -complete=customlist,gnvim#command#complete Gnvim
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'Command', <f-args>)
+
+command! -nargs=0 GnvimZen
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ToggleZen')
+command! -nargs=0 GnvimInspect
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'Inspect')
+command! -nargs=0 GnvimRestart
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'Restart')
+command! -nargs=1 -complete=customlist,gnvim#log_level_complete GnvimLogLevel
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetLogLevel',
+            \                <q-args>)
+command! -nargs=1 GnvimOpacity
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetOpacity',
+            \                str2float(<q-args>))
+command! -nargs=0 GnvimFileTree
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'ToggleFileTree',
+            \                getcwd())
+command! -nargs=0 GnvimKeepAbove
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetKeepAbove')
+command! -nargs=0 GnvimSticky
+            \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetSticky')
+
+" Deprecated, use `:Gnvim animations`.
 command! -nargs=1 GnvimCursorEnableAnimations
             \ call gnvim#cursor#enable_animations(<q-args>)
+
+" nvim-qt compatible commands and functions.
+command! -nargs=? -bang -complete=customlist,gnvim#font_complete GuiFont
+            \ call GuiFont(<q-args>, <bang>0)
+command! -nargs=? GuiLinespace call GuiLinespace(<q-args>)
+
+function! GuiFont(fname, ...) abort
+    if a:fname ==# ''
+        echo &guifont
+        return
+    endif
+
+    " The force argument is accepted for compatibility, but all fonts are
+    " used as is.
+    let &guifont = a:fname
+endfunction
+
+function! GuiLinespace(height) abort
+    if a:height ==# ''
+        echo &linespace
+        return
+    endif
+
+    let &linespace = str2nr(a:height)
+endfunction
+
+let g:GuiFont = &guifont
+let g:GuiLinespace = &linespace
+
+" Files opened or written in gnvim show up in the desktop's recent files.
+augroup gnvim_recent
+    autocmd!
+    autocmd BufReadPost,BufWritePost *
+                \ if &buftype ==# '' && expand('<afile>') !=# ''
+                \ | call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'AddRecent',
+                \                  expand('<afile>:p'))
+                \ | endif
+augroup END
+
+" Keep the file tree in the current directory.
+augroup gnvim_cwd
+    autocmd!
+    autocmd DirChanged *
+                \ call rpcnotify(g:gnvim_channel_id, 'Gnvim', 'SetCwd', getcwd())
+augroup END
+
+augroup gnvim_gui_vars
+    autocmd!
+    autocmd OptionSet guifont let g:GuiFont = &guifont
+    autocmd OptionSet linespace let g:GuiLinespace = &linespace
+augroup END
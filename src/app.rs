@@ -0,0 +1,388 @@
+//! Gnvim, the application: its command line, and the window around the
+//! `NeovimView`.
+
+use gtk::prelude::*;
+use gtk::traits::SettingsExt;
+use gtk::{gdk, gio, glib};
+
+use log::{debug, error};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::Error;
+use crate::{
+    args, config, crash, dbus, files, logger, nvim_bridge, nvim_gio, restart,
+    sessions, ui, version,
+};
+
+async fn build(
+    app: &gtk::Application,
+    args: &args::Args,
+    files: Vec<files::FileArg>,
+    restore: Option<restart::Restore>,
+) -> Result<(), Error> {
+    let config = config::Config::load();
+
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let bridge = nvim_bridge::NvimBridge::new(tx.clone());
+
+    if let Some(ref path) = args.record {
+        if let Err(err) = nvim_bridge::record::start(path) {
+            error!("Failed to create recording '{}': {}", path, err);
+            return Ok(());
+        }
+    }
+
+    let replay = match args.replay {
+        Some(ref path) => match nvim_bridge::record::read(path) {
+            Ok(entries) => Some(entries),
+            Err(err) => {
+                error!("Failed to read recording '{}': {}", path, err);
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    // After a restart, attach to the same nvim again.
+    let attach = match restore {
+        Some(restart::Restore::Attach(ref address)) => {
+            Some(Some(address.clone()))
+        }
+        _ => args.attach.clone(),
+    };
+
+    // The command and process of the nvim that we started, if we did.
+    let (nvim, child) = match attach {
+        Some(ref address) => {
+            let path = match address {
+                Some(address) => address.into(),
+                None => {
+                    let sessions = sessions::find().await;
+                    match ui::pick_session(&sessions).await {
+                        Some(path) => path,
+                        // Nothing to attach to.
+                        None => return Ok(()),
+                    }
+                }
+            };
+
+            let conn = nvim_gio::connect_socket(&path)?;
+            let nvim = nvim_gio::new_socket(bridge, conn, tx.clone())?;
+
+            // The nvim wasn't started by us, so our runtime isn't loaded.
+            nvim.command(&args.nvim_runtime_cmd()).await?;
+
+            (nvim, None)
+        }
+        None => {
+            let cmd_args = args.nvim_cmd(&config);
+
+            // Print the nvim cmd which is executed if asked.
+            if args.print_nvim_cmd {
+                println!("nvim cmd: {:?}", cmd_args);
+            }
+
+            match nvim_gio::new_child(
+                bridge,
+                cmd_args.iter().map(|a| std::ffi::OsStr::new(a)).collect(),
+                tx.clone(),
+            ) {
+                Ok((nvim, child)) => (nvim, Some((cmd_args, child))),
+                Err(err) => {
+                    ui::show_start_error(&cmd_args, &err.to_string(), "").await;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    // When replaying, the redraw events come from the recording instead.
+    if let Err(err) = setup_nvim(&nvim, args, &config, replay.is_none()).await {
+        return match child {
+            // Most likely our nvim exited (e.g. because of an error in the
+            // user's config), tell the user why.
+            Some((cmd_args, child)) => {
+                let stderr = nvim_gio::child_stderr(&child).await;
+                ui::show_start_error(&cmd_args, &err.to_string(), &stderr)
+                    .await;
+                Ok(())
+            }
+            None => Err(Error::Setup(err)),
+        };
+    }
+
+    let grid_scroll_speed = grid_scroll_speed(&nvim).await;
+
+    let ui = ui::UI::init(
+        app,
+        rx,
+        args.geometry,
+        nvim.clone(),
+        grid_scroll_speed,
+        &config,
+    )
+    .expect("failed to init ui");
+
+    if let Some(entries) = replay {
+        ui.start();
+        ui::common::spawn_local(nvim_bridge::record::replay(
+            entries,
+            args.replay_speed,
+            tx,
+        ));
+        return Ok(());
+    }
+
+    // An attached or restored nvim already has its own buffers.
+    if files.is_empty() && attach.is_none() && restore.is_none() {
+        ui.show_welcome();
+    }
+
+    ui.start();
+
+    // Our plugin tells us when nvim's directory changes, but we need the
+    // initial one too (e.g. for the project settings).
+    let n = nvim.clone();
+    ui::common::spawn_local(async move {
+        if let Err(err) = n
+            .command("doautocmd <nomodeline> gnvim_cwd DirChanged")
+            .await
+        {
+            error!("Failed to get the current directory: {}", err);
+        }
+    });
+
+    if let Some(restart::Restore::Session(path)) = restore {
+        ui::common::spawn_local(async move {
+            if let Err(err) = restart::restore_session(&nvim, &path).await {
+                error!("Failed to restore the session: {}", err);
+            }
+        });
+        return Ok(());
+    }
+
+    // Open the files only after the UI is running, so that any prompts
+    // (e.g. swap file warnings) can be answered.
+    ui::common::spawn_local(async move {
+        for file in files.iter() {
+            if let Err(err) = files::open_file(&nvim, file).await {
+                error!("Failed to open '{}': {}", file.path, err);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Checks that nvim is compatible with us, subscribes to our events, tells
+/// nvim about us, and attaches the UI if `ui_attach` is set.
+pub(crate) async fn setup_nvim(
+    nvim: &nvim_gio::GioNeovim,
+    args: &args::Args,
+    config: &config::Config,
+    ui_attach: bool,
+) -> Result<(), String> {
+    let call_err = |err: Box<nvim_rs::error::CallError>| err.to_string();
+
+    let api_info = nvim.get_api_info().await.map_err(call_err)?;
+    nvim_bridge::check_nvim_version(&api_info[1])?;
+
+    nvim.subscribe("Gnvim").await.map_err(call_err)?;
+    nvim.set_var("gnvim_channel_id", api_info[0].clone())
+        .await
+        .map_err(call_err)?;
+
+    // Set before attaching, so that the user's config (which an embedded
+    // nvim sources only after the UI has attached) can use these.
+    let ui_opts = args.nvim_ui_opts(&config.ext);
+    let scale = gdk::Screen::default()
+        .map(|screen| screen.monitor_scale_factor(screen.primary_monitor()))
+        .unwrap_or(1);
+    nvim.set_var("gnvim", 1.into()).await.map_err(call_err)?;
+    nvim.set_var("gnvim_version", args::version().into())
+        .await
+        .map_err(call_err)?;
+    nvim.set_var(
+        "gnvim_capabilities",
+        nvim_bridge::capabilities(&ui_opts.to_value_map(), scale),
+    )
+    .await
+    .map_err(call_err)?;
+    nvim.set_client_info(
+        "gnvim",
+        vec![("commit".into(), args::git_hash().into())],
+        "ui",
+        vec![],
+        vec![
+            (
+                "website".into(),
+                "https://github.com/vhakulinen/gnvim".into(),
+            ),
+            ("license".into(), "MIT".into()),
+        ],
+    )
+    .await
+    .map_err(call_err)?;
+
+    if ui_attach {
+        nvim.ui_attach(80, 30, &ui_opts).await.map_err(call_err)?;
+    }
+
+    Ok(())
+}
+
+/// Runs gnvim, with the command line arguments.
+pub fn run() {
+    let args = args::Args::from_cli();
+
+    logger::init(args.log_file.as_deref(), args.log_level);
+
+    // Set if a crashed gnvim started us to show its crash report.
+    if crash::show_report() {
+        return;
+    }
+    crash::install_hook();
+
+    // Set if we were restarted with `:GnvimRestart`.
+    let restore = restart::Restore::take();
+
+    if args.version {
+        print!("{}", version::report(&args, &config::Config::load()));
+        return;
+    }
+
+    if args.remote_send.is_some() || args.remote_expr.is_some() {
+        if let Err(err) = remote(&args) {
+            eprintln!("Failed to talk to running gnvim: {:?}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Gdk reads the backend when it's initialized.
+    if let Some(ref backend) = args.backend {
+        std::env::set_var("GDK_BACKEND", backend);
+    }
+
+    if let Err(err) = gtk::init() {
+        error!("Failed to initialize gtk: {}", err);
+        return;
+    }
+
+    // Desktop actions are handled by the running instance, if there is one.
+    if let Some(ref action) = args.action {
+        match dbus::activate_in_running(action) {
+            Ok(()) => return,
+            Err(err) => debug!("No running instance: {:?}", err),
+        }
+    }
+
+    let mut flags = gio::ApplicationFlags::empty();
+    flags.insert(gio::ApplicationFlags::NON_UNIQUE);
+    flags.insert(gio::ApplicationFlags::HANDLES_OPEN);
+    let app = gtk::Application::new(Some("com.github.vhakulinen.gnvim"), flags);
+
+    gdk::set_program_class("GNvim");
+    glib::set_application_name("GNvim");
+    gtk::Window::set_default_icon_name("gnvim");
+
+    if args.prefer_dark_theme {
+        if let Some(settings) = gtk::Settings::default() {
+            settings.set_gtk_application_prefer_dark_theme(true);
+        }
+    }
+
+    // Pass our files to gtk so they come back to us through the `open`
+    // signal, same as when we're started by a file manager. The paths are
+    // made absolute so gio doesn't mistake `file.rs:42` for an uri.
+    // After a restart, the files are already open in the restored session.
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut gtk_args = vec![std::env::args().next().unwrap_or_default()];
+    if restore.is_none() {
+        gtk_args.extend(
+            args.open_files
+                .iter()
+                .map(|file| cwd.join(file).to_string_lossy().into_owned()),
+        );
+    }
+
+    let args = Rc::new(args);
+
+    let args_ref = args.clone();
+    let restore = RefCell::new(restore);
+    app.connect_activate(move |app| {
+        build_ui(app, &args_ref, vec![], restore.borrow_mut().take());
+    });
+
+    app.connect_open(move |app, files, _| {
+        let files = files
+            .iter()
+            .filter_map(|file| file.path())
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+
+        if !args.new_instance {
+            match dbus::open_in_running(&files) {
+                Ok(()) => return,
+                Err(err) => debug!("No running instance: {:?}", err),
+            }
+        }
+
+        let files = files.iter().map(|file| files::FileArg::parse(file));
+        build_ui(app, &args, files.collect(), None);
+    });
+
+    app.run_with_args(&gtk_args);
+
+    nvim_bridge::record::flush();
+}
+
+/// Speed of the grids' scroll animation, from `g:gnvim_grid_scroll_speed`.
+pub(crate) async fn grid_scroll_speed(nvim: &nvim_gio::GioNeovim) -> i64 {
+    nvim.get_var("gnvim_grid_scroll_speed")
+        .await
+        .ok()
+        .and_then(|val| val.as_i64())
+        .unwrap_or(300)
+        .max(0)
+}
+
+/// Handles the `--remote-*` cli arguments.
+fn remote(args: &args::Args) -> Result<(), Error> {
+    if let Some(ref keys) = args.remote_send {
+        dbus::send_keys_to_running(keys)?;
+    }
+
+    if let Some(ref expr) = args.remote_expr {
+        println!("{}", dbus::eval_in_running(expr)?);
+    }
+
+    Ok(())
+}
+
+fn build_ui(
+    app: &gtk::Application,
+    args: &args::Args,
+    files: Vec<files::FileArg>,
+    restore: Option<restart::Restore>,
+) {
+    let restored = restore.is_some();
+    let c = glib::MainContext::default();
+    c.block_on(async move {
+        if let Err(err) = build(app, args, files, restore).await {
+            error!("Failed to build UI: {:?}", err);
+            return;
+        }
+
+        // The action was already activated before the restart.
+        if restored {
+            return;
+        }
+
+        if let Some(ref action) = args.action {
+            app.activate_action(action, None);
+        }
+    });
+}
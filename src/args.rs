@@ -1,22 +1,56 @@
 use structopt::{clap, StructOpt};
 
+use crate::config::{Config, ExtConfig};
+use crate::flatpak;
+
 include!(concat!(env!("OUT_DIR"), "/gnvim_version.rs"));
 
+/// Gnvim's version.
+pub fn version() -> &'static str {
+    VERSION
+}
+
+/// Hash of the commit gnvim was built from.
+pub fn git_hash() -> &'static str {
+    GIT_HASH
+}
+
 /// Gnvim is a graphical UI for neovim.
 #[derive(StructOpt, Debug)]
 #[structopt(
     name = "gnvim",
     version = VERSION,
-    author = "Ville Hakulinen"
+    author = "Ville Hakulinen",
+    global_settings = &[clap::AppSettings::DisableVersion]
 )]
 pub struct Args {
+    /// Prints version information, including the versions of GTK and
+    /// Neovim.
+    #[structopt(short = "V", long = "version")]
+    pub version: bool,
+
     /// Prints the executed neovim command.
     #[structopt(long = "print-nvim-cmd")]
     pub print_nvim_cmd: bool,
 
-    /// Path to neovim binary.
-    #[structopt(long = "nvim", name = "BIN", default_value = "nvim")]
-    pub nvim_path: String,
+    /// Path to neovim binary. Defaults to the `path` of the `[nvim]` section
+    /// in the config file, or `nvim`.
+    #[structopt(long = "nvim", name = "BIN", env = "GNVIM_NVIM")]
+    pub nvim_path: Option<String>,
+
+    /// Runs nvim inside the Flatpak sandbox, instead of on the host.
+    #[structopt(long = "no-host-spawn")]
+    pub no_host_spawn: bool,
+
+    /// Gdk backend to use (e.g. broadway, to show gnvim in a browser).
+    /// Defaults to the `GDK_BACKEND` environment variable, or the first
+    /// available one.
+    #[structopt(
+        long = "backend",
+        value_name = "BACKEND",
+        possible_values = &["x11", "wayland", "broadway"]
+    )]
+    pub backend: Option<String>,
 
     /// Path for gnvim runtime files.
     #[structopt(
@@ -26,10 +60,72 @@ pub struct Args {
     )]
     pub gnvim_rtp: String,
 
-    /// Files to open.
+    /// Files to open. Line and column can be given with `file:line:col`.
     #[structopt(value_name = "FILES")]
     pub open_files: Vec<String>,
 
+    /// Attaches to a running nvim listening on ADDRESS (a unix socket)
+    /// instead of starting a new one. If ADDRESS is omitted, the running
+    /// sessions are listed for picking.
+    #[structopt(long = "attach", value_name = "ADDRESS")]
+    pub attach: Option<Option<String>>,
+
+    /// Opens the files in a new instance instead of the running one.
+    #[structopt(long = "new-instance")]
+    pub new_instance: bool,
+
+    /// Sends keys to the running instance (see `:help nvim_input()`).
+    #[structopt(long = "remote-send", value_name = "KEYS")]
+    pub remote_send: Option<String>,
+
+    /// Evaluates an expression in the running instance and prints the
+    /// result.
+    #[structopt(long = "remote-expr", value_name = "EXPR")]
+    pub remote_expr: Option<String>,
+
+    /// Activates an application action (new-window, new-tab, open-file or
+    /// open-recent) in the running instance. If there is no running
+    /// instance, a new one is started and the action is activated in it.
+    #[structopt(long = "action", value_name = "ACTION")]
+    pub action: Option<String>,
+
+    /// Records the redraw events from nvim to FILE, for replaying them
+    /// later with `--replay`.
+    #[structopt(long = "record", value_name = "FILE")]
+    pub record: Option<String>,
+
+    /// Replays redraw events recorded with `--record` from FILE, instead of
+    /// showing the ones from nvim.
+    #[structopt(
+        long = "replay",
+        value_name = "FILE",
+        conflicts_with = "record"
+    )]
+    pub replay: Option<String>,
+
+    /// Speed multiplier for `--replay` (e.g. 2 for twice as fast).
+    #[structopt(
+        long = "replay-speed",
+        value_name = "SPEED",
+        default_value = "1.0",
+        parse(try_from_str = parse_replay_speed)
+    )]
+    pub replay_speed: f64,
+
+    /// Writes the log to FILE, in addition to stderr. The file is rotated
+    /// once it grows past 5 MB.
+    #[structopt(long = "log-file", value_name = "FILE")]
+    pub log_file: Option<String>,
+
+    /// Log level. Defaults to `info` when logging to a file, and `error`
+    /// otherwise (or the `RUST_LOG` environment variable, if set).
+    #[structopt(
+        long = "log-level",
+        value_name = "LEVEL",
+        possible_values = &["off", "error", "warn", "info", "debug", "trace"]
+    )]
+    pub log_level: Option<log::LevelFilter>,
+
     /// Arguments that are passed to nvim.
     #[structopt(value_name = "ARGS", last = true)]
     pub nvim_args: Vec<String>,
@@ -46,6 +142,16 @@ pub struct Args {
     #[structopt(long = "disable-ext-tabline")]
     pub disable_ext_tabline: bool,
 
+    /// Enables externalized messages (experimental). Implies externalized
+    /// command line.
+    #[structopt(long = "enable-ext-messages")]
+    pub enable_ext_messages: bool,
+
+    /// Disables externalized windows (each window drawn on a grid of its
+    /// own). Nvim then draws the windows, floats included, on one grid.
+    #[structopt(long = "disable-ext-multigrid")]
+    pub disable_ext_multigrid: bool,
+
     /// Instruct GTK to prefer dark theme
     #[structopt(long = "gtk-prefer-dark-theme")]
     pub prefer_dark_theme: bool,
@@ -72,40 +178,93 @@ impl Args {
         }))
     }
 
-    pub fn nvim_ui_opts(&self) -> nvim_rs::UiAttachOptions {
+    /// Options for `nvim_ui_attach`. The cli flags take precedence over
+    /// the config file's `[ext]` section.
+    pub fn nvim_ui_opts(&self, ext: &ExtConfig) -> nvim_rs::UiAttachOptions {
         let mut ui_opts = nvim_rs::UiAttachOptions::new();
         ui_opts.set_rgb(true);
         ui_opts.set_linegrid_external(true);
-        ui_opts.set_multigrid_external(true);
-        ui_opts.set_popupmenu_external(!self.disable_ext_popupmenu);
-        ui_opts.set_tabline_external(!self.disable_ext_tabline);
-        ui_opts.set_cmdline_external(!self.disable_ext_cmdline);
+        ui_opts.set_multigrid_external(
+            !self.disable_ext_multigrid && ext.multigrid.unwrap_or(true),
+        );
+        ui_opts.set_popupmenu_external(
+            !self.disable_ext_popupmenu && ext.popupmenu.unwrap_or(true),
+        );
+        ui_opts.set_tabline_external(
+            !self.disable_ext_tabline && ext.tabline.unwrap_or(true),
+        );
+        ui_opts.set_cmdline_external(
+            !self.disable_ext_cmdline && ext.cmdline.unwrap_or(true),
+        );
+        ui_opts.set_messages_external(
+            self.enable_ext_messages || ext.messages.unwrap_or(false),
+        );
 
         ui_opts
     }
 
-    pub fn nvim_cmd(&self) -> Vec<String> {
-        let mut args: Vec<String> = vec![
-            self.nvim_path.clone(),
+    /// If nvim is started on the host, from inside a Flatpak sandbox.
+    fn host_spawn(&self) -> bool {
+        !self.no_host_spawn && flatpak::is_sandboxed()
+    }
+
+    /// Commands to load our runtime to an nvim that we didn't start.
+    pub fn nvim_runtime_cmd(&self) -> String {
+        // Nvim that we didn't start is outside of our sandbox, if any.
+        let rtp = if flatpak::is_sandboxed() {
+            flatpak::host_path(&self.gnvim_rtp)
+        } else {
+            self.gnvim_rtp.clone()
+        };
+
+        format!(
+            "let g:gnvim=1 | set termguicolors | let &rtp.=',{}' \
+             | runtime! plugin/gnvim.vim",
+            rtp
+        )
+    }
+
+    /// Neovim binary to start.
+    fn nvim_bin<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.nvim_path
+            .as_deref()
+            .or(config.nvim_path.as_deref())
+            .unwrap_or("nvim")
+    }
+
+    /// Command to run the neovim binary, without any arguments.
+    pub fn nvim_bin_cmd(&self, config: &Config) -> Vec<String> {
+        let mut args = vec![];
+        if self.host_spawn() {
+            args.extend(flatpak::host_spawn_cmd());
+        }
+
+        args.push(self.nvim_bin(config).to_string());
+        args
+    }
+
+    pub fn nvim_cmd(&self, config: &Config) -> Vec<String> {
+        let mut args = self.nvim_bin_cmd(config);
+        let mut rtp = self.gnvim_rtp.clone();
+        if self.host_spawn() {
+            rtp = flatpak::host_path(&rtp);
+        }
+
+        args.extend(vec![
             "--embed".to_string(),
             "--cmd".to_string(),
             "let g:gnvim=1".to_string(),
             "--cmd".to_string(),
             "set termguicolors".to_string(),
             "--cmd".to_string(),
-            format!("let &rtp.=',{}'", self.gnvim_rtp),
-        ];
+            format!("let &rtp.=',{}'", rtp),
+        ]);
 
         // Pass arguments from cli to nvim.
         for arg in self.nvim_args.iter() {
             args.push(arg.to_string());
         }
 
-        // Open files "normally" through nvim.
-        for file in self.open_files.iter() {
-            args.push(file.to_string());
-        }
-
         args
     }
 }
@@ -123,3 +282,11 @@ fn parse_geometry(input: &str) -> Result<(i32, i32), String> {
         }
     }
 }
+
+fn parse_replay_speed(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        Ok(_) => Err(String::from("must be greater than zero")),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -0,0 +1,603 @@
+//! Gnvim's own config file, `$XDG_CONFIG_HOME/gnvim/config.ini`.
+//!
+//! The file is in the key file (ini) format:
+//!
+//! ```ini
+//! [nvim]
+//! path=/opt/nvim-nightly/bin/nvim
+//!
+//! [keybindings]
+//! <Primary>plus=zoom-in
+//! F11=fullscreen
+//! # Send <C-S-n> to nvim instead of opening a new window.
+//! <Primary><Shift>n=nvim
+//!
+//! [ext]
+//! popupmenu=false
+//! messages=true
+//!
+//! [cmdline]
+//! floating=true
+//!
+//! [grid]
+//! padding=8
+//! cell_width=widest
+//! cell_width_percent=90
+//! cell_height_percent=110
+//! glyph_overflow=shrink
+//! im_indicator=false
+//!
+//! [fonts]
+//! popupmenu=Cantarell:h11
+//! altfont=Comic Mono
+//! ligatures=false
+//! antialias=gray
+//! hint_style=slight
+//! hex_boxes=false
+//!
+//! [scroll]
+//! easing=ease-in-out-cubic
+//! proportional=true
+//! mouse_speed=100
+//! messages_speed=0
+//!
+//! [popupmenu]
+//! max_items=10
+//! min_width=200
+//! show_menu=false
+//!
+//! [toolbar]
+//! # label=icon command
+//! Save=document-save-symbolic write
+//! Terminal=utilities-terminal-symbolic tabnew | terminal
+//!
+//! [wildmenu]
+//! horizontal=true
+//!
+//! [window]
+//! menubar=true
+//!
+//! [zen]
+//! font_size=2
+//! width=100
+//! ```
+
+use std::path::PathBuf;
+
+use gtk::glib;
+
+use log::{debug, error};
+
+/// Keybinding "action" that removes the GUI binding from a key chord, so it
+/// is sent to nvim instead.
+pub const NVIM_ACTION: &str = "nvim";
+
+const CMDLINE_GROUP: &str = "cmdline";
+const EXT_GROUP: &str = "ext";
+const FONTS_GROUP: &str = "fonts";
+const GRID_GROUP: &str = "grid";
+const KEYBINDINGS_GROUP: &str = "keybindings";
+const NVIM_GROUP: &str = "nvim";
+const POPUPMENU_GROUP: &str = "popupmenu";
+const SCROLL_GROUP: &str = "scroll";
+const TOOLBAR_GROUP: &str = "toolbar";
+const WILDMENU_GROUP: &str = "wildmenu";
+const WINDOW_GROUP: &str = "window";
+const ZEN_GROUP: &str = "zen";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    /// Neovim binary to start, unless given on the command line.
+    pub nvim_path: Option<String>,
+    /// Key chords (in GTK's accelerator format) and the names of the actions
+    /// they're bound to, in the order they appear in the file.
+    pub keybindings: Vec<(String, String)>,
+    pub ext: ExtConfig,
+    /// If the cmdline is shown as a floating panel at the upper third of the
+    /// window, instead of at the top edge.
+    pub cmdline_floating: bool,
+    pub fonts: FontsConfig,
+    /// Padding (in pixels) around the grids.
+    pub grid_padding: i32,
+    /// Width of the grid cells: `font`, `widest` or a width in pixels.
+    pub grid_cell_width: Option<String>,
+    /// Width of the grid cells, in percents of the width from the font.
+    pub grid_cell_width_percent: Option<u32>,
+    /// Height of the grid cells, in percents of the height from the font.
+    pub grid_cell_height_percent: Option<u32>,
+    /// What to do with glyphs that don't fit in their cells: `overflow`,
+    /// `clip` or `shrink`.
+    pub grid_glyph_overflow: Option<String>,
+    /// If the input method indicator is hidden (`im_indicator=false`).
+    pub disable_im_indicator: bool,
+    pub popupmenu: PopupmenuConfig,
+    pub scroll: ScrollConfig,
+    /// Buttons of the toolbar, in the order they appear in the file. No
+    /// buttons, no toolbar.
+    pub toolbar: Vec<ToolbarButton>,
+    /// If the wildmenu is shown as a single row above the cmdline.
+    pub wildmenu_horizontal: bool,
+    /// If the window has a menubar.
+    pub menubar: bool,
+    pub zen: ZenConfig,
+}
+
+/// Nvim's UI components that are externalized (drawn by us), see
+/// `:h ui-ext-options`. `None` keeps the default.
+#[derive(Debug, Default, PartialEq)]
+pub struct ExtConfig {
+    pub popupmenu: Option<bool>,
+    pub cmdline: Option<bool>,
+    pub tabline: Option<bool>,
+    pub messages: Option<bool>,
+    pub multigrid: Option<bool>,
+}
+
+/// Fonts (in `guifont` format) of the UI components, to use instead of the
+/// grid's font.
+#[derive(Debug, Default, PartialEq)]
+pub struct FontsConfig {
+    pub cmdline: Option<String>,
+    pub popupmenu: Option<String>,
+    pub tabline: Option<String>,
+    /// Font for highlights with the `altfont` attribute. Only the family is
+    /// used, the size is the grid's.
+    pub altfont: Option<String>,
+    /// If the font's ligatures are disabled (`ligatures=false`).
+    pub disable_ligatures: bool,
+    /// If missing glyphs are drawn as the fonts draw them, instead of as
+    /// hex boxes (`hex_boxes=false`).
+    pub disable_hex_boxes: bool,
+    /// Antialiasing of the grids' text: `none`, `gray` or `subpixel`.
+    pub antialias: Option<String>,
+    /// Hinting of the grids' text: `none`, `slight`, `medium` or `full`.
+    pub hint_style: Option<String>,
+    /// Order of the subpixels for subpixel antialiasing: `rgb`, `bgr`,
+    /// `vrgb` or `vbgr`.
+    pub subpixel_order: Option<String>,
+}
+
+/// Popupmenu's settings. `None` keeps the default.
+#[derive(Debug, Default, PartialEq)]
+pub struct PopupmenuConfig {
+    /// Width (in pixels) when the details view is not open. Maximum width,
+    /// if `min_width` is set.
+    pub width: Option<i32>,
+    /// Width (in pixels) when the details view is open.
+    pub width_details: Option<i32>,
+    /// Minimum width (in pixels) when the details view is not open.
+    pub min_width: Option<i32>,
+    /// Maximum number of visible items.
+    pub max_items: Option<i32>,
+    /// If the `menu` of the items is shown.
+    pub show_menu: Option<bool>,
+    /// If the `menu` is shown for all items, and not just the selected one.
+    pub show_menu_on_all_items: Option<bool>,
+}
+
+/// Grid scroll animation's settings.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScrollConfig {
+    /// Name of the easing curve (e.g. `linear`).
+    pub easing: Option<String>,
+    /// If the animation's duration is proportional to the scrolled
+    /// distance.
+    pub proportional: bool,
+    /// Speed (in ms) of the scrolls made with the mouse.
+    pub mouse_speed: Option<i64>,
+    /// Speed (in ms) of the message grid's scrolls.
+    pub messages_speed: Option<i64>,
+}
+
+/// Toolbar button, which runs an nvim command when clicked.
+#[derive(Debug, PartialEq)]
+pub struct ToolbarButton {
+    /// Label, shown as the button's tooltip.
+    pub label: String,
+    /// Name of the icon.
+    pub icon: String,
+    /// Ex command to run.
+    pub command: String,
+}
+
+/// Zen mode's settings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZenConfig {
+    /// How much the font size is increased.
+    pub font_size: f32,
+    /// Width (in columns) of the centered grid.
+    pub width: u64,
+}
+
+impl Default for ZenConfig {
+    fn default() -> Self {
+        ZenConfig {
+            font_size: 0.0,
+            width: 100,
+        }
+    }
+}
+
+impl Config {
+    /// Path to the config file.
+    pub fn path() -> PathBuf {
+        glib::user_config_dir().join("gnvim").join("config.ini")
+    }
+
+    /// Loads the config file. Missing or broken config file results in the
+    /// default config.
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !path.exists() {
+            debug!("No config file at {:?}", path);
+            return Self::default();
+        }
+
+        let kf = glib::KeyFile::new();
+        if let Err(err) = kf.load_from_file(&path, glib::KeyFileFlags::NONE) {
+            error!("Failed to load config file {:?}: {}", path, err);
+            return Self::default();
+        }
+
+        Self::from_key_file(&kf)
+    }
+
+    fn from_key_file(kf: &glib::KeyFile) -> Self {
+        let keybindings = kf
+            .keys(KEYBINDINGS_GROUP)
+            .map(|(keys, _)| keys)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|accel| {
+                let action = kf.string(KEYBINDINGS_GROUP, &accel).ok()?;
+                Some((accel.to_string(), action.trim().to_string()))
+            })
+            .collect();
+
+        let ext = |key| kf.boolean(EXT_GROUP, key).ok();
+        let ext = ExtConfig {
+            popupmenu: ext("popupmenu"),
+            cmdline: ext("cmdline"),
+            tabline: ext("tabline"),
+            messages: ext("messages"),
+            multigrid: ext("multigrid"),
+        };
+
+        let font = |key| {
+            kf.string(FONTS_GROUP, key)
+                .ok()
+                .map(|font| font.trim().to_string())
+        };
+        let fonts = FontsConfig {
+            cmdline: font("cmdline"),
+            popupmenu: font("popupmenu"),
+            tabline: font("tabline"),
+            altfont: font("altfont"),
+            disable_ligatures: !kf
+                .boolean(FONTS_GROUP, "ligatures")
+                .unwrap_or(true),
+            disable_hex_boxes: !kf
+                .boolean(FONTS_GROUP, "hex_boxes")
+                .unwrap_or(true),
+            antialias: font("antialias"),
+            hint_style: font("hint_style"),
+            subpixel_order: font("subpixel_order"),
+        };
+
+        let int = |key| kf.integer(POPUPMENU_GROUP, key).ok();
+        let boolean = |key| kf.boolean(POPUPMENU_GROUP, key).ok();
+        let popupmenu = PopupmenuConfig {
+            width: int("width"),
+            width_details: int("width_details"),
+            min_width: int("min_width"),
+            max_items: int("max_items"),
+            show_menu: boolean("show_menu"),
+            show_menu_on_all_items: boolean("menu_on_all_items"),
+        };
+
+        let speed = |key| {
+            kf.integer(SCROLL_GROUP, key)
+                .ok()
+                .map(|speed| i64::from(speed.max(0)))
+        };
+        let scroll = ScrollConfig {
+            easing: kf
+                .string(SCROLL_GROUP, "easing")
+                .ok()
+                .map(|easing| easing.trim().to_string()),
+            proportional: kf
+                .boolean(SCROLL_GROUP, "proportional")
+                .unwrap_or(false),
+            mouse_speed: speed("mouse_speed"),
+            messages_speed: speed("messages_speed"),
+        };
+
+        let toolbar = kf
+            .keys(TOOLBAR_GROUP)
+            .map(|(keys, _)| keys)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|label| {
+                let value = kf.string(TOOLBAR_GROUP, &label).ok()?;
+                let (icon, command) = match value.trim().split_once(' ') {
+                    Some((icon, command)) => (icon, command.trim()),
+                    None => {
+                        error!("Toolbar button '{}' has no command", label);
+                        return None;
+                    }
+                };
+
+                Some(ToolbarButton {
+                    label: label.to_string(),
+                    icon: icon.to_string(),
+                    command: command.to_string(),
+                })
+            })
+            .collect();
+
+        let percent = |key| {
+            kf.integer(GRID_GROUP, key)
+                .ok()
+                .filter(|percent| *percent > 0)
+                .map(|percent| percent as u32)
+        };
+
+        let default_zen = ZenConfig::default();
+        let zen = ZenConfig {
+            font_size: kf
+                .double(ZEN_GROUP, "font_size")
+                .map(|size| size as f32)
+                .unwrap_or(default_zen.font_size),
+            width: kf.uint64(ZEN_GROUP, "width").unwrap_or(default_zen.width),
+        };
+
+        Config {
+            nvim_path: kf
+                .string(NVIM_GROUP, "path")
+                .ok()
+                .map(|path| path.trim().to_string())
+                .filter(|path| !path.is_empty()),
+            keybindings,
+            ext,
+            cmdline_floating: kf
+                .boolean(CMDLINE_GROUP, "floating")
+                .unwrap_or(false),
+            fonts,
+            grid_padding: kf.integer(GRID_GROUP, "padding").unwrap_or(0).max(0),
+            grid_cell_width: kf
+                .string(GRID_GROUP, "cell_width")
+                .ok()
+                .map(|width| width.trim().to_string()),
+            grid_cell_width_percent: percent("cell_width_percent"),
+            grid_cell_height_percent: percent("cell_height_percent"),
+            grid_glyph_overflow: kf
+                .string(GRID_GROUP, "glyph_overflow")
+                .ok()
+                .map(|overflow| overflow.trim().to_string()),
+            disable_im_indicator: !kf
+                .boolean(GRID_GROUP, "im_indicator")
+                .unwrap_or(true),
+            popupmenu,
+            scroll,
+            toolbar,
+            wildmenu_horizontal: kf
+                .boolean(WILDMENU_GROUP, "horizontal")
+                .unwrap_or(false),
+            menubar: kf.boolean(WINDOW_GROUP, "menubar").unwrap_or(false),
+            zen,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(data: &str) -> Config {
+        let kf = glib::KeyFile::new();
+        kf.load_from_data(data, glib::KeyFileFlags::NONE).unwrap();
+        Config::from_key_file(&kf)
+    }
+
+    #[test]
+    fn test_keybindings() {
+        let config = parse(
+            "# comment\n\
+             [keybindings]\n\
+             <Primary>plus=zoom-in\n\
+             F11 = fullscreen\n\
+             <Primary><Shift>n=nvim\n\
+             [other]\n\
+             foo=bar\n",
+        );
+
+        assert_eq!(
+            config.keybindings,
+            vec![
+                (String::from("<Primary>plus"), String::from("zoom-in")),
+                (String::from("F11"), String::from("fullscreen")),
+                (String::from("<Primary><Shift>n"), String::from("nvim")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nvim() {
+        assert_eq!(
+            parse("[nvim]\npath = /opt/nvim/bin/nvim\n").nvim_path,
+            Some(String::from("/opt/nvim/bin/nvim"))
+        );
+        assert_eq!(parse("[nvim]\npath=\n").nvim_path, None);
+    }
+
+    #[test]
+    fn test_ext() {
+        assert_eq!(
+            parse("[ext]\npopupmenu=false\nmessages=true\ntabline=foo\n").ext,
+            ExtConfig {
+                popupmenu: Some(false),
+                messages: Some(true),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_cmdline() {
+        assert!(parse("[cmdline]\nfloating=true\n").cmdline_floating);
+        assert!(!parse("[cmdline]\nfloating=0\n").cmdline_floating);
+    }
+
+    #[test]
+    fn test_fonts() {
+        let config = parse(
+            "[fonts]\n\
+             popupmenu = Cantarell:h11\n\
+             tabline=Sans\n\
+             altfont=Comic Mono\n\
+             ligatures=false\n\
+             hex_boxes=false\n\
+             antialias=subpixel\n\
+             hint_style = slight\n\
+             subpixel_order=bgr\n",
+        );
+
+        assert_eq!(
+            config.fonts,
+            FontsConfig {
+                popupmenu: Some(String::from("Cantarell:h11")),
+                tabline: Some(String::from("Sans")),
+                altfont: Some(String::from("Comic Mono")),
+                disable_ligatures: true,
+                disable_hex_boxes: true,
+                antialias: Some(String::from("subpixel")),
+                hint_style: Some(String::from("slight")),
+                subpixel_order: Some(String::from("bgr")),
+                ..Default::default()
+            }
+        );
+
+        assert!(!parse("[fonts]\nligatures=true\n").fonts.disable_ligatures);
+        assert!(!parse("").fonts.disable_ligatures);
+    }
+
+    #[test]
+    fn test_grid() {
+        assert_eq!(parse("[grid]\npadding=8\n").grid_padding, 8);
+        assert_eq!(parse("[grid]\npadding=-1\n").grid_padding, 0);
+        assert_eq!(
+            parse("[grid]\ncell_width = widest\n").grid_cell_width,
+            Some(String::from("widest"))
+        );
+        assert_eq!(parse("").grid_cell_width, None);
+
+        let config =
+            parse("[grid]\ncell_width_percent=90\ncell_height_percent=0\n");
+        assert_eq!(config.grid_cell_width_percent, Some(90));
+        assert_eq!(config.grid_cell_height_percent, None);
+        assert_eq!(
+            parse("[grid]\nglyph_overflow=clip\n").grid_glyph_overflow,
+            Some(String::from("clip"))
+        );
+        assert!(parse("[grid]\nim_indicator=false\n").disable_im_indicator);
+        assert!(!parse("").disable_im_indicator);
+    }
+
+    #[test]
+    fn test_popupmenu() {
+        let config = parse(
+            "[popupmenu]\n\
+             max_items=10\n\
+             min_width=200\n\
+             show_menu=false\n\
+             width=foo\n",
+        );
+
+        assert_eq!(
+            config.popupmenu,
+            PopupmenuConfig {
+                max_items: Some(10),
+                min_width: Some(200),
+                show_menu: Some(false),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_scroll() {
+        let config = parse(
+            "[scroll]\n\
+             easing = linear\n\
+             proportional=true\n\
+             messages_speed=0\n\
+             mouse_speed=-1\n",
+        );
+
+        assert_eq!(
+            config.scroll,
+            ScrollConfig {
+                easing: Some(String::from("linear")),
+                proportional: true,
+                mouse_speed: Some(0),
+                messages_speed: Some(0),
+            }
+        );
+        assert!(!parse("[scroll]\nproportional=foo\n").scroll.proportional);
+    }
+
+    #[test]
+    fn test_toolbar() {
+        let config = parse(
+            "[toolbar]\n\
+             Save=document-save-symbolic write\n\
+             Terminal = utilities-terminal-symbolic  tabnew | terminal\n\
+             Broken=edit-undo-symbolic\n",
+        );
+
+        assert_eq!(
+            config.toolbar,
+            vec![
+                ToolbarButton {
+                    label: String::from("Save"),
+                    icon: String::from("document-save-symbolic"),
+                    command: String::from("write"),
+                },
+                ToolbarButton {
+                    label: String::from("Terminal"),
+                    icon: String::from("utilities-terminal-symbolic"),
+                    command: String::from("tabnew | terminal"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wildmenu() {
+        assert!(parse("[wildmenu]\nhorizontal=true\n").wildmenu_horizontal);
+        assert!(!parse("[wildmenu]\nhorizontal=foo\n").wildmenu_horizontal);
+    }
+
+    #[test]
+    fn test_window() {
+        assert!(parse("[window]\nmenubar=true\n").menubar);
+        assert!(!parse("[window]\nmenubar=foo\n").menubar);
+    }
+
+    #[test]
+    fn test_zen() {
+        assert_eq!(
+            parse("[zen]\nfont_size=2\n").zen,
+            ZenConfig {
+                font_size: 2.0,
+                width: 100,
+            }
+        );
+        assert_eq!(parse("[zen]\nwidth=foo\n").zen, ZenConfig::default());
+    }
+
+    #[test]
+    fn test_no_keybindings() {
+        assert_eq!(parse("[other]\nfoo=bar\n"), Config::default());
+    }
+}
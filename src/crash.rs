@@ -0,0 +1,208 @@
+//! Crash reporting. When we panic, a dialog with the error and its
+//! backtrace is shown, instead of the window just disappearing.
+//!
+//! The dialog is shown by a new gnvim process, started in a report-only
+//! mode. The panicking one can't show it, as its main loop would run the
+//! sources that panicked (e.g. with the UI state borrowed) again.
+
+use std::backtrace::Backtrace;
+use std::env;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use gtk::gdk;
+use gtk::prelude::*;
+
+use crate::args;
+use crate::nvim_bridge;
+
+/// Where the issues are reported.
+const ISSUES_URL: &str = "https://github.com/vhakulinen/gnvim/issues/new";
+
+/// Environment variable through which the crashed process tells the
+/// reporting one where the report is.
+const REPORT_ENV: &str = "GNVIM_CRASH_REPORT";
+
+/// Set once the report is made, so that panics while unwinding from the
+/// first one don't make more.
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the panic hook that shows the crash dialog. The panic is still
+/// printed to stderr too.
+pub fn install_hook() {
+    let default_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+        nvim_bridge::record::flush();
+
+        // Only for the GUI, not e.g. for `--version`.
+        if !gtk::is_initialized() || REPORTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let backtrace = Backtrace::force_capture().to_string();
+        if let Err(err) = spawn_reporter(
+            &panic_message(info),
+            &issue_report(info, &backtrace),
+        ) {
+            eprintln!("Failed to show the crash report: {}", err);
+        }
+    }));
+}
+
+/// Writes the report to a file, and starts a new gnvim to show it.
+fn spawn_reporter(message: &str, report: &str) -> io::Result<()> {
+    let path =
+        env::temp_dir().join(format!("gnvim-crash-{}.txt", std::process::id()));
+    // The message is on the first line, the report on the rest.
+    fs::write(&path, format!("{}\n{}", message.replace('\n', " "), report))?;
+
+    Command::new(env::current_exe()?)
+        .env(REPORT_ENV, &path)
+        .spawn()?;
+
+    Ok(())
+}
+
+/// Shows the crash report and removes its file, if we were started to show
+/// one (see `install_hook`). Returns true if we were.
+pub fn show_report() -> bool {
+    let path = match env::var_os(REPORT_ENV) {
+        Some(path) => PathBuf::from(path),
+        None => return false,
+    };
+    env::remove_var(REPORT_ENV);
+
+    let data = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    match data {
+        Ok(data) => {
+            let (message, report) =
+                data.split_once('\n').unwrap_or((&data, ""));
+            match gtk::init() {
+                Ok(()) => show_dialog(message, report),
+                Err(err) => eprintln!("Failed to initialize gtk: {}", err),
+            }
+        }
+        Err(err) => {
+            eprintln!("Failed to read the crash report: {}", err);
+        }
+    }
+
+    true
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+
+    match info.location() {
+        Some(location) => format!("{} ({})", payload, location),
+        None => payload.to_string(),
+    }
+}
+
+fn issue_report(info: &PanicHookInfo, backtrace: &str) -> String {
+    format_issue_report(args::version(), &panic_message(info), backtrace)
+}
+
+/// Formats an issue report, ready to be pasted to a new issue.
+fn format_issue_report(
+    version: &str,
+    message: &str,
+    backtrace: &str,
+) -> String {
+    format!(
+        "**Gnvim crashed**\n\
+         \n\
+         Version: {}\n\
+         OS: {}\n\
+         \n\
+         Error: `{}`\n\
+         \n\
+         <details><summary>Backtrace</summary>\n\
+         \n\
+         ```\n\
+         {}\n\
+         ```\n\
+         </details>\n\
+         \n\
+         **Steps to reproduce**\n\
+         \n",
+        version,
+        std::env::consts::OS,
+        message,
+        backtrace.trim_end(),
+    )
+}
+
+/// Shows the crash dialog. Blocks until the dialog is closed.
+fn show_dialog(message: &str, report: &str) {
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::None,
+        "GNvim crashed",
+    );
+    dialog.set_secondary_text(Some(&format!(
+        "{}\n\nPlease report this at {}",
+        message, ISSUES_URL
+    )));
+    dialog.add_button("_Copy Issue Report", gtk::ResponseType::Apply);
+    dialog.add_button("_Close", gtk::ResponseType::Close);
+    dialog.set_default_response(gtk::ResponseType::Close);
+
+    let view = gtk::TextView::new();
+    view.set_editable(false);
+    view.set_monospace(true);
+    view.buffer().unwrap().set_text(report);
+
+    let scroll = gtk::ScrolledWindow::new(
+        None::<&gtk::Adjustment>,
+        None::<&gtk::Adjustment>,
+    );
+    scroll.set_size_request(600, 300);
+    scroll.add(&view);
+
+    let expander = gtk::Expander::new(Some("Details"));
+    expander.add(&scroll);
+    if let Ok(area) = dialog.message_area().downcast::<gtk::Box>() {
+        area.pack_start(&expander, true, true, 0);
+    }
+    dialog.show_all();
+
+    while dialog.run() == gtk::ResponseType::Apply {
+        let clipboard = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        clipboard.set_text(report);
+        // Keep the report in the clipboard after we're gone.
+        clipboard.store();
+    }
+
+    dialog.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_issue_report() {
+        let report =
+            format_issue_report("v0.1.0", "oops (src/main.rs:1:1)", "bt\n");
+
+        assert!(report.contains("Version: v0.1.0\n"));
+        assert!(report.contains("Error: `oops (src/main.rs:1:1)`\n"));
+        assert!(report.contains("```\nbt\n```\n"));
+    }
+}
@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use log::{debug, error};
+use nvim_rs::Value;
+
+use crate::error::Error;
+use crate::files::{self, FileArg};
+use crate::nvim_gio::GioNeovim;
+use crate::thread_guard::ThreadGuard;
+use crate::ui::common::spawn_local;
+
+/// Well known name on the session bus. The first running gnvim instance
+/// owns the name, later instances are queued behind it.
+pub const BUS_NAME: &str = "org.gnvim";
+/// Object path where our interface is exported.
+pub const OBJECT_PATH: &str = "/org/gnvim";
+/// Name of our interface.
+pub const INTERFACE_NAME: &str = "org.gnvim";
+
+/// Our desktop file, that docks know our launcher entry by.
+const LAUNCHER_APP_URI: &str = "application://gnvim.desktop";
+/// Object path of our launcher entry.
+const LAUNCHER_ENTRY_PATH: &str = "/com/canonical/unity/launcherentry/gnvim";
+/// Interface of the launcher entry API.
+const LAUNCHER_ENTRY_INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+
+const INTROSPECTION_XML: &str = r#"
+<node>
+  <interface name="org.gnvim">
+    <method name="OpenFiles">
+      <arg type="as" name="files" direction="in"/>
+    </method>
+    <method name="Eval">
+      <arg type="s" name="expr" direction="in"/>
+      <arg type="s" name="result" direction="out"/>
+    </method>
+    <method name="SendKeys">
+      <arg type="s" name="keys" direction="in"/>
+    </method>
+    <method name="Focus"/>
+    <method name="NewWindow"/>
+    <method name="ActivateAction">
+      <arg type="s" name="action" direction="in"/>
+    </method>
+  </interface>
+</node>
+"#;
+
+/// Exports the `org.gnvim` interface on the session bus. The interface is
+/// removed from the bus once `window` is destroyed.
+pub fn export(
+    window: &gtk::ApplicationWindow,
+    nvim: GioNeovim,
+) -> Result<(), Error> {
+    let conn = gio::bus_get_sync(gio::BusType::Session, gio::NONE_CANCELLABLE)?;
+
+    let node = gio::DBusNodeInfo::for_xml(INTROSPECTION_XML)?;
+    let interface = node
+        .lookup_interface(INTERFACE_NAME)
+        .expect("interface missing from the introspection data");
+
+    // D-Bus callbacks are required to be Send + Sync, but they're always
+    // called on the main thread (the one that registered the object).
+    let target = ThreadGuard::new((window.clone(), nvim));
+    let registration_id = conn.register_object(
+        OBJECT_PATH,
+        &interface,
+        move |_, _, _, _, method, params, invocation| {
+            let target = target.borrow();
+            handle_method_call(
+                &target.0, &target.1, method, params, invocation,
+            );
+        },
+        // We don't export any properties.
+        |_, _, _, _, _| glib::Variant::from_tuple(&[]),
+        |_, _, _, _, _, _| false,
+    )?;
+
+    let owner_id = gio::bus_own_name_on_connection(
+        &conn,
+        BUS_NAME,
+        gio::BusNameOwnerFlags::NONE,
+        |_, name| debug!("Acquired dbus name '{}'", name),
+        |_, name| debug!("Lost dbus name '{}'", name),
+    );
+
+    let ids = RefCell::new(Some((registration_id, owner_id)));
+    window.connect_destroy(move |_| {
+        if let Some((registration_id, owner_id)) = ids.borrow_mut().take() {
+            gio::bus_unown_name(owner_id);
+            if let Err(err) = conn.unregister_object(registration_id) {
+                error!("Failed to unregister dbus object: {}", err);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Calls `method` on the running gnvim instance, if there is one. Won't
+/// start a new instance.
+pub fn call_running(
+    method: &str,
+    params: Option<&glib::Variant>,
+) -> Result<glib::Variant, Error> {
+    let conn = gio::bus_get_sync(gio::BusType::Session, gio::NONE_CANCELLABLE)?;
+    let res = conn.call_sync(
+        Some(BUS_NAME),
+        OBJECT_PATH,
+        INTERFACE_NAME,
+        method,
+        params,
+        None,
+        gio::DBusCallFlags::NO_AUTO_START,
+        -1,
+        gio::NONE_CANCELLABLE,
+    )?;
+
+    Ok(res)
+}
+
+/// Sends `keys` as input to the running gnvim instance.
+pub fn send_keys_to_running(keys: &str) -> Result<(), Error> {
+    call_running("SendKeys", Some(&(keys,).to_variant()))?;
+    Ok(())
+}
+
+/// Evaluates `expr` in the running gnvim instance.
+pub fn eval_in_running(expr: &str) -> Result<String, Error> {
+    let res = call_running("Eval", Some(&(expr,).to_variant()))?;
+    let (res,) = res.get::<(String,)>().unwrap_or_default();
+    Ok(res)
+}
+
+/// Activates application action `action` in the running gnvim instance.
+pub fn activate_in_running(action: &str) -> Result<(), Error> {
+    call_running("ActivateAction", Some(&(action,).to_variant()))?;
+    Ok(())
+}
+
+/// Opens `files` in the running gnvim instance and brings its window to
+/// the front.
+pub fn open_in_running(files: &[String]) -> Result<(), Error> {
+    call_running("OpenFiles", Some(&(files.to_vec(),).to_variant()))?;
+    call_running("Focus", None)?;
+    Ok(())
+}
+
+fn handle_method_call(
+    window: &gtk::ApplicationWindow,
+    nvim: &GioNeovim,
+    method: &str,
+    params: glib::Variant,
+    invocation: gio::DBusMethodInvocation,
+) {
+    match method {
+        "OpenFiles" => {
+            let (files,) = params.get::<(Vec<String>,)>().unwrap_or_default();
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                for file in files {
+                    let arg = FileArg::parse(&file);
+                    if let Err(err) = files::open_file(&nvim, &arg).await {
+                        return invocation.return_dbus_error(
+                            "org.gnvim.Error.Failed",
+                            &format!("Failed to open '{}': {}", file, err),
+                        );
+                    }
+                }
+
+                invocation.return_value(None);
+            });
+        }
+        "Eval" => {
+            let (expr,) = params.get::<(String,)>().unwrap_or_default();
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                match nvim.eval(&expr).await {
+                    Ok(val) => invocation.return_value(Some(
+                        &(value_to_string(&val),).to_variant(),
+                    )),
+                    Err(err) => invocation.return_dbus_error(
+                        "org.gnvim.Error.Failed",
+                        &err.to_string(),
+                    ),
+                }
+            });
+        }
+        "SendKeys" => {
+            let (keys,) = params.get::<(String,)>().unwrap_or_default();
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                match nvim.input(&keys).await {
+                    Ok(_) => invocation.return_value(None),
+                    Err(err) => invocation.return_dbus_error(
+                        "org.gnvim.Error.Failed",
+                        &err.to_string(),
+                    ),
+                }
+            });
+        }
+        "Focus" => {
+            window.present();
+            invocation.return_value(None);
+        }
+        "NewWindow" => match new_instance() {
+            Ok(()) => invocation.return_value(None),
+            Err(err) => invocation.return_dbus_error(
+                "org.gnvim.Error.Failed",
+                &format!("Failed to start new instance: {}", err),
+            ),
+        },
+        "ActivateAction" => {
+            let (action,) = params.get::<(String,)>().unwrap_or_default();
+            let app = window.application();
+            match app.filter(|app| app.lookup_action(&action).is_some()) {
+                Some(app) => {
+                    window.present();
+                    app.activate_action(&action, None);
+                    invocation.return_value(None);
+                }
+                None => invocation.return_dbus_error(
+                    "org.gnvim.Error.UnknownAction",
+                    &format!("Unknown action: {}", action),
+                ),
+            }
+        }
+        _ => invocation.return_dbus_error(
+            "org.freedesktop.DBus.Error.UnknownMethod",
+            &format!("Unknown method: {}", method),
+        ),
+    }
+}
+
+/// Shows `progress` (a fraction from 0.0 to 1.0) on our launcher entry, or
+/// hides the progress if it's `None`. The launcher entry is the icon in a
+/// dock or a taskbar that supports Unity's LauncherEntry API.
+pub fn set_launcher_progress(progress: Option<f64>) -> Result<(), Error> {
+    let props = glib::VariantDict::new(None);
+    props.insert("progress", &progress.unwrap_or(0.0).clamp(0.0, 1.0));
+    props.insert("progress-visible", &progress.is_some());
+    update_launcher_entry(&props)
+}
+
+/// Sets the urgent state of our launcher entry.
+pub fn set_launcher_urgent(urgent: bool) -> Result<(), Error> {
+    let props = glib::VariantDict::new(None);
+    props.insert("urgent", &urgent);
+    update_launcher_entry(&props)
+}
+
+fn update_launcher_entry(props: &glib::VariantDict) -> Result<(), Error> {
+    let conn = gio::bus_get_sync(gio::BusType::Session, gio::NONE_CANCELLABLE)?;
+    let params = glib::Variant::from_tuple(&[
+        LAUNCHER_APP_URI.to_variant(),
+        props.end(),
+    ]);
+    conn.emit_signal(
+        None,
+        LAUNCHER_ENTRY_PATH,
+        LAUNCHER_ENTRY_INTERFACE,
+        "Update",
+        Some(&params),
+    )?;
+
+    Ok(())
+}
+
+/// Starts a new gnvim process.
+pub fn new_instance() -> std::io::Result<()> {
+    let exe = std::env::current_exe()?;
+    std::process::Command::new(exe).spawn()?;
+    Ok(())
+}
+
+fn value_to_string(val: &Value) -> String {
+    val.as_str()
+        .map(String::from)
+        .unwrap_or_else(|| val.to_string())
+}
@@ -1,10 +1,14 @@
+use gtk::glib;
+
 use crate::nvim_gio;
 
 #[derive(Debug)]
 pub enum Error {
     Start(nvim_gio::Error),
     Call(Box<nvim_rs::error::CallError>),
+    Setup(String),
     Cairo(gtk::cairo::Error),
+    Glib(glib::Error),
     GridDoesNotExist(i64),
     FailedToCreateSurface(),
     GetPangoMetrics(),
@@ -28,3 +32,9 @@ impl From<gtk::cairo::Error> for Error {
         Error::Cairo(arg)
     }
 }
+
+impl From<glib::Error> for Error {
+    fn from(arg: glib::Error) -> Self {
+        Error::Glib(arg)
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use nvim_rs::Value;
+
+use crate::nvim_gio::GioNeovim;
+
+/// File to open, with an optional cursor position.
+#[derive(Debug, PartialEq)]
+pub struct FileArg {
+    pub path: String,
+    /// Line number (1-based).
+    pub line: Option<u64>,
+    /// Column number (1-based).
+    pub col: Option<u64>,
+}
+
+impl FileArg {
+    /// Parses `arg` in `path[:line[:col]]` form (e.g. `file.rs:42`). If
+    /// a file named `arg` exists, the suffix is not parsed.
+    pub fn parse(arg: &str) -> Self {
+        let plain = FileArg {
+            path: arg.to_string(),
+            line: None,
+            col: None,
+        };
+
+        if Path::new(arg).exists() {
+            return plain;
+        }
+
+        let mut parts = arg.rsplitn(3, ':');
+        let last = parts.next().and_then(|p| p.parse::<u64>().ok());
+        let second = parts.next();
+        let rest = parts.next();
+
+        match (rest, second, last) {
+            // path:line:col
+            (Some(path), Some(line), Some(col)) if !path.is_empty() => {
+                match line.parse::<u64>() {
+                    Ok(line) => FileArg {
+                        path: path.to_string(),
+                        line: Some(line),
+                        col: Some(col),
+                    },
+                    // Not a line number, so only the last part is a line.
+                    Err(_) => FileArg {
+                        path: format!("{}:{}", path, line),
+                        line: Some(col),
+                        col: None,
+                    },
+                }
+            }
+            // path:line
+            (None, Some(path), Some(line)) if !path.is_empty() => FileArg {
+                path: path.to_string(),
+                line: Some(line),
+                col: None,
+            },
+            _ => plain,
+        }
+    }
+}
+
+/// Opens `file` in nvim and moves the cursor to the file's position,
+/// if one was given.
+pub async fn open_file(
+    nvim: &GioNeovim,
+    file: &FileArg,
+) -> Result<(), Box<nvim_rs::error::CallError>> {
+    let escaped = nvim
+        .call_function("fnameescape", vec![file.path.as_str().into()])
+        .await?;
+    nvim.command(&format!("edit {}", escaped.as_str().unwrap_or_default()))
+        .await?;
+
+    if let Some(line) = file.line {
+        nvim.call_function(
+            "cursor",
+            vec![Value::from(line), Value::from(file.col.unwrap_or(1))],
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_arg(path: &str, line: Option<u64>, col: Option<u64>) -> FileArg {
+        FileArg {
+            path: path.to_string(),
+            line,
+            col,
+        }
+    }
+
+    #[test]
+    fn test_file_arg_parse() {
+        assert_eq!(FileArg::parse("foo.rs"), file_arg("foo.rs", None, None));
+        assert_eq!(
+            FileArg::parse("foo.rs:42"),
+            file_arg("foo.rs", Some(42), None)
+        );
+        assert_eq!(
+            FileArg::parse("foo.rs:42:7"),
+            file_arg("foo.rs", Some(42), Some(7))
+        );
+        assert_eq!(FileArg::parse("a:b:42"), file_arg("a:b", Some(42), None));
+        assert_eq!(
+            FileArg::parse("foo.rs:bar"),
+            file_arg("foo.rs:bar", None, None)
+        );
+        assert_eq!(FileArg::parse(":42"), file_arg(":42", None, None));
+    }
+
+    #[test]
+    fn test_file_arg_parse_existing_file() {
+        // Existing files are used as is.
+        let dir = std::env::temp_dir().join("gnvim-test-file-arg");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("file:12");
+        std::fs::write(&path, "").unwrap();
+
+        let path = path.to_str().unwrap();
+        assert_eq!(FileArg::parse(path), file_arg(path, None, None));
+    }
+}
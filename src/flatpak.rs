@@ -0,0 +1,75 @@
+//! Running inside a Flatpak sandbox.
+//!
+//! The sandbox doesn't have the user's nvim (nor its plugins, or the tools
+//! they use), so by default nvim is started on the host with
+//! `flatpak-spawn --host`. Our runtime files are then read by the host's
+//! nvim, from where the sandbox's `/app` is on the host.
+
+use std::path::Path;
+
+use gtk::glib;
+
+/// File that flatpak adds to the root of every sandbox.
+const INFO_FILE: &str = "/.flatpak-info";
+
+/// If we're running inside a Flatpak sandbox.
+pub fn is_sandboxed() -> bool {
+    Path::new(INFO_FILE).exists()
+}
+
+/// Command prefix for running a command on the host. The host process is
+/// killed when we exit.
+pub fn host_spawn_cmd() -> Vec<String> {
+    vec![
+        "flatpak-spawn".to_string(),
+        "--host".to_string(),
+        "--watch-bus".to_string(),
+    ]
+}
+
+/// Maps `path` in the sandbox to the same file on the host, for paths
+/// under `/app`. Other paths are returned as is.
+pub fn host_path(path: &str) -> String {
+    match app_path() {
+        Some(app_path) => map_app_path(path, &app_path),
+        None => path.to_string(),
+    }
+}
+
+/// Where the sandbox's `/app` is on the host.
+fn app_path() -> Option<String> {
+    let kf = glib::KeyFile::new();
+    kf.load_from_file(INFO_FILE, glib::KeyFileFlags::NONE)
+        .ok()?;
+    kf.string("Instance", "app-path").ok().map(String::from)
+}
+
+fn map_app_path(path: &str, app_path: &str) -> String {
+    match path.strip_prefix("/app") {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", app_path.trim_end_matches('/'), rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_app_path() {
+        let app_path = "/var/lib/flatpak/app/gnvim/x86_64/master/abc/files";
+
+        assert_eq!(
+            map_app_path("/app/share/gnvim/runtime", app_path),
+            format!("{}/share/gnvim/runtime", app_path)
+        );
+        assert_eq!(map_app_path("/app", &format!("{}/", app_path)), app_path);
+        assert_eq!(
+            map_app_path("/usr/share/gnvim/runtime", app_path),
+            "/usr/share/gnvim/runtime"
+        );
+        assert_eq!(map_app_path("/apple", app_path), "/apple");
+    }
+}
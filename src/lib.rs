@@ -0,0 +1,28 @@
+//! Gnvim, a GUI for Neovim. Its editor pane, `NeovimView`, can be embedded
+//! into other GTK apps.
+
+extern crate gtk;
+extern crate pangocairo;
+extern crate structopt;
+
+mod app;
+mod args;
+mod config;
+mod crash;
+mod dbus;
+mod error;
+mod files;
+mod flatpak;
+mod logger;
+mod nvim_bridge;
+mod nvim_gio;
+mod project;
+mod restart;
+mod sessions;
+mod thread_guard;
+mod ui;
+mod version;
+
+pub use crate::app::run;
+pub use crate::error::Error;
+pub use crate::ui::{NeovimView, Nvim};
@@ -0,0 +1,184 @@
+//! Logging, to stderr and optionally to a file (`--log-file`). The level
+//! is set with `--log-level`, and can be changed at runtime with
+//! `:GnvimLogLevel`. If `RUST_LOG` is set, it filters what is written to
+//! stderr like env_logger does.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use env_logger::filter::{self, Filter};
+use gtk::glib;
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Size after which the log file is rotated.
+const MAX_SIZE: u64 = 5 * 1024 * 1024;
+/// Number of rotated log files to keep (`gnvim.log.1` being the newest).
+const KEEP: usize = 3;
+
+/// Log file that is rotated once it grows past `MAX_SIZE`.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: &Path) -> io::Result<Self> {
+        if fs::metadata(path).is_ok_and(|meta| meta.len() >= MAX_SIZE) {
+            rotate(path, KEEP)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(LogFile {
+            path: path.to_path_buf(),
+            size: file.metadata()?.len(),
+            file,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_SIZE {
+            rotate(&self.path, KEEP)?;
+            *self = LogFile::open(&self.path)?;
+        }
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+/// Path of the `n`th rotated log file.
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!(".{}", n));
+    path.into()
+}
+
+/// Moves `path` to `path.1`, `path.1` to `path.2` and so on, dropping the
+/// ones past `keep`.
+fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+    for n in (1..keep).rev() {
+        let from = rotated_path(path, n);
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, n + 1))?;
+        }
+    }
+
+    fs::rename(path, rotated_path(path, 1))
+}
+
+struct Logger {
+    /// Filter from `RUST_LOG`, for stderr.
+    env_filter: Option<Filter>,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = glib::DateTime::new_now_local()
+            .and_then(|now| now.format("%F %T"))
+            .map(|time| time.to_string())
+            .unwrap_or_default();
+        let line = format!(
+            "[{} {:<5} {}] {}\n",
+            time,
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let to_stderr = match self.env_filter {
+            Some(ref filter) => filter.matches(record),
+            None => true,
+        };
+        if to_stderr {
+            eprint!("{}", line);
+        }
+
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                if let Err(err) = file.write(&line) {
+                    eprintln!("Failed to write to the log file: {}", err);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(ref file) = self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
+
+/// Initializes the logger. Without `level`, logs errors only, or
+/// informational messages too when logging to a file. `RUST_LOG`, if set,
+/// takes precedence over the default.
+pub fn init(log_file: Option<&str>, level: Option<LevelFilter>) {
+    let env_filter = std::env::var("RUST_LOG")
+        .ok()
+        .map(|filters| filter::Builder::new().parse(&filters).build());
+
+    let level = level.unwrap_or_else(|| match (&env_filter, log_file) {
+        (Some(filter), _) => filter.filter(),
+        (None, Some(_)) => LevelFilter::Info,
+        (None, None) => LevelFilter::Error,
+    });
+
+    let file = log_file.and_then(|path| match LogFile::open(path.as_ref()) {
+        Ok(file) => Some(Mutex::new(file)),
+        Err(err) => {
+            eprintln!("Failed to open log file '{}': {}", path, err);
+            None
+        }
+    });
+
+    let logger = Box::new(Logger { env_filter, file });
+    if log::set_logger(Box::leak(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Changes the log level (`:GnvimLogLevel`).
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate() {
+        let dir = std::env::temp_dir()
+            .join(format!("gnvim-log-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gnvim.log");
+
+        for n in 0..4 {
+            fs::write(&path, n.to_string()).unwrap();
+            rotate(&path, 3).unwrap();
+        }
+
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1)).unwrap(), "3");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 2)).unwrap(), "2");
+        assert_eq!(fs::read_to_string(rotated_path(&path, 3)).unwrap(), "1");
+        assert!(!rotated_path(&path, 4).exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+//! Registry of the `:Gnvim` ex-command's subcommands.
+
+use nvim_rs::Value;
+
+use super::GnvimEvent;
+
+/// Subcommand of `:Gnvim`.
+pub struct Command {
+    /// Name of the subcommand (e.g. `font`).
+    pub name: &'static str,
+    /// Completion candidates for each argument.
+    pub args: &'static [&'static [&'static str]],
+    /// Parses the subcommand's arguments into an event.
+    pub parse: fn(&[&str]) -> Result<GnvimEvent, String>,
+}
+
+const ON_OFF: &[&str] = &["on", "off"];
+
+/// All the subcommands of `:Gnvim`.
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "animations",
+        args: &[ON_OFF],
+        parse: |args| {
+            Ok(GnvimEvent::EnableCursorAnimations(parse_on_off(args, 0)?))
+        },
+    },
+    Command {
+        name: "debug_overlay",
+        args: &[ON_OFF],
+        parse: |args| {
+            Ok(GnvimEvent::EnableDebugOverlay(parse_on_off(args, 0)?))
+        },
+    },
+    Command {
+        name: "ext",
+        args: &[&["cmdline", "messages", "popupmenu", "tabline"], ON_OFF],
+        parse: |args| {
+            let enable = parse_on_off(args, 1)?;
+            match get(args, 0)? {
+                "cmdline" => Ok(GnvimEvent::EnableExtCmdline(enable)),
+                "messages" => Ok(GnvimEvent::EnableExtMessages(enable)),
+                "popupmenu" => Ok(GnvimEvent::EnableExtPopupmenu(enable)),
+                "tabline" => Ok(GnvimEvent::EnableExtTabline(enable)),
+                ext => Err(format!("Unknown ext component: {}", ext)),
+            }
+        },
+    },
+    Command {
+        name: "font",
+        args: &[],
+        parse: |args| {
+            if args.is_empty() {
+                return Err(String::from("font missing"));
+            }
+
+            Ok(GnvimEvent::SetOption(
+                String::from("guifont"),
+                args.join(" ").into(),
+            ))
+        },
+    },
+    Command {
+        name: "fullscreen",
+        args: &[ON_OFF],
+        parse: |args| Ok(GnvimEvent::SetFullscreen(parse_on_off(args, 0)?)),
+    },
+    Command {
+        name: "im_indicator",
+        args: &[ON_OFF],
+        parse: |args| Ok(GnvimEvent::EnableImIndicator(parse_on_off(args, 0)?)),
+    },
+    Command {
+        name: "keep_above",
+        args: &[ON_OFF],
+        parse: |args| {
+            Ok(GnvimEvent::SetKeepAbove(Some(parse_on_off(args, 0)?)))
+        },
+    },
+    Command {
+        name: "linespace",
+        args: &[],
+        parse: |args| {
+            Ok(GnvimEvent::SetOption(
+                String::from("linespace"),
+                parse_number::<i64>(args, 0)?.into(),
+            ))
+        },
+    },
+    Command {
+        name: "opacity",
+        args: &[],
+        parse: |args| Ok(GnvimEvent::SetOpacity(parse_number(args, 0)?)),
+    },
+    Command {
+        name: "padding",
+        args: &[],
+        parse: |args| Ok(GnvimEvent::SetPadding(parse_number(args, 0)?)),
+    },
+    Command {
+        name: "particles",
+        args: &[ON_OFF],
+        parse: |args| {
+            Ok(GnvimEvent::EnableCursorParticles(parse_on_off(args, 0)?))
+        },
+    },
+    Command {
+        name: "popupmenu",
+        args: &[&[
+            "max_items",
+            "menu_on_all_items",
+            "min_width",
+            "show_menu",
+            "width",
+            "width_details",
+        ]],
+        parse: |args| match get(args, 0)? {
+            "max_items" => {
+                Ok(GnvimEvent::PopupmenuMaxItems(parse_number(args, 1)?))
+            }
+            "menu_on_all_items" => Ok(GnvimEvent::PopupmenuShowMenuOnAllItems(
+                parse_on_off(args, 1)?,
+            )),
+            "min_width" => {
+                Ok(GnvimEvent::PopupmenuMinWidth(parse_number(args, 1)?))
+            }
+            "show_menu" => {
+                Ok(GnvimEvent::PopupmenuShowMenu(parse_on_off(args, 1)?))
+            }
+            "width" => Ok(GnvimEvent::PopupmenuWidth(parse_number(args, 1)?)),
+            "width_details" => {
+                Ok(GnvimEvent::PopupmenuWidthDetails(parse_number(args, 1)?))
+            }
+            opt => Err(format!("Unknown popupmenu option: {}", opt)),
+        },
+    },
+    Command {
+        name: "showmode",
+        args: &[ON_OFF],
+        parse: |args| Ok(GnvimEvent::EnableShowMode(parse_on_off(args, 0)?)),
+    },
+    Command {
+        name: "sticky",
+        args: &[ON_OFF],
+        parse: |args| Ok(GnvimEvent::SetSticky(Some(parse_on_off(args, 0)?))),
+    },
+    Command {
+        name: "title_prefix",
+        args: &[],
+        parse: |args| Ok(GnvimEvent::SetTitlePrefix(args.join(" "))),
+    },
+];
+
+fn get<'a>(args: &[&'a str], i: usize) -> Result<&'a str, String> {
+    args.get(i)
+        .copied()
+        .ok_or(format!("argument {} missing", i + 1))
+}
+
+fn parse_on_off(args: &[&str], i: usize) -> Result<bool, String> {
+    match get(args, i)? {
+        "on" | "1" => Ok(true),
+        "off" | "0" => Ok(false),
+        arg => Err(format!("Expected 'on' or 'off', got '{}'", arg)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(
+    args: &[&str],
+    i: usize,
+) -> Result<T, String> {
+    let arg = get(args, i)?;
+    arg.parse()
+        .map_err(|_| format!("Expected a number, got '{}'", arg))
+}
+
+/// Parses `:Gnvim` command's arguments.
+pub fn parse(args: &[&str]) -> Result<GnvimEvent, String> {
+    let (name, args) = args.split_first().ok_or("No command given")?;
+    let cmd = COMMANDS
+        .iter()
+        .find(|cmd| cmd.name == *name)
+        .ok_or(format!("Unknown command: {}", name))?;
+
+    (cmd.parse)(args)
+}
+
+/// Completes `:Gnvim` command's arguments. `line` is the command line up to
+/// the cursor.
+pub fn complete(line: &str) -> Vec<String> {
+    let mut words = line.split_whitespace().collect::<Vec<_>>();
+    // Drop the `Gnvim` command it self.
+    if !words.is_empty() {
+        words.remove(0);
+    }
+
+    // Unless the line ends with a whitespace, the last word is the one that
+    // is being completed.
+    let lead = if line.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        words.pop().unwrap_or("")
+    };
+
+    let candidates: Vec<&str> = match words.split_first() {
+        None => COMMANDS.iter().map(|cmd| cmd.name).collect(),
+        Some((name, args)) => COMMANDS
+            .iter()
+            .find(|cmd| cmd.name == *name)
+            .and_then(|cmd| cmd.args.get(args.len()))
+            .map(|args| args.to_vec())
+            .unwrap_or_default(),
+    };
+
+    candidates
+        .into_iter()
+        .filter(|c| c.starts_with(lead))
+        .map(String::from)
+        .collect()
+}
+
+/// Converts `:Gnvim` command's arguments from nvim to strings.
+pub(crate) fn args_from_values(args: &[Value]) -> Result<Vec<&str>, String> {
+    args.iter()
+        .map(|arg| arg.as_str().ok_or(format!("Value is not an str: {}", arg)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse(&["animations", "off"]),
+            Ok(GnvimEvent::EnableCursorAnimations(false))
+        );
+        assert_eq!(
+            parse(&["particles", "on"]),
+            Ok(GnvimEvent::EnableCursorParticles(true))
+        );
+        assert_eq!(
+            parse(&["debug_overlay", "on"]),
+            Ok(GnvimEvent::EnableDebugOverlay(true))
+        );
+        assert_eq!(
+            parse(&["font", "Fira", "Code:h12"]),
+            Ok(GnvimEvent::SetOption(
+                String::from("guifont"),
+                "Fira Code:h12".into()
+            ))
+        );
+        assert_eq!(
+            parse(&["linespace", "4"]),
+            Ok(GnvimEvent::SetOption(String::from("linespace"), 4.into()))
+        );
+        assert_eq!(parse(&["padding", "8"]), Ok(GnvimEvent::SetPadding(8)));
+        assert_eq!(
+            parse(&["keep_above", "on"]),
+            Ok(GnvimEvent::SetKeepAbove(Some(true)))
+        );
+        assert_eq!(
+            parse(&["sticky", "off"]),
+            Ok(GnvimEvent::SetSticky(Some(false)))
+        );
+        assert_eq!(
+            parse(&["ext", "tabline", "on"]),
+            Ok(GnvimEvent::EnableExtTabline(true))
+        );
+        assert_eq!(
+            parse(&["ext", "messages", "off"]),
+            Ok(GnvimEvent::EnableExtMessages(false))
+        );
+        assert_eq!(
+            parse(&["showmode", "off"]),
+            Ok(GnvimEvent::EnableShowMode(false))
+        );
+        assert_eq!(
+            parse(&["im_indicator", "off"]),
+            Ok(GnvimEvent::EnableImIndicator(false))
+        );
+        assert_eq!(
+            parse(&["popupmenu", "width", "300"]),
+            Ok(GnvimEvent::PopupmenuWidth(300))
+        );
+        assert_eq!(
+            parse(&["popupmenu", "max_items", "10"]),
+            Ok(GnvimEvent::PopupmenuMaxItems(10))
+        );
+        assert_eq!(
+            parse(&["popupmenu", "show_menu", "off"]),
+            Ok(GnvimEvent::PopupmenuShowMenu(false))
+        );
+        assert!(parse(&["animations", "maybe"]).is_err());
+        assert!(parse(&["linespace"]).is_err());
+        assert!(parse(&["foo"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(complete("Gnvim f"), vec!["font", "fullscreen"]);
+        assert_eq!(complete("Gnvim ").len(), COMMANDS.len());
+        assert_eq!(complete("Gnvim animations "), vec!["on", "off"]);
+        assert_eq!(complete("Gnvim ext t"), vec!["tabline"]);
+        assert_eq!(complete("Gnvim ext tabline o"), vec!["on", "off"]);
+        assert!(complete("Gnvim font ").is_empty());
+        assert!(complete("Gnvim foo ").is_empty());
+    }
+}
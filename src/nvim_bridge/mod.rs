@@ -2,10 +2,10 @@ use log::{debug, error};
 
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::channel::oneshot;
 use futures::future::Future;
 use gtk::glib;
 use nvim_rs::{create::Spawner, neovim::Neovim, Handler, Value};
@@ -14,6 +14,10 @@ use crate::nvim_gio::GioWriter;
 use crate::thread_guard::ThreadGuard;
 use crate::ui::color::{Color, Highlight};
 
+pub use gnvim_core::row::LineCell;
+
+pub mod commands;
+pub mod record;
 #[cfg(test)]
 mod tests;
 
@@ -73,49 +77,53 @@ macro_rules! try_u64 {
     };
 }
 
-impl Highlight {
-    fn from_map_val(map: &[(Value, Value)]) -> Self {
-        let mut hl = Highlight::default();
-        for (prop, val) in map {
-            hl.set(unwrap_str!(prop), val.clone());
-        }
-        hl
+fn highlight_from_map_val(map: &[(Value, Value)]) -> Highlight {
+    let mut hl = Highlight::default();
+    for (prop, val) in map {
+        set_highlight_prop(&mut hl, unwrap_str!(prop), val.clone());
     }
+    hl
+}
 
-    fn set(&mut self, prop: &str, val: Value) {
-        match prop {
-            "foreground" => {
-                self.foreground = val.as_u64().map(Color::from_u64);
-            }
-            "background" => {
-                self.background = val.as_u64().map(Color::from_u64);
-            }
-            "special" => {
-                self.special = val.as_u64().map(Color::from_u64);
-            }
-            "reverse" => {
-                self.reverse = unwrap_bool!(val);
-            }
-            "italic" => {
-                self.italic = unwrap_bool!(val);
-            }
-            "bold" => {
-                self.bold = unwrap_bool!(val);
-            }
-            "underline" => {
-                self.underline = unwrap_bool!(val);
-            }
-            "undercurl" => {
-                self.undercurl = unwrap_bool!(val);
-            }
-            "blend" => {
-                self.blend = unwrap_f64!(val) / 100.0;
-            }
-            "cterm_fg" => {}
-            "cterm_bg" => {}
-            _ => {
-                debug!("Unknown highligh property: {}", prop);
-            }
+fn set_highlight_prop(hl: &mut Highlight, prop: &str, val: Value) {
+    match prop {
+        "foreground" => {
+            hl.foreground = val.as_u64().map(Color::from_u64);
+        }
+        "background" => {
+            hl.background = val.as_u64().map(Color::from_u64);
+        }
+        "special" => {
+            hl.special = val.as_u64().map(Color::from_u64);
+        }
+        "reverse" => {
+            hl.reverse = unwrap_bool!(val);
+        }
+        "italic" => {
+            hl.italic = unwrap_bool!(val);
+        }
+        "bold" => {
+            hl.bold = unwrap_bool!(val);
+        }
+        "underline" => {
+            hl.underline = unwrap_bool!(val);
+        }
+        "undercurl" => {
+            hl.undercurl = unwrap_bool!(val);
+        }
+        "altfont" => {
+            hl.altfont = unwrap_bool!(val);
+        }
+        "nocombine" => {
+            hl.nocombine = unwrap_bool!(val);
+        }
+        "blend" => {
+            hl.blend = unwrap_f64!(val) / 100.0;
+        }
+        "cterm_fg" => {}
+        "cterm_bg" => {}
+        _ => {
+            debug!("Unknown highligh property: {}", prop);
         }
     }
 }
@@ -158,20 +166,36 @@ impl Default for CursorShape {
 
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct ModeInfo {
-    /// The cursor blinking period (in ms)
+    /// Delay before the cursor starts blinking (in ms).
+    pub blink_wait: u64,
+    /// Time that the cursor is shown when blinking (in ms).
     pub blink_on: u64,
+    /// Time that the cursor is hidden when blinking (in ms).
+    pub blink_off: u64,
     pub cursor_shape: CursorShape,
     /// The cursor's width (in percentages, from 0..1).
     pub cell_percentage: f64,
+    /// Highlight of the cursor (e.g. `Cursor`). Zero means the cell's colors
+    /// reversed.
+    pub attr_id: u64,
     // TODO(ville): Implement the rest.
 }
 
 impl ModeInfo {
     fn set(&mut self, prop: &str, val: Value) {
         match prop {
+            "blinkwait" => {
+                self.blink_wait = unwrap_u64!(val);
+            }
             "blinkon" => {
                 self.blink_on = unwrap_u64!(val);
             }
+            "blinkoff" => {
+                self.blink_off = unwrap_u64!(val);
+            }
+            "attr_id" => {
+                self.attr_id = unwrap_u64!(val);
+            }
             "cursor_shape" => {
                 self.cursor_shape = CursorShape::from_string(unwrap_str!(val))
             }
@@ -189,23 +213,27 @@ impl ModeInfo {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Cell {
-    pub text: String,
-    pub hl_id: u64,
-    pub repeat: u64,
-    pub double_width: bool,
-}
-
 #[derive(Debug, PartialEq)]
 pub enum OptionSet {
     /// Font name.
     GuiFont(String),
+    /// Font name for double width characters.
+    GuiFontWide(String),
     /// Space between lines.
     LineSpace(i64),
+    /// Transparency of the popupmenu (0-100).
+    PumBlend(i64),
+    /// When to show the tabline (0: never, 1: with 2+ tabs, 2: always).
+    ShowTabline(i64),
+    /// Whether to send mouse move events to nvim.
+    MouseMoveEvent(bool),
+    /// Whether the window under the mouse pointer should become the
+    /// current window.
+    MouseFocus(bool),
     ExtTabline(bool),
     ExtCmdline(bool),
     ExtPopupmenu(bool),
+    ExtMessages(bool),
     /// Event name.
     NotSupported(String),
 }
@@ -219,19 +247,30 @@ impl From<Value> for OptionSet {
                 let val = unwrap_str!(args[1]);
                 OptionSet::GuiFont(String::from(val))
             }
+            "guifontwide" => {
+                let val = unwrap_str!(args[1]);
+                OptionSet::GuiFontWide(String::from(val))
+            }
             "linespace" => {
                 let val = unwrap_i64!(args[1]);
                 OptionSet::LineSpace(val)
             }
+            "pumblend" => OptionSet::PumBlend(unwrap_i64!(args[1])),
+            "showtabline" => OptionSet::ShowTabline(unwrap_i64!(args[1])),
+            "mousemoveevent" => {
+                OptionSet::MouseMoveEvent(unwrap_bool!(args[1]))
+            }
+            "mousefocus" => OptionSet::MouseFocus(unwrap_bool!(args[1])),
             "ext_tabline" => OptionSet::ExtTabline(unwrap_bool!(args[1])),
             "ext_cmdline" => OptionSet::ExtCmdline(unwrap_bool!(args[1])),
             "ext_popupmenu" => OptionSet::ExtPopupmenu(unwrap_bool!(args[1])),
+            "ext_messages" => OptionSet::ExtMessages(unwrap_bool!(args[1])),
             _ => OptionSet::NotSupported(String::from(name)),
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CompletionItemKind {
     Class,
     Color,
@@ -410,7 +449,7 @@ pub struct GridLineSegment {
     pub grid: i64,
     pub row: u64,
     pub col_start: u64,
-    pub cells: Vec<Cell>,
+    pub cells: Vec<LineCell>,
 }
 
 impl From<Value> for GridLineSegment {
@@ -421,7 +460,7 @@ impl From<Value> for GridLineSegment {
         let row = unwrap_u64!(entry[1]);
         let col_start = unwrap_u64!(entry[2]);
 
-        let mut cells: Vec<Cell> = vec![];
+        let mut cells: Vec<LineCell> = vec![];
 
         for entry in unwrap_array!(entry[3]) {
             let entry = unwrap_array!(entry);
@@ -450,7 +489,7 @@ impl From<Value> for GridLineSegment {
                 }
             }
 
-            cells.push(Cell {
+            cells.push(LineCell {
                 hl_id,
                 repeat,
                 text: String::from(text),
@@ -597,7 +636,7 @@ impl From<Value> for HlAttrDefine {
         let id = unwrap_u64!(args[0]);
         let map = unwrap_map!(args[1]);
 
-        let hl = Highlight::from_map_val(map);
+        let hl = highlight_from_map_val(map);
 
         HlAttrDefine { id, hl }
     }
@@ -870,6 +909,32 @@ impl From<Value> for WindowExternalPos {
     }
 }
 
+/// Position of a `ui_watched` extmark visible in a window (`win_extmark`).
+#[derive(Debug, PartialEq)]
+pub struct WindowExtmark {
+    pub grid: i64,
+    pub win: Value,
+    pub ns_id: i64,
+    pub mark_id: i64,
+    /// Position in the window's grid.
+    pub row: u64,
+    pub col: u64,
+}
+
+impl From<Value> for WindowExtmark {
+    fn from(args: Value) -> Self {
+        let args = unwrap_array!(args);
+        Self {
+            grid: unwrap_i64!(args[0]),
+            win: args[1].clone(),
+            ns_id: unwrap_i64!(args[2]),
+            mark_id: unwrap_i64!(args[3]),
+            row: unwrap_u64!(args[4]),
+            col: unwrap_u64!(args[5]),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct MsgSetPos {
     pub grid: i64,
@@ -890,6 +955,39 @@ impl From<Value> for MsgSetPos {
     }
 }
 
+/// Parses message content (`[[attr_id, text], ...]`) from ext_messages events.
+fn parse_msg_content(val: &Value) -> Vec<(u64, String)> {
+    unwrap_array!(val)
+        .iter()
+        .map(|chunk| {
+            let hl_id = unwrap_u64!(chunk[0]);
+            let text = unwrap_str!(chunk[1]);
+
+            (hl_id, String::from(text))
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MsgShow {
+    /// Kind of the message (e.g. `emsg`), or empty if unknown.
+    pub kind: String,
+    pub content: Vec<(u64, String)>,
+    /// If the message should replace the previous one.
+    pub replace_last: bool,
+}
+
+impl From<Value> for MsgShow {
+    fn from(args: Value) -> Self {
+        let args = unwrap_array!(args);
+        Self {
+            kind: unwrap_str!(args[0]).to_string(),
+            content: parse_msg_content(&args[1]),
+            replace_last: unwrap_bool!(args[2]),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum RedrawEvent {
     SetTitle(Vec<String>),
@@ -929,7 +1027,13 @@ pub enum RedrawEvent {
     WindowExternalPos(Vec<WindowExternalPos>),
     WindowHide(Vec<i64>),
     WindowClose(Vec<i64>),
+    WindowExtmark(Vec<WindowExtmark>),
     MsgSetPos(Vec<MsgSetPos>),
+    MsgShow(Vec<MsgShow>),
+    MsgClear(),
+    MsgShowMode(Vec<Vec<(u64, String)>>),
+    MsgRuler(Vec<Vec<(u64, String)>>),
+    MsgHistoryShow(Vec<MsgShow>),
 
     Ignored(String),
     Unknown(String),
@@ -982,7 +1086,13 @@ impl fmt::Display for RedrawEvent {
             }
             RedrawEvent::WindowHide(..) => write!(fmt, "WindowHide"),
             RedrawEvent::WindowClose(..) => write!(fmt, "WindowClose"),
+            RedrawEvent::WindowExtmark(..) => write!(fmt, "WindowExtmark"),
             RedrawEvent::MsgSetPos(..) => write!(fmt, "MsgSetPos"),
+            RedrawEvent::MsgShow(..) => write!(fmt, "MsgShow"),
+            RedrawEvent::MsgClear(..) => write!(fmt, "MsgClear"),
+            RedrawEvent::MsgShowMode(..) => write!(fmt, "MsgShowMode"),
+            RedrawEvent::MsgRuler(..) => write!(fmt, "MsgRuler"),
+            RedrawEvent::MsgHistoryShow(..) => write!(fmt, "MsgHistoryShow"),
 
             RedrawEvent::Ignored(..) => write!(fmt, "Ignored"),
             RedrawEvent::Unknown(e) => write!(fmt, "Unknown({})", e),
@@ -996,25 +1106,124 @@ pub enum GnvimEvent {
 
     PopupmenuWidth(u64),
     PopupmenuWidthDetails(u64),
+    /// Minimum width of the popupmenu, zero for fixed width.
+    PopupmenuMinWidth(u64),
+    /// Maximum number of visible popupmenu items, zero for no limit.
+    PopupmenuMaxItems(u64),
+    PopupmenuShowMenu(bool),
     PopupmenuShowMenuOnAllItems(bool),
 
+    /// Show the history dropdown of the cmdline.
+    CmdlineHistory,
+
     EnableCursorAnimations(bool),
+    /// Emit particles from the cursor when it moves.
+    EnableCursorParticles(bool),
+    /// Draw the cell boundaries, baselines and damaged areas on top of the
+    /// grids.
+    EnableDebugOverlay(bool),
+    /// Change the log level.
+    SetLogLevel(log::LevelFilter),
+
+    SetFullscreen(bool),
+    /// Keep the window above other windows. `None` toggles.
+    SetKeepAbove(Option<bool>),
+    /// Show the window on all workspaces. `None` toggles.
+    SetSticky(Option<bool>),
+    /// Show progress (from 0.0 to 1.0) on the dock/taskbar icon. `None`
+    /// hides the progress.
+    SetProgress(Option<f64>),
+    /// Set the urgency hint of the window.
+    SetUrgent(bool),
+    SetOpacity(f64),
+    /// Set the padding (in pixels) around the grids.
+    SetPadding(u64),
+    /// Enter or leave the zen mode.
+    ToggleZen,
+    /// Show the inspector window, with the UI's internal state.
+    Inspect,
+    /// Restart gnvim, keeping nvim's session.
+    Restart,
+    /// Show or hide the file tree, with nvim's current directory.
+    ToggleFileTree(String),
+    /// Nvim's current directory changed.
+    SetCwd(String),
+    SetTitlePrefix(String),
+    /// Add a file (absolute path) to the desktop's recent files.
+    AddRecent(String),
+    /// Resize the window to given width and height (in pixels).
+    ResizeWindow(i32, i32),
+
+    /// Set the accelerators (e.g. `<Primary>plus`) of an application
+    /// action.
+    SetAccels(String, Vec<String>),
+
+    /// Set nvim option (e.g. from `:Gnvim font`).
+    SetOption(String, Value),
+
+    /// Show the mode indicator (with ext_messages).
+    EnableShowMode(bool),
+
+    /// Show the input method indicator.
+    EnableImIndicator(bool),
 
     EnableExtTabline(bool),
     EnableExtCmdline(bool),
     EnableExtPopupmenu(bool),
+    EnableExtMessages(bool),
 
     Unknown(String),
 }
 
-pub enum Request {}
+/// Requests that nvim can make to us through `rpcrequest()`.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    /// Cell and grid metrics of the main grid.
+    GetMetrics,
+    /// Gnvim's version.
+    GetVersion,
+    /// Requests that we support.
+    GetCapabilities,
+    /// Contents of a clipboard register ("+" or "*"), in the format of
+    /// clipboard provider's paste function (see `:h clipboard`).
+    ClipboardGet(String),
+    /// Sets contents of a clipboard register ("+" or "*") with lines and
+    /// regtype.
+    ClipboardSet(String, Vec<String>, String),
+    /// Shows a file chooser. If the bool is true, the dialog is for saving a
+    /// file. Returns the selected path or nil.
+    FileDialog(bool),
+    /// Names of the available monospace fonts.
+    ListFonts,
+    /// Completion candidates for `:Gnvim` command. The string is the command
+    /// line up to the cursor.
+    CompleteCommand(String),
+    /// Gnvim event (which are normally sent with `rpcnotify()`). Responds
+    /// with nil once the event is handled.
+    Event(GnvimEvent),
+}
+
+impl Request {
+    /// Names of the requests, as they are given to `rpcrequest()`.
+    pub const NAMES: &'static [&'static str] = &[
+        "GetMetrics",
+        "GetVersion",
+        "GetCapabilities",
+        "ClipboardGet",
+        "ClipboardSet",
+        "FileDialog",
+        "ListFonts",
+        "CompleteCommand",
+    ];
+}
 
 /// Message type that we are sending to the UI.
 pub enum Message {
     /// RPC notify (see `:h rpcnotify()`).
     Notify(Notify),
-    /// RPC Request (see `: rpcrequest()`).
-    Request(Sender<Result<Value, Value>>, Request),
+    /// RPC Request (see `: rpcrequest()`). The response is sent to the
+    /// channel.
+    Request(oneshot::Sender<Result<Value, Value>>, Request),
     /// Nvim went away or reading from the rcp connection failed.
     Close,
 }
@@ -1023,23 +1232,12 @@ pub enum Message {
 pub struct NvimBridge {
     /// Channel to send messages to the ui.
     tx: Arc<ThreadGuard<glib::Sender<Message>>>,
-
-    /// Channel to pass to the UI when we receive a request from nvim.
-    /// The UI should send values to this channel when ever it gets a message
-    /// Message::Request on its receiving end of `tx`.
-    request_tx: Arc<ThreadGuard<Sender<Result<Value, Value>>>>,
-    /// Receiving end of `request_tx`.
-    request_rx: Arc<ThreadGuard<Receiver<Result<Value, Value>>>>,
 }
 
 impl NvimBridge {
     pub fn new(tx: glib::Sender<Message>) -> Self {
-        let (request_tx, request_rx) = channel();
-
         NvimBridge {
             tx: Arc::new(ThreadGuard::new(tx)),
-            request_tx: Arc::new(ThreadGuard::new(request_tx)),
-            request_rx: Arc::new(ThreadGuard::new(request_rx)),
         }
     }
 }
@@ -1057,16 +1255,18 @@ impl Handler for NvimBridge {
         match name.as_str() {
             "Gnvim" => match parse_request(args) {
                 Ok(msg) => {
-                    let tx = self.tx.borrow_mut();
-                    tx.send(Message::Request(
-                        self.request_tx.borrow_mut().clone(),
-                        msg,
-                    ))
-                    .unwrap();
-                    let rx = self.request_rx.borrow_mut();
-                    rx.recv().unwrap()
+                    let (tx, rx) = oneshot::channel();
+                    self.tx
+                        .borrow_mut()
+                        .send(Message::Request(tx, msg))
+                        .unwrap();
+                    rx.await.unwrap_or_else(|_| {
+                        Err("Request was dropped by the UI".into())
+                    })
+                }
+                Err(err) => {
+                    Err(format!("Failed to parse request: {}", err).into())
                 }
-                Err(_) => Err("Failed to parse request".into()),
             },
             _ => {
                 error!("Unknown request: {}", name);
@@ -1081,6 +1281,12 @@ impl Handler for NvimBridge {
         args: Vec<Value>,
         _neovim: Neovim<<Self as Handler>::Writer>,
     ) {
+        if name == "redraw" {
+            if let Err(err) = record::record(&args) {
+                error!("Failed to record redraw notify: {}", err);
+            }
+        }
+
         if let Some(notify) = parse_notify(&name, args) {
             let tx = self.tx.borrow_mut();
             tx.send(Message::Notify(notify)).unwrap();
@@ -1103,13 +1309,56 @@ impl Spawner for NvimBridge {
     }
 }
 
-fn parse_request(_args: Vec<Value>) -> Result<Request, ()> {
-    //let cmd = unwrap_str!(args[0]);
+pub(crate) fn parse_request(args: Vec<Value>) -> Result<Request, String> {
+    let cmd = try_str!(args.get(0).ok_or("No request given")?, "request");
+    let res = match cmd {
+        "GetMetrics" => Request::GetMetrics,
+        "GetVersion" => Request::GetVersion,
+        "GetCapabilities" => Request::GetCapabilities,
+        "ClipboardGet" => Request::ClipboardGet(
+            try_str!(args.get(1).ok_or("register missing")?, "register")
+                .to_string(),
+        ),
+        "ClipboardSet" => {
+            let lines = args
+                .get(2)
+                .and_then(|lines| lines.as_array())
+                .ok_or("lines missing")?
+                .iter()
+                .map(|line| Ok(try_str!(line, "line").to_string()))
+                .collect::<Result<Vec<String>, String>>()?;
+
+            Request::ClipboardSet(
+                try_str!(args.get(1).ok_or("register missing")?, "register")
+                    .to_string(),
+                lines,
+                args.get(3)
+                    .and_then(|regtype| regtype.as_str())
+                    .unwrap_or("v")
+                    .to_string(),
+            )
+        }
+        "FileDialog" => Request::FileDialog(
+            args.get(1)
+                .map(|save| {
+                    save.as_bool() == Some(true) || save.as_u64() == Some(1)
+                })
+                .unwrap_or(false),
+        ),
+        "ListFonts" => Request::ListFonts,
+        "CompleteCommand" => Request::CompleteCommand(
+            try_str!(args.get(1).ok_or("command line missing")?, "cmdline")
+                .to_string(),
+        ),
+        _ => match parse_gnvim_event(args)? {
+            GnvimEvent::Unknown(cmd) => {
+                return Err(format!("Unknown request: {}", cmd))
+            }
+            event => Request::Event(event),
+        },
+    };
 
-    //match cmd {
-    //_ => Err(()),
-    //}
-    Err(())
+    Ok(res)
 }
 
 fn parse_notify(name: &str, args: Vec<Value>) -> Option<Notify> {
@@ -1218,9 +1467,36 @@ fn parse_single_redraw_event(cmd: &str, args: Vec<Value>) -> RedrawEvent {
                 })
                 .collect(),
         ),
+        "win_extmark" => RedrawEvent::WindowExtmark(
+            args.into_iter().map(WindowExtmark::from).collect(),
+        ),
         "msg_set_pos" => RedrawEvent::MsgSetPos(
             args.into_iter().map(MsgSetPos::from).collect(),
         ),
+        "msg_show" => {
+            RedrawEvent::MsgShow(args.into_iter().map(MsgShow::from).collect())
+        }
+        "msg_clear" => RedrawEvent::MsgClear(),
+        "msg_showmode" => RedrawEvent::MsgShowMode(
+            args.into_iter().map(|v| parse_msg_content(&v[0])).collect(),
+        ),
+        "msg_ruler" => RedrawEvent::MsgRuler(
+            args.into_iter().map(|v| parse_msg_content(&v[0])).collect(),
+        ),
+        "msg_history_show" => RedrawEvent::MsgHistoryShow(
+            args.into_iter()
+                .flat_map(|v| {
+                    unwrap_array!(v[0])
+                        .iter()
+                        .map(|entry| MsgShow {
+                            kind: unwrap_str!(entry[0]).to_string(),
+                            content: parse_msg_content(&entry[1]),
+                            replace_last: false,
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+        ),
 
         "mouse_on" | "mouse_off" => RedrawEvent::Ignored(cmd.to_string()),
         _ => RedrawEvent::Unknown(cmd.to_string()),
@@ -1253,6 +1529,22 @@ pub(crate) fn parse_gnvim_event(
                 try_u64!(args.get(1).ok_or("width missing")?, "pmenu width");
             GnvimEvent::PopupmenuWidthDetails(w)
         }
+        "PopupmenuSetMinWidth" => {
+            let w =
+                try_u64!(args.get(1).ok_or("width missing")?, "pmenu width");
+            GnvimEvent::PopupmenuMinWidth(w)
+        }
+        "PopupmenuSetMaxItems" => {
+            let n = try_u64!(
+                args.get(1).ok_or("count missing")?,
+                "pmenu max items"
+            );
+            GnvimEvent::PopupmenuMaxItems(n)
+        }
+        "PopupmenuShowMenu" => GnvimEvent::PopupmenuShowMenu(try_bool(
+            args.get(1).ok_or("bool missing")?,
+            "pmenu show menu",
+        )?),
         "PopupmenuShowMenuOnAllItems" => {
             let b = try_u64!(
                 args.get(1).ok_or("bool missing")?,
@@ -1261,11 +1553,97 @@ pub(crate) fn parse_gnvim_event(
 
             GnvimEvent::PopupmenuShowMenuOnAllItems(b != 0)
         }
-        "EnableCursorAnimations" => GnvimEvent::EnableCursorAnimations(
-            try_u64!(
+        "CmdlineHistory" => GnvimEvent::CmdlineHistory,
+        "EnableCursorAnimations" | "SetCursorAnimations" => {
+            GnvimEvent::EnableCursorAnimations(try_bool(
                 args.get(1).ok_or("argument missing")?,
-                "failed to parse enable cursor animations argument"
-            ) == 1,
+                "failed to parse enable cursor animations argument",
+            )?)
+        }
+        "EnableCursorParticles" => GnvimEvent::EnableCursorParticles(try_bool(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse enable cursor particles argument",
+        )?),
+        "Command" => commands::parse(&commands::args_from_values(&args[1..])?)?,
+        "SetAccels" => GnvimEvent::SetAccels(
+            try_str!(args.get(1).ok_or("action missing")?, "action")
+                .to_string(),
+            args.get(2)
+                .and_then(|accels| accels.as_array())
+                .ok_or("accels missing")?
+                .iter()
+                .map(|accel| Ok(try_str!(accel, "accel").to_string()))
+                .collect::<Result<Vec<String>, String>>()?,
+        ),
+        "SetLogLevel" => GnvimEvent::SetLogLevel(
+            try_str!(args.get(1).ok_or("level missing")?, "log level")
+                .parse()
+                .map_err(|err| format!("Invalid log level: {}", err))?,
+        ),
+        "SetFullscreen" => GnvimEvent::SetFullscreen(try_bool(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse fullscreen argument",
+        )?),
+        "SetKeepAbove" => GnvimEvent::SetKeepAbove(
+            args.get(1)
+                .map(|arg| try_bool(arg, "failed to parse keep above argument"))
+                .transpose()?,
+        ),
+        "SetSticky" => GnvimEvent::SetSticky(
+            args.get(1)
+                .map(|arg| try_bool(arg, "failed to parse sticky argument"))
+                .transpose()?,
+        ),
+        "SetProgress" => GnvimEvent::SetProgress(
+            match args.get(1).ok_or("progress missing")? {
+                Value::Nil => None,
+                progress => Some(
+                    progress
+                        .as_f64()
+                        .or_else(|| progress.as_u64().map(|v| v as f64))
+                        .ok_or("Value is not a number: progress")?,
+                ),
+            },
+        ),
+        "SetUrgent" => GnvimEvent::SetUrgent(try_bool(
+            args.get(1).ok_or("argument missing")?,
+            "failed to parse urgent argument",
+        )?),
+        "SetOpacity" => {
+            let opacity = args.get(1).ok_or("opacity missing")?;
+            GnvimEvent::SetOpacity(
+                opacity
+                    .as_f64()
+                    .or_else(|| opacity.as_u64().map(|v| v as f64))
+                    .ok_or("Value is not a number: opacity")?,
+            )
+        }
+        "SetPadding" => GnvimEvent::SetPadding(try_u64!(
+            args.get(1).ok_or("padding missing")?,
+            "padding"
+        )),
+        "ToggleZen" => GnvimEvent::ToggleZen,
+        "Inspect" => GnvimEvent::Inspect,
+        "Restart" => GnvimEvent::Restart,
+        "ToggleFileTree" => GnvimEvent::ToggleFileTree(
+            try_str!(args.get(1).ok_or("cwd missing")?, "cwd").to_string(),
+        ),
+        "SetCwd" => GnvimEvent::SetCwd(
+            try_str!(args.get(1).ok_or("cwd missing")?, "cwd").to_string(),
+        ),
+        "SetTitlePrefix" => GnvimEvent::SetTitlePrefix(
+            try_str!(args.get(1).ok_or("prefix missing")?, "title prefix")
+                .to_string(),
+        ),
+        "AddRecent" => GnvimEvent::AddRecent(
+            try_str!(args.get(1).ok_or("path missing")?, "recent path")
+                .to_string(),
+        ),
+        "ResizeWindow" => GnvimEvent::ResizeWindow(
+            try_u64!(args.get(1).ok_or("width missing")?, "window width")
+                as i32,
+            try_u64!(args.get(2).ok_or("height missing")?, "window height")
+                as i32,
         ),
         "EnableExtTabline" => GnvimEvent::EnableExtTabline(
             try_u64!(
@@ -1285,12 +1663,25 @@ pub(crate) fn parse_gnvim_event(
                 "failed to parse enable ext popupmenu argument"
             ) == 1,
         ),
+        "EnableExtMessages" => GnvimEvent::EnableExtMessages(
+            try_u64!(
+                args.get(1).ok_or("argument missing")?,
+                "failed to parse enable ext messages argument"
+            ) == 1,
+        ),
         _ => GnvimEvent::Unknown(String::from(cmd)),
     };
 
     Ok(res)
 }
 
+/// Accepts both booleans and numbers (like vim does).
+fn try_bool(val: &Value, msg: &str) -> Result<bool, String> {
+    val.as_bool()
+        .or_else(|| val.as_u64().map(|v| v != 0))
+        .ok_or(format!("Value is not a bool: {}", msg))
+}
+
 fn map_to_hash(val: &Value) -> HashMap<&str, &Value> {
     let mut h = HashMap::new();
     for (prop, val) in unwrap_map!(val) {
@@ -1299,3 +1690,86 @@ fn map_to_hash(val: &Value) -> HashMap<&str, &Value> {
 
     h
 }
+
+/// Oldest nvim version (major, minor) that we work with. Older ones don't
+/// have `ext_multigrid`.
+const MIN_NVIM_VERSION: (u64, u64) = (0, 4);
+
+fn map_get<'a>(map: &'a Value, key: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// Version of nvim, from its api metadata.
+#[derive(Debug, PartialEq)]
+pub struct NvimVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub api_level: u64,
+}
+
+impl NvimVersion {
+    /// Parses the version from `metadata`, which is the second item of
+    /// `nvim_get_api_info()`'s result (or the output of `nvim --api-info`).
+    pub fn parse(metadata: &Value) -> Result<Self, String> {
+        let version = map_get(metadata, "version")
+            .ok_or("Failed to get the version of Neovim")?;
+        let num = |key| map_get(version, key).and_then(Value::as_u64);
+
+        match (num("major"), num("minor")) {
+            (Some(major), Some(minor)) => Ok(NvimVersion {
+                major,
+                minor,
+                patch: num("patch").unwrap_or(0),
+                api_level: num("api_level").unwrap_or(0),
+            }),
+            _ => Err(format!("Invalid Neovim version: {}", version)),
+        }
+    }
+}
+
+impl fmt::Display for NvimVersion {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Checks that the nvim we're talking to is new enough for us. `metadata`
+/// is the second item of `nvim_get_api_info()`'s result.
+pub fn check_nvim_version(metadata: &Value) -> Result<(), String> {
+    let version = NvimVersion::parse(metadata)?;
+
+    if (version.major, version.minor) < MIN_NVIM_VERSION {
+        return Err(format!(
+            "Neovim {} is too old, GNvim needs {}.{} or newer",
+            version, MIN_NVIM_VERSION.0, MIN_NVIM_VERSION.1
+        ));
+    }
+
+    Ok(())
+}
+
+/// Capabilities of the UI, for `g:gnvim_capabilities`: the ext options that
+/// are on (from `UiAttachOptions::to_value_map`), the renderer and the
+/// display's scale factor.
+pub fn capabilities(ui_opts: &Value, scale: i32) -> Value {
+    let mut caps = ui_opts
+        .as_map()
+        .map(|opts| {
+            opts.iter()
+                .filter(|(key, _)| {
+                    key.as_str().is_some_and(|key| key.starts_with("ext_"))
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    caps.push(("renderer".into(), "cairo".into()));
+    caps.push(("scale".into(), scale.into()));
+
+    Value::Map(caps)
+}
@@ -0,0 +1,187 @@
+//! Recording of the redraw notifications from nvim (`--record`), and
+//! replaying them back to the UI (`--replay`).
+//!
+//! A recording is a stream of msgpack arrays, one for each redraw
+//! notification: `[ms, args]`, where `ms` is the time since the start of
+//! the recording (in milliseconds) and `args` are the arguments of the
+//! notification.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::{Mutex, TryLockError};
+use std::time::{Duration, Instant};
+
+use gtk::glib;
+use log::error;
+use nvim_rs::Value;
+
+use super::{parse_notify, Message};
+
+/// The recording, if we're recording. Global, so that it can be flushed
+/// when we exit or crash.
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+/// Writes redraw notifications to a file.
+struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    fn create(path: &str) -> io::Result<Self> {
+        Ok(Recorder {
+            writer: BufWriter::new(File::create(path)?),
+            start: Instant::now(),
+        })
+    }
+
+    fn record(&mut self, args: &[Value]) -> io::Result<()> {
+        let ms = self.start.elapsed().as_millis() as u64;
+        let entry =
+            Value::Array(vec![Value::from(ms), Value::Array(args.to_vec())]);
+
+        rmpv::encode::write_value(&mut self.writer, &entry)?;
+        Ok(())
+    }
+}
+
+/// Starts recording the redraw notifications to `path`.
+pub fn start(path: &str) -> io::Result<()> {
+    let recorder = Recorder::create(path)?;
+    *RECORDER.lock().unwrap() = Some(recorder);
+    Ok(())
+}
+
+/// Records the arguments of a redraw notification, if we're recording.
+pub fn record(args: &[Value]) -> io::Result<()> {
+    match *RECORDER.lock().unwrap() {
+        Some(ref mut recorder) => recorder.record(args),
+        None => Ok(()),
+    }
+}
+
+/// Writes out the buffered part of the recording. Called when we exit, and
+/// from the panic hook, so the recording is usable even if we crash.
+pub fn flush() {
+    // Don't wait for the lock, the panic might have happened while it was
+    // held by this thread.
+    let mut recorder = match RECORDER.try_lock() {
+        Ok(recorder) => recorder,
+        Err(TryLockError::Poisoned(err)) => err.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+
+    if let Some(ref mut recorder) = *recorder {
+        if let Err(err) = recorder.writer.flush() {
+            error!("Failed to write the recording: {}", err);
+        }
+    }
+}
+
+/// Recorded redraw notification.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    /// Time since the start of the recording.
+    pub at: Duration,
+    pub args: Vec<Value>,
+}
+
+/// Reads a recording from `path`.
+pub fn read(path: &str) -> Result<Vec<Entry>, String> {
+    let data = std::fs::read(path).map_err(|err| err.to_string())?;
+    decode(&data)
+}
+
+fn decode(mut data: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut entries = vec![];
+
+    while !data.is_empty() {
+        let val = rmpv::decode::read_value(&mut data)
+            .map_err(|err| err.to_string())?;
+
+        let entry = match val {
+            Value::Array(entry) => entry,
+            _ => return Err(format!("Entry is not an array: {}", val)),
+        };
+
+        match entry.as_slice() {
+            [Value::Integer(ms), Value::Array(args)] => {
+                let ms = ms.as_u64().ok_or("Invalid timestamp")?;
+                entries.push(Entry {
+                    at: Duration::from_millis(ms),
+                    args: args.clone(),
+                });
+            }
+            _ => return Err(format!("Invalid entry: {:?}", entry)),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Sends `entries` to the UI through `tx`, with the same timing as they
+/// were recorded with, sped up by `speed`.
+pub async fn replay(
+    entries: Vec<Entry>,
+    speed: f64,
+    tx: glib::Sender<Message>,
+) {
+    let start = Instant::now();
+
+    for entry in entries {
+        let at = entry.at.div_f64(speed);
+        if let Some(wait) = at.checked_sub(start.elapsed()) {
+            glib::timeout_future(wait).await;
+        }
+
+        match parse_notify("redraw", entry.args) {
+            Some(notify) => {
+                if tx.send(Message::Notify(notify)).is_err() {
+                    // The UI is gone.
+                    return;
+                }
+            }
+            None => error!("Failed to parse a recorded redraw notify"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let args = vec![Value::Array(vec![
+            Value::from("flush"),
+            Value::Array(vec![]),
+        ])];
+
+        let mut data = vec![];
+        for ms in &[0u64, 16] {
+            let entry = Value::Array(vec![
+                Value::from(*ms),
+                Value::Array(args.clone()),
+            ]);
+            rmpv::encode::write_value(&mut data, &entry).unwrap();
+        }
+
+        assert_eq!(
+            decode(&data),
+            Ok(vec![
+                Entry {
+                    at: Duration::from_millis(0),
+                    args: args.clone(),
+                },
+                Entry {
+                    at: Duration::from_millis(16),
+                    args,
+                },
+            ])
+        );
+
+        let mut data = vec![];
+        rmpv::encode::write_value(&mut data, &Value::from("foo")).unwrap();
+        assert!(decode(&data).is_err());
+    }
+}
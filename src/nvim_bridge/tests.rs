@@ -16,11 +16,11 @@ mod parse_redraw_event_tests {
 
     use crate::nvim_bridge::{self, GridScrollRegion};
     use crate::nvim_bridge::{
-        Cell, CmdlineBlockAppend, CmdlinePos, CmdlineShow, CmdlineSpecialChar,
+        CmdlineBlockAppend, CmdlinePos, CmdlineShow, CmdlineSpecialChar,
         CompletionItem, CompletionItemKind, CursorShape, DefaultColorsSet,
         GridCursorGoto, GridLineSegment, GridResize, GridScroll, HlAttrDefine,
-        ModeChange, ModeInfo, ModeInfoSet, OptionSet, PopupmenuShow,
-        RedrawEvent, TablineUpdate,
+        LineCell, ModeChange, ModeInfo, ModeInfoSet, MsgShow, OptionSet,
+        PopupmenuShow, RedrawEvent, TablineUpdate, WindowExtmark,
     };
     use crate::ui::color::{Color, Highlight};
     use nvim_rs::Value;
@@ -46,25 +46,25 @@ mod parse_redraw_event_tests {
                 row: 1,
                 col_start: 4,
                 cells: vec![
-                    Cell {
+                    LineCell {
                         hl_id: 1,
                         repeat: 4,
                         text: " ".to_owned(),
                         double_width: false,
                     },
-                    Cell {
+                    LineCell {
                         hl_id: 4,
                         repeat: 1,
                         text: "3".to_owned(),
                         double_width: false,
                     },
-                    Cell {
+                    LineCell {
                         hl_id: 4,
                         repeat: 1,
                         text: "3".to_owned(),
                         double_width: true,
                     },
-                    Cell {
+                    LineCell {
                         hl_id: 1,
                         repeat: 1,
                         text: "".to_owned(),
@@ -77,13 +77,13 @@ mod parse_redraw_event_tests {
                 row: 4,
                 col_start: 1,
                 cells: vec![
-                    Cell {
+                    LineCell {
                         hl_id: 3,
                         repeat: 2,
                         text: "i".to_owned(),
                         double_width: false,
                     },
-                    Cell {
+                    LineCell {
                         hl_id: 1,
                         repeat: 1,
                         text: "2".to_owned(),
@@ -243,6 +243,8 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: true,
                     undercurl: false,
+                    altfont: false,
+                    nocombine: false,
                     blend: 0.0,
                 },
             },
@@ -257,6 +259,8 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: false,
                     undercurl: true,
+                    altfont: false,
+                    nocombine: false,
                     blend: 0.0,
                 },
             },
@@ -271,6 +275,8 @@ mod parse_redraw_event_tests {
                     bold: true,
                     underline: false,
                     undercurl: true,
+                    altfont: true,
+                    nocombine: true,
                     blend: 0.3,
                 },
             },
@@ -285,6 +291,8 @@ mod parse_redraw_event_tests {
                     bold: false,
                     underline: false,
                     undercurl: false,
+                    altfont: false,
+                    nocombine: false,
                     blend: 0.0,
                 },
             },
@@ -324,6 +332,8 @@ mod parse_redraw_event_tests {
                     ("italic".into(), true.into()),
                     ("bold".into(), true.into()),
                     ("undercurl".into(), true.into()),
+                    ("altfont".into(), true.into()),
+                    ("nocombine".into(), true.into()),
                     ("blend".into(), 30.into()),
                 )),
             )),
@@ -338,12 +348,22 @@ mod parse_redraw_event_tests {
         let expected = vec![RedrawEvent::OptionSet(vec![
             OptionSet::GuiFont("my awesome font:h32".into()),
             OptionSet::LineSpace(32),
+            OptionSet::GuiFontWide("my wide font:h32".into()),
+            OptionSet::PumBlend(20),
+            OptionSet::ShowTabline(2),
+            OptionSet::MouseMoveEvent(true),
+            OptionSet::MouseFocus(true),
         ])];
 
         let res = nvim_bridge::parse_redraw_event(args!(
             "option_set".into(),
             Value::Array(vec!("guifont".into(), "my awesome font:h32".into(),)),
-            Value::Array(vec!("linespace".into(), 32.into()))
+            Value::Array(vec!("linespace".into(), 32.into())),
+            Value::Array(vec!("guifontwide".into(), "my wide font:h32".into())),
+            Value::Array(vec!("pumblend".into(), 20.into())),
+            Value::Array(vec!("showtabline".into(), 2.into())),
+            Value::Array(vec!("mousemoveevent".into(), true.into())),
+            Value::Array(vec!("mousefocus".into(), true.into()))
         ));
 
         assert_eq!(expected, res);
@@ -355,14 +375,18 @@ mod parse_redraw_event_tests {
             cursor_shape_enabled: true,
             mode_info: vec![
                 ModeInfo {
+                    blink_wait: 700,
                     blink_on: 32,
+                    blink_off: 250,
                     cursor_shape: CursorShape::Horizontal,
                     cell_percentage: 0.32,
+                    attr_id: 5,
                 },
                 ModeInfo {
                     blink_on: 1,
                     cursor_shape: CursorShape::Block,
                     cell_percentage: 1.0,
+                    ..ModeInfo::default()
                 },
             ],
         }])];
@@ -373,9 +397,13 @@ mod parse_redraw_event_tests {
                 true.into(),
                 Value::Array(vec!(
                     Value::Map(vec!(
+                        ("blinkwait".into(), 700.into()),
                         ("blinkon".into(), 32.into()),
+                        ("blinkoff".into(), 250.into()),
                         ("cursor_shape".into(), "horizontal".into()),
                         ("cell_percentage".into(), 32.into()),
+                        ("attr_id".into(), 5.into()),
+                        ("attr_id_lm".into(), 6.into()),
                     )),
                     Value::Map(vec!(
                         ("blinkon".into(), 1.into()),
@@ -633,6 +661,138 @@ mod parse_redraw_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn win_extmark() {
+        let expected = vec![RedrawEvent::WindowExtmark(vec![WindowExtmark {
+            grid: 4,
+            win: Value::from(1000),
+            ns_id: 2,
+            mark_id: 7,
+            row: 10,
+            col: 3,
+        }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "win_extmark".into(),
+            Value::Array(vec!(
+                4.into(),
+                1000.into(),
+                2.into(),
+                7.into(),
+                10.into(),
+                3.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn msg_show() {
+        let expected = vec![RedrawEvent::MsgShow(vec![MsgShow {
+            kind: "emsg".to_string(),
+            content: vec![(3, "E492: Not an editor command".to_string())],
+            replace_last: true,
+        }])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_show".into(),
+            Value::Array(vec!(
+                "emsg".into(),
+                Value::Array(vec!(Value::Array(vec!(
+                    3.into(),
+                    "E492: Not an editor command".into(),
+                )))),
+                true.into(),
+            ))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn msg_clear() {
+        let expected = vec![RedrawEvent::MsgClear()];
+
+        let res = nvim_bridge::parse_redraw_event(args!("msg_clear".into()));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn msg_showmode() {
+        let expected = vec![RedrawEvent::MsgShowMode(vec![vec![(
+            5,
+            "-- INSERT --".to_string(),
+        )]])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_showmode".into(),
+            Value::Array(vec!(Value::Array(vec!(Value::Array(vec!(
+                5.into(),
+                "-- INSERT --".into(),
+            )))),))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn msg_ruler() {
+        let expected = vec![RedrawEvent::MsgRuler(vec![vec![(
+            0,
+            "12,5           Top".to_string(),
+        )]])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_ruler".into(),
+            Value::Array(vec!(Value::Array(vec!(Value::Array(vec!(
+                0.into(),
+                "12,5           Top".into(),
+            )))),))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn msg_history_show() {
+        let expected = vec![RedrawEvent::MsgHistoryShow(vec![
+            MsgShow {
+                kind: "echomsg".to_string(),
+                content: vec![(0, "foo".to_string())],
+                replace_last: false,
+            },
+            MsgShow {
+                kind: "".to_string(),
+                content: vec![(0, "bar".to_string())],
+                replace_last: false,
+            },
+        ])];
+
+        let res = nvim_bridge::parse_redraw_event(args!(
+            "msg_history_show".into(),
+            Value::Array(vec!(Value::Array(vec!(
+                Value::Array(vec!(
+                    "echomsg".into(),
+                    Value::Array(vec!(Value::Array(vec!(
+                        0.into(),
+                        "foo".into()
+                    )))),
+                )),
+                Value::Array(vec!(
+                    "".into(),
+                    Value::Array(vec!(Value::Array(vec!(
+                        0.into(),
+                        "bar".into()
+                    )))),
+                )),
+            )),))
+        ));
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn mouse_on() {
         let expected = vec![RedrawEvent::Ignored("mouse_on".to_owned())];
@@ -670,6 +830,14 @@ mod parse_gnvim_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn cmdline_history() {
+        assert_eq!(
+            nvim_bridge::parse_gnvim_event(vec!["CmdlineHistory".into()]),
+            Ok(GnvimEvent::CmdlineHistory)
+        );
+    }
+
     #[test]
     fn popupmenu_set_width() {
         let expected: Result<GnvimEvent, String> =
@@ -696,6 +864,45 @@ mod parse_gnvim_event_tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn popupmenu_set_min_width() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuMinWidth(200));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetMinWidth".into(),
+            200.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_set_max_items() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuMaxItems(10));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuSetMaxItems".into(),
+            10.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn popupmenu_show_menu() {
+        let expected: Result<GnvimEvent, String> =
+            Ok(GnvimEvent::PopupmenuShowMenu(false));
+
+        let res = nvim_bridge::parse_gnvim_event(vec![
+            "PopupmenuShowMenu".into(),
+            false.into(),
+        ]);
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn popupmenu_menu_on_all_items() {
         let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
@@ -715,4 +922,267 @@ mod parse_gnvim_event_tests {
             assert_eq!(expected, res);
         }
     }
+
+    #[test]
+    fn gui_control() {
+        let data: Vec<(Result<GnvimEvent, String>, Vec<Value>)> = vec![
+            (
+                Ok(GnvimEvent::SetFullscreen(true)),
+                vec!["SetFullscreen".into(), true.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetFullscreen(false)),
+                vec!["SetFullscreen".into(), 0.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetOpacity(0.5)),
+                vec!["SetOpacity".into(), 0.5.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetOpacity(1.0)),
+                vec!["SetOpacity".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetPadding(8)),
+                vec!["SetPadding".into(), 8.into()],
+            ),
+            (Ok(GnvimEvent::ToggleZen), vec!["ToggleZen".into()]),
+            (Ok(GnvimEvent::Inspect), vec!["Inspect".into()]),
+            (Ok(GnvimEvent::Restart), vec!["Restart".into()]),
+            (
+                Ok(GnvimEvent::ToggleFileTree(String::from("/tmp"))),
+                vec!["ToggleFileTree".into(), "/tmp".into()],
+            ),
+            (
+                Ok(GnvimEvent::SetCwd(String::from("/tmp"))),
+                vec!["SetCwd".into(), "/tmp".into()],
+            ),
+            (
+                Ok(GnvimEvent::SetKeepAbove(None)),
+                vec!["SetKeepAbove".into()],
+            ),
+            (
+                Ok(GnvimEvent::SetKeepAbove(Some(false))),
+                vec!["SetKeepAbove".into(), false.into()],
+            ),
+            (Ok(GnvimEvent::SetSticky(None)), vec!["SetSticky".into()]),
+            (
+                Ok(GnvimEvent::SetProgress(Some(0.5))),
+                vec!["SetProgress".into(), 0.5.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetProgress(None)),
+                vec!["SetProgress".into(), Value::Nil],
+            ),
+            (
+                Ok(GnvimEvent::SetUrgent(true)),
+                vec!["SetUrgent".into(), true.into()],
+            ),
+            (
+                Ok(GnvimEvent::AddRecent(String::from("/tmp/foo.rs"))),
+                vec!["AddRecent".into(), "/tmp/foo.rs".into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableExtMessages(true)),
+                vec!["EnableExtMessages".into(), 1.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetLogLevel(log::LevelFilter::Debug)),
+                vec!["SetLogLevel".into(), "debug".into()],
+            ),
+            (
+                Ok(GnvimEvent::SetTitlePrefix(String::from("[gnvim] "))),
+                vec!["SetTitlePrefix".into(), "[gnvim] ".into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableCursorAnimations(false)),
+                vec!["SetCursorAnimations".into(), false.into()],
+            ),
+            (
+                Ok(GnvimEvent::EnableCursorParticles(true)),
+                vec!["EnableCursorParticles".into(), true.into()],
+            ),
+            (
+                Ok(GnvimEvent::SetAccels(
+                    String::from("zoom-in"),
+                    vec![String::from("<Primary>i")],
+                )),
+                vec![
+                    "SetAccels".into(),
+                    "zoom-in".into(),
+                    Value::Array(vec!["<Primary>i".into()]),
+                ],
+            ),
+            (
+                Ok(GnvimEvent::ResizeWindow(800, 600)),
+                vec!["ResizeWindow".into(), 800.into(), 600.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_gnvim_event(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+}
+
+mod parse_request_tests {
+
+    use crate::nvim_bridge;
+    use crate::nvim_bridge::Request;
+    use nvim_rs::Value;
+
+    #[test]
+    fn simple_requests() {
+        let data: Vec<(Result<Request, String>, Vec<Value>)> = vec![
+            (Ok(Request::GetMetrics), vec!["GetMetrics".into()]),
+            (Ok(Request::GetVersion), vec!["GetVersion".into()]),
+            (Ok(Request::GetCapabilities), vec!["GetCapabilities".into()]),
+            (Ok(Request::ListFonts), vec!["ListFonts".into()]),
+            (
+                Err(String::from("Unknown request: Foo")),
+                vec!["Foo".into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_request(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    #[test]
+    fn clipboard() {
+        let res =
+            nvim_bridge::parse_request(vec!["ClipboardGet".into(), "+".into()]);
+        assert_eq!(Ok(Request::ClipboardGet(String::from("+"))), res);
+
+        let res = nvim_bridge::parse_request(vec![
+            "ClipboardSet".into(),
+            "*".into(),
+            Value::Array(vec!["foo".into(), "bar".into()]),
+            "V".into(),
+        ]);
+        assert_eq!(
+            Ok(Request::ClipboardSet(
+                String::from("*"),
+                vec![String::from("foo"), String::from("bar")],
+                String::from("V"),
+            )),
+            res
+        );
+    }
+
+    #[test]
+    fn event() {
+        let res =
+            nvim_bridge::parse_request(vec!["SetFullscreen".into(), 1.into()]);
+
+        assert_eq!(
+            Ok(Request::Event(nvim_bridge::GnvimEvent::SetFullscreen(true))),
+            res
+        );
+    }
+
+    #[test]
+    fn file_dialog() {
+        let data: Vec<(Result<Request, String>, Vec<Value>)> = vec![
+            (Ok(Request::FileDialog(false)), vec!["FileDialog".into()]),
+            (
+                Ok(Request::FileDialog(true)),
+                vec!["FileDialog".into(), 1.into()],
+            ),
+            (
+                Ok(Request::FileDialog(true)),
+                vec!["FileDialog".into(), true.into()],
+            ),
+        ];
+
+        for (expected, input) in data.into_iter() {
+            let res = nvim_bridge::parse_request(input);
+
+            assert_eq!(expected, res);
+        }
+    }
+}
+
+mod check_nvim_version_tests {
+
+    use crate::nvim_bridge;
+    use nvim_rs::Value;
+
+    fn metadata(major: u64, minor: u64, patch: u64) -> Value {
+        Value::Map(vec![(
+            "version".into(),
+            Value::Map(vec![
+                ("major".into(), major.into()),
+                ("minor".into(), minor.into()),
+                ("patch".into(), patch.into()),
+            ]),
+        )])
+    }
+
+    #[test]
+    fn check_nvim_version() {
+        assert_eq!(nvim_bridge::check_nvim_version(&metadata(0, 9, 5)), Ok(()));
+        assert_eq!(nvim_bridge::check_nvim_version(&metadata(1, 0, 0)), Ok(()));
+        assert_eq!(
+            nvim_bridge::check_nvim_version(&metadata(0, 3, 8)),
+            Err(String::from(
+                "Neovim 0.3.8 is too old, GNvim needs 0.4 or newer"
+            ))
+        );
+        assert!(nvim_bridge::check_nvim_version(&Value::Nil).is_err());
+    }
+
+    #[test]
+    fn parse_nvim_version() {
+        let metadata = Value::Map(vec![(
+            "version".into(),
+            Value::Map(vec![
+                ("major".into(), 0.into()),
+                ("minor".into(), 9.into()),
+                ("patch".into(), 5.into()),
+                ("api_level".into(), 11.into()),
+                ("api_prerelease".into(), false.into()),
+            ]),
+        )]);
+
+        assert_eq!(
+            nvim_bridge::NvimVersion::parse(&metadata),
+            Ok(nvim_bridge::NvimVersion {
+                major: 0,
+                minor: 9,
+                patch: 5,
+                api_level: 11,
+            })
+        );
+    }
+}
+
+mod capabilities_tests {
+
+    use crate::nvim_bridge;
+    use nvim_rs::Value;
+
+    #[test]
+    fn capabilities() {
+        let ui_opts = Value::Map(vec![
+            ("rgb".into(), true.into()),
+            ("ext_popupmenu".into(), true.into()),
+            ("ext_messages".into(), false.into()),
+        ]);
+
+        assert_eq!(
+            nvim_bridge::capabilities(&ui_opts, 2),
+            Value::Map(vec![
+                ("ext_popupmenu".into(), true.into()),
+                ("ext_messages".into(), false.into()),
+                ("renderer".into(), "cairo".into()),
+                ("scale".into(), 2.into()),
+            ])
+        );
+    }
 }
@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use gtk::prelude::*;
 use gtk::{gio, glib};
 
@@ -6,8 +8,11 @@ use log::error;
 use nvim_rs::{create::Spawner, neovim::Neovim, Handler};
 
 use crate::nvim_bridge;
+use crate::thread_guard::ThreadGuard;
 
 pub mod compat;
+#[cfg(test)]
+mod tests;
 
 pub type GioWriter =
     Compat<gio::OutputStreamAsyncWrite<gio::PollableOutputStream>>;
@@ -46,11 +51,12 @@ impl From<glib::Error> for Error {
 
 use compat::Compat;
 
+/// Starts nvim with `args`. Returns the nvim client and the nvim process.
 pub fn new_child<H>(
     handler: H,
     args: Vec<&std::ffi::OsStr>,
     tx: glib::Sender<nvim_bridge::Message>,
-) -> Result<GioNeovim, Error>
+) -> Result<(GioNeovim, gio::Subprocess), Error>
 where
     H: Spawner + Handler<Writer = GioWriter>,
 {
@@ -61,17 +67,85 @@ where
 
     let p = gio::Subprocess::newv(&args, flags).map_err(Error::from)?;
 
-    let input = p
-        .stdin_pipe()
-        .ok_or(Error::Pipe)?
+    let input = p.stdin_pipe().ok_or(Error::Pipe)?;
+    let output = p.stdout_pipe().ok_or(Error::Pipe)?;
+
+    let nvim = new_neovim(handler, input, output, tx, None)?;
+    Ok((nvim, p))
+}
+
+/// Kills `child` (if it's still running), and returns what it wrote to its
+/// stderr.
+pub async fn child_stderr(child: &gio::Subprocess) -> String {
+    child.force_exit();
+
+    let stderr = match child.stderr_pipe() {
+        Some(stderr) => stderr,
+        None => return String::new(),
+    };
+
+    let mut output = vec![];
+    loop {
+        match stderr
+            .read_bytes_async_future(4096, glib::PRIORITY_DEFAULT)
+            .await
+        {
+            Ok(bytes) if !bytes.is_empty() => output.extend_from_slice(&bytes),
+            Ok(_) => break,
+            Err(err) => {
+                error!("Failed to read nvim's stderr: {}", err);
+                break;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+/// Connects to nvim listening on the unix socket at `path`.
+pub fn connect_socket(path: &Path) -> Result<gio::SocketConnection, Error> {
+    let client = gio::SocketClient::new();
+    let addr = gio::UnixSocketAddress::new(path);
+    Ok(SocketClientExt::connect(
+        &client,
+        &addr,
+        gio::NONE_CANCELLABLE,
+    )?)
+}
+
+/// Creates a new nvim client on top of `conn`. Closing `conn` will end the
+/// session.
+pub fn new_socket<H>(
+    handler: H,
+    conn: gio::SocketConnection,
+    tx: glib::Sender<nvim_bridge::Message>,
+) -> Result<GioNeovim, Error>
+where
+    H: Spawner + Handler<Writer = GioWriter>,
+{
+    let input = conn.output_stream();
+    let output = conn.input_stream();
+
+    new_neovim(handler, input, output, tx, Some(conn))
+}
+
+fn new_neovim<H>(
+    handler: H,
+    input: gio::OutputStream,
+    output: gio::InputStream,
+    tx: glib::Sender<nvim_bridge::Message>,
+    conn: Option<gio::SocketConnection>,
+) -> Result<GioNeovim, Error>
+where
+    H: Spawner + Handler<Writer = GioWriter>,
+{
+    let input = input
         .dynamic_cast::<gio::PollableOutputStream>()
         .map_err(|_| Error::ToPollaple)?;
     let write =
         Compat::new(input.into_async_write().map_err(|_| Error::ToAsync)?);
 
-    let output = p
-        .stdout_pipe()
-        .ok_or(Error::Pipe)?
+    let output = output
         .dynamic_cast::<gio::PollableInputStream>()
         .map_err(|_| Error::ToPollaple)?;
     let read =
@@ -83,8 +157,12 @@ where
 
     let c = glib::MainContext::default();
 
+    // The socket connection (if any) needs to be kept alive for as long as
+    // we're using its streams.
+    let conn = ThreadGuard::new(conn);
     c.spawn(async move {
         let _ = io.await;
+        drop(conn);
         if let Err(err) = tx.send(nvim_bridge::Message::Close) {
             error!("Failed to send close message to the gui: {}", err)
         }
@@ -0,0 +1,257 @@
+//! End-to-end tests, driving a real `nvim --embed` through `GioNeovim` and
+//! checking the grids that its redraw events produce.
+//!
+//! These need nvim, so they're ignored by default. Run them with
+//! `cargo test -- --ignored`. The nvim binary can be set with the
+//! `GNVIM_TEST_NVIM` environment variable.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use gtk::glib;
+
+use gnvim_core::row::Row;
+
+use super::*;
+use crate::nvim_bridge::{Message, Notify, NvimBridge, RedrawEvent};
+use crate::ui::rows;
+
+/// Nvim's io is run on the default main context, so only one test can use
+/// it at a time.
+static MAIN_CONTEXT: Mutex<()> = Mutex::new(());
+
+/// How long to wait for nvim to draw the expected content.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const COLS: i64 = 40;
+const LINES: i64 = 10;
+
+/// Grids built from the redraw events, with the grids' own row handling
+/// (see `ui::rows`), but without drawing them.
+#[derive(Default)]
+struct Screen {
+    grids: HashMap<i64, Vec<Row>>,
+    cursor: (i64, u64, u64),
+    closed: bool,
+}
+
+impl Screen {
+    fn handle(&mut self, msg: Message) {
+        match msg {
+            Message::Notify(Notify::RedrawEvent(events)) => {
+                events.into_iter().for_each(|event| self.redraw(event))
+            }
+            Message::Close => self.closed = true,
+            // Requests are answered by dropping them.
+            _ => {}
+        }
+    }
+
+    fn redraw(&mut self, event: RedrawEvent) {
+        match event {
+            RedrawEvent::GridResize(evts) => {
+                for evt in evts {
+                    rows::resize(
+                        self.grids.entry(evt.grid).or_default(),
+                        evt.height as usize,
+                        evt.width as usize,
+                    );
+                }
+            }
+            RedrawEvent::GridLine(lines) => {
+                for line in lines {
+                    let rows = self.grids.get_mut(&line.grid).unwrap();
+                    rows::put_line(rows, line).unwrap();
+                }
+            }
+            RedrawEvent::GridClear(grids) => {
+                for grid in grids {
+                    if let Some(rows) = self.grids.get_mut(&grid) {
+                        rows::clear(rows);
+                    }
+                }
+            }
+            RedrawEvent::GridDestroy(grids) => {
+                for grid in grids {
+                    self.grids.remove(&grid);
+                }
+            }
+            RedrawEvent::GridScroll(evts) => {
+                for evt in evts {
+                    let rows = self.grids.get_mut(&evt.grid).unwrap();
+                    rows::scroll(
+                        rows,
+                        &evt.reg.calc_area(evt.rows),
+                        evt.reg.0[2] as usize,
+                        evt.reg.0[3] as usize,
+                    );
+                }
+            }
+            RedrawEvent::GridCursorGoto(evts) => {
+                for evt in evts {
+                    self.cursor = (evt.grid, evt.row, evt.col);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Text of `grid`, one string for each row (with trailing whitespace
+    /// removed).
+    fn text(&self, grid: i64) -> Vec<String> {
+        self.grids
+            .get(&grid)
+            .map(|rows| {
+                rows.iter()
+                    .map(|row| {
+                        (0..row.len)
+                            .filter_map(|i| row.cell_at(i))
+                            .map(|cell| cell.text.as_str())
+                            .collect::<String>()
+                            .trim_end()
+                            .to_string()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Grid that has `line` as one of its rows.
+    fn grid_with_line(&self, line: &str) -> Option<i64> {
+        self.grids
+            .keys()
+            .copied()
+            .find(|grid| self.text(*grid).iter().any(|row| row == line))
+    }
+}
+
+/// Embedded nvim, and the screen it draws to.
+struct Harness {
+    nvim: GioNeovim,
+    screen: Rc<RefCell<Screen>>,
+    ctx: glib::MainContext,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let ctx = glib::MainContext::default();
+
+        let bin = std::env::var("GNVIM_TEST_NVIM")
+            .unwrap_or_else(|_| String::from("nvim"));
+        let args = [bin.as_str(), "--embed", "--clean", "-n"];
+        let (nvim, _) = new_child(
+            NvimBridge::new(tx.clone()),
+            args.iter().map(std::ffi::OsStr::new).collect(),
+            tx,
+        )
+        .expect("failed to start nvim");
+
+        let screen = Rc::new(RefCell::new(Screen::default()));
+        let s = screen.clone();
+        rx.attach(Some(&ctx), move |msg| {
+            s.borrow_mut().handle(msg);
+            glib::Continue(true)
+        });
+
+        let harness = Harness { nvim, screen, ctx };
+
+        let mut opts = nvim_rs::UiAttachOptions::new();
+        opts.set_rgb(true);
+        opts.set_linegrid_external(true);
+        opts.set_multigrid_external(true);
+        harness
+            .run(harness.nvim.ui_attach(COLS, LINES, &opts))
+            .expect("failed to attach");
+
+        harness
+    }
+
+    fn run<F: Future>(&self, f: F) -> F::Output {
+        self.ctx.block_on(f)
+    }
+
+    /// Runs the main context until `f` returns something, or panics after
+    /// `TIMEOUT`.
+    fn wait_for<T, F>(&self, msg: &str, f: F) -> T
+    where
+        F: Fn(&Screen) -> Option<T>,
+    {
+        let start = Instant::now();
+        loop {
+            if let Some(val) = f(&self.screen.borrow()) {
+                return val;
+            }
+
+            if self.screen.borrow().closed {
+                panic!("Nvim exited while waiting for {}", msg);
+            }
+
+            if start.elapsed() > TIMEOUT {
+                panic!("Timed out waiting for {}", msg);
+            }
+
+            self.run(glib::timeout_future(Duration::from_millis(10)));
+        }
+    }
+}
+
+/// File that is removed when dropped, also when the test fails.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn lock() -> MutexGuard<'static, ()> {
+    // A failed test poisons the lock, but doesn't affect the others.
+    MAIN_CONTEXT.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[test]
+#[ignore]
+fn test_typing() {
+    let _lock = lock();
+    let h = Harness::new();
+
+    h.run(h.nvim.input("ihello world<Esc>")).unwrap();
+
+    let grid = h.wait_for("typed text", |s| s.grid_with_line("hello world"));
+    // The cursor is left on the last character after <Esc>.
+    h.wait_for("cursor", |s| Some(()).filter(|_| s.cursor == (grid, 0, 10)));
+
+    h.run(h.nvim.input("0dw")).unwrap();
+    h.wait_for("deleted word", |s| s.grid_with_line("world"));
+}
+
+#[test]
+#[ignore]
+fn test_open_file() {
+    let _lock = lock();
+    let h = Harness::new();
+
+    let file = TempFile(
+        std::env::temp_dir()
+            .join(format!("gnvim-e2e-{}.txt", std::process::id())),
+    );
+    let lines = (1..=20).map(|i| format!("line {}", i)).collect::<Vec<_>>();
+    std::fs::write(&file.0, lines.join("\n")).unwrap();
+
+    h.run(h.nvim.command(&format!("edit {}", file.0.display())))
+        .unwrap();
+    let grid = h.wait_for("file contents", |s| s.grid_with_line("line 1"));
+
+    // Scrolling past the first screenful goes through grid_scroll.
+    h.run(h.nvim.input("<C-e><C-e>")).unwrap();
+    h.wait_for("scrolled contents", |s| {
+        let text = s.text(grid);
+        Some(()).filter(|_| text.first().map(String::as_str) == Some("line 3"))
+    });
+}
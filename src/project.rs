@@ -0,0 +1,138 @@
+//! Per-project GUI settings, from a `.gnvim.toml` file in the project's
+//! root (nvim's current directory or any of its parents):
+//!
+//! ```toml
+//! # Font size, in points.
+//! font_size = 18
+//! # Padding (in pixels) around the grids.
+//! padding = 24
+//! # Prefer the "dark" or "light" variant of the GTK theme.
+//! theme = "dark"
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// Name of the project settings file.
+const FILE_NAME: &str = ".gnvim.toml";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+/// Settings of a project. `None` keeps the global setting.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProjectConfig {
+    pub font_size: Option<f32>,
+    pub padding: Option<i32>,
+    pub theme: Option<Theme>,
+}
+
+impl ProjectConfig {
+    fn parse(data: &str) -> Result<Self, String> {
+        let table =
+            data.parse::<toml::Value>().map_err(|err| err.to_string())?;
+
+        let font_size = match table.get("font_size") {
+            Some(size) => Some(
+                size.as_float()
+                    .or_else(|| size.as_integer().map(|size| size as f64))
+                    .filter(|size| *size > 0.0)
+                    .ok_or("font_size must be a positive number")?
+                    as f32,
+            ),
+            None => None,
+        };
+
+        let padding = match table.get("padding") {
+            Some(padding) => Some(
+                padding
+                    .as_integer()
+                    .filter(|padding| *padding >= 0)
+                    .ok_or("padding must be a non-negative integer")?
+                    as i32,
+            ),
+            None => None,
+        };
+
+        let theme = match table.get("theme").map(|theme| theme.as_str()) {
+            Some(Some("dark")) => Some(Theme::Dark),
+            Some(Some("light")) => Some(Theme::Light),
+            Some(_) => return Err(String::from("theme must be dark or light")),
+            None => None,
+        };
+
+        Ok(ProjectConfig {
+            font_size,
+            padding,
+            theme,
+        })
+    }
+}
+
+/// Finds the project settings file for `cwd`, from it or its parents.
+fn find(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Loads the settings of the project that `cwd` is in. Returns the path of
+/// the settings file too.
+pub fn load(cwd: &Path) -> Option<Result<(PathBuf, ProjectConfig), String>> {
+    let path = find(cwd)?;
+    let res = std::fs::read_to_string(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|data| ProjectConfig::parse(&data))
+        .map(|config| (path.clone(), config))
+        .map_err(|err| format!("{}: {}", path.display(), err));
+
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ProjectConfig::parse(
+                "font_size = 18\n\
+                 padding = 24\n\
+                 theme = \"dark\"\n"
+            ),
+            Ok(ProjectConfig {
+                font_size: Some(18.0),
+                padding: Some(24),
+                theme: Some(Theme::Dark),
+            })
+        );
+        assert_eq!(
+            ProjectConfig::parse("font_size = 12.5\n"),
+            Ok(ProjectConfig {
+                font_size: Some(12.5),
+                ..Default::default()
+            })
+        );
+        assert_eq!(ProjectConfig::parse(""), Ok(ProjectConfig::default()));
+        assert!(ProjectConfig::parse("theme = \"blue\"\n").is_err());
+        assert!(ProjectConfig::parse("padding = -1\n").is_err());
+        assert!(ProjectConfig::parse("font_size = \n").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let root = std::env::temp_dir()
+            .join(format!("gnvim-project-test-{}", std::process::id()));
+        let sub = root.join("src").join("ui");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(root.join(FILE_NAME), "padding = 8\n").unwrap();
+
+        assert_eq!(find(&sub), Some(root.join(FILE_NAME)));
+        assert_eq!(find(&root), Some(root.join(FILE_NAME)));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
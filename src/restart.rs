@@ -0,0 +1,154 @@
+//! Restarting the GUI (`:GnvimRestart`), e.g. to pick up a new build of
+//! gnvim. Gnvim re-executes itself, and the new process picks up where the
+//! old one left off: an nvim that we attached to is attached to again, and
+//! an nvim that we started (which exits with us) has its session saved
+//! with `:mksession` and restored in the new nvim.
+
+use std::env;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::nvim_gio::GioNeovim;
+
+/// Environment variable through which the old process tells the new one
+/// what to restore.
+const RESTART_ENV: &str = "GNVIM_RESTART";
+
+/// What to restore after a restart.
+#[derive(Debug, PartialEq)]
+pub enum Restore {
+    /// Attach to the nvim listening on the address.
+    Attach(String),
+    /// Source the session file (and remove it).
+    Session(PathBuf),
+}
+
+impl Restore {
+    /// Takes what to restore, if we were restarted. Needs to be called
+    /// before starting nvim, so that it doesn't inherit the variable.
+    pub fn take() -> Option<Self> {
+        let val = env::var(RESTART_ENV).ok()?;
+        env::remove_var(RESTART_ENV);
+        Self::parse(&val)
+    }
+
+    fn parse(val: &str) -> Option<Self> {
+        if let Some(address) = val.strip_prefix("attach:") {
+            Some(Restore::Attach(address.to_string()))
+        } else {
+            val.strip_prefix("session:")
+                .map(|path| Restore::Session(path.into()))
+        }
+    }
+
+    fn to_env(&self) -> String {
+        match self {
+            Restore::Attach(address) => format!("attach:{}", address),
+            Restore::Session(path) => format!("session:{}", path.display()),
+        }
+    }
+}
+
+/// `s` as a vim string literal.
+fn vim_str(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Prepares `nvim` for the restart, and re-executes gnvim. Returns only if
+/// something went wrong.
+pub async fn restart(nvim: &GioNeovim) -> Result<(), String> {
+    let api_info = nvim.get_api_info().await.map_err(|e| e.to_string())?;
+    let chan = api_info[0].as_i64().ok_or("Invalid channel id")?;
+    let info = nvim.get_chan_info(chan).await.map_err(|e| e.to_string())?;
+    let embedded = info.iter().any(|(key, val)| {
+        key.as_str() == Some("stream") && val.as_str() == Some("stdio")
+    });
+
+    let restore = if embedded {
+        // Our nvim exits with us, so its state needs to be saved.
+        let modified = nvim
+            .eval("len(getbufinfo({'bufmodified': 1}))")
+            .await
+            .map_err(|e| e.to_string())?;
+        if modified.as_u64().unwrap_or(0) > 0 {
+            return Err(String::from(
+                "There are unsaved changes, write them first (e.g. :wa)",
+            ));
+        }
+
+        let path = env::temp_dir()
+            .join(format!("gnvim-restart-{}.vim", std::process::id()));
+        nvim.command(&format!(
+            "execute 'mksession!' fnameescape({})",
+            vim_str(&path.to_string_lossy())
+        ))
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Restore::Session(path)
+    } else {
+        let address = nvim
+            .get_vvar("servername")
+            .await
+            .map_err(|e| e.to_string())?;
+        let address = address
+            .as_str()
+            .filter(|address| !address.is_empty())
+            .ok_or("Nvim is not listening on any address")?
+            .to_string();
+
+        nvim.ui_detach().await.map_err(|e| e.to_string())?;
+
+        Restore::Attach(address)
+    };
+
+    let exe = env::current_exe().map_err(|e| e.to_string())?;
+    let err = Command::new(exe)
+        .args(env::args_os().skip(1))
+        .env(RESTART_ENV, restore.to_env())
+        .exec();
+
+    Err(err.to_string())
+}
+
+/// Restores the session saved before the restart.
+pub async fn restore_session(
+    nvim: &GioNeovim,
+    path: &Path,
+) -> Result<(), String> {
+    let res = nvim
+        .command(&format!(
+            "execute 'source' fnameescape({})",
+            vim_str(&path.to_string_lossy())
+        ))
+        .await
+        .map_err(|e| e.to_string());
+
+    let _ = std::fs::remove_file(path);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_env() {
+        let restores = vec![
+            Restore::Attach(String::from("/run/user/1000/nvim.1234.0")),
+            Restore::Session(PathBuf::from("/tmp/gnvim-restart-42.vim")),
+        ];
+
+        for restore in restores {
+            assert_eq!(Restore::parse(&restore.to_env()), Some(restore));
+        }
+
+        assert_eq!(Restore::parse("foo"), None);
+    }
+
+    #[test]
+    fn test_vim_str() {
+        assert_eq!(vim_str("/tmp/it's.vim"), "'/tmp/it''s.vim'");
+    }
+}
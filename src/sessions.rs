@@ -0,0 +1,120 @@
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use log::debug;
+
+use crate::nvim_bridge::NvimBridge;
+use crate::nvim_gio;
+
+/// Running nvim session that we can attach to.
+#[derive(Debug)]
+pub struct Session {
+    /// Path to the session's socket.
+    pub path: PathBuf,
+    /// Session's working directory.
+    pub cwd: String,
+    /// Session's current file.
+    pub file: String,
+}
+
+/// Finds the running nvim sessions from the runtime directory.
+pub async fn find() -> Vec<Session> {
+    let mut sessions = vec![];
+    for path in find_sockets(&glib::user_runtime_dir()) {
+        match probe(&path).await {
+            Some(session) => sessions.push(session),
+            None => debug!("Ignoring socket {:?}", path),
+        }
+    }
+
+    sessions
+}
+
+/// Finds nvim sockets in `dir`. Nvim names its sockets `nvim.<pid>.<n>`.
+fn find_sockets(dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut sockets = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_name().to_string_lossy().starts_with("nvim.")
+                && entry.file_type().map(|ft| ft.is_socket()).unwrap_or(false)
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    sockets.sort();
+
+    sockets
+}
+
+/// Connects to the socket at `path` and asks its info. Returns `None` if
+/// the socket isn't alive (or doesn't respond in time).
+async fn probe(path: &Path) -> Option<Session> {
+    let conn = nvim_gio::connect_socket(path).ok()?;
+
+    // We're only interested in the response to our request, so ignore any
+    // other messages.
+    let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    rx.attach(None, |_| glib::Continue(false));
+
+    let nvim =
+        nvim_gio::new_socket(NvimBridge::new(tx.clone()), conn.clone(), tx)
+            .ok()?;
+
+    let eval = nvim.eval("[getcwd(), expand('%:p')]");
+    let timeout = glib::timeout_future(Duration::from_millis(500));
+    let res = match future::select(Box::pin(eval), timeout).await {
+        Either::Left((Ok(res), _)) => res,
+        _ => nvim_rs::Value::Nil,
+    };
+
+    if let Err(err) = conn.close(gio::NONE_CANCELLABLE) {
+        debug!("Failed to close probe connection: {}", err);
+    }
+
+    let info = res.as_array()?;
+    let info = info.iter().map(|v| v.as_str().unwrap_or_default());
+    let (cwd, file) = match info.collect::<Vec<_>>().as_slice() {
+        [cwd, file] => (cwd.to_string(), file.to_string()),
+        _ => return None,
+    };
+
+    Some(Session {
+        path: path.to_path_buf(),
+        cwd,
+        file,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_sockets() {
+        let dir = std::env::temp_dir().join("gnvim-test-find-sockets");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let _a = std::os::unix::net::UnixListener::bind(dir.join("nvim.2.0"))
+            .unwrap();
+        let _b = std::os::unix::net::UnixListener::bind(dir.join("nvim.1.0"))
+            .unwrap();
+        let _c = std::os::unix::net::UnixListener::bind(dir.join("other.0"))
+            .unwrap();
+        std::fs::write(dir.join("nvim.3.0"), "").unwrap();
+
+        assert_eq!(
+            find_sockets(&dir),
+            vec![dir.join("nvim.1.0"), dir.join("nvim.2.0")]
+        );
+    }
+}
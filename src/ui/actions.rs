@@ -0,0 +1,514 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{gdk, gio};
+
+use log::{error, warn};
+
+use crate::args;
+use crate::config::{self, Config};
+use crate::dbus;
+use crate::files::{self, FileArg};
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+use crate::ui::find::FindBar;
+use crate::ui::font::Font;
+use crate::ui::palette::Palette;
+use crate::ui::view::InputBuffer;
+
+/// Application level actions, and their default accelerators. The
+/// accelerators can be changed in the config file, and with the `SetAccels`
+/// gnvim event. Some of the actions are also exposed in our .desktop file.
+const ACTIONS: &[(&str, &[&str])] = &[
+    ("fullscreen", &[]),
+    ("copy", &["<Primary><Shift>c"]),
+    ("paste", &["<Primary><Shift>v"]),
+    ("new-window", &["<Primary><Shift>n"]),
+    ("new-tab", &["<Primary><Shift>t"]),
+    ("open-file", &["<Primary><Shift>o"]),
+    ("open-recent", &["<Primary><Shift>r"]),
+    ("command-palette", &["<Primary><Shift>p"]),
+    ("find", &["<Primary><Shift>f"]),
+    // Keys like <C-s> and <C-z> belong to nvim, so the menu's actions
+    // don't have any by default.
+    ("save", &[]),
+    ("quit", &[]),
+    ("undo", &[]),
+    ("redo", &[]),
+    ("about", &[]),
+    (
+        "zoom-in",
+        &["<Primary>plus", "<Primary>equal", "<Primary>KP_Add"],
+    ),
+    ("zoom-out", &["<Primary>minus", "<Primary>KP_Subtract"]),
+    ("zoom-reset", &["<Primary>0", "<Primary>KP_0"]),
+];
+
+/// Adds our actions to `app`, and binds them to the keys from `config`.
+pub fn setup(
+    app: &gtk::Application,
+    window: &gtk::ApplicationWindow,
+    nvim: &GioNeovim,
+    input: &InputBuffer,
+    palette: &Rc<Palette>,
+    find_bar: &Rc<FindBar>,
+    config: &Config,
+) {
+    // The font before any zooming, for zoom-reset.
+    let default_font = Rc::new(RefCell::new(None));
+
+    for (name, accels) in ACTIONS {
+        let action = gio::SimpleAction::new(name, None);
+
+        match *name {
+            "fullscreen" => {
+                action.connect_activate(clone!(window => move |_, _| {
+                    let fullscreen = window
+                        .window()
+                        .map(|w| w.state().contains(gdk::WindowState::FULLSCREEN))
+                        .unwrap_or(false);
+                    if fullscreen {
+                        window.unfullscreen();
+                    } else {
+                        window.fullscreen();
+                    }
+                }));
+            }
+            "copy" => {
+                action.connect_activate(clone!(nvim => move |_, _| {
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = copy(&nvim).await {
+                            error!("Failed to copy: {}", err);
+                        }
+                    });
+                }));
+            }
+            "paste" => {
+                action.connect_activate(clone!(input => move |_, _| {
+                    let clipboard =
+                        gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+                    let input = input.clone();
+                    clipboard.request_text(move |_, text| {
+                        if let Some(text) = text {
+                            input.paste(text.to_string());
+                        }
+                    });
+                }));
+            }
+            "new-window" => {
+                action.connect_activate(|_, _| {
+                    if let Err(err) = dbus::new_instance() {
+                        error!("Failed to start new instance: {}", err);
+                    }
+                });
+            }
+            "new-tab" | "quit" | "undo" | "redo" => {
+                let cmd = match *name {
+                    "new-tab" => "tabnew",
+                    // Asks what to do with unsaved changes.
+                    "quit" => "confirm qall",
+                    "undo" => "undo",
+                    _ => "redo",
+                };
+
+                action.connect_activate(clone!(nvim => move |_, _| {
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = nvim.command(cmd).await {
+                            error!("Failed to run '{}': {}", cmd, err);
+                        }
+                    });
+                }));
+            }
+            "command-palette" => {
+                action.connect_activate(clone!(palette => move |_, _| {
+                    palette.show();
+                }));
+            }
+            "find" => {
+                action.connect_activate(clone!(find_bar => move |_, _| {
+                    find_bar.show();
+                }));
+            }
+            "save" => {
+                action.connect_activate(clone!(window, nvim => move |_, _| {
+                    let window = window.clone();
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = save(&window, &nvim).await {
+                            error!("Failed to save: {}", err);
+                        }
+                    });
+                }));
+            }
+            "about" => {
+                action.connect_activate(clone!(window => move |_, _| {
+                    show_about(&window);
+                }));
+            }
+            "open-file" => {
+                action.connect_activate(clone!(window, nvim => move |_, _| {
+                    open_file_dialog(&window, &nvim);
+                }));
+            }
+            "open-recent" => {
+                action.connect_activate(clone!(window, nvim => move |_, _| {
+                    open_recent_dialog(&window, &nvim);
+                }));
+            }
+            "zoom-in" | "zoom-out" | "zoom-reset" => {
+                let delta = match *name {
+                    "zoom-in" => Some(1.0),
+                    "zoom-out" => Some(-1.0),
+                    _ => None,
+                };
+
+                action.connect_activate(
+                    clone!(nvim, default_font => move |_, _| {
+                        let nvim = nvim.clone();
+                        let default_font = default_font.clone();
+                        spawn_local(async move {
+                            if let Err(err) = zoom(&nvim, &default_font, delta).await {
+                                error!("Failed to zoom: {}", err);
+                            }
+                        });
+                    }),
+                );
+            }
+            _ => unreachable!(),
+        }
+
+        app.add_action(&action);
+        app.set_accels_for_action(&format!("app.{}", name), accels);
+    }
+
+    for (accel, action) in config.keybindings.iter() {
+        bind(app, accel, action);
+    }
+
+    if config.menubar {
+        app.set_menubar(Some(&menubar()));
+    }
+}
+
+/// Menubar with our actions.
+fn menubar() -> gio::Menu {
+    let section = |items: &[(&str, &str)]| {
+        let section = gio::Menu::new();
+        for (label, action) in items {
+            section.append(Some(label), Some(&format!("app.{}", action)));
+        }
+        section
+    };
+
+    let file = gio::Menu::new();
+    file.append_section(
+        None,
+        &section(&[("_New Window", "new-window"), ("New _Tab", "new-tab")]),
+    );
+    file.append_section(
+        None,
+        &section(&[("_Open…", "open-file"), ("Open _Recent…", "open-recent")]),
+    );
+    file.append_section(None, &section(&[("_Save", "save")]));
+    file.append_section(None, &section(&[("_Quit", "quit")]));
+
+    let edit = gio::Menu::new();
+    edit.append_section(
+        None,
+        &section(&[("_Undo", "undo"), ("_Redo", "redo")]),
+    );
+    edit.append_section(
+        None,
+        &section(&[("_Copy", "copy"), ("_Paste", "paste")]),
+    );
+
+    let view = gio::Menu::new();
+    view.append_section(
+        None,
+        &section(&[
+            ("Zoom _In", "zoom-in"),
+            ("Zoom _Out", "zoom-out"),
+            ("_Normal Size", "zoom-reset"),
+        ]),
+    );
+    view.append_section(None, &section(&[("_Fullscreen", "fullscreen")]));
+
+    let help = section(&[("_About GNvim", "about")]);
+
+    let menu = gio::Menu::new();
+    menu.append_submenu(Some("_File"), &file);
+    menu.append_submenu(Some("_Edit"), &edit);
+    menu.append_submenu(Some("_View"), &view);
+    menu.append_submenu(Some("_Help"), &help);
+    menu
+}
+
+/// Writes the current buffer. If the buffer doesn't have a name, asks for
+/// one with a file chooser.
+async fn save(
+    window: &gtk::ApplicationWindow,
+    nvim: &GioNeovim,
+) -> Result<(), Box<nvim_rs::error::CallError>> {
+    let name = nvim.call_function("bufname", vec!["%".into()]).await?;
+    if !name.as_str().unwrap_or_default().is_empty() {
+        return nvim.command("write").await;
+    }
+
+    let dialog = gtk::FileChooserNative::new(
+        Some("Save File"),
+        Some(window),
+        gtk::FileChooserAction::Save,
+        None,
+        None,
+    );
+    dialog.set_do_overwrite_confirmation(true);
+
+    if dialog.run_future().await != gtk::ResponseType::Accept {
+        return Ok(());
+    }
+    let path = match dialog.filename() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let path = nvim
+        .call_function(
+            "fnameescape",
+            vec![path.to_string_lossy().as_ref().into()],
+        )
+        .await?;
+    // Overwriting was already confirmed by the dialog.
+    nvim.command(&format!("write! {}", path.as_str().unwrap_or_default()))
+        .await
+}
+
+fn show_about(window: &gtk::ApplicationWindow) {
+    let dialog = gtk::AboutDialog::new();
+    dialog.set_transient_for(Some(window));
+    dialog.set_modal(true);
+    dialog.set_program_name("GNvim");
+    dialog.set_version(Some(args::version()));
+    dialog.set_comments(Some("GUI for Neovim."));
+    dialog.set_logo_icon_name(Some("gnvim"));
+    dialog.set_website(Some("https://github.com/vhakulinen/gnvim"));
+    dialog.set_license_type(gtk::License::MitX11);
+
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
+
+/// Binds `accel` to `action`, removing it from any other action. If `action`
+/// is `config::NVIM_ACTION`, the key chord is left for nvim.
+fn bind(app: &gtk::Application, accel: &str, action: &str) {
+    // Normalize the accelerator, so that we can compare it to the ones gtk
+    // gives us.
+    let name = match gtk::accelerator_parse(accel) {
+        (0, _) => None,
+        (key, mods) => gtk::accelerator_name(key, mods),
+    };
+    let accel = match name {
+        Some(name) => name,
+        None => {
+            warn!("Invalid keybinding '{}' for '{}'", accel, action);
+            return;
+        }
+    };
+
+    if action != config::NVIM_ACTION && app.lookup_action(action).is_none() {
+        warn!("Unknown action '{}' for keybinding '{}'", action, accel);
+        return;
+    }
+
+    for other in app.actions_for_accel(&accel) {
+        let accels = app.accels_for_action(&other);
+        let accels = accels
+            .iter()
+            .map(|a| a.as_str())
+            .filter(|a| *a != accel.as_str())
+            .collect::<Vec<_>>();
+        app.set_accels_for_action(&other, &accels);
+    }
+
+    if action != config::NVIM_ACTION {
+        let name = format!("app.{}", action);
+        let mut accels = app.accels_for_action(&name);
+        accels.push(accel);
+        let accels = accels.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+        app.set_accels_for_action(&name, &accels);
+    }
+}
+
+/// Activates the action bound to `e`'s key on the first keyboard layout that
+/// has a Latin character on it, so that the accelerators keep working on
+/// Cyrillic, Greek and other layouts. `gtk::Window::activate_key` only knows
+/// the keys of the active layout. Returns true if an action was activated.
+pub fn activate_latin_accel(app: &gtk::Application, e: &gdk::EventKey) -> bool {
+    // Only non-Latin characters, as other keys are the same on all layouts.
+    match e.keyval().to_unicode() {
+        Some(c) if !c.is_ascii() => {}
+        _ => return false,
+    }
+
+    let mods = e.state() & gtk::accelerator_get_default_mod_mask();
+    if (mods - gdk::ModifierType::SHIFT_MASK).is_empty() {
+        // Typing, not a shortcut.
+        return false;
+    }
+
+    let keyval = gdk::Display::default()
+        .and_then(|display| gdk::Keymap::for_display(&display))
+        .and_then(|keymap| {
+            keymap
+                .entries_for_keycode(u32::from(e.hardware_keycode()))
+                .into_iter()
+                .filter(|(key, keyval)| {
+                    key.level == 0
+                        && gdk::keys::Key::from(*keyval)
+                            .to_unicode()
+                            .is_some_and(|c| c.is_ascii_graphic())
+                })
+                .min_by_key(|(key, _)| key.group)
+                .map(|(_, keyval)| keyval)
+        });
+
+    let accel =
+        match keyval.and_then(|keyval| gtk::accelerator_name(keyval, mods)) {
+            Some(accel) => accel,
+            None => return false,
+        };
+
+    match app.actions_for_accel(&accel).first() {
+        Some(action) => match action.strip_prefix("app.") {
+            Some(name) if app.lookup_action(name).is_some() => {
+                app.activate_action(name, None);
+                true
+            }
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+/// Copies nvim's visual selection to the clipboard.
+async fn copy(nvim: &GioNeovim) -> Result<(), Box<nvim_rs::error::CallError>> {
+    let text = nvim.call_function("gnvim#selection", vec![]).await?;
+    let text = text.as_str().unwrap_or_default();
+    if text.is_empty() {
+        return Ok(());
+    }
+
+    gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(text);
+
+    // Leave the visual mode, like yanking does.
+    nvim.input("<Esc>").await?;
+
+    Ok(())
+}
+
+/// Changes the font size by `delta`, or resets it to `default_font` if
+/// `delta` is `None`.
+async fn zoom(
+    nvim: &GioNeovim,
+    default_font: &RefCell<Option<String>>,
+    delta: Option<f32>,
+) -> Result<(), Box<nvim_rs::error::CallError>> {
+    let guifont = nvim.get_option("guifont").await?;
+    let guifont = guifont.as_str().unwrap_or_default().to_string();
+
+    let guifont = match delta {
+        Some(delta) => {
+            let font = Font::from_guifont(&guifont).unwrap_or_default();
+            default_font.borrow_mut().get_or_insert(guifont);
+            font.resized(delta).to_guifont()
+        }
+        None => match default_font.borrow_mut().take() {
+            Some(font) => font,
+            // Not zoomed.
+            None => return Ok(()),
+        },
+    };
+
+    nvim.set_option("guifont", guifont.into()).await
+}
+
+/// Shows a file chooser, and opens the selected files in nvim.
+fn open_file_dialog(window: &gtk::ApplicationWindow, nvim: &GioNeovim) {
+    let dialog = gtk::FileChooserNative::new(
+        Some("Open File"),
+        Some(window),
+        gtk::FileChooserAction::Open,
+        None,
+        None,
+    );
+    dialog.set_select_multiple(true);
+
+    // The dialog needs to be kept alive until we get a response.
+    let keep_alive = Rc::new(RefCell::new(Some(dialog.clone())));
+    dialog.connect_response(clone!(nvim => move |dialog, res| {
+        keep_alive.borrow_mut().take();
+
+        if res != gtk::ResponseType::Accept {
+            return;
+        }
+
+        let files = dialog
+            .files()
+            .iter()
+            .filter_map(|file| file.path())
+            .map(|path| FileArg::parse(&path.to_string_lossy()))
+            .collect::<Vec<_>>();
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            for file in files.iter() {
+                if let Err(err) = files::open_file(&nvim, file).await {
+                    error!("Failed to open '{}': {}", file.path, err);
+                }
+            }
+        });
+    }));
+
+    dialog.show();
+}
+
+/// Shows the desktop's recent files, and opens the selected ones in nvim.
+fn open_recent_dialog(window: &gtk::ApplicationWindow, nvim: &GioNeovim) {
+    let dialog =
+        gtk::RecentChooserDialog::new(Some("Open Recent"), Some(window));
+    dialog.add_buttons(&[
+        ("_Cancel", gtk::ResponseType::Cancel),
+        ("_Open", gtk::ResponseType::Accept),
+    ]);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+    dialog.set_select_multiple(true);
+    dialog.set_local_only(true);
+    dialog.set_show_not_found(false);
+    dialog.set_sort_type(gtk::RecentSortType::Mru);
+
+    dialog.connect_response(clone!(nvim => move |dialog, res| {
+        if res == gtk::ResponseType::Accept {
+            let files = dialog
+                .uris()
+                .iter()
+                .filter_map(|uri| gio::File::for_uri(uri).path())
+                .map(|path| FileArg::parse(&path.to_string_lossy()))
+                .collect::<Vec<_>>();
+
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                for file in files.iter() {
+                    if let Err(err) = files::open_file(&nvim, file).await {
+                        error!("Failed to open '{}': {}", file.path, err);
+                    }
+                }
+            });
+        }
+
+        dialog.close();
+    }));
+
+    dialog.show_all();
+}
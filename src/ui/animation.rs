@@ -4,6 +4,7 @@ pub struct Animation<T> {
     pub end: T,
     pub start_time: i64,
     pub end_time: i64,
+    pub easing: Easing,
 }
 
 impl<T> Animation<T> {
@@ -11,16 +12,96 @@ impl<T> Animation<T> {
         if frame_time < self.end_time {
             let t = (frame_time - self.start_time) as f64
                 / (self.end_time - self.start_time) as f64;
-            Some(ease_out_cubic(t))
+            Some(self.easing.apply(t))
         } else {
             None
         }
     }
 }
 
-/// From clutter-easing.c, based on Robert Penner's
-/// infamous easing equations, MIT license.
+/// Easing curve of an animation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseOutQuad,
+    #[default]
+    EaseOutCubic,
+    EaseInOutCubic,
+}
+
+impl Easing {
+    /// Parses the easing from its name (e.g. `ease-out-cubic`).
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease-out-quad" => Some(Easing::EaseOutQuad),
+            "ease-out-cubic" => Some(Easing::EaseOutCubic),
+            "ease-in-out-cubic" => Some(Easing::EaseInOutCubic),
+            _ => None,
+        }
+    }
+
+    /// Maps the animation's progress `t` (from 0.0 to 1.0) to the curve.
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseOutQuad => ease_out_quad(t),
+            Easing::EaseOutCubic => ease_out_cubic(t),
+            Easing::EaseInOutCubic => ease_in_out_cubic(t),
+        }
+    }
+}
+
+// The easing functions are from clutter-easing.c, based on Robert Penner's
+// infamous easing equations, MIT license.
+
+fn ease_out_quad(t: f64) -> f64 {
+    -t * (t - 2f64)
+}
+
 fn ease_out_cubic(t: f64) -> f64 {
     let p = t - 1f64;
     p * p * p + 1f64
 }
+
+fn ease_in_out_cubic(t: f64) -> f64 {
+    let p = t * 2f64;
+    if p < 1f64 {
+        0.5 * p * p * p
+    } else {
+        let p = p - 2f64;
+        0.5 * (p * p * p + 2f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_easing_from_name() {
+        assert_eq!(Easing::parse("linear"), Some(Easing::Linear));
+        assert_eq!(
+            Easing::parse("ease-in-out-cubic"),
+            Some(Easing::EaseInOutCubic)
+        );
+        assert_eq!(Easing::parse("foo"), None);
+    }
+
+    #[test]
+    fn test_easing_apply() {
+        for easing in &[
+            Easing::Linear,
+            Easing::EaseOutQuad,
+            Easing::EaseOutCubic,
+            Easing::EaseInOutCubic,
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+        }
+
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert_eq!(Easing::EaseOutQuad.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOutCubic.apply(0.5), 0.5);
+    }
+}
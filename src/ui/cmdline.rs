@@ -1,14 +1,27 @@
-use gtk::glib;
 use gtk::prelude::*;
+use gtk::{glib, pango};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use log::error;
 
 use crate::nvim_bridge;
 use crate::nvim_gio::GioNeovim;
 use crate::ui::color::{Color, HlDefs, HlGroup};
-use crate::ui::common::calc_line_space;
+use crate::ui::common::{calc_line_space, spawn_local};
 use crate::ui::font::{Font, FontUnit};
 use crate::ui::wildmenu::Wildmenu;
 
 const MAX_WIDTH: i32 = 650;
+/// Maximum height of the cmdline input, after which it'll scroll.
+const MAX_INPUT_HEIGHT: i32 = 250;
+/// Blink interval of the special character (`cmdline_special_char`).
+const SPECIAL_CHAR_BLINK: Duration = Duration::from_millis(500);
+/// Maximum number of entries in the history dropdown.
+const MAX_HISTORY: usize = 50;
+/// Maximum height of the history dropdown.
+const MAX_HISTORY_HEIGHT: i32 = 300;
 
 #[derive(Default)]
 pub struct CmdlineColors {
@@ -180,10 +193,215 @@ impl CmdlineBlock {
     }
 }
 
+/// Returns the name of the history (see `:help hist-names`) for the
+/// cmdline's `firstc`.
+fn history_name(firstc: &str) -> Option<&'static str> {
+    match firstc {
+        ":" => Some("cmd"),
+        "/" | "?" => Some("search"),
+        "=" => Some("expr"),
+        _ => None,
+    }
+}
+
+/// Fetches (at most `MAX_HISTORY`) entries of history `name`, latest first.
+async fn fetch_history(
+    nvim: &GioNeovim,
+    name: &str,
+) -> Result<Vec<String>, Box<nvim_rs::error::CallError>> {
+    // In one go, instead of a `histget` call for each entry. Deleted
+    // entries are empty.
+    let expr = format!(
+        "filter(map(range(histnr('{name}'), 1, -1), \
+         {{_, i -> histget('{name}', i)}}), {{_, e -> !empty(e)}})[:{last}]",
+        name = name,
+        last = MAX_HISTORY - 1,
+    );
+    let entries = nvim.eval(&expr).await?;
+
+    Ok(entries
+        .as_array()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Button and dropdown for recalling the cmdline history with the mouse.
+struct CmdlineHistory {
+    css_provider: gtk::CssProvider,
+    button: gtk::Button,
+    popover: gtk::Popover,
+    list: gtk::ListBox,
+    scroll: gtk::ScrolledWindow,
+
+    /// Name of the history of the current cmdline.
+    name: Rc<RefCell<Option<&'static str>>>,
+
+    nvim: GioNeovim,
+}
+
+impl CmdlineHistory {
+    fn new(nvim: GioNeovim) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let button = gtk::Button::from_icon_name(
+            Some("document-open-recent-symbolic"),
+            gtk::IconSize::Button,
+        );
+        button.set_relief(gtk::ReliefStyle::None);
+        button.set_can_focus(false);
+        button.set_tooltip_text(Some("History"));
+        button.set_no_show_all(true);
+        add_css_provider!(&css_provider, button);
+
+        let list = gtk::ListBox::new();
+        list.set_activate_on_single_click(true);
+
+        let scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scroll.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scroll.add(&list);
+        scroll.show_all();
+
+        let popover = gtk::Popover::new(Some(&button));
+        popover.set_position(gtk::PositionType::Bottom);
+        popover.add(&scroll);
+
+        list.connect_row_activated(clone!(nvim, popover => move |_, row| {
+            let entry = row
+                .child()
+                .and_then(|label| label.downcast::<gtk::Label>().ok())
+                .map(|label| label.text().to_string())
+                .unwrap_or_default();
+            popover.hide();
+
+            // Replace the whole cmdline with the entry.
+            let input = format!("<C-e><C-u>{}", entry.replace("<", "<lt>"));
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.input(&input).await {
+                    error!("Failed to recall history entry: {}", err);
+                }
+            });
+        }));
+
+        let history = CmdlineHistory {
+            css_provider,
+            button,
+            popover,
+            list,
+            scroll,
+            name: Rc::new(RefCell::new(None)),
+            nvim,
+        };
+
+        let show = history.show_fn();
+        history.button.connect_clicked(move |_| show());
+
+        history
+    }
+
+    fn widget(&self) -> gtk::Widget {
+        self.button.clone().upcast()
+    }
+
+    /// Sets the history to show, based on the cmdline's `firstc`.
+    fn set_firstc(&self, firstc: &str) {
+        let name = history_name(firstc);
+        self.button.set_visible(name.is_some());
+        self.name.replace(name);
+    }
+
+    /// Fetches the history and shows the dropdown.
+    fn show(&self) {
+        self.show_fn()();
+    }
+
+    fn hide(&self) {
+        self.popover.hide();
+    }
+
+    fn show_fn(&self) -> impl Fn() + 'static {
+        let nvim = self.nvim.clone();
+        let name = self.name.clone();
+        let popover = self.popover.clone();
+        let list = self.list.clone();
+        let scroll = self.scroll.clone();
+
+        move || {
+            let name = match *name.borrow() {
+                Some(name) => name,
+                None => return,
+            };
+
+            let nvim = nvim.clone();
+            let popover = popover.clone();
+            let list = list.clone();
+            let scroll = scroll.clone();
+            spawn_local(async move {
+                let entries = match fetch_history(&nvim, name).await {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        error!("Failed to get {} history: {}", name, err);
+                        return;
+                    }
+                };
+
+                list.foreach(|child| list.remove(child));
+                for entry in entries.iter() {
+                    let label = gtk::Label::new(Some(entry));
+                    label.set_xalign(0.0);
+                    label.set_ellipsize(pango::EllipsizeMode::End);
+                    label.set_max_width_chars(60);
+                    list.add(&label);
+                }
+                list.show_all();
+
+                if entries.is_empty() {
+                    return;
+                }
+
+                scroll.set_min_content_height(
+                    list.preferred_height().1.min(MAX_HISTORY_HEIGHT),
+                );
+                popover.show();
+            });
+        }
+    }
+
+    fn set_colors(&self, colors: &CmdlineColors, hl_defs: &HlDefs) {
+        let css = format!(
+            "button {{
+                color: #{fg};
+                background: none;
+                border: none;
+                box-shadow: none;
+                padding: 0px 4px;
+            }}",
+            fg = colors.fg.unwrap_or(hl_defs.default_fg).as_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
 struct CmdlineInput {
     frame: gtk::Frame,
     textview: gtk::TextView,
     css_provider: gtk::CssProvider,
+    history: CmdlineHistory,
+
+    /// Tag for the special character (`cmdline_special_char`). Its
+    /// foreground is the background color, and it is toggled for blinking.
+    special_char_tag: gtk::TextTag,
+    /// Timer for blinking the special character.
+    special_char_blink: Option<glib::SourceId>,
 
     /// Content, excluding prompt, firstc etc.
     content: String,
@@ -198,25 +416,77 @@ struct CmdlineInput {
 }
 
 impl CmdlineInput {
-    fn new() -> Self {
+    fn new(nvim: GioNeovim) -> Self {
         let css_provider = gtk::CssProvider::new();
 
         let textview = gtk::TextView::new();
         textview.set_editable(false);
+        textview.set_wrap_mode(gtk::WrapMode::Char);
+
+        // Lines after literal newlines are indented like the wrapped lines.
+        let tag = gtk::TextTag::new(Some("continuation"));
+        tag.set_indent(0);
+        textview.buffer().unwrap().tag_table().unwrap().add(&tag);
+
+        let special_char_tag = gtk::TextTag::new(Some("special-char"));
+        textview
+            .buffer()
+            .unwrap()
+            .tag_table()
+            .unwrap()
+            .add(&special_char_tag);
 
         // Catch all button events to prevent selection of text etc.
         textview.connect_button_press_event(|_, _| Inhibit(true));
+        // We have the focus only to show the cursor. The keys are nvim's, so
+        // skip the textview's own handling, and pass them on to the view.
+        textview.connect_key_press_event(|textview, _| {
+            textview.stop_signal_emission("key-press-event");
+            Inhibit(false)
+        });
+        textview.connect_key_release_event(|textview, _| {
+            textview.stop_signal_emission("key-release-event");
+            Inhibit(false)
+        });
 
         let scroll = gtk::ScrolledWindow::new(
             None::<&gtk::Adjustment>,
             None::<&gtk::Adjustment>,
         );
-        scroll.set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Never);
+        scroll.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Never);
         scroll.add(&textview);
 
+        // Grow with the content, until it gets too high and we'll need to
+        // scroll instead.
+        let scroll_weak = scroll.downgrade();
+        textview.connect_size_allocate(clone!(scroll_weak => move |tv, _| {
+            let scroll = upgrade_weak!(scroll_weak);
+            let h = tv.preferred_height().1;
+
+            if h > MAX_INPUT_HEIGHT {
+                if scroll.size_request().1 == -1 {
+                    scroll.set_size_request(-1, MAX_INPUT_HEIGHT);
+                    scroll.set_policy(
+                        gtk::PolicyType::Never,
+                        gtk::PolicyType::Automatic,
+                    );
+                }
+            } else if scroll.size_request().1 != -1 {
+                scroll.set_size_request(-1, -1);
+                scroll
+                    .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Never);
+            }
+        }));
+
+        let history = CmdlineHistory::new(nvim);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        box_.pack_start(&scroll, true, true, 0);
+        box_.pack_end(&history.widget(), false, false, 0);
+
         // Wrap the textview into a frame, mainly to add some padding (with css).
         let frame = gtk::Frame::new(None);
-        frame.add(&scroll);
+        frame.add(&box_);
 
         add_css_provider!(&css_provider, frame, textview);
 
@@ -224,6 +494,9 @@ impl CmdlineInput {
             frame,
             textview,
             css_provider,
+            history,
+            special_char_tag,
+            special_char_blink: None,
 
             content: String::new(),
             prompt_len: 0,
@@ -241,6 +514,8 @@ impl CmdlineInput {
         content: nvim_bridge::CmdlineShow,
         hl_defs: &HlDefs,
     ) {
+        self.stop_special_char_blink();
+
         let buffer = self.textview.buffer().unwrap();
 
         // Reset the buffer.
@@ -255,8 +530,18 @@ impl CmdlineInput {
             content.prompt
         );
         buffer.insert(&mut iter, &prompt);
+        self.history.set_firstc(&content.firstc);
         self.prompt_len = prompt.chars().count() as i32;
 
+        // Indent wrapped lines so that they start after the prompt.
+        let prompt_width = self
+            .textview
+            .create_pango_layout(Some(&prompt))
+            .pixel_size()
+            .0;
+        self.textview.set_left_margin(prompt_width);
+        self.textview.set_indent(-prompt_width);
+
         // Write the contents.
         for item in content.content.iter() {
             let hl = hl_defs.get(&item.0).unwrap();
@@ -271,6 +556,14 @@ impl CmdlineInput {
             buffer.insert_markup(&mut iter, &markup);
         }
 
+        if buffer.line_count() > 1 {
+            buffer.apply_tag_by_name(
+                "continuation",
+                &buffer.iter_at_line(1),
+                &buffer.end_iter(),
+            );
+        }
+
         self.current_level = content.level;
         self.content = content.content.into_iter().map(|c| c.1).collect();
 
@@ -279,13 +572,49 @@ impl CmdlineInput {
         self.set_cursor(content.pos as usize, content.level);
     }
 
-    fn show_special_char(&mut self, ch: String, _shift: bool, _level: u64) {
-        // TODO(ville): What to do with `_shift` and `_level`?
+    /// Shows the pending special character (e.g. `^V` or `^R`) at the
+    /// cursor, until the next `cmdline_show`.
+    fn show_special_char(&mut self, ch: String, shift: bool, level: u64) {
+        if level != self.current_level {
+            return;
+        }
+
+        self.stop_special_char_blink();
+
         let buffer = self.textview.buffer().unwrap();
         let mark_insert = buffer.get_insert().unwrap();
         let mut iter = buffer.iter_at_mark(&mark_insert);
+        let offset = iter.offset();
+
+        if !shift {
+            // Overwrite the character under the cursor.
+            let mut end = iter.clone();
+            end.forward_char();
+            buffer.delete(&mut iter, &mut end);
+        }
+
         buffer.insert(&mut iter, &ch);
-        iter.backward_char();
+        buffer.apply_tag(
+            &self.special_char_tag,
+            &buffer.iter_at_offset(offset),
+            &iter,
+        );
+        // Keep the cursor on the special character.
+        buffer.place_cursor(&buffer.iter_at_offset(offset));
+
+        let tag = self.special_char_tag.clone();
+        self.special_char_blink =
+            Some(glib::timeout_add_local(SPECIAL_CHAR_BLINK, move || {
+                tag.set_foreground_set(!tag.is_foreground_set());
+                Continue(true)
+            }));
+    }
+
+    fn stop_special_char_blink(&mut self) {
+        if let Some(id) = self.special_char_blink.take() {
+            glib::source::source_remove(id);
+        }
+        self.special_char_tag.set_foreground_set(false);
     }
 
     fn set_colors(&self, colors: &CmdlineColors, hl_defs: &HlDefs) {
@@ -309,6 +638,14 @@ impl CmdlineInput {
         );
         CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
             .unwrap();
+
+        self.history.set_colors(colors, hl_defs);
+
+        self.special_char_tag.set_foreground(Some(&format!(
+            "#{}",
+            colors.bg.unwrap_or(hl_defs.default_bg).as_hex()
+        )));
+        self.special_char_tag.set_foreground_set(false);
     }
 
     fn set_cursor(&mut self, pos: usize, level: u64) {
@@ -343,6 +680,11 @@ impl CmdlineInput {
 pub struct Cmdline {
     css_provider: gtk::CssProvider,
     fixed: gtk::Fixed,
+    /// Container for the cmdline and wildmenu.
+    box_: gtk::Box,
+    /// If we're floating at the upper third of the window, instead of
+    /// being docked to its top edge.
+    floating: Rc<Cell<bool>>,
 
     input: CmdlineInput,
     block: CmdlineBlock,
@@ -366,7 +708,7 @@ impl Cmdline {
         // Inner box contains cmdline block and input.
         let inner_box = gtk::Box::new(gtk::Orientation::Vertical, 0);
 
-        let input = CmdlineInput::new();
+        let input = CmdlineInput::new(nvim.clone());
         let block = CmdlineBlock::new();
         inner_box.pack_start(&block.widget(), true, true, 0);
         inner_box.pack_start(&input.widget(), true, true, 0);
@@ -390,18 +732,24 @@ impl Cmdline {
 
         parent.add_overlay(&fixed);
 
-        parent.connect_size_allocate(clone!(fixed, box_ => move |_, alloc| {
-            // Make sure we'll fit to the available space.
-            let width = MAX_WIDTH.min(alloc.width);
-            box_.set_size_request(width, -1);
+        let floating = Rc::new(Cell::new(false));
+        parent.connect_size_allocate(
+            clone!(fixed, box_, floating => move |_, alloc| {
+                // Make sure we'll fit to the available space.
+                let width = MAX_WIDTH.min(alloc.width);
+                box_.set_size_request(width, -1);
 
-            let x = alloc.width / 2 - width / 2;
-            fixed.move_(&box_, x, 0);
-        }));
+                let x = alloc.width / 2 - width / 2;
+                let y = if floating.get() { alloc.height / 4 } else { 0 };
+                fixed.move_(&box_, x, y);
+            }),
+        );
 
         Cmdline {
             css_provider,
             fixed,
+            box_,
+            floating,
             input,
             block,
             wildmenu,
@@ -457,6 +805,10 @@ impl Cmdline {
             frame > box {{
                 box-shadow: none;
             }}
+
+            box.floating, box.floating > frame {{
+                border-radius: 6px;
+            }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Point),
             bg = self.colors.border.unwrap_or(hl_defs.default_bg).as_hex()
@@ -465,7 +817,14 @@ impl Cmdline {
             .unwrap();
     }
 
-    pub fn hide(&self) {
+    /// Widget that takes the keyboard focus while the cmdline is shown.
+    pub fn focus_widget(&self) -> gtk::Widget {
+        self.input.textview.clone().upcast()
+    }
+
+    pub fn hide(&mut self) {
+        self.input.stop_special_char_blink();
+        self.input.history.hide();
         self.fixed.hide();
     }
 
@@ -486,6 +845,11 @@ impl Cmdline {
         }
     }
 
+    /// Shows the history dropdown of the current cmdline.
+    pub fn show_history(&self) {
+        self.input.history.show();
+    }
+
     pub fn show_special_char(&mut self, ch: String, shift: bool, level: u64) {
         self.input.show_special_char(ch, shift, level);
     }
@@ -551,6 +915,34 @@ impl Cmdline {
         self.fixed.check_resize();
     }
 
+    /// Sets if the cmdline floats at the upper third of the window, instead
+    /// of being docked to its top edge.
+    pub fn set_floating(&mut self, floating: bool) {
+        self.floating.set(floating);
+
+        let style = self.box_.style_context();
+        if floating {
+            style.add_class("floating");
+        } else {
+            style.remove_class("floating");
+        }
+
+        // Reposition.
+        if let Some(parent) = self.fixed.parent() {
+            parent.queue_resize();
+        }
+    }
+
+    /// Sets if the wildmenu is shown as a single row above the cmdline,
+    /// instead of a list below it.
+    pub fn set_wildmenu_horizontal(&mut self, horizontal: bool) {
+        self.wildmenu.set_horizontal(horizontal);
+        self.box_.reorder_child(
+            &self.wildmenu.widget(),
+            if horizontal { 0 } else { 1 },
+        );
+    }
+
     pub fn wildmenu_select(&mut self, item_num: i32) {
         self.wildmenu.select(item_num);
     }
@@ -559,3 +951,17 @@ impl Cmdline {
         self.wildmenu.set_colors(hl_defs);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_name() {
+        assert_eq!(history_name(":"), Some("cmd"));
+        assert_eq!(history_name("/"), Some("search"));
+        assert_eq!(history_name("?"), Some("search"));
+        assert_eq!(history_name("="), Some("expr"));
+        assert_eq!(history_name(""), None);
+    }
+}
@@ -1,11 +1,92 @@
 use futures::future::Future;
-use gtk::{gdk, glib};
+use gtk::prelude::*;
+use gtk::{gdk, gio, glib};
+
+use crate::nvim_gio::GioNeovim;
+
+/// Size (in bytes) of the chunks that pastes are sent to nvim in.
+const PASTE_CHUNK_SIZE: usize = 64 * 1024;
 
 pub fn spawn_local<F: Future<Output = ()> + 'static>(f: F) {
     let c = glib::MainContext::default();
     c.spawn_local(f);
 }
 
+/// Pastes `text` with `nvim_paste`, in chunks so that big pastes don't block
+/// nvim (and can be cancelled). Newlines and `<`s are kept as they are,
+/// unlike with `nvim_input`.
+pub async fn paste(
+    nvim: &GioNeovim,
+    text: &str,
+) -> Result<(), Box<nvim_rs::error::CallError>> {
+    for (chunk, phase) in paste_chunks(text, PASTE_CHUNK_SIZE) {
+        if !nvim.paste(chunk, true, phase).await? {
+            // Cancelled, e.g. with <C-c>.
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits `text` into chunks of at most `size` bytes, with the `nvim_paste`
+/// phase of each. Characters and `\r\n`s are not split. `size` needs to be
+/// at least 4, the size of the longest character.
+fn paste_chunks(text: &str, size: usize) -> Vec<(&str, i64)> {
+    let mut chunks = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end < rest.len()
+            && rest[..end].ends_with('\r')
+            && rest[end..].starts_with('\n')
+        {
+            end -= 1;
+        }
+
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    let last = chunks.len().saturating_sub(1);
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let phase = match i {
+                _ if last == 0 => -1,
+                0 => 1,
+                _ if i == last => 3,
+                _ => 2,
+            };
+            (chunk, phase)
+        })
+        .collect()
+}
+
+/// Paths of the local files in the desktop's recent files, most recently
+/// used first.
+pub fn gtk_recent_files() -> Vec<String> {
+    let mut items = gtk::RecentManager::default()
+        .map(|manager| manager.items())
+        .unwrap_or_default();
+    items.sort_by_key(|info| std::cmp::Reverse(info.modified()));
+
+    items
+        .into_iter()
+        .filter(|info| info.is_local())
+        .filter_map(|info| {
+            let uri = info.uri()?;
+            let path = gio::File::for_uri(&uri).path()?;
+            Some(path.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
 pub fn calc_line_space(space: i64) -> (i32, i32) {
     let half = space as f64 / 2.0;
     if half as f64 % 2.0 != 0.0 {
@@ -39,28 +120,23 @@ pub fn get_preferred_horizontal_position(
     (x, width)
 }
 
-/// Calculate the preferred height and y-position.
+/// Calculate the preferred height and y-position. Below `pos` is preferred,
+/// but if there isn't enough room there, we'll go above `pos` if there is more
+/// room above it.
 pub fn get_preferred_vertical_position(
     area: &gdk::Rectangle,
     pos: &gdk::Rectangle,
-    mut height: i32,
+    height: i32,
 ) -> (i32, i32) {
-    let mut y = pos.y - height;
-
-    if y < area.y {
-        let max_above = area.y + pos.y;
-        let max_below = area.height - (pos.y + pos.height);
-
-        if max_above > max_below {
-            y = area.y;
-            height = max_above;
-        } else {
-            y = pos.y + pos.height;
-            height = height.min(max_below);
-        }
-    }
+    let max_above = pos.y - area.y;
+    let max_below = area.y + area.height - (pos.y + pos.height);
 
-    (y, height)
+    if height <= max_below || max_below >= max_above {
+        (pos.y + pos.height, height.min(max_below))
+    } else {
+        let height = height.min(max_above);
+        (pos.y - height, height)
+    }
 }
 
 #[cfg(test)]
@@ -68,6 +144,21 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_paste_chunks() {
+        assert_eq!(paste_chunks("", 4), vec![]);
+        assert_eq!(paste_chunks("foo", 4), vec![("foo", -1)]);
+        assert_eq!(
+            paste_chunks("foo\nbar\nbaz", 4),
+            vec![("foo\n", 1), ("bar\n", 2), ("baz", 3)]
+        );
+        assert_eq!(
+            paste_chunks("foo\r\nbar", 4),
+            vec![("foo", 1), ("\r\nba", 2), ("r", 3)]
+        );
+        assert_eq!(paste_chunks("aäö", 4), vec![("aä", 1), ("ö", 3)]);
+    }
+
     #[test]
     fn test_calc_line_space() {
         assert_eq!((1, 0), calc_line_space(1));
@@ -77,7 +168,7 @@ mod test {
 
     #[test]
     fn test_get_preferred_vertical_position1() {
-        // Case 1: there is room just fine in the obvious position.
+        // Case 1: there is room just fine below the `pos`.
         let area = gdk::Rectangle {
             x: 0,
             y: 0,
@@ -92,7 +183,7 @@ mod test {
         };
         let height = 30;
         let (y, h) = get_preferred_vertical_position(&area, &pos, height);
-        assert_eq!(y, 0);
+        assert_eq!(y, 45);
         assert_eq!(h, 30);
     }
 
@@ -162,6 +253,28 @@ mod test {
         assert_eq!(h, 30);
     }
 
+    #[test]
+    fn test_get_preferred_vertical_position5() {
+        // Case 5: `pos` is near the bottom, so there isn't enough room below
+        // it. We should go above it.
+        let area = gdk::Rectangle {
+            x: 0,
+            y: 0,
+            width: 300,
+            height: 300,
+        };
+        let pos = gdk::Rectangle {
+            x: 0,
+            y: 270,
+            width: 300,
+            height: 15,
+        };
+        let height = 100;
+        let (y, h) = get_preferred_vertical_position(&area, &pos, height);
+        assert_eq!(y, 170);
+        assert_eq!(h, 100);
+    }
+
     #[test]
     fn test_get_preferred_horizontal_position1() {
         // Case 1: Everything fits.
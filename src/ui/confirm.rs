@@ -0,0 +1,232 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+
+/// Confirm prompt from nvim (`msg_show` with kind `confirm` or
+/// `confirm_sub`).
+#[derive(Debug, PartialEq)]
+struct Confirm {
+    /// The question.
+    message: String,
+    /// Labels of the choices (with gtk mnemonics), and the keys to send to
+    /// nvim for them.
+    choices: Vec<(String, String)>,
+}
+
+/// Parses a `confirm()` prompt, e.g.:
+///
+/// ```text
+/// Save changes to "foo"?
+/// [Y]es, (N)o, (C)ancel:
+/// ```
+fn parse_confirm(text: &str) -> Option<Confirm> {
+    let text = text.trim_end();
+    let (message, choices) = match text.rsplit_once('\n') {
+        Some((message, choices)) => (message.trim(), choices),
+        None => ("", text),
+    };
+
+    let choices = choices
+        .trim_end_matches(':')
+        .split(", ")
+        .map(|choice| {
+            let start = choice.find(&['(', '['][..])?;
+            let key = choice[start + 1..].chars().next()?;
+            let end = start + 1 + key.len_utf8();
+            if !choice[end..].starts_with(&[')', ']'][..]) {
+                return None;
+            }
+
+            let label = format!(
+                "{}_{}{}",
+                choice[..start].replace('_', "__"),
+                key,
+                choice[end + 1..].replace('_', "__"),
+            );
+            Some((label, key.to_string()))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if choices.is_empty() {
+        return None;
+    }
+
+    Some(Confirm {
+        message: message.to_string(),
+        choices,
+    })
+}
+
+/// Parses a `:s///c` prompt, e.g. `replace with bar (y/n/a/q/l/^E/^Y)?`.
+fn parse_confirm_sub(text: &str) -> Option<Confirm> {
+    let text = text.trim_end();
+    let start = text.rfind('(')?;
+    let keys = text[start + 1..]
+        .trim_end_matches('?')
+        .trim_end_matches(')');
+
+    let choices = keys
+        .split('/')
+        .filter_map(|key| {
+            let label = match key {
+                "y" => "_Yes",
+                "n" => "_No",
+                "a" => "_All",
+                "q" => "_Quit",
+                "l" => "_Last",
+                // Scrolling (^E and ^Y) doesn't make sense for us.
+                _ => return None,
+            };
+            Some((label.to_string(), key.to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    if choices.is_empty() {
+        return None;
+    }
+
+    Some(Confirm {
+        message: text[..start].trim().to_string(),
+        choices,
+    })
+}
+
+/// Dialog of a confirm prompt.
+pub struct ConfirmDialog {
+    dialog: gtk::MessageDialog,
+    /// If the prompt is answered (or dismissed), so no more keys are sent
+    /// for it.
+    handled: Rc<Cell<bool>>,
+}
+
+impl ConfirmDialog {
+    /// Closes the dialog without answering the prompt.
+    pub fn dismiss(&self) {
+        // Answered dialogs are destroyed already.
+        if !self.handled.replace(true) {
+            // Safe, as nothing uses the dialog after this.
+            unsafe { self.dialog.destroy() };
+        }
+    }
+}
+
+/// Shows a confirm prompt of `kind` as a dialog, and sends the selected
+/// choice to nvim. Returns the dialog, or `None` if the prompt couldn't be
+/// parsed.
+pub fn show_confirm(
+    parent: Option<&gtk::Window>,
+    nvim: &GioNeovim,
+    kind: &str,
+    text: &str,
+) -> Option<ConfirmDialog> {
+    let confirm = match kind {
+        "confirm" => parse_confirm(text),
+        "confirm_sub" => parse_confirm_sub(text),
+        _ => None,
+    }?;
+
+    let dialog = gtk::MessageDialog::new(
+        parent,
+        gtk::DialogFlags::MODAL | gtk::DialogFlags::DESTROY_WITH_PARENT,
+        gtk::MessageType::Question,
+        gtk::ButtonsType::None,
+        &confirm.message,
+    );
+
+    for (i, (label, _)) in confirm.choices.iter().enumerate() {
+        dialog.add_button(label, gtk::ResponseType::Other(i as u16));
+    }
+
+    let keys = confirm
+        .choices
+        .into_iter()
+        .map(|(_, key)| key)
+        .collect::<Vec<_>>();
+    let handled = Rc::new(Cell::new(false));
+    dialog.connect_response(clone!(nvim, handled => move |dialog, res| {
+        if handled.replace(true) {
+            return;
+        }
+
+        let key = match res {
+            gtk::ResponseType::Other(i) => keys[i as usize].clone(),
+            // Closing the dialog (e.g. with Escape) cancels the prompt.
+            gtk::ResponseType::DeleteEvent => String::from("<Esc>"),
+            _ => return,
+        };
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.input(&key).await {
+                error!("Failed to answer confirm prompt: {}", err);
+            }
+        });
+
+        // Destroyed instead of closed, as closing would come back here as a
+        // delete event. Safe, as nothing uses the dialog after this.
+        unsafe { dialog.destroy() };
+    }));
+
+    dialog.show_all();
+
+    Some(ConfirmDialog { dialog, handled })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choices(choices: &[(&str, &str)]) -> Vec<(String, String)> {
+        choices
+            .iter()
+            .map(|(label, key)| (label.to_string(), key.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_confirm() {
+        assert_eq!(
+            parse_confirm("Save changes to \"foo\"?\n[Y]es, (N)o, (C)ancel: "),
+            Some(Confirm {
+                message: String::from("Save changes to \"foo\"?"),
+                choices: choices(&[
+                    ("_Yes", "Y"),
+                    ("_No", "N"),
+                    ("_Cancel", "C")
+                ]),
+            })
+        );
+        assert_eq!(
+            parse_confirm("\nSave (A)ll, my_(f)ile: "),
+            Some(Confirm {
+                message: String::new(),
+                choices: choices(&[("Save _All", "A"), ("my___file", "f")]),
+            })
+        );
+        assert_eq!(parse_confirm("Not a prompt"), None);
+    }
+
+    #[test]
+    fn test_parse_confirm_sub() {
+        assert_eq!(
+            parse_confirm_sub("replace with bar (y/n/a/q/l/^E/^Y)?"),
+            Some(Confirm {
+                message: String::from("replace with bar"),
+                choices: choices(&[
+                    ("_Yes", "y"),
+                    ("_No", "n"),
+                    ("_All", "a"),
+                    ("_Quit", "q"),
+                    ("_Last", "l"),
+                ]),
+            })
+        );
+        assert_eq!(parse_confirm_sub("replace with bar"), None);
+    }
+}
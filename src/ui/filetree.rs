@@ -0,0 +1,262 @@
+use std::path::{Path, PathBuf};
+
+use gtk::prelude::*;
+use gtk::{glib, pango};
+
+use log::error;
+
+use crate::files::{self, FileArg};
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+
+const COLUMN_ICON: u32 = 0;
+const COLUMN_NAME: u32 = 1;
+const COLUMN_PATH: u32 = 2;
+const COLUMN_IS_DIR: u32 = 3;
+
+/// Width of the sidebar, in pixels.
+const WIDTH: i32 = 240;
+
+/// Sidebar with the file tree of nvim's current directory. Directories are
+/// read when they're expanded.
+pub struct FileTree {
+    widget: gtk::ScrolledWindow,
+    store: gtk::TreeStore,
+    /// Nvim's current directory.
+    cwd: Option<PathBuf>,
+    /// If `store` has the contents of `cwd`.
+    loaded: bool,
+}
+
+impl FileTree {
+    pub fn new(nvim: GioNeovim) -> Self {
+        let store = gtk::TreeStore::new(&[
+            String::static_type(),
+            String::static_type(),
+            String::static_type(),
+            bool::static_type(),
+        ]);
+
+        let cell_icon = gtk::CellRendererPixbuf::new();
+        let cell_name = gtk::CellRendererText::new();
+        cell_name.set_ellipsize(pango::EllipsizeMode::End);
+
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&cell_icon, false);
+        column.add_attribute(&cell_icon, "icon-name", COLUMN_ICON as i32);
+        column.pack_start(&cell_name, true);
+        column.add_attribute(&cell_name, "text", COLUMN_NAME as i32);
+
+        let tree = gtk::TreeView::with_model(&store);
+        tree.append_column(&column);
+        tree.set_headers_visible(false);
+        tree.set_enable_search(false);
+        tree.set_activate_on_single_click(true);
+        // Keep the keyboard focus in the grid.
+        tree.set_can_focus(false);
+
+        tree.connect_test_expand_row(|tree, iter, _| {
+            if let Some(store) = tree
+                .model()
+                .and_then(|m| m.downcast::<gtk::TreeStore>().ok())
+            {
+                load_children(&store, iter);
+            }
+            Inhibit(false)
+        });
+
+        tree.connect_row_activated(move |tree, path, _| {
+            let model = match tree.model() {
+                Some(model) => model,
+                None => return,
+            };
+            let iter = match model.iter(path) {
+                Some(iter) => iter,
+                None => return,
+            };
+
+            if column_value::<bool>(&model, &iter, COLUMN_IS_DIR) {
+                if tree.row_expanded(path) {
+                    tree.collapse_row(path);
+                } else {
+                    tree.expand_row(path, false);
+                }
+                return;
+            }
+
+            let file = FileArg {
+                path: column_value(&model, &iter, COLUMN_PATH),
+                line: None,
+                col: None,
+            };
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = files::open_file(&nvim, &file).await {
+                    error!("Failed to open '{}': {}", file.path, err);
+                }
+            });
+        });
+
+        let widget = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        widget.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        widget.set_size_request(WIDTH, -1);
+        widget.set_no_show_all(true);
+        widget.add(&tree);
+        tree.show();
+
+        FileTree {
+            widget,
+            store,
+            cwd: None,
+            loaded: false,
+        }
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.widget.clone().upcast()
+    }
+
+    /// Sets the directory to show. The tree is reloaded only if it's
+    /// visible, otherwise when it's shown next time.
+    pub fn set_cwd(&mut self, cwd: &str) {
+        let cwd = PathBuf::from(cwd);
+        if self.cwd.as_ref() == Some(&cwd) {
+            return;
+        }
+
+        self.cwd = Some(cwd);
+        self.loaded = false;
+
+        if self.widget.is_visible() {
+            self.load();
+        }
+    }
+
+    /// Shows or hides the file tree.
+    pub fn toggle(&mut self) {
+        if self.widget.is_visible() {
+            self.widget.hide();
+            return;
+        }
+
+        if !self.loaded {
+            self.load();
+        }
+        self.widget.show();
+    }
+
+    fn load(&mut self) {
+        self.store.clear();
+        if let Some(ref cwd) = self.cwd {
+            add_entries(&self.store, None, cwd);
+        }
+        self.loaded = true;
+    }
+}
+
+fn column_value<T>(model: &gtk::TreeModel, iter: &gtk::TreeIter, col: u32) -> T
+where
+    T: for<'a> glib::value::FromValue<'a> + Default,
+{
+    model.value(iter, col as i32).get::<T>().unwrap_or_default()
+}
+
+/// Reads the directory of `parent`, if it hasn't been read yet.
+fn load_children(store: &gtk::TreeStore, parent: &gtk::TreeIter) {
+    let model = store.upcast_ref::<gtk::TreeModel>();
+    let placeholder = match store.iter_children(Some(parent)) {
+        Some(child) => child,
+        None => return,
+    };
+
+    // Only the placeholder child is without a path.
+    if !column_value::<String>(model, &placeholder, COLUMN_PATH).is_empty() {
+        return;
+    }
+
+    let path = column_value::<String>(model, parent, COLUMN_PATH);
+    add_entries(store, Some(parent), Path::new(&path));
+    store.remove(&placeholder);
+}
+
+/// Adds the entries of directory `dir` under `parent`.
+fn add_entries(
+    store: &gtk::TreeStore,
+    parent: Option<&gtk::TreeIter>,
+    dir: &Path,
+) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                let is_dir = entry.path().is_dir();
+                (entry.file_name().to_string_lossy().into_owned(), is_dir)
+            })
+            .collect(),
+        Err(err) => {
+            error!("Failed to read '{}': {}", dir.display(), err);
+            return;
+        }
+    };
+
+    for (name, is_dir) in sort_entries(entries) {
+        let path = dir.join(&name).to_string_lossy().into_owned();
+        let icon = if is_dir { "folder" } else { "text-x-generic" };
+        let iter = store.insert_with_values(
+            parent,
+            None,
+            &[
+                (COLUMN_ICON, &icon),
+                (COLUMN_NAME, &name),
+                (COLUMN_PATH, &path),
+                (COLUMN_IS_DIR, &is_dir),
+            ],
+        );
+
+        // Placeholder so the directory can be expanded.
+        if is_dir {
+            store.insert_with_values(Some(&iter), None, &[(COLUMN_PATH, &"")]);
+        }
+    }
+}
+
+/// Sorts directory entries (name and if it's a directory), directories
+/// first. Hidden entries are left out.
+fn sort_entries(mut entries: Vec<(String, bool)>) -> Vec<(String, bool)> {
+    entries.retain(|(name, _)| !name.starts_with('.'));
+    entries.sort_by(|(a, a_dir), (b, b_dir)| {
+        b_dir
+            .cmp(a_dir)
+            .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+    });
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_entries() {
+        let entries = vec![
+            (String::from("b.rs"), false),
+            (String::from("src"), true),
+            (String::from(".git"), true),
+            (String::from("A.md"), false),
+            (String::from("Docs"), true),
+        ];
+
+        assert_eq!(
+            sort_entries(entries),
+            vec![
+                (String::from("Docs"), true),
+                (String::from("src"), true),
+                (String::from("A.md"), false),
+                (String::from("b.rs"), false),
+            ]
+        );
+    }
+}
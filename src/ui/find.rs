@@ -0,0 +1,325 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::gdk;
+use gtk::prelude::*;
+
+use log::error;
+
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+
+const CSS: &str = "
+frame.find {
+    background-color: @theme_bg_color;
+    border-radius: 6px;
+    padding: 6px;
+}
+";
+
+/// Find and replace bar, in the top right corner. Uses nvim's last search
+/// pattern (`@/`) and `:substitute`, so `n`, `N` and `:s//` work with the
+/// pattern afterwards.
+pub struct FindBar {
+    frame: gtk::Frame,
+    find: gtk::Entry,
+    replace: gtk::Entry,
+    case_sensitive: gtk::ToggleButton,
+    regex: gtk::ToggleButton,
+    window: gtk::ApplicationWindow,
+    nvim: GioNeovim,
+
+    /// Widget that had the focus before the bar was shown.
+    prev_focus: RefCell<Option<gtk::Widget>>,
+}
+
+impl FindBar {
+    /// Creates the (hidden) find bar. Must be called before the window's
+    /// other key press handlers are connected, so we get the keypresses
+    /// while the bar has the focus.
+    pub fn new(
+        parent: &gtk::Overlay,
+        window: &gtk::ApplicationWindow,
+        nvim: GioNeovim,
+    ) -> Rc<Self> {
+        let css_provider = gtk::CssProvider::new();
+        if let Err(err) =
+            CssProviderExt::load_from_data(&css_provider, CSS.as_bytes())
+        {
+            error!("Failed to load find bar css: {}", err);
+        }
+
+        let find = gtk::Entry::new();
+        find.set_placeholder_text(Some("Find"));
+        find.set_width_chars(30);
+        let replace = gtk::Entry::new();
+        replace.set_placeholder_text(Some("Replace"));
+        replace.set_width_chars(30);
+
+        let case_sensitive = gtk::ToggleButton::with_label("Aa");
+        case_sensitive.set_tooltip_text(Some("Match case"));
+        let regex = gtk::ToggleButton::with_label(".*");
+        regex.set_tooltip_text(Some("Regular expression"));
+
+        let icon_button = |icon: &str, tooltip: &str| {
+            let button =
+                gtk::Button::from_icon_name(Some(icon), gtk::IconSize::Button);
+            button.set_tooltip_text(Some(tooltip));
+            button
+        };
+        let prev = icon_button("go-up-symbolic", "Previous match");
+        let next = icon_button("go-down-symbolic", "Next match");
+        let close = icon_button("window-close-symbolic", "Close");
+        close.set_relief(gtk::ReliefStyle::None);
+        let replace_one = gtk::Button::with_label("Replace");
+        let replace_all = gtk::Button::with_label("Replace All");
+
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(6);
+        grid.attach(&find, 0, 0, 1, 1);
+        grid.attach(&case_sensitive, 1, 0, 1, 1);
+        grid.attach(&regex, 2, 0, 1, 1);
+        grid.attach(&prev, 3, 0, 1, 1);
+        grid.attach(&next, 4, 0, 1, 1);
+        grid.attach(&close, 5, 0, 1, 1);
+        grid.attach(&replace, 0, 1, 1, 1);
+        grid.attach(&replace_one, 1, 1, 2, 1);
+        grid.attach(&replace_all, 3, 1, 3, 1);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_halign(gtk::Align::End);
+        frame.set_valign(gtk::Align::Start);
+        frame.set_margin(10);
+        frame.set_no_show_all(true);
+        frame.style_context().add_class("find");
+        frame.add(&grid);
+        parent.add_overlay(&frame);
+
+        add_css_provider!(&css_provider, frame);
+
+        let bar = Rc::new(FindBar {
+            frame,
+            find,
+            replace,
+            case_sensitive,
+            regex,
+            window: window.clone(),
+            nvim,
+            prev_focus: RefCell::new(None),
+        });
+
+        // Highlight the matches while typing.
+        let weak = Rc::downgrade(&bar);
+        let update = move || {
+            let bar = upgrade_weak!(weak);
+            bar.run(format!(
+                "call setreg('/', {}) | let v:hlsearch = 1",
+                vim_string(&bar.pattern())
+            ));
+        };
+        bar.find
+            .connect_changed(clone!(update => move |_| update()));
+        bar.case_sensitive
+            .connect_toggled(clone!(update => move |_| update()));
+        bar.regex.connect_toggled(move |_| update());
+
+        let weak = Rc::downgrade(&bar);
+        prev.connect_clicked(move |_| {
+            upgrade_weak!(weak).search(false);
+        });
+        let weak = Rc::downgrade(&bar);
+        next.connect_clicked(move |_| {
+            upgrade_weak!(weak).search(true);
+        });
+        let weak = Rc::downgrade(&bar);
+        replace_one.connect_clicked(move |_| {
+            upgrade_weak!(weak).replace_next();
+        });
+        let weak = Rc::downgrade(&bar);
+        replace_all.connect_clicked(move |_| {
+            upgrade_weak!(weak).replace_all();
+        });
+        let weak = Rc::downgrade(&bar);
+        close.connect_clicked(move |_| {
+            upgrade_weak!(weak).hide();
+        });
+
+        let weak = Rc::downgrade(&bar);
+        window.connect_key_press_event(move |window, e| {
+            let bar = upgrade_weak!(weak, Inhibit(false));
+            let focus = match window.focus() {
+                Some(focus) if focus.is_ancestor(&bar.frame) => focus,
+                _ => return Inhibit(false),
+            };
+
+            let shift = e.state().contains(gdk::ModifierType::SHIFT_MASK);
+            match e.keyval() {
+                gdk::keys::constants::Escape => bar.hide(),
+                gdk::keys::constants::Return
+                | gdk::keys::constants::KP_Enter
+                    if focus == bar.find =>
+                {
+                    bar.search(!shift)
+                }
+                gdk::keys::constants::Return
+                | gdk::keys::constants::KP_Enter
+                    if focus == bar.replace =>
+                {
+                    bar.replace_next()
+                }
+                // The rest goes to the bar, and not to nvim.
+                _ => {
+                    window.propagate_key_event(e);
+                }
+            }
+
+            Inhibit(true)
+        });
+
+        bar
+    }
+
+    /// Shows the bar, and focuses the find entry.
+    pub fn show(&self) {
+        if !self.frame.is_visible() {
+            self.prev_focus.replace(self.window.focus());
+        }
+        self.frame.show_all();
+        self.find.grab_focus();
+    }
+
+    /// Hides the bar, and gives the focus back to where it was.
+    fn hide(&self) {
+        self.frame.hide();
+        if let Some(focus) = self.prev_focus.take() {
+            focus.grab_focus();
+        }
+    }
+
+    fn pattern(&self) -> String {
+        search_pattern(
+            &self.find.text(),
+            self.case_sensitive.is_active(),
+            self.regex.is_active(),
+        )
+    }
+
+    fn run(&self, cmd: String) {
+        let nvim = self.nvim.clone();
+        spawn_local(async move {
+            if let Err(err) = nvim.command(&cmd).await {
+                error!("Failed to run '{}': {}", cmd, err);
+            }
+        });
+    }
+
+    /// Moves to the next (or previous) match, like `n` (or `N`) after `/`.
+    fn search(&self, forward: bool) {
+        if self.find.text().is_empty() {
+            return;
+        }
+
+        self.run(format!(
+            "call setreg('/', {}) | let v:searchforward = {} \
+             | let v:hlsearch = 1 | silent! normal! n",
+            vim_string(&self.pattern()),
+            forward as u8,
+        ));
+    }
+
+    /// Replaces the match at (or after) the cursor, and moves to the next
+    /// one.
+    fn replace_next(&self) {
+        if self.find.text().is_empty() {
+            return;
+        }
+
+        let pattern = self.pattern();
+        let replacement =
+            replacement(&self.replace.text(), self.regex.is_active());
+        self.run(format!(
+            "call setreg('/', {}) | let v:searchforward = 1 \
+             | if search(@/, 'c') | s/\\%#{}/{}/e | endif \
+             | let v:hlsearch = 1 | silent! normal! n",
+            vim_string(&pattern),
+            pattern,
+            replacement,
+        ));
+    }
+
+    /// Replaces all the matches in the buffer.
+    fn replace_all(&self) {
+        if self.find.text().is_empty() {
+            return;
+        }
+
+        let pattern = self.pattern();
+        let replacement =
+            replacement(&self.replace.text(), self.regex.is_active());
+        self.run(format!(
+            "call setreg('/', {}) | %s/{}/{}/ge",
+            vim_string(&pattern),
+            pattern,
+            replacement,
+        ));
+    }
+}
+
+/// Vimscript string literal of `s`.
+fn vim_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Search pattern for `text`, with `/` escaped so the pattern can be used in
+/// `:s/`. Without `regex`, `text` is matched literally.
+fn search_pattern(text: &str, case_sensitive: bool, regex: bool) -> String {
+    let case = if case_sensitive { "\\C" } else { "\\c" };
+    let text = if regex {
+        text.to_string()
+    } else {
+        format!("\\V{}", text.replace('\\', "\\\\"))
+    };
+
+    format!("{}{}", case, text.replace('/', "\\/"))
+}
+
+/// Replacement string for `:s/` from `text`. Without `regex`, `text` is
+/// inserted literally.
+fn replacement(text: &str, regex: bool) -> String {
+    let text = if regex {
+        text.to_string()
+    } else {
+        text.replace('\\', "\\\\")
+            .replace('&', "\\&")
+            .replace('~', "\\~")
+    };
+
+    // Keep the replacement in the command, `|` would end it.
+    text.replace('/', "\\/").replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vim_string() {
+        assert_eq!(vim_string("foo"), "'foo'");
+        assert_eq!(vim_string("it's"), "'it''s'");
+    }
+
+    #[test]
+    fn test_search_pattern() {
+        assert_eq!(search_pattern("a.b", false, false), "\\c\\Va.b");
+        assert_eq!(search_pattern("a\\/b", true, false), "\\C\\Va\\\\\\/b");
+        assert_eq!(search_pattern("a.*/b", true, true), "\\Ca.*\\/b");
+    }
+
+    #[test]
+    fn test_replacement() {
+        assert_eq!(replacement("a&b~c\\", false), "a\\&b\\~c\\\\");
+        assert_eq!(replacement("\\1/&", true), "\\1\\/&");
+        assert_eq!(replacement("a|b", false), "a\\|b");
+    }
+}
@@ -1,93 +1,23 @@
-use std::fmt;
-use std::fmt::Display;
+use gtk::prelude::*;
+use gtk::{cairo, gdk, pango};
 
-use gtk::pango;
+use log::error;
 
-const DEFAULT_HEIGHT: f32 = 14.0;
+use crate::config::FontsConfig;
 
-pub enum FontUnit {
-    #[allow(dead_code)]
-    Pixel,
-    Point,
-}
-
-impl Display for FontUnit {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            FontUnit::Pixel => write!(fmt, "px"),
-            FontUnit::Point => write!(fmt, "pt"),
-        }
-    }
-}
+pub use gnvim_core::font::{Font, FontUnit, DEFAULT_HEIGHT};
 
-#[derive(Clone, Debug)]
-pub struct Font {
-    name: String,
-    pub height: f32,
+pub trait FontExt {
+    /// Returns a pango::FontDescription version of self.
+    fn as_pango_font(&self) -> pango::FontDescription;
 }
 
-impl Font {
-    /// Parses nvim `guifont` option.
-    ///
-    /// If invalid height is specified, defaults to `DEFAULT_HEIGHT`.
-    pub fn from_guifont(guifont: &str) -> Result<Self, ()> {
-        let mut parts = guifont.split(':');
-
-        let name = parts.next().ok_or(())?;
-
-        if name.is_empty() {
-            return Err(());
-        }
-
-        let mut font = Font {
-            name: name.to_string(),
-            height: DEFAULT_HEIGHT,
-        };
-
-        for part in parts {
-            let mut chars = part.chars();
-            if let Some(ch) = chars.next() {
-                match ch {
-                    'h' => {
-                        let rest = chars.collect::<String>();
-                        let h = rest.parse::<f32>().or(Err(()))?;
-                        if h <= 0.0 {
-                            // Ignore zero sized font.
-                            continue;
-                        }
-                        font.height = h;
-                    }
-                    _ => {
-                        println!("Not supported guifont option: {}", part);
-                    }
-                }
-            }
-        }
-
-        Ok(font)
-    }
-
-    /// Returns a CSS representation of self for a wild (`*`) CSS selector.
-    /// On gtk version below 3.20 unit needs to be `FontUnit::Pixel` and
-    /// with version 3.20 and up, unit needs to be `FontUnit::Point`. This is
-    /// to work around some gtk issues on versions before 3.20.
-    pub fn as_wild_css(&self, unit: FontUnit) -> String {
-        format!(
-            "* {{ \
-             font-family: \"{font_family}\"; \
-             font-size: {font_size}{font_unit}; \
-             }}",
-            font_family = self.name,
-            font_size = self.height,
-            font_unit = unit,
-        )
-    }
-
-    /// Returns a pango::FontDescription version of self.
-    pub fn as_pango_font(&self) -> pango::FontDescription {
+impl FontExt for Font {
+    fn as_pango_font(&self) -> pango::FontDescription {
         let mut font_desc = pango::FontDescription::from_string(&format!(
             "{} {}",
-            self.name, self.height
+            self.family(),
+            self.height
         ));
 
         // Make sure we dont have a font with size of 0, otherwise we'll
@@ -100,69 +30,87 @@ impl Font {
     }
 }
 
-impl Default for Font {
-    fn default() -> Self {
-        Font {
-            name: String::from("Monospace"),
-            height: DEFAULT_HEIGHT,
-        }
+/// Lists the names of the available monospace font families.
+pub fn monospace_families() -> Vec<String> {
+    let font_map = match pangocairo::FontMap::default() {
+        Some(font_map) => font_map,
+        None => return vec![],
+    };
+
+    let mut families = font_map
+        .list_families()
+        .iter()
+        .filter(|family| family.is_monospace())
+        .filter_map(|family| family.name())
+        .map(String::from)
+        .collect::<Vec<_>>();
+    families.sort();
+
+    families
+}
+
+fn antialias_from_name(name: &str) -> Option<cairo::Antialias> {
+    match name {
+        "none" => Some(cairo::Antialias::None),
+        "gray" => Some(cairo::Antialias::Gray),
+        "subpixel" => Some(cairo::Antialias::Subpixel),
+        _ => None,
+    }
+}
+
+fn hint_style_from_name(name: &str) -> Option<cairo::HintStyle> {
+    match name {
+        "none" => Some(cairo::HintStyle::None),
+        "slight" => Some(cairo::HintStyle::Slight),
+        "medium" => Some(cairo::HintStyle::Medium),
+        "full" => Some(cairo::HintStyle::Full),
+        _ => None,
+    }
+}
+
+fn subpixel_order_from_name(name: &str) -> Option<cairo::SubpixelOrder> {
+    match name {
+        "rgb" => Some(cairo::SubpixelOrder::Rgb),
+        "bgr" => Some(cairo::SubpixelOrder::Bgr),
+        "vrgb" => Some(cairo::SubpixelOrder::Vrgb),
+        "vbgr" => Some(cairo::SubpixelOrder::Vbgr),
+        _ => None,
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_as_wild_css() {
-        let font = Font {
-            name: "foo".to_string(),
-            height: 10.0,
-        };
-
-        assert_eq!(
-            font.as_wild_css(FontUnit::Point),
-            "* { \
-             font-family: \"foo\"; \
-             font-size: 10pt; \
-             }"
-        );
-
-        assert_eq!(
-            font.as_wild_css(FontUnit::Pixel),
-            "* { \
-             font-family: \"foo\"; \
-             font-size: 10px; \
-             }"
-        );
+/// Font options (antialiasing and hinting) of the grids, from the config
+/// file. The options that aren't set are the screen's. Returns `None` if
+/// none are set.
+pub fn font_options(config: &FontsConfig) -> Option<cairo::FontOptions> {
+    if config.antialias.is_none()
+        && config.hint_style.is_none()
+        && config.subpixel_order.is_none()
+    {
+        return None;
     }
 
-    #[test]
-    fn test_from_guifont() {
-        // Font with proper height.
-        let f = Font::from_guifont("monospace:h11").unwrap();
-        assert_eq!(f.name, "monospace");
-        assert_eq!(f.height, 11.0);
-
-        // Font with invalid height.
-        let f = Font::from_guifont("font:h");
-        assert_eq!(f.is_err(), true);
-        let f = Font::from_guifont("font:hn");
-        assert_eq!(f.is_err(), true);
-
-        // Font with height zero.
-        let f = Font::from_guifont("foo:h0").unwrap();
-        assert_eq!(f.name, "foo");
-        assert_eq!(f.height, DEFAULT_HEIGHT);
-
-        // Font with negative height.
-        let f = Font::from_guifont("font:h-1").unwrap();
-        assert_eq!(f.name, "font");
-        assert_eq!(f.height, DEFAULT_HEIGHT);
-
-        // Font with no height.
-        let f = Font::from_guifont("bar").unwrap();
-        assert_eq!(f.name, "bar");
-        assert_eq!(f.height, DEFAULT_HEIGHT);
+    let mut options = gdk::Screen::default()
+        .and_then(|screen| screen.font_options())
+        .or_else(|| cairo::FontOptions::new().ok())?;
+
+    if let Some(ref name) = config.antialias {
+        match antialias_from_name(name) {
+            Some(antialias) => options.set_antialias(antialias),
+            None => error!("Invalid antialias: {}", name),
+        }
+    }
+    if let Some(ref name) = config.hint_style {
+        match hint_style_from_name(name) {
+            Some(hint_style) => options.set_hint_style(hint_style),
+            None => error!("Invalid hint style: {}", name),
+        }
+    }
+    if let Some(ref name) = config.subpixel_order {
+        match subpixel_order_from_name(name) {
+            Some(order) => options.set_subpixel_order(order),
+            None => error!("Invalid subpixel order: {}", name),
+        }
     }
+
+    Some(options)
 }
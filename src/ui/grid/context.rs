@@ -2,13 +2,17 @@ use gtk::prelude::*;
 use gtk::DrawingArea;
 use gtk::{cairo, gdk, pango};
 
+use gnvim_core::render::Rect;
+
 use crate::error::Error;
 use crate::ui::color::HlDefs;
-use crate::ui::font::Font;
+use crate::ui::font::{Font, FontExt};
 use crate::ui::grid::cursor::Cursor;
-use crate::ui::grid::render;
+use crate::ui::grid::particles::Particles;
+use crate::ui::grid::render::{self, ItemizeCache};
 use crate::ui::grid::row::{Cell, Row};
-use crate::ui::grid::Surfaces;
+use crate::ui::grid::rows as grid_rows;
+use crate::ui::grid::{ScrollAnimation, Surfaces};
 
 /// Context is manipulated by Grid.
 pub struct Context {
@@ -22,6 +26,11 @@ pub struct Context {
     pub cursor: Cursor,
     /// Cairo context for cursor.
     pub cursor_context: cairo::Context,
+    /// Particles emitted from the cursor.
+    pub particles: Particles,
+    /// Overlay that the cursor is drawn to, instead of the grid, while it
+    /// moves here from another grid (see `Grid::draw_cursor_to`).
+    pub cursor_overlay: Option<gtk::Widget>,
 
     /// If the current status is busy or not. When busy, the cursor is not
     /// drawn (like when in terminal mode in inserting text).
@@ -29,11 +38,32 @@ pub struct Context {
 
     /// If the grid that this context belongs to is active or not.
     pub active: bool,
-    /// Grid scroll speed, in ms.
-    pub scroll_speed: i64,
+    /// Horizontal and vertical padding around the grid, in pixels.
+    pub padding: (f64, f64),
+    /// Grid scroll animation.
+    pub scroll: ScrollAnimation,
+    /// Grid's own scroll speed, overriding the ones in `scroll`.
+    pub scroll_speed: Option<i64>,
+    /// Monotonic time (in microseconds) of the latest mouse scroll.
+    pub mouse_scroll_time: i64,
+
+    /// Itemized texts of the grid, for rendering.
+    pub itemize_cache: ItemizeCache,
 
     /// Areas to call queue_draw_area on the drawing area on flush.
     pub queue_draw_area: Vec<(f64, f64, f64, f64)>,
+    /// Next row to redraw, when the whole grid is being redrawn (see
+    /// `Grid::redraw`).
+    pub redraw_row: Option<usize>,
+    /// Cell (row, col) that the ligatures are broken at, so that the cursor
+    /// doesn't show half of a ligature (see `Grid::cursor_goto`).
+    pub ligature_break: Option<(usize, usize)>,
+
+    /// If the debug overlay (cell boundaries, baselines and damaged areas)
+    /// is drawn on top of the grid.
+    pub debug_overlay: bool,
+    /// Areas that were damaged on the latest flush, for the debug overlay.
+    pub damage: Vec<(f64, f64, f64, f64)>,
 }
 
 impl Context {
@@ -43,11 +73,14 @@ impl Context {
         win: &gdk::Window,
         font: Font,
         line_space: i64,
+        cell_width: CellWidth,
+        cell_scale: CellScale,
+        render_options: RenderOptions,
         cols: usize,
         rows: usize,
         hl_defs: &HlDefs,
         enable_cursor_animations: bool,
-        scroll_speed: i64,
+        scroll: ScrollAnimation,
     ) -> Result<Self, Error> {
         let pango_context = da.pango_context();
 
@@ -57,6 +90,9 @@ impl Context {
         let mut cell_metrics = CellMetrics {
             font,
             line_space,
+            cell_width,
+            cell_scale,
+            options: render_options,
             ..CellMetrics::default()
         };
         cell_metrics.update(&pango_context)?;
@@ -81,12 +117,24 @@ impl Context {
 
             cursor,
             cursor_context,
+            particles: Particles::default(),
+            cursor_overlay: None,
 
             busy: false,
             active: false,
-            scroll_speed,
+            padding: (0.0, 0.0),
+            scroll,
+            scroll_speed: None,
+            mouse_scroll_time: 0,
+
+            itemize_cache: ItemizeCache::default(),
 
             queue_draw_area: vec![],
+            redraw_row: None,
+            ligature_break: None,
+
+            debug_overlay: false,
+            damage: vec![],
         })
     }
 
@@ -102,20 +150,13 @@ impl Context {
         let prev_rows = self.rows.len();
         let prev_cols = self.rows.get(0).map(|r| r.len()).unwrap_or(0);
 
-        if self.rows.len() != rows {
-            self.rows.resize_with(rows, || Row::new(cols));
-        }
-
-        if self.rows.get(0).unwrap().len() != cols {
-            for row in self.rows.iter_mut() {
-                row.resize(cols);
-            }
-        }
+        grid_rows::resize(&mut self.rows, rows, cols);
 
         let pctx = da.pango_context();
         pctx.set_font_description(&self.cell_metrics.font.as_pango_font());
 
         self.cell_metrics.update(&pctx)?;
+        self.itemize_cache.clear();
 
         let old_surfaces = std::mem::replace(
             &mut self.surfaces,
@@ -133,25 +174,22 @@ impl Context {
         self.surfaces.offset_y_anim = old_surfaces.offset_y_anim;
 
         // Keep the old content.
-        self.surfaces.front.set_source_surface(
-            &old_surfaces.front.target(),
-            0.0,
-            0.0,
-        )?;
-        self.surfaces.front.set_operator(cairo::Operator::Source);
         // Make sure we only paint the area that _was_ visible before this update
         // so we don't undo the bg color paint we did earlier. Note that we're
         // calculating the used area based on the current cell metrics. This is
         // becuase if font changes that might reduce the area we "have available".
         // Otherwise, when changing to smaller font, we might draw our "old" surface
         // on a area that wont be cleared by nvim (e.g. over "fresh" whitespace).
-        self.surfaces.front.rectangle(
+        self.surfaces.front.paint_from(
+            &old_surfaces.front,
             0.0,
-            0.0,
-            self.cell_metrics.width * prev_cols as f64,
-            self.cell_metrics.height * prev_rows as f64,
-        );
-        self.surfaces.front.fill()?;
+            Rect {
+                x: 0.0,
+                y: 0.0,
+                width: self.cell_metrics.width * prev_cols as f64,
+                height: self.cell_metrics.height * prev_rows as f64,
+            },
+        )?;
 
         Ok(())
     }
@@ -172,6 +210,7 @@ impl Context {
         self.cell_metrics.font = font;
         self.cell_metrics.line_space = line_space;
         self.cell_metrics.update(&pango_context)?;
+        self.itemize_cache.clear();
 
         self.cursor_context =
             Cursor::new_cairo_context(win, &self.cell_metrics)?;
@@ -189,7 +228,25 @@ impl Context {
 
         // Dont use cursor.get_position here, because we want to use the position on the screen.
         let pos = self.cursor.pos.unwrap_or((0.0, 0.0));
+        self.cell_rect(pos, double_width)
+    }
 
+    /// Returns x, y, width and height of the cell that the cursor is on, or
+    /// is moving to (i.e. ignoring the cursor's animation).
+    pub fn get_cursor_target_rect(&self) -> (i32, i32, i32, i32) {
+        let double_width = self
+            .cell_at_cursor()
+            .map(|cell| cell.double_width)
+            .unwrap_or(false);
+        let pos = self.cursor.get_position().unwrap_or((0.0, 0.0));
+        self.cell_rect(pos, double_width)
+    }
+
+    fn cell_rect(
+        &self,
+        pos: (f64, f64),
+        double_width: bool,
+    ) -> (i32, i32, i32, i32) {
         let cm = &self.cell_metrics;
         let (x, y) = render::get_coords(cm.height, cm.width, pos.0, pos.1);
         (
@@ -207,6 +264,10 @@ impl Context {
     pub fn cursor_goto(&mut self, row: u64, col: u64, clock: &gdk::FrameClock) {
         // Clear old cursor position.
         let (x, y, w, h) = self.get_cursor_rect();
+        self.particles.emit(
+            (f64::from(x) + f64::from(w) / 2.0, f64::from(y + h / 2)),
+            clock.frame_time(),
+        );
         self.queue_draw_area.push((
             f64::from(x),
             f64::from(y),
@@ -240,17 +301,25 @@ impl Context {
 
         self.cursor.tick(ft);
 
+        if let Some(ref overlay) = self.cursor_overlay {
+            overlay.queue_draw();
+            if self.cursor.animation.is_none() {
+                self.cursor_overlay = None;
+            }
+        }
+
+        if let Some((x, y, w, h)) = self.particles.tick(ft) {
+            da.queue_draw_area(x, y, w, h);
+        }
+
         // We're not blinking, so skip the blink animation phase.
-        if self.cursor.blink_on == 0 {
+        if !self.cursor.is_blinking() {
             return Ok(());
         }
 
         let (x, y, w, h) = self.get_cursor_rect();
 
-        let mut alpha = self.cursor.alpha;
-        if alpha > 1.0 {
-            alpha = 2.0 - alpha;
-        }
+        let alpha = self.cursor.alpha;
 
         let cr = &self.cursor_context;
         cr.save()?;
@@ -280,6 +349,17 @@ impl Context {
         Ok(())
     }
 
+    /// Returns the cell (row, col) at `x`, `y` (in pixels) of the grid's
+    /// widget.
+    pub fn cell_at_position(&self, x: f64, y: f64) -> (u64, u64) {
+        let x = (x - self.padding.0).max(0.0);
+        let y = (y - self.padding.1).max(0.0);
+        (
+            (y / self.cell_metrics.height).floor() as u64,
+            (x / self.cell_metrics.width).floor() as u64,
+        )
+    }
+
     pub fn cell_at_cursor(&self) -> Option<&Cell> {
         self.cursor.get_position().and_then(|pos| {
             self.rows
@@ -289,31 +369,53 @@ impl Context {
     }
 }
 
-/// Cell metrics tells the size (and other metrics) of the cells in a grid.
-#[derive(Default, Debug, Clone)]
-pub struct CellMetrics {
-    pub height: f64,
-    pub width: f64,
-    pub ascent: f64,
-    pub decent: f64,
-    pub underline_thickness: f64,
-    pub underline_position: f64,
-
-    pub line_space: i64,
-    pub font: Font,
+pub use gnvim_core::render::{
+    CellMetrics, CellScale, CellWidth, RenderOptions,
+};
+
+/// Pango specific functionality for `CellMetrics`.
+pub trait CellMetricsExt {
+    /// Updates the metrics from the font in `ctx`.
+    fn update(&mut self, ctx: &pango::Context) -> Result<(), Error>;
 }
 
-impl CellMetrics {
-    pub fn update(&mut self, ctx: &pango::Context) -> Result<(), Error> {
+/// Width of the widest printable ASCII character in the font of `ctx`.
+fn widest_char_width(ctx: &pango::Context) -> f64 {
+    let layout = pango::Layout::new(ctx);
+    let width = ('!'..='~')
+        .map(|c| {
+            layout.set_text(&c.to_string());
+            layout.extents().1.width
+        })
+        .max()
+        .unwrap_or(0);
+
+    f64::from(width) / f64::from(pango::SCALE)
+}
+
+impl CellMetricsExt for CellMetrics {
+    fn update(&mut self, ctx: &pango::Context) -> Result<(), Error> {
         let fm = ctx
             .metrics(Some(&self.font.as_pango_font()), None)
             .ok_or(Error::GetPangoMetrics())?;
-        let extra = self.line_space as f64 / 2.0;
         let scale = f64::from(pango::SCALE);
-        self.ascent = (f64::from(fm.ascent()) / scale + extra).ceil();
-        self.decent = (f64::from(fm.descent()) / scale + extra).ceil();
+        let ascent = f64::from(fm.ascent()) / scale;
+        let descent = f64::from(fm.descent()) / scale;
+        // The extra height of the scale is split above and below the text,
+        // like the line space.
+        let extra = self.line_space as f64 / 2.0
+            + (ascent + descent) * (self.cell_scale.height - 1.0) / 2.0;
+        self.ascent = (ascent + extra).ceil();
+        self.decent = (descent + extra).ceil();
         self.height = self.ascent + self.decent;
-        self.width = f64::from(fm.approximate_char_width()) / scale;
+        self.width = match self.cell_width {
+            CellWidth::Font => {
+                f64::from(fm.approximate_char_width()) / scale
+                    * self.cell_scale.width
+            }
+            CellWidth::Widest => widest_char_width(ctx) * self.cell_scale.width,
+            CellWidth::Fixed(width) => width,
+        };
 
         self.underline_position =
             f64::from(fm.underline_position()) / scale - extra;
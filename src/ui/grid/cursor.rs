@@ -17,12 +17,22 @@ pub struct Cursor {
 
     /// Alpha color. Used to make the cursor blink.
     pub alpha: f64,
-    /// The duration of the blink.
+    /// Delay before the blinking starts (in ms).
+    pub blink_wait: u64,
+    /// Time the cursor is shown when blinking (in ms).
     pub blink_on: u64,
+    /// Time the cursor is hidden when blinking (in ms).
+    pub blink_off: u64,
+    /// Frame time when the blink cycle (starting with `blink_wait`) started.
+    /// Reset when the cursor moves, so it won't blink while typing.
+    pub blink_start: Option<i64>,
     /// Width of the cursor.
     pub cell_percentage: f64,
     /// Color of the cursor.
     pub color: Color,
+    /// Highlight of the cursor, from the current mode. Zero for the
+    /// reversed colors of the cell under the cursor.
+    pub attr_id: u64,
 }
 
 impl Cursor {
@@ -43,6 +53,8 @@ impl Cursor {
     }
 
     pub fn goto(&mut self, row: f64, col: f64, frame_time: i64) {
+        self.blink_start = Some(frame_time);
+
         // When we get our first cursor_goto, set the position directly.
         if self.pos.is_none() {
             self.pos = Some((row, col));
@@ -59,27 +71,40 @@ impl Cursor {
                 end: (row, col),
                 start_time: frame_time,
                 end_time: frame_time + 1000 * duration,
+                ..Animation::default()
             });
         }
     }
 
     pub fn tick(&mut self, frame_time: i64) {
-        self.blink();
+        self.blink(frame_time);
         self.animate_position(frame_time);
     }
 
-    fn blink(&mut self) {
-        // If we dont need to blink, return.
-        if self.blink_on == 0 {
+    /// If the cursor blinks. Like in vim, if any of the blink times is
+    /// zero, there is no blinking.
+    pub fn is_blinking(&self) -> bool {
+        self.blink_wait != 0 && self.blink_on != 0 && self.blink_off != 0
+    }
+
+    fn blink(&mut self, frame_time: i64) {
+        if !self.is_blinking() {
+            self.alpha = 1.0;
             return;
         }
 
-        // Assuming a 60hz framerate
-        self.alpha += 100.0 / (6.0 * self.blink_on as f64);
+        let start = *self.blink_start.get_or_insert(frame_time);
+        // Frame time is in microseconds.
+        let t = ((frame_time - start) / 1000).max(0) as u64;
 
-        if self.alpha > 2.0 {
-            self.alpha = 0.0;
-        }
+        self.alpha = if t < self.blink_wait
+            || (t - self.blink_wait) % (self.blink_on + self.blink_off)
+                < self.blink_on
+        {
+            1.0
+        } else {
+            0.0
+        };
     }
 
     fn animate_position(&mut self, frame_time: i64) {
@@ -118,22 +143,48 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_cursor_blink100() {
+    fn test_cursor_blink() {
         let mut cursor = Cursor::default();
-        cursor.blink_on = 100;
-        cursor.alpha = 1.0;
+        cursor.blink_wait = 700;
+        cursor.blink_on = 400;
+        cursor.blink_off = 250;
+
+        let ms = |ms: i64| ms * 1000;
+        cursor.blink(ms(1000));
+        assert_eq!(cursor.alpha, 1.0);
 
-        cursor.blink();
-        assert_eq!(cursor.alpha, 1.1666666666666667);
+        // Wait.
+        cursor.blink(ms(1699));
+        assert_eq!(cursor.alpha, 1.0);
+        // On.
+        cursor.blink(ms(2099));
+        assert_eq!(cursor.alpha, 1.0);
+        // Off.
+        cursor.blink(ms(2100));
+        assert_eq!(cursor.alpha, 0.0);
+        cursor.blink(ms(2349));
+        assert_eq!(cursor.alpha, 0.0);
+        // On again.
+        cursor.blink(ms(2350));
+        assert_eq!(cursor.alpha, 1.0);
+
+        // Moving the cursor restarts the wait.
+        cursor.disable_animation = true;
+        cursor.goto(1.0, 1.0, ms(2100));
+        cursor.blink(ms(2700));
+        assert_eq!(cursor.alpha, 1.0);
     }
 
     #[test]
     fn test_cursor_blink0() {
         let mut cursor = Cursor::default();
-        cursor.blink_on = 0;
-        cursor.alpha = 1.0;
+        cursor.blink_wait = 700;
+        cursor.blink_on = 400;
+        cursor.blink_off = 0;
+        cursor.alpha = 0.0;
 
-        cursor.blink();
+        cursor.blink(1000);
+        assert!(!cursor.is_blinking());
         assert_eq!(cursor.alpha, 1.0);
     }
 
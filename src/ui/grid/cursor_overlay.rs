@@ -0,0 +1,68 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use gtk::prelude::*;
+use gtk::DrawingArea;
+
+use log::error;
+
+use crate::ui::grid::context::Context;
+use crate::ui::grid::grid::draw_cursor;
+
+/// Grid that the cursor is moving to: its context, and its drawing area.
+type Target = Option<(Weak<RefCell<Context>>, DrawingArea)>;
+
+/// Drawing area on top of all the grids. The cursor is drawn to it while it
+/// moves from one grid to another (see `Grid::draw_cursor_to`), so that
+/// neither of the grids clips it.
+pub struct CursorOverlay {
+    da: DrawingArea,
+    target: Rc<RefCell<Target>>,
+}
+
+impl CursorOverlay {
+    pub fn new() -> Self {
+        let da = DrawingArea::new();
+        let target: Rc<RefCell<Target>> = Rc::new(RefCell::new(None));
+
+        da.connect_draw(clone!(target => move |da, cr| {
+            let target = target.borrow();
+            let (ctx, grid_da) = match target.as_ref() {
+                Some((ctx, grid_da)) => (ctx, grid_da),
+                None => return Inhibit(false),
+            };
+            let ctx = match ctx.upgrade() {
+                Some(ctx) => ctx,
+                None => return Inhibit(false),
+            };
+            let ctx = ctx.borrow();
+
+            // Once the cursor is in its grid, the grid draws it.
+            if ctx.cursor_overlay.is_none() || ctx.busy || !ctx.active {
+                return Inhibit(false);
+            }
+
+            if let Some((x, y)) = grid_da.translate_coordinates(da, 0, 0) {
+                cr.translate(f64::from(x), f64::from(y));
+                if let Err(err) = draw_cursor(cr, &ctx) {
+                    error!("Failed to draw the cursor: {:?}", err);
+                }
+            }
+
+            Inhibit(false)
+        }));
+
+        CursorOverlay { da, target }
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.da.clone().upcast()
+    }
+
+    /// Draws the cursor of the grid that has `ctx` and `da` until it has
+    /// moved to its place.
+    pub fn set_target(&self, ctx: Weak<RefCell<Context>>, da: &DrawingArea) {
+        *self.target.borrow_mut() = Some((ctx, da.clone()));
+        self.da.queue_draw();
+    }
+}
@@ -2,6 +2,7 @@ use std::cell::RefCell;
 use std::fmt;
 use std::fmt::Display;
 use std::rc::Rc;
+use std::time::Instant;
 
 use gtk::gdk::{EventMask, ModifierType};
 use gtk::{cairo, gdk, glib};
@@ -9,16 +10,20 @@ use gtk::{DrawingArea, EventBox};
 
 use gtk::prelude::*;
 
+use log::error;
+
+use gnvim_core::render::Rect;
+
 use crate::error::Error;
-use crate::nvim_bridge::{
-    GridLineSegment, GridScrollArea, GridScrollRegion, ModeInfo,
-};
+use crate::nvim_bridge::{GridLineSegment, GridScrollRegion, ModeInfo};
 use crate::ui::color::HlDefs;
 use crate::ui::font::Font;
 use crate::ui::grid::context::Context;
 use crate::ui::grid::render;
-
-use super::row::Segment;
+use crate::ui::grid::rows;
+use crate::ui::grid::{
+    CellScale, CellWidth, CursorOverlay, RenderOptions, ScrollAnimation,
+};
 
 pub struct GridMetrics {
     // Row count in the grid.
@@ -70,6 +75,75 @@ impl Display for MouseButton {
     }
 }
 
+/// Interval (in milliseconds) that `KINETIC_FRICTION` is relative to. The
+/// kinetic scrolling itself is stepped on each frame, so the deceleration is
+/// the same regardless of the display's refresh rate.
+const KINETIC_INTERVAL: f64 = 16.0;
+/// How much of the kinetic scrolling's velocity is kept on each interval.
+const KINETIC_FRICTION: f64 = 0.95;
+/// Velocity (pixels per millisecond) at which the kinetic scrolling stops.
+const KINETIC_MIN_VELOCITY: f64 = 0.05;
+
+/// State of a two finger pan on a touch screen.
+#[derive(Default)]
+struct TouchPan {
+    /// Center of the touch points.
+    center: (f64, f64),
+    /// Time of the last update.
+    time: u32,
+    /// Pan velocity in pixels per millisecond.
+    velocity: (f64, f64),
+    /// Tick callback of the kinetic scrolling after the pan has ended.
+    kinetic: Option<gtk::TickCallbackId>,
+    /// Frame time of the previous kinetic scrolling step.
+    kinetic_time: Option<i64>,
+}
+
+/// Accumulates smooth scrolling deltas to `scroll_delta`. Once a delta
+/// reaches -1.0 or +1.0, given delta is reset and the direction to scroll is
+/// returned.
+pub fn accumulate_scroll(
+    scroll_delta: &RefCell<(f64, f64)>,
+    dx: f64,
+    dy: f64,
+) -> Option<ScrollDirection> {
+    let (prev_dx, prev_dy) = *scroll_delta.borrow();
+    let dy = prev_dy + dy;
+    let dx = prev_dx + dx;
+
+    let (new_delta, dir) = if dy <= -1.0 {
+        ((dx, 0.0), Some(ScrollDirection::Up))
+    } else if dy >= 1.0 {
+        ((dx, 0.0), Some(ScrollDirection::Down))
+    } else if dx <= -1.0 {
+        ((0.0, dy), Some(ScrollDirection::Left))
+    } else if dx >= 1.0 {
+        ((0.0, dy), Some(ScrollDirection::Right))
+    } else {
+        ((dx, dy), None)
+    };
+
+    *scroll_delta.borrow_mut() = new_delta;
+    dir
+}
+
+/// Converts keyboard modifiers to the format `nvim_input_mouse` expects.
+pub fn mouse_modifiers(state: ModifierType) -> String {
+    let mut modifiers = String::new();
+
+    if state.contains(ModifierType::SHIFT_MASK) {
+        modifiers.push_str("S-");
+    }
+    if state.contains(ModifierType::CONTROL_MASK) {
+        modifiers.push_str("C-");
+    }
+    if state.contains(ModifierType::MOD1_MASK) {
+        modifiers.push_str("A-");
+    }
+
+    modifiers
+}
+
 /// Single grid in the neovim UI. This matches the `ui-linegrid` stuff in
 /// the ui.txt documentation for neovim.
 pub struct Grid {
@@ -83,10 +157,16 @@ pub struct Grid {
     /// Pointer position for dragging if we should call callback from
     /// `connect_motion_events_for_drag`.
     drag_position: Rc<RefCell<(u64, u64)>>,
+    /// Pointer position for moving if we should call callback from
+    /// `connect_mouse_move_events`.
+    move_position: Rc<RefCell<(u64, u64)>>,
 
     /// Smooth scrolling indicator.
     scroll_delta: Rc<RefCell<(f64, f64)>>,
 
+    /// Touch gestures. Gtk doesn't keep these alive for us.
+    gestures: RefCell<Vec<gtk::Gesture>>,
+
     /// Input context that need to be updated for the cursor position
     im_context: Option<gtk::IMMulticontext>,
 }
@@ -98,11 +178,14 @@ impl Grid {
         win: &gdk::Window,
         font: Font,
         line_space: i64,
+        cell_width: CellWidth,
+        cell_scale: CellScale,
+        render_options: RenderOptions,
         cols: usize,
         rows: usize,
         hl_defs: &HlDefs,
         enable_cursor_animations: bool,
-        scroll_speed: i64,
+        scroll: ScrollAnimation,
     ) -> Result<Self, Error> {
         let da = DrawingArea::new();
         let ctx = Rc::new(RefCell::new(Context::new(
@@ -110,26 +193,39 @@ impl Grid {
             win,
             font,
             line_space,
+            cell_width,
+            cell_scale,
+            render_options,
             cols,
             rows,
             hl_defs,
             enable_cursor_animations,
-            scroll_speed,
+            scroll,
         )?));
 
         da.connect_draw(clone!(ctx => move |_, cr| {
             let mut ctx = ctx.borrow_mut();
-            drawingarea_draw(cr, &mut ctx).expect("failed to draw");
+            if let Err(err) = drawingarea_draw(cr, &mut ctx) {
+                error!("Failed to draw: {:?}", err);
+            }
             Inhibit(false)
         }));
 
         let eb = EventBox::new();
-        eb.add_events(EventMask::SCROLL_MASK | EventMask::SMOOTH_SCROLL_MASK);
+        eb.add_events(
+            EventMask::SCROLL_MASK
+                | EventMask::SMOOTH_SCROLL_MASK
+                | EventMask::POINTER_MOTION_MASK
+                | EventMask::ENTER_NOTIFY_MASK
+                | EventMask::TOUCH_MASK,
+        );
         eb.add(&da);
 
         da.add_tick_callback(clone!(ctx => move |da, clock| {
             let mut ctx = ctx.borrow_mut();
-            ctx.tick(da, clock).expect("context tick failed");
+            if let Err(err) = ctx.tick(da, clock) {
+                error!("Failed to tick: {:?}", err);
+            }
             glib::Continue(true)
         }));
 
@@ -139,8 +235,10 @@ impl Grid {
             eb,
             context: ctx,
             drag_position: Rc::new(RefCell::new((0, 0))),
+            move_position: Rc::new(RefCell::new((0, 0))),
             im_context: None,
             scroll_delta: Rc::new(RefCell::new((0.0, 0.0))),
+            gestures: RefCell::new(vec![]),
         })
     }
 
@@ -152,21 +250,37 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
 
         if let Some(cell) = ctx.cell_at_cursor() {
-            // If cursor isn't blinking, drawn the inverted cell into
+            let cursor_hl = Some(ctx.cursor.attr_id)
+                .filter(|id| *id != 0)
+                .and_then(|id| hl_defs.get(&id));
+            let hl = render::cursor_highlight(
+                hl_defs.get(&cell.hl_id).unwrap(),
+                cursor_hl,
+                hl_defs,
+            );
+
+            // If cursor isn't blinking, drawn the cursor's cell into
             // the cursor's cairo context.
-            if ctx.cursor.blink_on == 0 {
+            if !ctx.cursor.is_blinking() {
                 render::cursor_cell(
                     &ctx.cursor_context,
                     &self.da.pango_context(),
                     cell,
                     &ctx.cell_metrics,
+                    &hl,
                     hl_defs,
                 )?;
             }
 
             // Update cursor color.
-            let hl = hl_defs.get(&cell.hl_id).unwrap();
-            ctx.cursor.color = hl.foreground.unwrap_or(hl_defs.default_fg);
+            ctx.cursor.color = render::hl_colors(&hl, hl_defs).1;
+        }
+
+        if ctx.debug_overlay && !ctx.queue_draw_area.is_empty() {
+            // Redraw everything, so the previous damage gets erased from
+            // the overlay.
+            ctx.damage = ctx.queue_draw_area.clone();
+            self.da.queue_draw();
         }
 
         while let Some(area) = ctx.queue_draw_area.pop() {
@@ -186,6 +300,21 @@ impl Grid {
         self.im_context = Some(im_context.clone());
     }
 
+    /// Returns the area (x, y, width and height) of the cell that the cursor
+    /// is on, or is moving to.
+    pub fn cursor_target_rect(&self) -> (i32, i32, i32, i32) {
+        self.context.borrow().get_cursor_target_rect()
+    }
+
+    /// Tells the input method (if this grid has one) where the cursor is, so
+    /// it can place its candidate window next to it. `rect` is relative to
+    /// this grid.
+    pub fn set_im_cursor_location(&self, rect: &gdk::Rectangle) {
+        if let Some(ref im_context) = self.im_context {
+            im_context.set_cursor_location(rect);
+        }
+    }
+
     /// Returns position (+ width and height) for cell (row, col) relative
     /// to the top level window of this grid.
     pub fn get_rect_for_cell(&self, row: u64, col: u64) -> gdk::Rectangle {
@@ -199,7 +328,7 @@ impl Grid {
         );
 
         let (x, y) = self
-            .eb
+            .da
             .translate_coordinates(
                 &self.eb.parent().unwrap(),
                 x as i32,
@@ -216,62 +345,181 @@ impl Grid {
     }
 
     /// Connects `f` to internal widget's scroll events. `f` params are scroll
-    /// direction, row, col.
+    /// direction, modifiers (see `mouse_modifiers`), row, col.
+    ///
+    /// On touch screens, panning with two fingers scrolls too.
     pub fn connect_scroll_events<F: 'static>(&self, f: F)
     where
-        F: Fn(ScrollDirection, u64, u64) -> Inhibit,
+        F: Fn(ScrollDirection, String, u64, u64) -> Inhibit,
     {
         let ctx = self.context.clone();
         let scroll_delta = self.scroll_delta.clone();
+        let f = Rc::new(f);
 
         // NOTE(ville): Once we bump gtk from 3.20 to 3.24, use GtkEventControllerScroll
         // to improve smooth scrolling (ref #175).
-        self.eb
-            .connect_scroll_event(clone!(ctx, scroll_delta => move |_, e| {
-                let ctx = ctx.borrow_mut();
+        self.eb.connect_scroll_event(
+            clone!(ctx, scroll_delta, f => move |_, e| {
+                let mut ctx = ctx.borrow_mut();
+                ctx.mouse_scroll_time = glib::monotonic_time();
                 let dir = match e.direction() {
                     gdk::ScrollDirection::Right => ScrollDirection::Right,
                     gdk::ScrollDirection::Left => ScrollDirection::Left,
                     gdk::ScrollDirection::Up => ScrollDirection::Up,
                     gdk::ScrollDirection::Down => ScrollDirection::Down,
                     gdk::ScrollDirection::Smooth => {
-                        // Smooth scrolling. During scroll, many little deltas
-                        // are accumulated in scroll_deltas. Once a delta
-                        // reaches -1.0 or +1.0, given delta is reset and
-                        // scroll opreation is made effective.
-                        let (smooth_dx, smooth_dy) = e.scroll_deltas().unwrap();
-                        let (prev_dx, prev_dy) = *scroll_delta.borrow();
-                        let dy = prev_dy + smooth_dy;
-                        let dx = prev_dx + smooth_dx;
-
-                        let (new_delta, dir) = if dy <= -1.0 {
-                            ((dx, 0.0), Some(ScrollDirection::Up))
-                        } else if dy >= 1.0 {
-                            ((dx, 0.0), Some(ScrollDirection::Down))
-                        } else if dx <= -1.0 {
-                            ((0.0, dy), Some(ScrollDirection::Left))
-                        } else if dx >= 1.0 {
-                            ((0.0, dy), Some(ScrollDirection::Right))
-                        }else {
-                            ((dx, dy), None)
-                        };
-
-                        *scroll_delta.borrow_mut() = new_delta;
-                        if let Some(dir) = dir {
-                            dir
-                        } else {
-                            return Inhibit(false);
+                        let (dx, dy) = e.scroll_deltas().unwrap();
+                        match accumulate_scroll(&scroll_delta, dx, dy) {
+                            Some(dir) => dir,
+                            None => return Inhibit(false),
                         }
                     },
                     _ => { return Inhibit(false); },
                 };
 
                 let pos = e.position();
-                let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-                let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+                let (row, col) = ctx.cell_at_position(pos.0, pos.1);
+
+                f(dir, mouse_modifiers(e.state()), row, col)
+            }),
+        );
+
+        // Two finger pan. The pan is tracked from the center of the touch
+        // points, and fed to the same smooth scrolling as above. Once the
+        // fingers are lifted, the scrolling continues with deceleration.
+        let pan = Rc::new(RefCell::new(TouchPan::default()));
+        let scroll = Rc::new(move |dx: f64, dy: f64, (x, y): (f64, f64)| {
+            let mut ctx = ctx.borrow_mut();
+            ctx.mouse_scroll_time = glib::monotonic_time();
+            // Moving the fingers up scrolls down.
+            let dx = -dx / ctx.cell_metrics.width;
+            let dy = -dy / ctx.cell_metrics.height;
+
+            if let Some(dir) = accumulate_scroll(&scroll_delta, dx, dy) {
+                let (row, col) = ctx.cell_at_position(x, y);
+                f(dir, String::new(), row, col);
+            }
+        });
+
+        let gesture = gtk::GestureZoom::new(&self.eb);
+        gesture.connect_begin(clone!(pan => move |gesture, _| {
+            // Touchpad pinches also trigger the zoom gesture.
+            let touch = gesture
+                .device()
+                .map(|d| d.source() == gdk::InputSource::Touchscreen)
+                .unwrap_or(false);
+            if !touch {
+                gesture.set_state(gtk::EventSequenceState::Denied);
+                return;
+            }
+
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+
+            let mut pan = pan.borrow_mut();
+            if let Some(id) = pan.kinetic.take() {
+                id.remove();
+            }
+            pan.center = gesture.bounding_box_center().unwrap_or_default();
+            pan.time = gtk::current_event_time();
+            pan.velocity = (0.0, 0.0);
+        }));
+
+        gesture.connect_update(clone!(pan, scroll => move |gesture, _| {
+            let center = match gesture.bounding_box_center() {
+                Some(center) => center,
+                None => return,
+            };
+            let time = gtk::current_event_time();
+
+            let (dx, dy) = {
+                let mut pan = pan.borrow_mut();
+                let dx = center.0 - pan.center.0;
+                let dy = center.1 - pan.center.1;
+
+                let dt = time.saturating_sub(pan.time);
+                if dt > 0 {
+                    pan.velocity = (dx / dt as f64, dy / dt as f64);
+                }
+                pan.center = center;
+                pan.time = time;
+
+                (dx, dy)
+            };
+
+            scroll(dx, dy, center);
+        }));
 
-                f(dir, row, col)
-            }));
+        let eb = self.eb.clone();
+        gesture.connect_end(clone!(pan, scroll => move |_, _| {
+            pan.borrow_mut().kinetic_time = None;
+            let id = eb.add_tick_callback(
+                clone!(pan, scroll => move |_, clock| {
+                    let ft = clock.frame_time();
+                    let (velocity, center, dt) = {
+                        let mut pan = pan.borrow_mut();
+                        // On the first step, use the display's refresh
+                        // interval. Frame time is in microseconds.
+                        let prev = pan
+                            .kinetic_time
+                            .unwrap_or_else(|| ft - clock.refresh_info(ft).0);
+                        let dt = (ft - prev) as f64 / 1000.0;
+                        pan.kinetic_time = Some(ft);
+
+                        let friction =
+                            KINETIC_FRICTION.powf(dt / KINETIC_INTERVAL);
+                        pan.velocity.0 *= friction;
+                        pan.velocity.1 *= friction;
+                        (pan.velocity, pan.center, dt)
+                    };
+
+                    if velocity.0.hypot(velocity.1) < KINETIC_MIN_VELOCITY {
+                        pan.borrow_mut().kinetic = None;
+                        return glib::Continue(false);
+                    }
+
+                    scroll(velocity.0 * dt, velocity.1 * dt, center);
+
+                    glib::Continue(true)
+                }),
+            );
+
+            pan.borrow_mut().kinetic = Some(id);
+        }));
+
+        self.gestures.borrow_mut().push(gesture.upcast());
+    }
+
+    /// Connects `f` to touch screen "clicks": a tap is a left click, and a
+    /// long press is a right click. `f` params are button, row, col.
+    pub fn connect_touch_click_events<F: 'static>(&self, f: F)
+    where
+        F: Fn(MouseButton, u64, u64),
+    {
+        let ctx = self.context.clone();
+        let f = Rc::new(move |button, x: f64, y: f64| {
+            let ctx = ctx.borrow();
+            let (row, col) = ctx.cell_at_position(x, y);
+
+            f(button, row, col);
+        });
+
+        let tap = gtk::GestureMultiPress::new(&self.eb);
+        tap.set_touch_only(true);
+        tap.connect_released(clone!(f => move |_, _, x, y| {
+            f(MouseButton::Left, x, y);
+        }));
+
+        let long_press = gtk::GestureLongPress::new(&self.eb);
+        long_press.set_touch_only(true);
+        long_press.connect_pressed(clone!(f => move |gesture, x, y| {
+            // Claiming the touch cancels the tap.
+            gesture.set_state(gtk::EventSequenceState::Claimed);
+            f(MouseButton::Right, x, y);
+        }));
+
+        let mut gestures = self.gestures.borrow_mut();
+        gestures.push(tap.upcast());
+        gestures.push(long_press.upcast());
     }
 
     /// Connects `f` to internal widget's motion events. `f` params are button,
@@ -284,6 +532,15 @@ impl Grid {
         let drag_position = self.drag_position.clone();
 
         self.eb.connect_motion_notify_event(move |_, e| {
+            // Not dragging if no button is pressed.
+            if !e.state().intersects(
+                ModifierType::BUTTON1_MASK
+                    | ModifierType::BUTTON2_MASK
+                    | ModifierType::BUTTON3_MASK,
+            ) {
+                return Inhibit(false);
+            }
+
             let ctx = ctx.borrow();
             let mut drag_position = drag_position.borrow_mut();
 
@@ -294,8 +551,7 @@ impl Grid {
             };
 
             let pos = e.position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (row, col) = ctx.cell_at_position(pos.0, pos.1);
 
             if drag_position.0 != col || drag_position.1 != row {
                 *drag_position = (col, row);
@@ -306,6 +562,63 @@ impl Grid {
         });
     }
 
+    /// Connects `f` to internal widget's motion events, when no mouse button
+    /// is pressed. `f` params are row, col. `f` is only called when the cell
+    /// under the pointer changes.
+    pub fn connect_mouse_move_events<F: 'static>(&self, f: F)
+    where
+        F: Fn(u64, u64) -> Inhibit,
+    {
+        let ctx = self.context.clone();
+        let move_position = self.move_position.clone();
+
+        self.eb.connect_motion_notify_event(move |_, e| {
+            if e.state().intersects(
+                ModifierType::BUTTON1_MASK
+                    | ModifierType::BUTTON2_MASK
+                    | ModifierType::BUTTON3_MASK,
+            ) {
+                return Inhibit(false);
+            }
+
+            let ctx = ctx.borrow();
+            let mut move_position = move_position.borrow_mut();
+
+            let pos = e.position();
+            let (row, col) = ctx.cell_at_position(pos.0, pos.1);
+
+            if move_position.0 != col || move_position.1 != row {
+                *move_position = (col, row);
+                f(row, col)
+            } else {
+                Inhibit(false)
+            }
+        });
+    }
+
+    /// Connects `f` to the mouse pointer entering the internal widget, when
+    /// no mouse button is pressed.
+    pub fn connect_enter_events<F: 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId
+    where
+        F: Fn(),
+    {
+        self.eb.connect_enter_notify_event(move |_, e| {
+            // Dragging (e.g. a visual selection) shouldn't trigger this.
+            if !e.state().intersects(
+                ModifierType::BUTTON1_MASK
+                    | ModifierType::BUTTON2_MASK
+                    | ModifierType::BUTTON3_MASK,
+            ) {
+                f();
+            }
+
+            Inhibit(false)
+        })
+    }
+
     /// Connects `f` to internal widget's mouse button press event. `f` params
     /// are button, row, col.
     pub fn connect_mouse_button_press_events<F: 'static>(&self, f: F)
@@ -315,6 +628,15 @@ impl Grid {
         let ctx = self.context.clone();
 
         self.eb.connect_button_press_event(move |_, e| {
+            // On double and triple clicks, gdk sends an extra 2BUTTON_PRESS
+            // or 3BUTTON_PRESS event after the normal press events. Nvim
+            // counts the clicks it self from the normal presses (like in
+            // the TUI), so forwarding the extra events would make it see one
+            // click too many.
+            if e.event_type() != gdk::EventType::ButtonPress {
+                return Inhibit(false);
+            }
+
             let ctx = ctx.borrow();
 
             let button = match e.button() {
@@ -324,8 +646,7 @@ impl Grid {
             };
 
             let pos = e.position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (row, col) = ctx.cell_at_position(pos.0, pos.1);
 
             f(button, row, col)
         });
@@ -349,8 +670,7 @@ impl Grid {
             };
 
             let pos = e.position();
-            let col = (pos.0 / ctx.cell_metrics.width).floor() as u64;
-            let row = (pos.1 / ctx.cell_metrics.height).floor() as u64;
+            let (row, col) = ctx.cell_at_position(pos.0, pos.1);
 
             f(button, row, col)
         });
@@ -383,11 +703,7 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
 
         let row = line.row as usize;
-        let mut affected_segments = ctx
-            .rows
-            .get_mut(row)
-            .ok_or(Error::PutLineRowNotFound(row))?
-            .update(line);
+        let mut affected_segments = rows::put_line(&mut ctx.rows, line)?;
 
         // NOTE(ville): I haven't noticed any cases where a character is overflowing
         //              to the left. Probably doesn't apply to languages that goes
@@ -404,41 +720,168 @@ impl Grid {
         )
     }
 
-    pub fn redraw(&self, hl_defs: &HlDefs) -> Result<(), Error> {
+    /// Starts redrawing the whole grid (e.g. after the default colors
+    /// changed). The rows are redrawn in chunks with `redraw_chunk`, so
+    /// that big grids don't block the UI.
+    pub fn redraw(&self) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.redraw_row = Some(0);
+    }
+
+    /// If the grid is being redrawn.
+    pub fn is_redrawing(&self) -> bool {
+        self.context.borrow().redraw_row.is_some()
+    }
+
+    /// Redraws rows of the ongoing redraw until `deadline` (but at least
+    /// one). Returns true if there are rows left.
+    pub fn redraw_chunk(
+        &self,
+        hl_defs: &HlDefs,
+        deadline: Instant,
+    ) -> Result<bool, Error> {
         let mut ctx = self.context.borrow_mut();
         let pango_context = self.da.pango_context();
-        ctx.rows
-            .iter_mut()
-            .enumerate()
-            .map(|(i, row)| (i, row.as_segments(0, row.len)))
-            .collect::<Vec<(usize, Vec<Segment>)>>()
-            .into_iter()
-            .try_for_each(|(i, segments)| {
-                render::put_segments(
-                    &mut ctx,
-                    &pango_context,
-                    hl_defs,
-                    segments,
-                    i,
-                )
-            })
+        let queued = ctx.queue_draw_area.len();
+
+        while let Some(i) = ctx.redraw_row {
+            let segments = match ctx.rows.get(i) {
+                Some(row) => row.as_segments(0, row.len),
+                None => {
+                    ctx.redraw_row = None;
+                    break;
+                }
+            };
+
+            render::put_segments(
+                &mut ctx,
+                &pango_context,
+                hl_defs,
+                segments,
+                i,
+            )?;
+            ctx.redraw_row = Some(i + 1);
+
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        // Show the redrawn rows now. The other queued areas are drawn on
+        // nvim's flush.
+        for area in ctx.queue_draw_area.split_off(queued) {
+            self.da.queue_draw_area(
+                area.0.floor() as i32,
+                area.1.floor() as i32,
+                area.2.ceil() as i32,
+                area.3.ceil() as i32,
+            );
+        }
+
+        Ok(ctx.redraw_row.is_some())
+    }
+
+    /// Current position of the cursor (possibly in the middle of its
+    /// animation) in pixels.
+    pub fn cursor_position(&self) -> Option<(f64, f64)> {
+        let ctx = self.context.borrow();
+        let (row, col) = ctx.cursor.pos?;
+        Some((col * ctx.cell_metrics.width, row * ctx.cell_metrics.height))
     }
 
-    pub fn cursor_goto(&self, row: u64, col: u64) {
+    /// Places the cursor to `x` and `y` (in pixels, possibly outside of the
+    /// grid), so that it'll animate from there on the next `cursor_goto`.
+    pub fn set_cursor_position(&self, x: f64, y: f64) {
+        let mut ctx = self.context.borrow_mut();
+        let cm = &ctx.cell_metrics;
+        let pos = (y / cm.height, x / cm.width);
+        ctx.cursor.pos = Some(pos);
+        ctx.cursor.animation = None;
+    }
+
+    /// Draws the cursor to `overlay` instead of this grid, until it has
+    /// moved to its place. For when it moves here from another grid, so
+    /// that this grid doesn't clip it.
+    pub fn draw_cursor_to(&self, overlay: &CursorOverlay) {
+        overlay.set_target(Rc::downgrade(&self.context), &self.da);
+        self.context.borrow_mut().cursor_overlay = Some(overlay.widget());
+    }
+
+    pub fn cursor_goto(
+        &self,
+        row: u64,
+        col: u64,
+        hl_defs: &HlDefs,
+    ) -> Result<(), Error> {
         let clock = self.da.frame_clock().unwrap();
         let mut ctx = self.context.borrow_mut();
         ctx.cursor_goto(row, col, &clock);
 
-        let (x, y, width, height) = ctx.get_cursor_rect();
-        if let Some(ref im_context) = self.im_context {
-            let rect = gdk::Rectangle {
-                x,
-                y,
-                width,
-                height,
-            };
-            im_context.set_cursor_location(&rect);
+        // Break the ligatures at the new cursor position instead of the old
+        // one, by redrawing the segments at both.
+        let at = Some((row as usize, col as usize))
+            .filter(|_| !ctx.cell_metrics.options.disable_ligatures);
+        let prev = std::mem::replace(&mut ctx.ligature_break, at);
+        if prev != at {
+            let pango_context = self.da.pango_context();
+            for (row, col) in prev.into_iter().chain(at) {
+                let segment = match ctx.rows.get(row) {
+                    Some(r) if col < r.len() => {
+                        r.as_segments(col, r.len() - 1).into_iter().next()
+                    }
+                    _ => None,
+                };
+
+                if let Some(segment) = segment {
+                    render::put_segments(
+                        &mut ctx,
+                        &pango_context,
+                        hl_defs,
+                        vec![segment],
+                        row,
+                    )?;
+                }
+            }
         }
+
+        Ok(())
+    }
+
+    /// Names of the animations that are still running.
+    pub fn pending_animations(&self) -> Vec<&'static str> {
+        let ctx = self.context.borrow();
+
+        let mut animations = vec![];
+        if ctx.cursor.animation.is_some() {
+            animations.push("cursor");
+        }
+        if ctx.surfaces.offset_y_anim.is_some() {
+            animations.push("scroll");
+        }
+        if !ctx.particles.is_empty() {
+            animations.push("particles");
+        }
+
+        animations
+    }
+
+    /// Returns the text from cell (row, col) to the cursor, if the cursor is
+    /// on the same row.
+    pub fn text_to_cursor(&self, row: u64, col: u64) -> Option<String> {
+        let ctx = self.context.borrow();
+
+        let (cursor_row, cursor_col) = ctx.cursor.get_position()?;
+        if cursor_row as u64 != row {
+            return None;
+        }
+
+        let row = ctx.rows.get(row as usize)?;
+        Some(
+            (col as usize..cursor_col as usize)
+                .filter_map(|i| row.cell_at(i))
+                .map(|cell| cell.text.as_str())
+                .collect(),
+        )
     }
 
     pub fn get_grid_metrics(&self) -> GridMetrics {
@@ -489,9 +932,10 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
 
         // Clear internal grid (rows).
-        for row in ctx.rows.iter_mut() {
-            row.clear();
-        }
+        rows::clear(&mut ctx.rows);
+
+        // The whole grid gets drawn with the current colors.
+        ctx.redraw_row = None;
 
         render::clear(&self.da, &mut ctx, hl_defs)
     }
@@ -509,36 +953,13 @@ impl Grid {
         let right = reg.0[3];
         let area = reg.calc_area(rows);
 
-        let GridScrollArea {
-            src_top,
-            src_bot,
-            dst_top,
-            dst_bot,
-            clr_top,
-            clr_bot,
-        } = area;
-
         // Modify the rows stored data of the rows.
-        let mut src = vec![];
-        for i in src_top as usize..src_bot as usize {
-            let row = ctx.rows.get(i).unwrap().clone();
-            let part = row.copy_range(left as usize, right as usize).clone();
-            src.push(part);
-        }
-        src.reverse();
+        rows::scroll(&mut ctx.rows, &area, left as usize, right as usize);
 
-        for i in dst_top as usize..dst_bot as usize {
-            ctx.rows
-                .get_mut(i)
-                .unwrap()
-                .insert_at(left as usize, src.pop().unwrap());
-        }
-
-        for i in clr_top as usize..clr_bot as usize {
-            ctx.rows
-                .get_mut(i)
-                .unwrap()
-                .clear_range(left as usize, right as usize);
+        // Rows that weren't redrawn yet might have been moved above the
+        // ongoing redraw.
+        if let Some(row) = ctx.redraw_row {
+            ctx.redraw_row = Some(row.min(area.dst_top as usize));
         }
 
         let clock = self.da.frame_clock().unwrap();
@@ -570,6 +991,29 @@ impl Grid {
         ctx.update_metrics(font, line_space, &self.da, win)
     }
 
+    /// Sets the font options (antialiasing and hinting) that the text is
+    /// drawn with. `None` uses the screen's. This might change the cell
+    /// metrics too.
+    pub fn set_font_options(
+        &self,
+        options: Option<&cairo::FontOptions>,
+        win: &gdk::Window,
+    ) -> Result<(), Error> {
+        self.da.set_font_options(options);
+
+        let mut ctx = self.context.borrow_mut();
+        let font = ctx.cell_metrics.font.clone();
+        let line_space = ctx.cell_metrics.line_space;
+        ctx.update_metrics(font, line_space, &self.da, win)
+    }
+
+    /// Sets the font for double width cells ('guifontwide'). Call `redraw`
+    /// to redraw the cells with it.
+    pub fn set_font_wide(&self, font: Option<Font>) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.cell_metrics.font_wide = font;
+    }
+
     /// Get the current line space value.
     pub fn get_line_space(&self) -> i64 {
         let ctx = self.context.borrow();
@@ -585,8 +1029,12 @@ impl Grid {
     pub fn set_mode(&self, mode: &ModeInfo) {
         let mut ctx = self.context.borrow_mut();
 
+        ctx.cursor.blink_wait = mode.blink_wait;
         ctx.cursor.blink_on = mode.blink_on;
+        ctx.cursor.blink_off = mode.blink_off;
+        ctx.cursor.blink_start = None;
         ctx.cursor.cell_percentage = mode.cell_percentage;
+        ctx.cursor.attr_id = mode.attr_id;
     }
 
     pub fn set_busy(&self, busy: bool) {
@@ -599,6 +1047,70 @@ impl Grid {
         let mut ctx = self.context.borrow_mut();
         ctx.cursor.disable_animation = !enable;
     }
+
+    /// Sets the horizontal (`x`) and vertical (`y`) padding (in pixels)
+    /// around the grid. The padding is painted with the default background
+    /// color.
+    pub fn set_padding(&self, x: i32, y: i32) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.padding = (f64::from(x), f64::from(y));
+
+        self.da.set_margin_start(x);
+        self.da.set_margin_end(x);
+        self.da.set_margin_top(y);
+        self.da.set_margin_bottom(y);
+    }
+
+    /// Translates `x`, `y` (in pixels) relative to this grid to be
+    /// relative to `dest`.
+    pub fn translate_coordinates(
+        &self,
+        dest: &Grid,
+        x: f64,
+        y: f64,
+    ) -> Option<(f64, f64)> {
+        self.da
+            .translate_coordinates(&dest.da, x as i32, y as i32)
+            .map(|(x, y)| (f64::from(x), f64::from(y)))
+    }
+
+    /// Translates `x`, `y` (in pixels) relative to this grid to be
+    /// relative to `dest`.
+    pub fn translate_coordinates_to_widget(
+        &self,
+        dest: &gtk::Widget,
+        x: i32,
+        y: i32,
+    ) -> Option<(i32, i32)> {
+        self.da.translate_coordinates(dest, x, y)
+    }
+
+    /// Id of the input method module (e.g. `ibus`) in use, if this grid has
+    /// the input method.
+    pub fn im_context_id(&self) -> Option<String> {
+        self.im_context
+            .as_ref()
+            .and_then(|im_context| im_context.context_id())
+            .map(|id| id.to_string())
+    }
+
+    /// Sets the grid's own scroll speed, overriding the global ones.
+    pub fn set_scroll_speed(&self, speed: Option<i64>) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.scroll_speed = speed;
+    }
+
+    pub fn enable_cursor_particles(&self, enable: bool) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.particles.enabled = enable;
+    }
+
+    pub fn enable_debug_overlay(&self, enable: bool) {
+        let mut ctx = self.context.borrow_mut();
+        ctx.debug_overlay = enable;
+        ctx.damage.clear();
+        self.da.queue_draw();
+    }
 }
 
 /// Handler for grid's drawingarea's draw event. Draws the internal cairo
@@ -609,47 +1121,59 @@ fn drawingarea_draw(
 ) -> Result<(), Error> {
     let prev = &ctx.surfaces.prev;
 
+    // Only the area that is being drawn needs to be updated.
+    let (x1, y1, x2, y2) = cr.clip_extents()?;
+    let area = Rect {
+        x: x1,
+        y: y1,
+        width: x2 - x1,
+        height: y2 - y1,
+    };
+
     if let Some(ref anim) = ctx.surfaces.offset_y_anim {
-        let surface = ctx.surfaces.back.target();
-        surface.flush();
+        ctx.surfaces.back.flush();
 
-        prev.save()?;
         let back_offset = ctx.surfaces.offset_y - anim.start;
-        prev.set_source_surface(&surface, 0.0, back_offset)?;
-        prev.paint()?;
-        prev.restore()?;
+        prev.paint_from(&ctx.surfaces.back, back_offset, area)?;
     }
 
-    let surface = ctx.surfaces.front.target();
-    surface.flush();
+    ctx.surfaces.front.flush();
+    prev.paint_from(&ctx.surfaces.front, ctx.surfaces.offset_y, area)?;
 
-    prev.save()?;
-    prev.set_source_surface(&surface, 0.0, ctx.surfaces.offset_y)?;
-    prev.paint()?;
-    prev.restore()?;
+    prev.draw(cr, area)?;
 
-    cr.save()?;
-    cr.set_source_surface(&prev.target(), 0.0, 0.0)?;
-    cr.paint()?;
-    cr.restore()?;
+    // If we're not "busy", draw the cursor (unless it's drawn to the cursor
+    // overlay).
+    if !ctx.busy && ctx.active && ctx.cursor_overlay.is_none() {
+        draw_cursor(cr, ctx)?;
+    }
 
-    // If we're not "busy", draw the cursor.
-    if !ctx.busy && ctx.active {
-        let (x, y, w, h) = ctx.get_cursor_rect();
+    ctx.particles.draw(cr, &ctx.cursor.color)?;
 
-        cr.save()?;
-        cr.rectangle(
-            f64::from(x),
-            f64::from(y),
-            f64::from(w) * ctx.cursor.cell_percentage,
-            f64::from(h),
-        );
-        let surface = ctx.cursor_context.target();
-        surface.flush();
-        cr.set_source_surface(&surface, x.into(), y.into())?;
-        cr.fill()?;
-        cr.restore()?;
+    if ctx.debug_overlay {
+        render::debug_overlay(cr, ctx)?;
     }
 
     Ok(())
 }
+
+/// Draws the cursor of `ctx` to `cr`, at the cursor's (possibly animated)
+/// position in the grid.
+pub fn draw_cursor(cr: &cairo::Context, ctx: &Context) -> Result<(), Error> {
+    let (x, y, w, h) = ctx.get_cursor_rect();
+
+    cr.save()?;
+    cr.rectangle(
+        f64::from(x),
+        f64::from(y),
+        f64::from(w) * ctx.cursor.cell_percentage,
+        f64::from(h),
+    );
+    let surface = ctx.cursor_context.target();
+    surface.flush();
+    cr.set_source_surface(&surface, x.into(), y.into())?;
+    cr.fill()?;
+    cr.restore()?;
+
+    Ok(())
+}
@@ -1,11 +1,17 @@
 mod context;
 mod cursor;
+mod cursor_overlay;
 #[allow(clippy::module_inception)]
 mod grid;
+mod particles;
 mod render;
-mod row;
+pub mod rows;
 mod surfaces;
 
-pub use self::context::CellMetrics;
-pub use self::grid::{Grid, GridMetrics};
-pub use self::surfaces::Surfaces;
+use gnvim_core::row;
+
+pub use self::context::{CellMetrics, CellScale, CellWidth, RenderOptions};
+pub use self::cursor_overlay::CursorOverlay;
+pub use self::grid::{accumulate_scroll, Grid, GridMetrics, ScrollDirection};
+pub use self::surfaces::{ScrollAnimation, Surfaces};
+pub use gnvim_core::render::GlyphOverflow;
@@ -0,0 +1,209 @@
+use gtk::cairo;
+
+use crate::error::Error;
+use crate::ui::color::Color;
+
+/// Number of particles emitted on each cursor movement.
+const COUNT: usize = 8;
+/// Lifetime of a particle, in microseconds.
+const LIFETIME: i64 = 400_000;
+/// Maximum initial speed of a particle, in pixels per second.
+const MAX_SPEED: f64 = 120.0;
+/// Radius of a particle, in pixels.
+const RADIUS: f64 = 1.5;
+
+struct Particle {
+    /// Position where the particle was emitted from.
+    origin: (f64, f64),
+    /// Initial velocity, in pixels per second.
+    velocity: (f64, f64),
+    /// Frame time of the emit.
+    born: i64,
+}
+
+impl Particle {
+    /// Age of the particle, from 0.0 to 1.0.
+    fn age(&self, frame_time: i64) -> f64 {
+        ((frame_time - self.born) as f64 / LIFETIME as f64).clamp(0.0, 1.0)
+    }
+
+    /// Position of the particle. The particle slows down towards the end
+    /// of its life.
+    fn position(&self, frame_time: i64) -> (f64, f64) {
+        let age = self.age(frame_time);
+        let t = LIFETIME as f64 / 1_000_000.0 * age * (1.0 - age / 2.0);
+        (
+            self.origin.0 + self.velocity.0 * t,
+            self.origin.1 + self.velocity.1 * t,
+        )
+    }
+}
+
+/// Small, fading particles emitted from the cursor as it moves. Purely
+/// cosmetic, and disabled by default.
+pub struct Particles {
+    pub enabled: bool,
+    particles: Vec<Particle>,
+    /// Latest frame time we've been ticked with.
+    now: i64,
+    /// State of our (xorshift) random number generator.
+    seed: u32,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Particles {
+            enabled: false,
+            particles: vec![],
+            now: 0,
+            seed: 0x9e37_79b9,
+        }
+    }
+}
+
+impl Particles {
+    /// If there are no particles alive.
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Returns a pseudo random number between -1.0 and 1.0.
+    fn random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        f64::from(self.seed) / f64::from(u32::MAX) * 2.0 - 1.0
+    }
+
+    /// Emits particles from `pos` (in pixels).
+    pub fn emit(&mut self, pos: (f64, f64), frame_time: i64) {
+        if !self.enabled {
+            return;
+        }
+
+        for _ in 0..COUNT {
+            let velocity =
+                (self.random() * MAX_SPEED, self.random() * MAX_SPEED);
+            self.particles.push(Particle {
+                origin: pos,
+                velocity,
+                born: frame_time,
+            });
+        }
+    }
+
+    /// Removes the particles that have died. Returns the area (x, y, w, h)
+    /// that needs to be redrawn, if any.
+    pub fn tick(&mut self, frame_time: i64) -> Option<(i32, i32, i32, i32)> {
+        self.now = frame_time;
+
+        // Include the particles that die now, so they'll get cleared.
+        let area = self.area();
+        self.particles.retain(|p| frame_time - p.born < LIFETIME);
+
+        area
+    }
+
+    /// Bounding box of the particles.
+    fn area(&self) -> Option<(i32, i32, i32, i32)> {
+        if self.particles.is_empty() {
+            return None;
+        }
+
+        // The particles might've moved since the last draw, so cover all
+        // the distance they can travel.
+        let reach = MAX_SPEED * LIFETIME as f64 / 1_000_000.0 + RADIUS;
+        let (x1, y1, x2, y2) = self.particles.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(x1, y1, x2, y2), p| {
+                (
+                    x1.min(p.origin.0),
+                    y1.min(p.origin.1),
+                    x2.max(p.origin.0),
+                    y2.max(p.origin.1),
+                )
+            },
+        );
+
+        Some((
+            (x1 - reach).floor() as i32,
+            (y1 - reach).floor() as i32,
+            (x2 - x1 + reach * 2.0).ceil() as i32,
+            (y2 - y1 + reach * 2.0).ceil() as i32,
+        ))
+    }
+
+    /// Draws the particles to `cr` with `color`.
+    pub fn draw(
+        &self,
+        cr: &cairo::Context,
+        color: &Color,
+    ) -> Result<(), Error> {
+        if self.particles.is_empty() {
+            return Ok(());
+        }
+
+        cr.save()?;
+        for p in self.particles.iter() {
+            let (x, y) = p.position(self.now);
+            cr.set_source_rgba(
+                color.r,
+                color.g,
+                color.b,
+                1.0 - p.age(self.now),
+            );
+            cr.arc(x, y, RADIUS, 0.0, std::f64::consts::PI * 2.0);
+            cr.fill()?;
+        }
+        cr.restore()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_particles_disabled() {
+        let mut particles = Particles::default();
+        particles.emit((10.0, 10.0), 0);
+        assert_eq!(particles.tick(0), None);
+    }
+
+    #[test]
+    fn test_particles_lifetime() {
+        let mut particles = Particles {
+            enabled: true,
+            ..Particles::default()
+        };
+        particles.emit((10.0, 10.0), 0);
+        assert_eq!(particles.particles.len(), COUNT);
+
+        assert!(particles.tick(LIFETIME / 2).is_some());
+        assert_eq!(particles.particles.len(), COUNT);
+
+        // Last redraw to clear the particles.
+        assert!(particles.tick(LIFETIME).is_some());
+        assert!(particles.particles.is_empty());
+        assert_eq!(particles.tick(LIFETIME + 1), None);
+    }
+
+    #[test]
+    fn test_particle_position() {
+        let p = Particle {
+            origin: (10.0, 20.0),
+            velocity: (100.0, -100.0),
+            born: 0,
+        };
+
+        assert_eq!(p.position(0), (10.0, 20.0));
+        // Half of the lifetime (0.2s) at full speed would be 20px, but the
+        // particle slows down.
+        let (x, y) = p.position(LIFETIME / 2);
+        assert!((x - 25.0).abs() < 1e-9);
+        assert!((y - 5.0).abs() < 1e-9);
+        assert_eq!(p.age(LIFETIME * 2), 1.0);
+    }
+}
@@ -3,142 +3,809 @@ use gtk::prelude::*;
 use gtk::DrawingArea;
 use gtk::{cairo, pango};
 
+use gnvim_core::lru::Lru;
+use gnvim_core::render::{self, Backend, GlyphOverflow, Rect};
+
 use crate::error::Error;
 use crate::nvim_bridge::GridScrollArea;
-use crate::ui::color::Highlight;
 use crate::ui::color::HlDefs;
+use crate::ui::color::{Color, Highlight};
 use crate::ui::grid::context::{CellMetrics, Context};
-use crate::ui::grid::row::{Cell, Segment};
-
-/// Renders text to `cr`.
-///
-/// * `cr` - The cairo context to render to.
-/// * `pango_context` - The pango context to use for text rendering.
-/// * `cm` - Cell metrics to use for text placement.
-/// * `hl` - The highlighting to use.
-/// * `hl_defs` - Global hl defs. Used to get default values.
-/// * `text` - The text to render.
-/// * `pos` - Target position for `cr`.
-fn render_text(
-    cr: &cairo::Context,
-    pango_context: &pango::Context,
-    cm: &CellMetrics,
-    hl: &Highlight,
-    hl_defs: &HlDefs,
-    text: &str,
-    pos: cairo::Rectangle,
-) -> Result<(), Error> {
-    let cairo::Rectangle {
-        x,
-        y,
-        width: w,
-        height: h,
-    } = pos;
-
-    let (fg, bg) = if hl.reverse {
-        (
-            hl.background.unwrap_or(hl_defs.default_bg),
-            hl.foreground.unwrap_or(hl_defs.default_fg),
-        )
-    } else {
-        (
-            hl.foreground.unwrap_or(hl_defs.default_fg),
-            hl.background.unwrap_or(hl_defs.default_bg),
-        )
-    };
+use crate::ui::grid::row::{self, Cell, Row, Segment};
+use crate::ui::grid::surfaces::{TiledSurface, MOUSE_SCROLL_TIMEOUT};
+
+pub use gnvim_core::render::{
+    cursor_highlight, get_coords, get_rect, hl_colors,
+};
+
+/// Font features that turn off the font's ligatures, including the
+/// contextual alternates that programming fonts implement them with.
+const NO_LIGATURES: &str = "liga=0, calt=0";
+
+/// Number of itemized texts to keep in `ItemizeCache`.
+const ITEMIZE_CACHE_SIZE: usize = 1024;
+
+/// What the itemization of a text depends on, besides the font.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ItemizeKey {
+    text: String,
+    bold: bool,
+    italic: bool,
+    altfont: Option<String>,
+}
 
-    cr.save()?;
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
-    cr.rectangle(x, y, w, h);
-    cr.fill()?;
-    cr.restore()?;
+/// Cache of `pango::itemize` results, which are slow to compute and often
+/// the same (e.g. for indentation and repetitive code). Needs to be cleared
+/// when the font changes.
+pub struct ItemizeCache {
+    items: Lru<ItemizeKey, Vec<pango::Item>>,
+}
 
-    let attrs = pango::AttrList::new();
+impl Default for ItemizeCache {
+    fn default() -> Self {
+        ItemizeCache {
+            items: Lru::new(ITEMIZE_CACHE_SIZE),
+        }
+    }
+}
 
+impl ItemizeCache {
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+/// Adds the font attributes of `hl` to `attrs`, for the text from byte
+/// `start` to `end`.
+fn insert_attrs(
+    attrs: &pango::AttrList,
+    hl: &Highlight,
+    altfont: Option<&str>,
+    ligatures: bool,
+    start: u32,
+    end: u32,
+) {
+    let mut list = vec![];
+
+    if !ligatures {
+        list.push(Attribute::new_font_features(NO_LIGATURES));
+    }
     if hl.bold {
-        let attr = Attribute::new_weight(pango::Weight::Bold);
-        attrs.insert(attr);
+        list.push(Attribute::new_weight(pango::Weight::Bold));
     }
     if hl.italic {
-        let attr = Attribute::new_style(pango::Style::Italic);
+        list.push(Attribute::new_style(pango::Style::Italic));
+    }
+    if let Some(family) = altfont {
+        list.push(Attribute::new_family(family));
+    }
+
+    for mut attr in list {
+        attr.set_start_index(start);
+        attr.set_end_index(end);
         attrs.insert(attr);
     }
+}
 
-    cr.save()?;
-    cr.set_source_rgb(fg.r, fg.g, fg.b);
-
-    let items =
-        pango::itemize(pango_context, text, 0, text.len() as i32, &attrs, None);
-
-    let mut x_offset = 0.0;
-    let scale = f64::from(pango::SCALE);
-    for item in items {
-        let a = item.analysis();
-        let item_offset = item.offset() as usize;
-        let mut glyphs = pango::GlyphString::new();
-
-        pango::shape(
-            &text[item_offset..item_offset + item.length() as usize],
-            a,
-            &mut glyphs,
-        );
+/// Splits `text` into items that can be shaped with a single font.
+fn itemize(
+    pango_context: &pango::Context,
+    text: &str,
+    hl: &Highlight,
+    altfont: Option<&str>,
+    ligatures: bool,
+) -> Vec<pango::Item> {
+    let attrs = pango::AttrList::new();
+    insert_attrs(&attrs, hl, altfont, ligatures, 0, u32::MAX);
 
-        cr.move_to(x + x_offset, y + cm.ascent);
-        pangocairo::functions::show_glyph_string(cr, &a.font(), &mut glyphs);
+    pango::itemize(pango_context, text, 0, text.len() as i32, &attrs, None)
+}
+
+/// Glyphs of a whole row, shaped at once so that the contextual forms that
+/// span highlight segments (e.g. Indic conjuncts and joined Arabic letters)
+/// are kept. The glyphs are then drawn a segment at a time.
+struct ShapedRow {
+    text: String,
+    /// Byte offset of each cell's text in `text`, and the end of `text`.
+    offsets: Vec<usize>,
+    /// Glyph runs of the row, in logical order (the order of the cells).
+    runs: Vec<pango::GlyphItem>,
+}
 
-        x_offset += f64::from(glyphs.width()) / scale;
+impl ShapedRow {
+    fn new(
+        pango_context: &pango::Context,
+        row: &Row,
+        hl_defs: &HlDefs,
+        ligatures: bool,
+    ) -> Self {
+        let mut text = String::new();
+        let mut offsets = Vec::with_capacity(row.len() + 1);
+        for cell in (0..row.len()).filter_map(|i| row.cell_at(i)) {
+            offsets.push(text.len());
+            text.push_str(&cell.text);
+        }
+        offsets.push(text.len());
+
+        let attrs = pango::AttrList::new();
+        for seg in row.as_segments(0, row.len()) {
+            if let Some(hl) = hl_defs.get(&seg.hl_id) {
+                let altfont = hl_defs.altfont.as_deref().filter(|_| hl.altfont);
+                insert_attrs(
+                    &attrs,
+                    hl,
+                    altfont,
+                    ligatures,
+                    offsets[seg.start] as u32,
+                    offsets[seg.start + seg.len] as u32,
+                );
+            }
+        }
+
+        let layout = pango::Layout::new(pango_context);
+        // Keep the cells from left to right, like when the segments are
+        // shaped one by one. This only sets the base direction, the runs of
+        // right-to-left text still come in visual order (see below).
+        layout.set_auto_dir(false);
+        layout.set_text(&text);
+        layout.set_attributes(Some(&attrs));
+
+        let mut runs = vec![];
+        if let Some(mut iter) = layout.iter() {
+            loop {
+                runs.extend(iter.run_readonly());
+                if !iter.next_run() {
+                    break;
+                }
+            }
+        }
+        // Nvim's grid has the text in logical order, so that's where the
+        // runs are drawn.
+        runs.sort_by_key(|run| run.item().offset());
+
+        ShapedRow {
+            text,
+            offsets,
+            runs,
+        }
     }
 
-    // Since we can't (for some reason) use pango attributes to draw
-    // underline and undercurl, we'll have to do that manually.
-    let sp = hl.special.unwrap_or(hl_defs.default_sp);
-    cr.set_source_rgb(sp.r, sp.g, sp.b);
-    if hl.undercurl {
-        pangocairo::functions::show_error_underline(
-            cr,
-            x,
-            y + h + cm.underline_position - cm.underline_thickness,
-            w,
-            cm.underline_thickness * 2.0,
-        );
+    /// Returns the glyph runs of the `len` cells from `start`, with the
+    /// cells they start from. A cluster that spans cells of different
+    /// segments is drawn with the segment that it starts in.
+    fn runs(&self, start: usize, len: usize) -> Vec<(usize, pango::GlyphItem)> {
+        let (start, end) =
+            match (self.offsets.get(start), self.offsets.get(start + len)) {
+                (Some(start), Some(end)) => (*start, *end),
+                _ => return vec![],
+            };
+
+        let mut runs = vec![];
+        for run in self.runs.iter() {
+            let item = run.item();
+            let run_start = item.offset() as usize;
+            let run_end = run_start + item.length() as usize;
+            if run_end <= start || run_start >= end {
+                continue;
+            }
+
+            let mut run = run.clone();
+            // Drop the clusters before the cells. Splitting leaves the
+            // clusters from the split index onwards to `run`.
+            if start > run_start
+                && run.split(&self.text, (start - run_start) as i32).is_none()
+            {
+                continue;
+            }
+
+            let item = run.item();
+            let run_start = item.offset() as usize;
+            let run_end = run_start + item.length() as usize;
+            if run_start >= end {
+                continue;
+            }
+
+            // And the ones after them.
+            if end < run_end {
+                if let Some(head) =
+                    run.split(&self.text, (end - run_start) as i32)
+                {
+                    runs.push((self.cell_at(run_start), head));
+                    continue;
+                }
+            }
+
+            runs.push((self.cell_at(run_start), run));
+        }
+
+        runs
     }
-    if hl.underline {
-        let y = y + h + cm.underline_position;
-        cr.rectangle(x, y, w, cm.underline_thickness);
-        cr.fill()?;
+
+    /// Returns the cell that the text at byte offset `offset` is in.
+    fn cell_at(&self, offset: usize) -> usize {
+        // The second halves of double width cells have no text, and so the
+        // same offset as the next cell.
+        self.offsets
+            .partition_point(|cell_offset| *cell_offset <= offset)
+            .saturating_sub(1)
     }
+}
+
+/// What `CairoBackend` draws to.
+enum Target<'a> {
+    Context(&'a cairo::Context),
+    Tiled(&'a TiledSurface),
+}
+
+impl<'a> Target<'a> {
+    /// Returns the contexts to draw `area` to.
+    fn contexts_in(&self, area: Rect) -> Vec<&'a cairo::Context> {
+        match *self {
+            Target::Context(cr) => vec![cr],
+            Target::Tiled(surface) => surface.contexts_in(area).collect(),
+        }
+    }
+}
+
+/// Renders with cairo, and pango for the text.
+struct CairoBackend<'a> {
+    target: Target<'a>,
+    pango_context: &'a pango::Context,
+    itemize_cache: Option<&'a mut ItemizeCache>,
+    /// If the text is drawn with the font's ligatures.
+    ligatures: bool,
+    /// The row that the segments are from, if it needs to be shaped as a
+    /// whole.
+    row: Option<ShapedRow>,
+    /// Start and length (in cells) of the segment being drawn.
+    segment: Option<(usize, usize)>,
+    /// Width of the cells.
+    cell_width: f64,
+    /// If the glyphs are centered in their cells (see
+    /// `CellMetrics::centers_glyphs`).
+    center_glyphs: bool,
+    /// What to do with glyphs that don't fit in their cells.
+    overflow: GlyphOverflow,
+    /// Font family for double width cells, if any.
+    font_wide: Option<&'a str>,
+    /// Column (of the row being drawn) that the ligatures are broken at.
+    ligature_break: Option<usize>,
+    /// If characters whose glyphs are missing are drawn as hex boxes.
+    hex_boxes: bool,
+    /// The row that the segments are from, for drawing them a cell at a
+    /// time (see `CairoBackend::cell_by_cell`).
+    cells: Option<&'a Row>,
+}
+
+/// Text that is shaped and placed on its own, see
+/// `CairoBackend::cell_texts`.
+struct CellText {
+    /// Column (relative to the segment) that the text starts from.
+    col: usize,
+    text: String,
+    /// Width in cells.
+    cols: usize,
+    /// If the text is emoji (see `row::is_emoji`).
+    emoji: bool,
+}
+
+/// Character whose glyph is missing from the fonts, drawn as a box with its
+/// code point (see `draw_hex_box`).
+struct HexBox {
+    cell: Rect,
+    c: char,
+}
 
+/// If any of `glyphs` is missing from its font.
+fn has_missing_glyphs(glyphs: &pango::GlyphString) -> bool {
+    glyphs
+        .glyph_info()
+        .iter()
+        .any(|info| info.glyph() & pango::ffi::PANGO_GLYPH_UNKNOWN_FLAG != 0)
+}
+
+/// Draws `c` to `cell` as a box with its code point in hex, in the current
+/// color of `cr`.
+fn draw_hex_box(
+    cr: &cairo::Context,
+    pango_context: &pango::Context,
+    cell: Rect,
+    c: char,
+) -> Result<(), Error> {
+    // The digits are on two rows, like terminals (and browsers) do.
+    let code = format!("{:04X}", u32::from(c));
+    let (top, bottom) = code.split_at(code.len() / 2);
+
+    let mut font = pango::FontDescription::from_string("Monospace");
+    font.set_absolute_size(cell.height * 0.35 * f64::from(pango::SCALE));
+    let layout = pango::Layout::new(pango_context);
+    layout.set_font_description(Some(&font));
+    layout.set_alignment(pango::Alignment::Center);
+    layout.set_text(&format!("{}\n{}", top, bottom));
+    let (_, logical) = layout.pixel_extents();
+
+    cr.save()?;
+    cr.set_line_width(1.0);
+    cr.rectangle(
+        cell.x.floor() + 1.5,
+        cell.y.floor() + 1.5,
+        cell.width.floor() - 3.0,
+        cell.height.floor() - 3.0,
+    );
+    cr.stroke()?;
+    cr.move_to(
+        cell.x + (cell.width - f64::from(logical.width)) / 2.0,
+        cell.y + (cell.height - f64::from(logical.height)) / 2.0,
+    );
+    pangocairo::functions::show_layout(cr, &layout);
     cr.restore()?;
 
     Ok(())
 }
 
-/// Draws (inverted) cell to `cr`.
+/// Glyphs, placed for drawing.
+struct Run {
+    /// Where the glyphs start.
+    x: f64,
+    font: pango::Font,
+    glyphs: pango::GlyphString,
+    /// Scale of the glyphs, around `x` and the baseline.
+    scale: f64,
+    /// Area the glyphs are clipped to.
+    clip: Option<Rect>,
+}
+
+impl<'a> CairoBackend<'a> {
+    /// Shapes `text` (on its own), returning the glyphs and their fonts.
+    fn shape(
+        &mut self,
+        text: &str,
+        hl: &Highlight,
+        altfont: Option<&str>,
+    ) -> Vec<(pango::Font, pango::GlyphString)> {
+        let pango_context = self.pango_context;
+        let ligatures = self.ligatures;
+        let uncached;
+        let items = match self.itemize_cache {
+            Some(ref mut cache) => {
+                let key = ItemizeKey {
+                    text: text.to_string(),
+                    bold: hl.bold,
+                    italic: hl.italic,
+                    altfont: altfont.map(String::from),
+                };
+                cache.items.get_or_insert_with(key, || {
+                    itemize(pango_context, text, hl, altfont, ligatures)
+                })
+            }
+            None => {
+                uncached = itemize(pango_context, text, hl, altfont, ligatures);
+                &uncached
+            }
+        };
+
+        items
+            .iter()
+            .map(|item| {
+                let a = item.analysis();
+                let item_offset = item.offset() as usize;
+                let mut glyphs = pango::GlyphString::new();
+
+                pango::shape(
+                    &text[item_offset..item_offset + item.length() as usize],
+                    a,
+                    &mut glyphs,
+                );
+
+                (a.font(), glyphs)
+            })
+            .collect()
+    }
+
+    /// If `text` is drawn a cell at a time, so that the glyphs can be
+    /// centered or fit in their cells. Emoji are, so that they don't shift
+    /// the cells after them when their width is off. So is the text that
+    /// the ligatures are broken in, and the double width cells that have
+    /// their own font.
+    fn cell_by_cell(&self, text: &str, area: Rect) -> bool {
+        self.center_glyphs
+            || self.overflow != GlyphOverflow::Overflow
+            || (self.font_wide.is_some() && self.has_double_width(area))
+            || row::is_emoji(text)
+            || self.breaks_ligatures()
+    }
+
+    /// If the segment being drawn (to `area`) has double width cells.
+    fn has_double_width(&self, area: Rect) -> bool {
+        match (self.cells, self.segment) {
+            (Some(row), Some((start, len))) => (start..start + len)
+                .filter_map(|col| row.cell_at(col))
+                .any(|cell| cell.double_width),
+            // Without the row (e.g. for the cursor), the text is a single
+            // cell.
+            _ => area.width > self.cell_width * 1.5,
+        }
+    }
+
+    /// If the ligatures are broken in the segment being drawn.
+    fn breaks_ligatures(&self) -> bool {
+        match (self.ligature_break, self.segment) {
+            (Some(col), Some((start, len))) => {
+                col >= start && col < start + len
+            }
+            _ => false,
+        }
+    }
+
+    /// Splits the text of the segment being drawn (to `area`) to the parts
+    /// that are shaped on their own. Unless the glyphs are centered, runs
+    /// of single width cells are kept together, for their ligatures.
+    /// Non-ASCII text is split to cells if it might not fit in them, and
+    /// all of the text if `split` is set.
+    fn cell_texts(&self, text: &str, area: Rect, split: bool) -> Vec<CellText> {
+        let (row, start, len) = match (self.cells, self.segment) {
+            (Some(row), Some((start, len))) => (row, start, len),
+            // Without the row (e.g. for the cursor), the text is a single
+            // cell.
+            _ => {
+                let cols = (area.width / self.cell_width).round().max(1.0);
+                return vec![CellText {
+                    col: 0,
+                    text: text.to_string(),
+                    cols: cols as usize,
+                    emoji: row::is_emoji(text),
+                }];
+            }
+        };
+
+        let mut texts: Vec<CellText> = vec![];
+        let mut prev_joins = false;
+        for col in start..start + len {
+            let cell = match row.cell_at(col) {
+                // Skip the second halves of double width cells.
+                Some(cell) if !cell.text.is_empty() => cell,
+                _ => continue,
+            };
+
+            let emoji = row::is_emoji(&cell.text);
+            let joins = !split
+                && !self.center_glyphs
+                && !cell.double_width
+                && !emoji
+                && self.ligature_break != Some(col)
+                && (cell.text.is_ascii()
+                    || self.overflow == GlyphOverflow::Overflow);
+            match texts.last_mut() {
+                Some(last) if prev_joins && joins => {
+                    last.text.push_str(&cell.text);
+                    last.cols += 1;
+                }
+                _ => texts.push(CellText {
+                    col: col - start,
+                    text: cell.text.clone(),
+                    cols: if cell.double_width { 2 } else { 1 },
+                    emoji,
+                }),
+            }
+            prev_joins = joins;
+        }
+
+        texts
+    }
+
+    /// Shapes the text of `area` a cell at a time, placing the glyphs of
+    /// each cell in it (see `cell_by_cell`). With `split`, every cell is
+    /// shaped on its own, and the ones with missing glyphs are returned as
+    /// hex boxes.
+    fn shape_cells(
+        &mut self,
+        text: &str,
+        area: Rect,
+        hl: &Highlight,
+        altfont: Option<&str>,
+        split: bool,
+    ) -> (Vec<Run>, Vec<HexBox>) {
+        let cell_width = self.cell_width;
+        let scale = f64::from(pango::SCALE);
+        let mut runs = vec![];
+        let mut hex_boxes = vec![];
+
+        for CellText {
+            col,
+            text,
+            cols,
+            emoji,
+        } in self.cell_texts(text, area, split)
+        {
+            // Double width cells with their own font are scaled to fit the
+            // cells exactly. Emoji are centered, as their advances rarely
+            // match the cells, and clipped when they are wider than the
+            // cells, unless they are to be shrunk.
+            let wide_font = self.font_wide.filter(|_| cols == 2);
+            let center = self.center_glyphs || emoji;
+            let overflow = match self.overflow {
+                GlyphOverflow::Overflow if emoji => GlyphOverflow::Clip,
+                overflow => overflow,
+            };
+
+            let cell = Rect {
+                x: area.x + col as f64 * cell_width,
+                width: cols as f64 * cell_width,
+                ..area
+            };
+
+            // Advance and horizontal ink extents of the cell's glyphs,
+            // relative to where they start.
+            let mut shaped = self.shape(&text, hl, wide_font.or(altfont));
+            if split && shaped.iter().any(|(_, g)| has_missing_glyphs(g)) {
+                if let Some(c) = text.chars().next() {
+                    hex_boxes.push(HexBox { cell, c });
+                }
+                continue;
+            }
+
+            let mut width = 0.0;
+            let mut ink: Option<(f64, f64)> = None;
+            for (font, glyphs) in shaped.iter_mut() {
+                let (rect, _) = glyphs.extents(font);
+                let left = width + f64::from(rect.x) / scale;
+                let right = left + f64::from(rect.width) / scale;
+                ink = Some(match ink {
+                    Some((l, r)) => (l.min(left), r.max(right)),
+                    None => (left, right),
+                });
+                width += f64::from(glyphs.width()) / scale;
+            }
+
+            let mut glyph_scale = match wide_font {
+                Some(_) if width > 0.0 => cell.width / width,
+                _ => 1.0,
+            };
+            let mut x = if center {
+                cell.x + (cell.width - width * glyph_scale) / 2.0
+            } else {
+                cell.x
+            };
+
+            if let (GlyphOverflow::Shrink, Some((left, right))) =
+                (overflow, ink)
+            {
+                if x + left * glyph_scale < cell.x
+                    || x + right * glyph_scale > cell.x + cell.width
+                {
+                    glyph_scale =
+                        (cell.width / (right - left)).min(glyph_scale);
+                    if center {
+                        x = cell.x + (cell.width - width * glyph_scale) / 2.0;
+                    }
+                    x = x
+                        .min(cell.x + cell.width - right * glyph_scale)
+                        .max(cell.x - left * glyph_scale);
+                }
+            }
+
+            let clip = Some(cell).filter(|_| overflow == GlyphOverflow::Clip);
+            for (font, mut glyphs) in shaped {
+                let run_x = x;
+                x += f64::from(glyphs.width()) / scale * glyph_scale;
+                runs.push(Run {
+                    x: run_x,
+                    font,
+                    glyphs,
+                    scale: glyph_scale,
+                    clip,
+                });
+            }
+        }
+
+        (runs, hex_boxes)
+    }
+}
+
+impl<'a> Backend for CairoBackend<'a> {
+    type Error = Error;
+
+    fn fill_rect(&mut self, rect: Rect, color: &Color) -> Result<(), Error> {
+        for cr in self.target.contexts_in(rect) {
+            cr.save()?;
+            cr.set_source_rgb(color.r, color.g, color.b);
+            cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+            cr.fill()?;
+            cr.restore()?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_text(
+        &mut self,
+        text: &str,
+        area: Rect,
+        y: f64,
+        hl: &Highlight,
+        altfont: Option<&str>,
+        color: &Color,
+    ) -> Result<(), Error> {
+        let scale = f64::from(pango::SCALE);
+
+        // Shape the text first, so we know the area the glyphs cover (and
+        // so what to draw them to).
+        let (mut runs, mut hex_boxes) = if self.cell_by_cell(text, area) {
+            self.shape_cells(text, area, hl, altfont, false)
+        } else {
+            let cell_width = self.cell_width;
+            let mut runs = vec![];
+            match (&self.row, self.segment) {
+                // The runs of the row start at their cells, which keeps
+                // right-to-left runs in the cells of their text.
+                (Some(row), Some((start, len))) => {
+                    for (col, run) in row.runs(start, len) {
+                        runs.push(Run {
+                            x: area.x + (col - start) as f64 * cell_width,
+                            font: run.item().analysis().font(),
+                            glyphs: run.glyph_string(),
+                            scale: 1.0,
+                            clip: None,
+                        });
+                    }
+                }
+                _ => {
+                    let mut x = area.x;
+                    for (font, mut glyphs) in self.shape(text, hl, altfont) {
+                        let run_x = x;
+                        x += f64::from(glyphs.width()) / scale;
+                        runs.push(Run {
+                            x: run_x,
+                            font,
+                            glyphs,
+                            scale: 1.0,
+                            clip: None,
+                        });
+                    }
+                }
+            }
+            (runs, vec![])
+        };
+
+        // Characters whose glyphs are missing are drawn as hex boxes, which
+        // takes drawing the text a cell at a time.
+        if self.hex_boxes
+            && runs.iter().any(|run| has_missing_glyphs(&run.glyphs))
+        {
+            let (cell_runs, cell_hex_boxes) =
+                self.shape_cells(text, area, hl, altfont, true);
+            runs = cell_runs;
+            hex_boxes = cell_hex_boxes;
+        }
+
+        let mut ink: Option<Rect> = None;
+        for run in runs.iter_mut() {
+            let (rect, _) = run.glyphs.extents(&run.font);
+            let rect = Rect {
+                x: run.x + f64::from(rect.x) / scale * run.scale,
+                y: y + f64::from(rect.y) / scale * run.scale,
+                width: f64::from(rect.width) / scale * run.scale,
+                height: f64::from(rect.height) / scale * run.scale,
+            };
+            let rect = match run.clip {
+                Some(clip) => match rect.intersection(&clip) {
+                    Some(rect) => rect,
+                    None => continue,
+                },
+                None => rect,
+            };
+            ink = Some(match ink {
+                Some(ink) => ink.union(&rect),
+                None => rect,
+            });
+        }
+        for hex_box in hex_boxes.iter() {
+            ink = Some(match ink {
+                Some(ink) => ink.union(&hex_box.cell),
+                None => hex_box.cell,
+            });
+        }
+
+        let ink = match ink {
+            Some(ink) => ink,
+            None => return Ok(()),
+        };
+
+        for cr in self.target.contexts_in(ink) {
+            cr.save()?;
+            cr.set_source_rgb(color.r, color.g, color.b);
+
+            for run in runs.iter_mut() {
+                cr.save()?;
+                if let Some(clip) = run.clip {
+                    cr.rectangle(clip.x, clip.y, clip.width, clip.height);
+                    cr.clip();
+                }
+                cr.translate(run.x, y);
+                cr.scale(run.scale, run.scale);
+                cr.move_to(0.0, 0.0);
+                pangocairo::functions::show_glyph_string(
+                    cr,
+                    &run.font,
+                    &mut run.glyphs,
+                );
+                cr.restore()?;
+            }
+
+            for hex_box in hex_boxes.iter() {
+                draw_hex_box(cr, self.pango_context, hex_box.cell, hex_box.c)?;
+            }
+
+            cr.restore()?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_undercurl(
+        &mut self,
+        rect: Rect,
+        color: &Color,
+    ) -> Result<(), Error> {
+        // Since we can't (for some reason) use pango attributes to draw
+        // undercurl, we'll have to do that manually.
+        for cr in self.target.contexts_in(rect) {
+            cr.save()?;
+            cr.set_source_rgb(color.r, color.g, color.b);
+            pangocairo::functions::show_error_underline(
+                cr,
+                rect.x,
+                rect.y,
+                rect.width,
+                rect.height,
+            );
+            cr.restore()?;
+        }
+
+        Ok(())
+    }
+
+    fn begin_segment(&mut self, segment: &Segment) {
+        self.segment = Some((segment.start, segment.len));
+    }
+}
+
+/// Draws the cell under the cursor, with `hl`, to `cr`.
 pub fn cursor_cell(
     cr: &cairo::Context,
     pango_context: &pango::Context,
     cell: &Cell,
     cm: &CellMetrics,
+    hl: &Highlight,
     hl_defs: &HlDefs,
 ) -> Result<(), Error> {
-    let mut hl = *hl_defs.get(&cell.hl_id).unwrap();
-
-    hl.reverse = !hl.reverse;
-
     let width = if cell.double_width {
         cm.width * 2.0
     } else {
         cm.width
     };
 
-    render_text(
-        cr,
-        pango_context,
+    render::render_text(
+        &mut CairoBackend {
+            target: Target::Context(cr),
+            pango_context,
+            itemize_cache: None,
+            ligatures: !cm.options.disable_ligatures,
+            row: None,
+            segment: None,
+            cell_width: cm.width,
+            center_glyphs: cm.centers_glyphs(),
+            overflow: cm.options.glyph_overflow,
+            font_wide: cm.font_wide.as_ref().map(|font| font.family()),
+            ligature_break: None,
+            hex_boxes: !cm.options.disable_hex_boxes,
+            cells: None,
+        },
         cm,
-        &hl,
+        hl,
         hl_defs,
         &cell.text,
-        cairo::Rectangle {
+        Rect {
             x: 0.0,
             y: 0.0,
             width,
@@ -147,7 +814,7 @@ pub fn cursor_cell(
     )
 }
 
-/// Renders `segments` to ctx.cairo_context.
+/// Renders `segments` to `ctx.surfaces.front`.
 pub fn put_segments(
     ctx: &mut Context,
     pango_context: &pango::Context,
@@ -155,29 +822,58 @@ pub fn put_segments(
     segments: Vec<Segment>,
     row: usize,
 ) -> Result<(), Error> {
-    let cw = ctx.cell_metrics.width;
-    let ch = ctx.cell_metrics.height;
-
-    for seg in segments {
-        let hl = hl_defs.get(&seg.hl_id).unwrap();
-
-        let pos = cairo::Rectangle {
-            x: (seg.start as f64 * cw).floor(),
-            y: (row as f64 * ch).floor(),
-            width: (seg.len as f64 * cw).ceil(),
-            height: ch.ceil(),
-        };
-
-        render_text(
-            &ctx.surfaces.front,
+    let center_glyphs = ctx.cell_metrics.centers_glyphs();
+    let options = ctx.cell_metrics.options;
+    let overflow = options.glyph_overflow;
+    let font_wide = ctx.cell_metrics.font_wide.as_ref().map(|f| f.family());
+
+    // Only the scripts with contextual forms need the whole row shaped at
+    // once. The segments of the other rows (e.g. with box drawing or icons)
+    // are shaped one by one, which is faster, and cached. Text that is drawn
+    // a cell at a time isn't shaped as a whole either.
+    let shaped_row = ctx
+        .rows
+        .get(row)
+        .filter(|r| {
+            r.has_complex_script()
+                && !center_glyphs
+                && overflow == GlyphOverflow::Overflow
+        })
+        .map(|r| {
+            ShapedRow::new(
+                pango_context,
+                r,
+                hl_defs,
+                !options.disable_ligatures,
+            )
+        });
+
+    let areas = render::render_segments(
+        &mut CairoBackend {
+            target: Target::Tiled(&ctx.surfaces.front),
             pango_context,
-            &ctx.cell_metrics,
-            hl,
-            hl_defs,
-            &seg.text,
-            pos,
-        )?;
+            itemize_cache: Some(&mut ctx.itemize_cache),
+            ligatures: !options.disable_ligatures,
+            row: shaped_row,
+            segment: None,
+            cell_width: ctx.cell_metrics.width,
+            center_glyphs,
+            overflow,
+            font_wide,
+            ligature_break: ctx
+                .ligature_break
+                .filter(|(r, _)| *r == row)
+                .map(|(_, col)| col),
+            hex_boxes: !options.disable_hex_boxes,
+            cells: ctx.rows.get(row),
+        },
+        &ctx.cell_metrics,
+        hl_defs,
+        segments,
+        row,
+    )?;
 
+    for pos in areas {
         ctx.queue_draw_area
             .push((pos.x, pos.y, pos.width, pos.height));
     }
@@ -185,22 +881,75 @@ pub fn put_segments(
     Ok(())
 }
 
+/// Draws the cell boundaries, baselines and the areas damaged on the latest
+/// flush on top of the grid. For debugging font metrics and clipping issues.
+pub fn debug_overlay(cr: &cairo::Context, ctx: &Context) -> Result<(), Error> {
+    let cm = &ctx.cell_metrics;
+    let rows = ctx.rows.len();
+    let cols = ctx.rows.first().map(|row| row.len()).unwrap_or(0);
+    let w = cols as f64 * cm.width;
+    let h = rows as f64 * cm.height;
+
+    cr.save()?;
+    cr.set_line_width(1.0);
+
+    // Cell boundaries. The half pixel offsets keep the lines sharp.
+    cr.set_source_rgba(0.5, 0.5, 0.5, 0.3);
+    for col in 0..=cols {
+        let x = (col as f64 * cm.width).floor() + 0.5;
+        cr.move_to(x, 0.0);
+        cr.line_to(x, h);
+    }
+    for row in 0..=rows {
+        let y = (row as f64 * cm.height).floor() + 0.5;
+        cr.move_to(0.0, y);
+        cr.line_to(w, y);
+    }
+    cr.stroke()?;
+
+    // Baselines.
+    cr.set_source_rgba(0.0, 0.6, 1.0, 0.6);
+    for row in 0..rows {
+        let y = (row as f64 * cm.height + cm.ascent).floor() + 0.5;
+        cr.move_to(0.0, y);
+        cr.line_to(w, y);
+    }
+    cr.stroke()?;
+
+    cr.set_source_rgba(1.0, 0.0, 0.0, 0.8);
+    for (x, y, w, h) in ctx.damage.iter() {
+        cr.rectangle(
+            x.floor() + 0.5,
+            y.floor() + 0.5,
+            w.ceil() - 1.0,
+            h.ceil() - 1.0,
+        );
+    }
+    cr.stroke()?;
+
+    cr.restore()?;
+
+    Ok(())
+}
+
 /// Clears whole `da` with `hl_defs.default_bg`.
 pub fn clear(
     da: &DrawingArea,
     ctx: &mut Context,
     hl_defs: &HlDefs,
 ) -> Result<(), Error> {
-    let cr = &ctx.surfaces.front;
     let w = da.allocated_width();
     let h = da.allocated_height();
-    let bg = &hl_defs.default_bg;
 
-    cr.save()?;
-    cr.set_source_rgb(bg.r, bg.g, bg.b);
-    cr.rectangle(0.0, 0.0, f64::from(w), f64::from(h));
-    cr.fill()?;
-    cr.restore()?;
+    ctx.surfaces.front.fill(
+        Rect {
+            x: 0.0,
+            y: 0.0,
+            width: f64::from(w),
+            height: f64::from(h),
+        },
+        &hl_defs.default_bg,
+    )?;
 
     ctx.queue_draw_area
         .push((0.0, 0.0, f64::from(w), f64::from(h)));
@@ -208,7 +957,7 @@ pub fn clear(
     Ok(())
 }
 
-/// Scrolls contents in `ctx.cairo_context` and `ctx.rows`, based on `reg`.
+/// Scrolls the contents of `ctx.surfaces.front`, based on `area`.
 pub fn scroll(
     ctx: &mut Context,
     hl_defs: &HlDefs,
@@ -231,15 +980,8 @@ pub fn scroll(
     let back = &ctx.surfaces.back;
     let prev = &ctx.surfaces.prev;
 
-    // Draw move the scrolled part on the cairo surface.
-    front.save()?;
-    // Create pattern which we can then "safely" draw to the surface. On X11, the pattern part was
-    // not needed but on wayland it is - I suppose it has something to do with the underlaying
-    // backbuffer.
-    front.push_group();
+    // Move the scrolled part on the surface.
     let (_, y) = get_coords(cm.height, cm.width, dst_top - src_top, 0.0);
-    front.set_source_surface(&front.target(), 0.0, y)?;
-    front.set_operator(cairo::Operator::Source);
     let (x1, y1, x2, y2) = get_rect(
         cm.height,
         cm.width,
@@ -250,48 +992,30 @@ pub fn scroll(
     );
     let w = x2 - x1;
     let h = y2 - y1;
-    front.rectangle(x1, y1, w, h);
-    front.fill()?;
-    // Draw the parttern.
-    front.pop_group_to_source()?;
-    front.set_operator(cairo::Operator::Source);
-    front.rectangle(x1, y1, w, h);
-    front.fill()?;
-    front.restore()?;
+    front.move_area(
+        y,
+        Rect {
+            x: x1,
+            y: y1,
+            width: w,
+            height: h,
+        },
+    )?;
 
     // Store the prev buffer in our back buffer.
-    back.save()?;
-    back.set_source_surface(&prev.target(), 0.0, 0.0)?;
-    back.paint()?;
-    back.restore()?;
+    back.paint_from(prev, 0.0, back.area())?;
 
     // Reset our prev buffer.
-    prev.save()?;
-    prev.set_source_rgb(bg.r, bg.g, bg.b);
-    prev.paint()?;
-    prev.restore()?;
+    prev.fill(prev.area(), bg)?;
 
     ctx.queue_draw_area.push((x1, y1, w, h));
-    ctx.surfaces.set_animation(y, ctx.scroll_speed, frame_time);
+    let mouse = frame_time - ctx.mouse_scroll_time < MOUSE_SCROLL_TIMEOUT;
+    let speed = ctx.scroll.speed(ctx.scroll_speed, mouse);
+    let duration =
+        ctx.scroll
+            .duration(speed, dst_top - src_top, ctx.rows.len() as f64);
+    ctx.surfaces
+        .set_animation(y, duration, ctx.scroll.easing, frame_time);
 
     Ok(())
 }
-
-pub fn get_rect(
-    col_h: f64,
-    col_w: f64,
-    top: f64,
-    bot: f64,
-    left: f64,
-    right: f64,
-) -> (f64, f64, f64, f64) {
-    let (x1, y1) = get_coords(col_h, col_w, top, left);
-    let (x2, y2) = get_coords(col_h, col_w, bot, right);
-    (x1, y1, x2, y2)
-}
-
-pub fn get_coords(h: f64, w: f64, row: f64, col: f64) -> (f64, f64) {
-    let x = col * w;
-    let y = row * h;
-    (x, y)
-}
@@ -0,0 +1,62 @@
+//! The cells of a grid, as the redraw events update them. Kept apart from
+//! the drawing, so that the end-to-end tests check the same cells that the
+//! grids draw.
+
+use crate::error::Error;
+use crate::nvim_bridge::{GridLineSegment, GridScrollArea};
+use crate::ui::grid::row::{Row, Segment};
+
+/// Resizes `rows` to `len` rows of `cols` cells.
+pub fn resize(rows: &mut Vec<Row>, len: usize, cols: usize) {
+    if rows.len() != len {
+        rows.resize_with(len, || Row::new(cols));
+    }
+
+    if rows.first().map(|r| r.len()) != Some(cols) {
+        for row in rows.iter_mut() {
+            row.resize(cols);
+        }
+    }
+}
+
+/// Puts the cells of `line` to their row. Returns the segments that
+/// changed.
+pub fn put_line(
+    rows: &mut [Row],
+    line: GridLineSegment,
+) -> Result<Vec<Segment>, Error> {
+    let row = line.row as usize;
+    Ok(rows
+        .get_mut(row)
+        .ok_or(Error::PutLineRowNotFound(row))?
+        .update(line.col_start as usize, line.cells))
+}
+
+/// Clears all of `rows`.
+pub fn clear(rows: &mut [Row]) {
+    for row in rows.iter_mut() {
+        row.clear();
+    }
+}
+
+/// Moves the cells between the columns `left` and `right` from the source
+/// rows of `area` to its destination rows, and clears the rows that were
+/// scrolled away from.
+pub fn scroll(
+    rows: &mut [Row],
+    area: &GridScrollArea,
+    left: usize,
+    right: usize,
+) {
+    let src = (area.src_top as usize..area.src_bot as usize)
+        .map(|i| rows[i].copy_range(left, right))
+        .collect::<Vec<_>>();
+
+    for (i, cells) in (area.dst_top as usize..area.dst_bot as usize).zip(src) {
+        rows[i].insert_at(left, cells);
+    }
+
+    for row in rows[area.clr_top as usize..area.clr_bot as usize].iter_mut() {
+        row.clear_range(left, right);
+    }
+}
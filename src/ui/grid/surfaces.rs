@@ -1,22 +1,287 @@
 use gtk::{cairo, gdk};
 
+use gnvim_core::render::Rect;
+
 use crate::error::Error;
-use crate::ui::animation::Animation;
+use crate::ui::animation::{Animation, Easing};
 use crate::ui::color::Color;
 
 use super::CellMetrics;
 
+/// Minimum duration (in ms) of a scroll animation when the duration is
+/// proportional to the scrolled distance.
+const MIN_SCROLL_DURATION: i64 = 80;
+/// Time (in microseconds) after a mouse scroll, during which the grid's
+/// scrolls are considered to be caused by the mouse.
+pub const MOUSE_SCROLL_TIMEOUT: i64 = 250_000;
+/// Size (in pixels) of the tiles the grid's surfaces are split into. Keeps
+/// the surfaces well below the backends' size limits, and the operations on
+/// them local to the area that changed, even for huge grids.
+const TILE_SIZE: i32 = 256;
+
+/// Settings of the grid scroll animation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScrollAnimation {
+    /// Duration of the animation, in ms. When `proportional`, the duration
+    /// of scrolling the whole grid.
+    pub speed: i64,
+    /// Duration of the scrolls made with the mouse wheel (or touch), in ms.
+    /// `None` for `speed`.
+    pub mouse_speed: Option<i64>,
+    /// Duration of the message grid's scrolls, in ms. `None` for the
+    /// other speeds.
+    pub messages_speed: Option<i64>,
+    pub easing: Easing,
+    /// If the duration depends on the scrolled distance, so short scrolls
+    /// finish quicker than long ones.
+    pub proportional: bool,
+}
+
+impl ScrollAnimation {
+    /// Speed of a scroll on a grid with its own speed of `grid_speed`.
+    /// `mouse` tells if the scroll was made with the mouse.
+    pub fn speed(&self, grid_speed: Option<i64>, mouse: bool) -> i64 {
+        grid_speed
+            .or_else(|| self.mouse_speed.filter(|_| mouse))
+            .unwrap_or(self.speed)
+    }
+
+    /// Duration (in ms) of scrolling `lines` lines of a grid with `rows`
+    /// rows, with `speed`.
+    pub fn duration(&self, speed: i64, lines: f64, rows: f64) -> i64 {
+        if !self.proportional || rows <= 0.0 {
+            return speed;
+        }
+
+        let ratio = (lines.abs() / rows).min(1.0);
+        let duration = (speed as f64 * ratio.sqrt()).round() as i64;
+        duration.max(speed.min(MIN_SCROLL_DURATION))
+    }
+}
+
+/// Tile of a `TiledSurface`.
+struct Tile {
+    cr: cairo::Context,
+    /// Area of the tile, in the coordinates of the whole surface.
+    rect: Rect,
+}
+
+/// Surface that is split into tiles of `TILE_SIZE`. The tiles' contexts
+/// use the coordinates of the whole surface, so drawing to an area is
+/// drawing the same to each tile in it.
+pub struct TiledSurface {
+    tiles: Vec<Tile>,
+    /// Area of the whole surface.
+    area: Rect,
+}
+
+impl TiledSurface {
+    pub fn new(
+        win: &gdk::Window,
+        width: f64,
+        height: f64,
+        fill: &Color,
+    ) -> Result<Self, Error> {
+        Self::with_tile_size(width, height, TILE_SIZE, fill, |w, h| {
+            win.create_similar_surface(cairo::Content::Color, w, h)
+        })
+    }
+
+    fn with_tile_size<F>(
+        width: f64,
+        height: f64,
+        tile_size: i32,
+        fill: &Color,
+        create_surface: F,
+    ) -> Result<Self, Error>
+    where
+        F: Fn(i32, i32) -> Option<cairo::Surface>,
+    {
+        let width = width.ceil() as i32;
+        let height = height.ceil() as i32;
+
+        let mut tiles = vec![];
+        for y in (0..height).step_by(tile_size as usize) {
+            for x in (0..width).step_by(tile_size as usize) {
+                let w = tile_size.min(width - x);
+                let h = tile_size.min(height - y);
+
+                let surface = create_surface(w, h)
+                    .ok_or(Error::FailedToCreateSurface())?;
+                // The device offset is in device pixels, which differ from
+                // ours on hidpi screens.
+                let (scale_x, scale_y) = surface.device_scale();
+                surface.set_device_offset(
+                    -f64::from(x) * scale_x,
+                    -f64::from(y) * scale_y,
+                );
+
+                let cr = cairo::Context::new(&surface)?;
+                cr.save()?;
+                cr.set_source_rgb(fill.r, fill.g, fill.b);
+                cr.paint()?;
+                cr.restore()?;
+
+                tiles.push(Tile {
+                    cr,
+                    rect: Rect {
+                        x: f64::from(x),
+                        y: f64::from(y),
+                        width: f64::from(w),
+                        height: f64::from(h),
+                    },
+                });
+            }
+        }
+
+        Ok(TiledSurface {
+            tiles,
+            area: Rect {
+                x: 0.0,
+                y: 0.0,
+                width: f64::from(width),
+                height: f64::from(height),
+            },
+        })
+    }
+
+    /// Area of the whole surface.
+    pub fn area(&self) -> Rect {
+        self.area
+    }
+
+    /// Returns the tiles in `area`, and the part of `area` in each.
+    fn tiles_in(&self, area: Rect) -> impl Iterator<Item = (&Tile, Rect)> {
+        self.tiles.iter().filter_map(move |tile| {
+            tile.rect.intersection(&area).map(|rect| (tile, rect))
+        })
+    }
+
+    /// Returns the contexts of the tiles in `area`.
+    pub fn contexts_in(
+        &self,
+        area: Rect,
+    ) -> impl Iterator<Item = &cairo::Context> {
+        self.tiles_in(area).map(|(tile, _)| &tile.cr)
+    }
+
+    /// Fills `area` with `color`.
+    pub fn fill(&self, area: Rect, color: &Color) -> Result<(), Error> {
+        for (tile, rect) in self.tiles_in(area) {
+            let cr = &tile.cr;
+            cr.save()?;
+            cr.set_source_rgb(color.r, color.g, color.b);
+            cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+            cr.fill()?;
+            cr.restore()?;
+        }
+
+        Ok(())
+    }
+
+    /// Completes the pending drawing, so the tiles can be used as a source.
+    pub fn flush(&self) {
+        for tile in self.tiles.iter() {
+            tile.cr.target().flush();
+        }
+    }
+
+    /// Paints the contents of `src`, moved down by `dy`, over `area` of
+    /// `cr` (a context of one of our tiles, or a group pushed on it).
+    fn paint_from_to(
+        cr: &cairo::Context,
+        src: &TiledSurface,
+        dy: f64,
+        area: Rect,
+    ) -> Result<(), Error> {
+        let src_area = Rect {
+            y: area.y - dy,
+            ..area
+        };
+
+        cr.save()?;
+        cr.set_operator(cairo::Operator::Source);
+        for (tile, rect) in src.tiles_in(src_area) {
+            cr.set_source_surface(&tile.cr.target(), 0.0, dy)?;
+            cr.rectangle(rect.x, rect.y + dy, rect.width, rect.height);
+            cr.fill()?;
+        }
+        cr.restore()?;
+
+        Ok(())
+    }
+
+    /// Paints the contents of `src`, moved down by `dy`, over `area`.
+    /// Parts of `area` that `src` doesn't cover are left as they are.
+    pub fn paint_from(
+        &self,
+        src: &TiledSurface,
+        dy: f64,
+        area: Rect,
+    ) -> Result<(), Error> {
+        for (tile, rect) in self.tiles_in(area) {
+            Self::paint_from_to(&tile.cr, src, dy, rect)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves our contents down by `dy`, filling `dst`.
+    pub fn move_area(&self, dy: f64, dst: Rect) -> Result<(), Error> {
+        let mut tiles = self.tiles_in(dst).collect::<Vec<_>>();
+        // The tiles are in rows from top to bottom. Handle the destination
+        // tiles in the order where each tile is read before it is written
+        // to, i.e. from the bottom when moving down and from the top when
+        // moving up.
+        if dy > 0.0 {
+            tiles.reverse();
+        }
+
+        for (tile, rect) in tiles {
+            // The source and the destination might be the same tile, and
+            // cairo doesn't define the result of drawing a surface onto
+            // itself. Depending on the surface type (and so on the gdk
+            // backend), it might just work, so draw through an intermediate
+            // group to get the same result on all of them.
+            let cr = &tile.cr;
+            cr.save()?;
+            cr.push_group();
+            Self::paint_from_to(cr, self, dy, rect)?;
+            cr.pop_group_to_source()?;
+            cr.set_operator(cairo::Operator::Source);
+            cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+            cr.fill()?;
+            cr.restore()?;
+        }
+
+        Ok(())
+    }
+
+    /// Draws `area` of the surface to `cr`.
+    pub fn draw(&self, cr: &cairo::Context, area: Rect) -> Result<(), Error> {
+        for (tile, rect) in self.tiles_in(area) {
+            cr.save()?;
+            cr.set_source_surface(&tile.cr.target(), 0.0, 0.0)?;
+            cr.rectangle(rect.x, rect.y, rect.width, rect.height);
+            cr.fill()?;
+            cr.restore()?;
+        }
+
+        Ok(())
+    }
+}
+
 pub struct Surfaces {
     // Front buffer is where all the new content will be drawn inbetween
     // draw signals.
-    pub front: cairo::Context,
+    pub front: TiledSurface,
     // Back buffer is where, when required, contents of previous draw iteration
     // is kept.
-    pub back: cairo::Context,
+    pub back: TiledSurface,
     // Prev is a intermedate buffer to which front and back buffers are drawn
     // before the contents are drawn to the screen. This allows us to aniamte
     // grid_scroll changes.
-    pub prev: cairo::Context,
+    pub prev: TiledSurface,
 
     pub offset_y: f64,
     pub offset_y_anim: Option<Animation<f64>>,
@@ -30,50 +295,32 @@ impl Surfaces {
         cols: usize,
         fill: &Color,
     ) -> Result<Self, Error> {
+        let w = cell_metrics.width * cols as f64;
+        let h = cell_metrics.height * rows as f64;
+
         Ok(Surfaces {
-            front: Self::create_surface(win, cell_metrics, rows, cols, fill)?,
-            back: Self::create_surface(win, cell_metrics, rows, cols, fill)?,
-            prev: Self::create_surface(win, cell_metrics, rows, cols, fill)?,
+            front: TiledSurface::new(win, w, h, fill)?,
+            back: TiledSurface::new(win, w, h, fill)?,
+            prev: TiledSurface::new(win, w, h, fill)?,
 
             offset_y: 0.0,
             offset_y_anim: None,
         })
     }
 
-    fn create_surface(
-        win: &gdk::Window,
-        cell_metrics: &CellMetrics,
-        rows: usize,
-        cols: usize,
-        fill: &Color,
-    ) -> Result<cairo::Context, Error> {
-        let w = cell_metrics.width * cols as f64;
-        let h = cell_metrics.height * rows as f64;
-
-        let surface = win
-            .create_similar_surface(
-                cairo::Content::Color,
-                w.ceil() as i32,
-                h.ceil() as i32,
-            )
-            .ok_or(Error::FailedToCreateSurface())?;
-
-        let cairo_context = cairo::Context::new(&surface)?;
-
-        cairo_context.save()?;
-        cairo_context.set_source_rgb(fill.r, fill.g, fill.b);
-        cairo_context.paint()?;
-        cairo_context.restore()?;
-
-        Ok(cairo_context)
-    }
-
-    pub fn set_animation(&mut self, y: f64, duration_ms: i64, ft_now: i64) {
+    pub fn set_animation(
+        &mut self,
+        y: f64,
+        duration_ms: i64,
+        easing: Easing,
+        ft_now: i64,
+    ) {
         self.offset_y_anim = Some(Animation {
             start: -y + self.offset_y,
             end: 0.0,
             start_time: ft_now,
             end_time: ft_now + 1000 * duration_ms,
+            easing,
         });
     }
 
@@ -94,3 +341,208 @@ impl Surfaces {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use gtk::prelude::*;
+
+    use super::*;
+
+    /// 1x4 surface in tiles of 1x2, with each row filled with a different
+    /// shade of red.
+    fn surface() -> TiledSurface {
+        surface_with(|w, h| {
+            cairo::ImageSurface::create(cairo::Format::Rgb24, w, h)
+                .ok()
+                .map(|surface| (*surface).clone())
+        })
+    }
+
+    /// Like `surface`, but with the tiles from `create_surface`.
+    fn surface_with<F>(create_surface: F) -> TiledSurface
+    where
+        F: Fn(i32, i32) -> Option<cairo::Surface>,
+    {
+        let surface = TiledSurface::with_tile_size(
+            1.0,
+            4.0,
+            2,
+            &Color::default(),
+            create_surface,
+        )
+        .unwrap();
+
+        for row in 0..4 {
+            surface
+                .fill(
+                    Rect {
+                        x: 0.0,
+                        y: f64::from(row),
+                        width: 1.0,
+                        height: 1.0,
+                    },
+                    &Color {
+                        r: f64::from(row) / 4.0,
+                        g: 0.0,
+                        b: 0.0,
+                    },
+                )
+                .unwrap();
+        }
+
+        surface
+    }
+
+    /// Red channel of each row.
+    fn rows(surface: &TiledSurface) -> Vec<u8> {
+        let image =
+            cairo::ImageSurface::create(cairo::Format::Rgb24, 1, 4).unwrap();
+        {
+            let cr = cairo::Context::new(&image).unwrap();
+            surface.draw(&cr, surface.area()).unwrap();
+        }
+
+        let stride = image.stride() as usize;
+        let data = image.take_data().unwrap();
+        // Rgb24 is stored as native endian u32s.
+        let red = if cfg!(target_endian = "little") { 2 } else { 1 };
+        (0..4).map(|row| data[row * stride + red]).collect()
+    }
+
+    #[test]
+    fn test_tiles() {
+        let surface = surface();
+        assert_eq!(surface.tiles.len(), 2);
+        assert_eq!(
+            surface.tiles[1].rect,
+            Rect {
+                x: 0.0,
+                y: 2.0,
+                width: 1.0,
+                height: 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_move_area() {
+        let surface = surface();
+        let before = rows(&surface);
+
+        // Across the tiles, down.
+        let dst = Rect {
+            x: 0.0,
+            y: 1.0,
+            width: 1.0,
+            height: 3.0,
+        };
+        surface.move_area(1.0, dst).unwrap();
+        assert_eq!(
+            rows(&surface),
+            vec![before[0], before[0], before[1], before[2]]
+        );
+
+        // And up.
+        let surface = self::surface();
+        let dst = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 3.0,
+        };
+        surface.move_area(-1.0, dst).unwrap();
+        assert_eq!(
+            rows(&surface),
+            vec![before[1], before[2], before[3], before[3]]
+        );
+    }
+
+    /// `move_area` on the surfaces of the broadway backend (`--backend
+    /// broadway`), instead of plain image surfaces. Needs a broadway
+    /// display, so run it with e.g.:
+    ///
+    /// ```sh
+    /// broadwayd :5 &
+    /// BROADWAY_DISPLAY=:5 cargo test test_move_area_broadway -- --ignored
+    /// ```
+    #[test]
+    #[ignore]
+    fn test_move_area_broadway() {
+        std::env::set_var("GDK_BACKEND", "broadway");
+        gtk::init().expect("No broadway display");
+
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.realize();
+        let win = window.window().unwrap();
+        assert_eq!(
+            win.display().type_().name(),
+            "GdkBroadwayDisplay",
+            "Not on broadway"
+        );
+
+        let surface = surface_with(|w, h| {
+            win.create_similar_surface(cairo::Content::Color, w, h)
+        });
+        let before = rows(&surface);
+
+        let dst = Rect {
+            x: 0.0,
+            y: 1.0,
+            width: 1.0,
+            height: 3.0,
+        };
+        surface.move_area(1.0, dst).unwrap();
+        assert_eq!(
+            rows(&surface),
+            vec![before[0], before[0], before[1], before[2]]
+        );
+    }
+
+    #[test]
+    fn test_paint_from() {
+        let src = surface();
+        let before = rows(&src);
+
+        let dst = surface();
+        dst.fill(dst.area(), &Color::default()).unwrap();
+        dst.paint_from(&src, 2.0, dst.area()).unwrap();
+        assert_eq!(rows(&dst), vec![0, 0, before[0], before[1]]);
+    }
+
+    #[test]
+    fn test_scroll_duration() {
+        let mut scroll = ScrollAnimation {
+            speed: 300,
+            mouse_speed: None,
+            messages_speed: None,
+            easing: Easing::default(),
+            proportional: false,
+        };
+        assert_eq!(scroll.duration(300, 1.0, 40.0), 300);
+
+        scroll.proportional = true;
+        assert_eq!(scroll.duration(300, 40.0, 40.0), 300);
+        assert_eq!(scroll.duration(300, -100.0, 40.0), 300);
+        assert_eq!(scroll.duration(300, 10.0, 40.0), 150);
+        assert_eq!(scroll.duration(300, 1.0, 40.0), MIN_SCROLL_DURATION);
+        assert_eq!(scroll.duration(0, 1.0, 40.0), 0);
+    }
+
+    #[test]
+    fn test_scroll_speed() {
+        let mut scroll = ScrollAnimation {
+            speed: 300,
+            mouse_speed: None,
+            messages_speed: None,
+            easing: Easing::default(),
+            proportional: false,
+        };
+        assert_eq!(scroll.speed(None, true), 300);
+
+        scroll.mouse_speed = Some(100);
+        assert_eq!(scroll.speed(None, true), 100);
+        assert_eq!(scroll.speed(None, false), 300);
+        assert_eq!(scroll.speed(Some(0), true), 0);
+        assert_eq!(scroll.speed(Some(0), false), 0);
+    }
+}
@@ -0,0 +1,224 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk::prelude::*;
+
+use crate::ui::color::{HlDefs, HlGroup};
+use crate::ui::font::{Font, FontUnit};
+
+/// Ids of the input method modules that are GTK's own, i.e. not an actual
+/// input method.
+const BUILTIN_MODULES: &[&str] = &[
+    "",
+    "simple",
+    "gtk-im-context-simple",
+    "none",
+    "gtk-im-context-none",
+];
+
+/// Input method status indicator, shown below the cursor while the input
+/// method is composing text (e.g. a Compose sequence), or when a keyboard
+/// layout other than the first one is in use. GTK doesn't tell the input
+/// method's engine or language, so the indicator shows the input method
+/// module (e.g. `ibus`) and the layout's number.
+pub struct ImIndicator {
+    css_provider: gtk::CssProvider,
+    /// State shared with the input method's signal handlers.
+    shared: Rc<Shared>,
+
+    font: Font,
+}
+
+struct Shared {
+    /// Container that covers the grids, for placing the label at the
+    /// cursor.
+    fixed: gtk::Fixed,
+    label: gtk::Label,
+    /// If the indicator should be shown at all.
+    enabled: Cell<bool>,
+    /// If the input method is composing text (has a preedit).
+    composing: Cell<bool>,
+    /// Keyboard layout group of the latest key press.
+    layout_group: Rc<Cell<u8>>,
+    /// Input method module, and the position of the indicator, from the
+    /// latest `update`.
+    context_id: RefCell<Option<String>>,
+    pos: Cell<Option<(i32, i32)>>,
+}
+
+impl Shared {
+    fn refresh(&self) {
+        let text = indicator_text(
+            self.context_id.borrow().as_deref().unwrap_or_default(),
+            self.composing.get(),
+            self.layout_group.get(),
+        );
+
+        match (text, self.pos.get()) {
+            (Some(text), Some((x, y))) if self.enabled.get() => {
+                self.label.set_text(&text);
+                self.fixed.move_(&self.label, x, y);
+                self.label.show();
+            }
+            _ => self.label.hide(),
+        }
+    }
+}
+
+impl ImIndicator {
+    pub fn new(parent: &gtk::Overlay) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let label = gtk::Label::new(None);
+        label.set_no_show_all(true);
+        label.style_context().add_class("im-indicator");
+
+        let fixed = gtk::Fixed::new();
+        fixed.put(&label, 0, 0);
+        parent.add_overlay(&fixed);
+        parent.set_overlay_pass_through(&fixed, true);
+
+        add_css_provider!(&css_provider, label);
+
+        ImIndicator {
+            css_provider,
+            shared: Rc::new(Shared {
+                fixed,
+                label,
+                enabled: Cell::new(true),
+                composing: Cell::new(false),
+                layout_group: Rc::new(Cell::new(0)),
+                context_id: RefCell::new(None),
+                pos: Cell::new(None),
+            }),
+            font: Font::default(),
+        }
+    }
+
+    /// Keyboard layout group, to be updated on key presses.
+    pub fn layout_group(&self) -> Rc<Cell<u8>> {
+        self.shared.layout_group.clone()
+    }
+
+    /// Shows the indicator while `im_context` is composing text. Input
+    /// methods that draw the text being composed themselves (see
+    /// `set_use_preedit`) might not tell about it.
+    pub fn connect_im_context(&self, im_context: &gtk::IMMulticontext) {
+        let shared = self.shared.clone();
+        im_context.connect_preedit_start(move |_| {
+            shared.composing.set(true);
+            shared.refresh();
+        });
+
+        let shared = self.shared.clone();
+        im_context.connect_preedit_end(move |_| {
+            shared.composing.set(false);
+            shared.refresh();
+        });
+    }
+
+    pub fn enable(&mut self, enable: bool) {
+        self.shared.enabled.set(enable);
+        self.shared.refresh();
+    }
+
+    /// Container that covers the grids, for translating coordinates to
+    /// `update`.
+    pub fn widget(&self) -> gtk::Widget {
+        self.shared.fixed.clone().upcast()
+    }
+
+    /// Updates the indicator for the input method module `context_id`, and
+    /// moves it to `pos` (below the cursor, relative to `widget()`).
+    pub fn update(&self, context_id: Option<&str>, pos: Option<(i32, i32)>) {
+        *self.shared.context_id.borrow_mut() = context_id.map(String::from);
+        self.shared.pos.set(pos);
+        self.shared.refresh();
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.font = font;
+        self.set_styles(hl_defs);
+    }
+
+    pub fn set_colors(&self, hl_defs: &HlDefs) {
+        self.set_styles(hl_defs);
+    }
+
+    fn set_styles(&self, hl_defs: &HlDefs) {
+        let pmenu = hl_defs
+            .get_hl_group(&HlGroup::Pmenu)
+            .cloned()
+            .unwrap_or_default();
+
+        let css = format!(
+            "{font_wild}
+
+            label.im-indicator {{
+                padding: 0px 6px;
+                border-radius: 4px;
+                font-size: smaller;
+                color: #{fg};
+                background: #{bg};
+            }}
+            ",
+            font_wild = self.font.as_wild_css(FontUnit::Point),
+            fg = pmenu.foreground.unwrap_or(hl_defs.default_fg).as_hex(),
+            bg = pmenu.background.unwrap_or(hl_defs.default_bg).as_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+/// Text of the indicator for the input method module `context_id` (if it
+/// is `composing`) and the keyboard layout `group`, or `None` if neither is
+/// worth showing.
+fn indicator_text(
+    context_id: &str,
+    composing: bool,
+    group: u8,
+) -> Option<String> {
+    let module = Some(context_id).filter(|_| composing).map(|id| {
+        if BUILTIN_MODULES.contains(&id) {
+            // GTK's own composes e.g. Compose sequences and dead keys.
+            String::from("compose")
+        } else {
+            id.to_string()
+        }
+    });
+    let layout = Some(group)
+        .filter(|group| *group > 0)
+        .map(|group| format!("layout {}", group + 1));
+
+    match (module, layout) {
+        (Some(module), Some(layout)) => {
+            Some(format!("{} · {}", module, layout))
+        }
+        (module, layout) => module.or(layout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indicator_text() {
+        assert_eq!(indicator_text("", false, 0), None);
+        assert_eq!(indicator_text("ibus", false, 0), None);
+        assert_eq!(indicator_text("ibus", true, 0), Some(String::from("ibus")));
+        assert_eq!(
+            indicator_text("gtk-im-context-simple", true, 0),
+            Some(String::from("compose"))
+        );
+        assert_eq!(
+            indicator_text("ibus", false, 1),
+            Some(String::from("layout 2"))
+        );
+        assert_eq!(
+            indicator_text("fcitx", true, 2),
+            Some(String::from("fcitx · layout 3"))
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write;
+
+use gtk::prelude::*;
+
+/// What the inspector shows about a grid.
+#[derive(Debug, Default)]
+pub struct GridInfo {
+    pub id: i64,
+    pub cols: f64,
+    pub rows: f64,
+    /// Position (in pixels) and the name of the container, if the grid
+    /// has a window.
+    pub window: Option<(f64, f64, &'static str)>,
+    pub animations: Vec<&'static str>,
+}
+
+/// What the inspector shows about the UI.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Grids, sorted by their id.
+    pub grids: Vec<GridInfo>,
+    pub current_grid: i64,
+    /// Number of highlight definitions.
+    pub hl_defs: usize,
+    /// Sizes of the window containers, by their name.
+    pub containers: Vec<(&'static str, i32, i32)>,
+}
+
+impl Report {
+    /// Formats the report as text.
+    pub fn format(&self) -> String {
+        let mut text = String::new();
+
+        let _ = writeln!(text, "Grids ({}):", self.grids.len());
+        for grid in self.grids.iter() {
+            let _ = write!(text, "  {}: {}x{}", grid.id, grid.cols, grid.rows);
+            if grid.id == self.current_grid {
+                text.push_str(" (current)");
+            }
+            match grid.window {
+                Some((x, y, container)) => {
+                    let _ = write!(text, " at {},{} in {}", x, y, container);
+                }
+                None if grid.id == 1 => text.push_str(" (main grid)"),
+                None => text.push_str(" (no window)"),
+            }
+            if !grid.animations.is_empty() {
+                let _ =
+                    write!(text, ", animating: {}", grid.animations.join(", "));
+            }
+            text.push('\n');
+        }
+
+        text.push_str("\nContainers:\n");
+        for (name, width, height) in self.containers.iter() {
+            let _ = writeln!(text, "  {}: {}x{}", name, width, height);
+        }
+
+        let _ = writeln!(text, "\nHighlight definitions: {}", self.hl_defs);
+
+        text
+    }
+}
+
+/// Developer window showing the internal state of the UI, for debugging
+/// (`:GnvimInspect`). The contents are a snapshot, which is updated when
+/// the window is shown again.
+pub struct Inspector {
+    window: gtk::Window,
+    buffer: gtk::TextBuffer,
+}
+
+impl Inspector {
+    pub fn new(parent: &gtk::Window) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title("GNvim Inspector");
+        window.set_transient_for(Some(parent));
+        window.set_destroy_with_parent(true);
+        window.set_default_size(500, 400);
+
+        let view = gtk::TextView::new();
+        view.set_editable(false);
+        view.set_monospace(true);
+        view.set_left_margin(6);
+        view.set_top_margin(6);
+
+        let scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scroll.add(&view);
+        window.add(&scroll);
+
+        // Keep the window around, so it can be shown again.
+        window.connect_delete_event(|window, _| {
+            window.hide();
+            gtk::Inhibit(true)
+        });
+
+        Inspector {
+            buffer: view.buffer().unwrap(),
+            window,
+        }
+    }
+
+    /// Shows the window with `report`.
+    pub fn show(&self, report: &Report) {
+        self.buffer.set_text(&report.format());
+        self.window.show_all();
+        self.window.present();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_format() {
+        let report = Report {
+            grids: vec![
+                GridInfo {
+                    id: 1,
+                    cols: 80.0,
+                    rows: 30.0,
+                    ..Default::default()
+                },
+                GridInfo {
+                    id: 2,
+                    cols: 80.0,
+                    rows: 29.0,
+                    window: Some((0.0, 0.0, "windows")),
+                    animations: vec!["cursor", "scroll"],
+                },
+                GridInfo {
+                    id: 4,
+                    cols: 20.0,
+                    rows: 5.0,
+                    ..Default::default()
+                },
+            ],
+            current_grid: 2,
+            hl_defs: 42,
+            containers: vec![("windows", 640, 480)],
+        };
+
+        assert_eq!(
+            report.format(),
+            "Grids (3):\n\
+             \x20 1: 80x30 (main grid)\n\
+             \x20 2: 80x29 (current) at 0,0 in windows, animating: cursor, \
+             scroll\n\
+             \x20 4: 20x5 (no window)\n\
+             \n\
+             Containers:\n\
+             \x20 windows: 640x480\n\
+             \n\
+             Highlight definitions: 42\n"
+        );
+    }
+}
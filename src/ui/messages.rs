@@ -0,0 +1,640 @@
+use std::time::Duration;
+
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+
+use log::error;
+
+use crate::nvim_bridge::MsgShow;
+use crate::nvim_gio::GioNeovim;
+use crate::ui::color::{Color, HlDefs, HlGroup};
+use crate::ui::common::spawn_local;
+use crate::ui::confirm::{show_confirm, ConfirmDialog};
+use crate::ui::font::{Font, FontUnit};
+
+#[derive(Default)]
+pub struct MessagesColors {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub border: Option<Color>,
+    pub error: Option<Color>,
+    pub warning: Option<Color>,
+    pub info: Option<Color>,
+
+    /// Mode indicator's colors for normal, insert, visual and replace modes.
+    pub mode_normal: Option<Color>,
+    pub mode_insert: Option<Color>,
+    pub mode_visual: Option<Color>,
+    pub mode_replace: Option<Color>,
+}
+
+/// Nvim's externalized messages (`ext_messages`).
+pub struct Messages {
+    css_provider: gtk::CssProvider,
+    nvim: GioNeovim,
+
+    /// Container for the messages, in the bottom right corner.
+    box_: gtk::Box,
+    /// Container for the error toasts, in the top right corner.
+    toasts: gtk::Box,
+    /// Ruler (`msg_ruler`), below the messages.
+    ruler: gtk::Label,
+    /// Search count badge (`search_count` messages), next to the ruler.
+    search_count: gtk::Label,
+    /// Mode indicator (`msg_showmode`), in the bottom left corner.
+    showmode: gtk::Label,
+    /// If the mode indicator should be shown at all.
+    showmode_enabled: bool,
+    /// Style class of the mode indicator, based on the current mode.
+    mode_class: &'static str,
+    /// Dialog of the current confirm prompt, if any.
+    confirm: Option<ConfirmDialog>,
+
+    colors: MessagesColors,
+    font: Font,
+}
+
+impl Messages {
+    pub fn new(parent: &gtk::Overlay, nvim: GioNeovim) -> Self {
+        let css_provider = gtk::CssProvider::new();
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 6);
+
+        let ruler = gtk::Label::new(None);
+        ruler.set_halign(gtk::Align::End);
+        ruler.set_no_show_all(true);
+        ruler.style_context().add_class("ruler");
+
+        let search_count = gtk::Label::new(None);
+        search_count.set_no_show_all(true);
+        search_count.style_context().add_class("search-count");
+
+        let bottom = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        bottom.set_halign(gtk::Align::End);
+        bottom.pack_start(&search_count, false, false, 0);
+        bottom.pack_start(&ruler, false, false, 0);
+
+        let right = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        right.set_halign(gtk::Align::End);
+        right.set_valign(gtk::Align::End);
+        right.set_margin(10);
+        right.pack_start(&box_, false, false, 0);
+        right.pack_start(&bottom, false, false, 0);
+        parent.add_overlay(&right);
+        parent.set_overlay_pass_through(&right, true);
+
+        let toasts = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        toasts.set_halign(gtk::Align::End);
+        toasts.set_valign(gtk::Align::Start);
+        toasts.set_margin(10);
+        toasts.set_no_show_all(true);
+        // Not pass-through, so the copy buttons can be clicked. Hidden when
+        // empty, so it doesn't block the grid.
+        parent.add_overlay(&toasts);
+
+        let showmode = gtk::Label::new(None);
+        showmode.set_halign(gtk::Align::Start);
+        showmode.set_valign(gtk::Align::End);
+        showmode.set_margin(10);
+        showmode.set_no_show_all(true);
+        showmode.style_context().add_class("showmode");
+        parent.add_overlay(&showmode);
+        parent.set_overlay_pass_through(&showmode, true);
+
+        add_css_provider!(&css_provider, showmode, ruler, search_count);
+
+        Messages {
+            css_provider,
+            nvim,
+            box_,
+            toasts,
+            ruler,
+            search_count,
+            showmode,
+            showmode_enabled: true,
+            mode_class: "normal",
+            confirm: None,
+            colors: MessagesColors::default(),
+            font: Font::default(),
+        }
+    }
+
+    /// Shows a message (`msg_show`). Confirm prompts are shown as dialogs, and
+    /// errors as toasts.
+    pub fn show(&mut self, msg: MsgShow, hl_defs: &HlDefs) {
+        if msg.kind == "confirm" || msg.kind == "confirm_sub" {
+            // Nvim doesn't tell when the prompt is done, so the previous
+            // dialog is closed only when a new prompt comes in.
+            if let Some(dialog) = self.confirm.take() {
+                dialog.dismiss();
+            }
+
+            let text: String =
+                msg.content.iter().map(|(_, text)| text.as_str()).collect();
+            let parent = self
+                .box_
+                .toplevel()
+                .and_then(|w| w.downcast::<gtk::Window>().ok());
+            self.confirm =
+                show_confirm(parent.as_ref(), &self.nvim, &msg.kind, &text);
+
+            if self.confirm.is_some() {
+                return;
+            }
+        }
+
+        if msg.kind == "search_count" {
+            let text: String =
+                msg.content.iter().map(|(_, text)| text.as_str()).collect();
+            if let Some(count) = search_count_text(&text) {
+                let ctx = self.search_count.style_context();
+                ctx.remove_class("stale");
+                self.search_count.set_text(count);
+                self.search_count.show();
+                return;
+            }
+        }
+
+        if ERROR_KINDS.contains(&msg.kind.as_str()) {
+            self.show_toast(msg, hl_defs);
+            return;
+        }
+
+        self.add_message(msg, hl_defs);
+    }
+
+    fn add_message(&self, msg: MsgShow, hl_defs: &HlDefs) {
+        if msg.replace_last {
+            if let Some(last) = self.box_.children().last() {
+                self.box_.remove(last);
+            }
+        }
+
+        let markup = content_markup(&msg.content, hl_defs);
+
+        if markup.is_empty() {
+            return;
+        }
+
+        let label = gtk::Label::new(None);
+        label.set_markup(markup.trim_end());
+        label.set_line_wrap(true);
+        label.set_max_width_chars(80);
+        label.set_xalign(0.0);
+
+        let content = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        content.pack_end(&label, true, true, 0);
+
+        let frame = gtk::Frame::new(None);
+        frame.add(&content);
+        let ctx = frame.style_context();
+        ctx.add_class("message");
+        if !msg.kind.is_empty() {
+            ctx.add_class(&msg.kind);
+        }
+        if let Some((class, icon)) = kind_style(&msg.kind) {
+            ctx.add_class(class);
+            let image = kind_icon(icon);
+            content.pack_start(&image, false, false, 0);
+            add_css_provider!(&self.css_provider, image);
+        }
+
+        add_css_provider!(&self.css_provider, frame, label);
+
+        self.box_.pack_start(&frame, false, false, 0);
+        frame.show_all();
+    }
+
+    /// Shows an error message as a toast, which is removed after
+    /// `TOAST_TIMEOUT`.
+    fn show_toast(&self, msg: MsgShow, hl_defs: &HlDefs) {
+        let text: String =
+            msg.content.iter().map(|(_, text)| text.as_str()).collect();
+        let text = text.trim().to_string();
+
+        if text.is_empty() {
+            return;
+        }
+
+        let label = gtk::Label::new(None);
+        label.set_markup(content_markup(&msg.content, hl_defs).trim());
+        label.set_line_wrap(true);
+        label.set_max_width_chars(80);
+        label.set_xalign(0.0);
+
+        let copy = gtk::Button::from_icon_name(
+            Some("edit-copy-symbolic"),
+            gtk::IconSize::Menu,
+        );
+        copy.set_relief(gtk::ReliefStyle::None);
+        copy.set_valign(gtk::Align::Start);
+        copy.set_tooltip_text(Some("Copy"));
+        copy.connect_clicked(move |_| {
+            gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD).set_text(&text);
+        });
+
+        let content = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        content.pack_start(&label, true, true, 0);
+        content.pack_start(&copy, false, false, 0);
+
+        let frame = gtk::Frame::new(None);
+        frame.add(&content);
+        let ctx = frame.style_context();
+        ctx.add_class("toast");
+        ctx.add_class(&msg.kind);
+        if let Some((class, icon)) = kind_style(&msg.kind) {
+            ctx.add_class(class);
+            let image = kind_icon(icon);
+            content.pack_start(&image, false, false, 0);
+            content.reorder_child(&image, 0);
+            add_css_provider!(&self.css_provider, image);
+        }
+
+        add_css_provider!(&self.css_provider, frame, label, copy);
+
+        self.toasts.pack_start(&frame, false, false, 0);
+        frame.show_all();
+        self.toasts.show();
+
+        let toasts = self.toasts.clone();
+        glib::timeout_add_local(TOAST_TIMEOUT, move || {
+            toasts.remove(&frame);
+            if toasts.children().is_empty() {
+                toasts.hide();
+            }
+
+            Continue(false)
+        });
+    }
+
+    /// Clears all the messages (`msg_clear`).
+    pub fn clear(&self) {
+        for child in self.box_.children() {
+            self.box_.remove(&child);
+        }
+
+        if !self.search_count.is_visible() {
+            return;
+        }
+
+        // Keep the search count around (dimmed, as it might be out of date)
+        // while the search matches are highlighted.
+        let nvim = self.nvim.clone();
+        let search_count = self.search_count.clone();
+        spawn_local(async move {
+            match nvim.get_vvar("hlsearch").await {
+                Ok(hlsearch) if hlsearch.as_i64() == Some(1) => {
+                    search_count.style_context().add_class("stale");
+                }
+                Ok(_) => search_count.hide(),
+                Err(err) => error!("Failed to get v:hlsearch: {}", err),
+            }
+        });
+    }
+
+    /// Hides the messages, toasts, ruler, search count and mode indicator,
+    /// for when the messages are no longer externalized.
+    pub fn hide_all(&self) {
+        for child in self.box_.children() {
+            self.box_.remove(&child);
+        }
+        for child in self.toasts.children() {
+            self.toasts.remove(&child);
+        }
+
+        self.ruler.hide();
+        self.search_count.hide();
+        self.showmode.hide();
+    }
+
+    /// Shows the message history (`msg_history_show`).
+    pub fn history_show(&self, msgs: Vec<MsgShow>, hl_defs: &HlDefs) {
+        self.clear();
+        for msg in msgs {
+            self.add_message(msg, hl_defs);
+        }
+    }
+
+    /// Sets the content of the mode indicator (`msg_showmode`).
+    pub fn set_showmode(&self, content: &[(u64, String)]) {
+        let text: String =
+            content.iter().map(|(_, text)| text.as_str()).collect();
+        let text = showmode_text(&text);
+
+        self.showmode.set_text(&text);
+        self.showmode
+            .set_visible(self.showmode_enabled && !text.is_empty());
+    }
+
+    /// Sets the content of the ruler (`msg_ruler`).
+    pub fn set_ruler(&self, content: &[(u64, String)], hl_defs: &HlDefs) {
+        // The ruler is padded for the TUI, which we don't need.
+        let content = content
+            .iter()
+            .map(|(hl_id, text)| {
+                (
+                    *hl_id,
+                    text.split_whitespace().collect::<Vec<_>>().join("  "),
+                )
+            })
+            .collect::<Vec<_>>();
+        let markup = content_markup(&content, hl_defs);
+
+        self.ruler.set_markup(markup.trim());
+        self.ruler.set_visible(!markup.trim().is_empty());
+    }
+
+    pub fn enable_showmode(&mut self, enable: bool) {
+        self.showmode_enabled = enable;
+        if !enable {
+            self.showmode.hide();
+        }
+    }
+
+    /// Sets the mode indicator's color based on nvim's mode (`mode_change`).
+    pub fn set_mode(&mut self, mode: &str) {
+        let class = if mode.starts_with("insert") {
+            "insert"
+        } else if mode.starts_with("visual") || mode.starts_with("select") {
+            "visual"
+        } else if mode.starts_with("replace") {
+            "replace"
+        } else {
+            "normal"
+        };
+
+        let ctx = self.showmode.style_context();
+        ctx.remove_class(self.mode_class);
+        ctx.add_class(class);
+        self.mode_class = class;
+    }
+
+    pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
+        self.font = font;
+        self.set_styles(hl_defs);
+    }
+
+    pub fn set_colors(&mut self, hl_defs: &HlDefs) {
+        let get =
+            |group| hl_defs.get_hl_group(&group).cloned().unwrap_or_default();
+
+        self.colors = MessagesColors {
+            fg: get(HlGroup::Cmdline).foreground,
+            bg: get(HlGroup::Cmdline).background,
+            border: get(HlGroup::MsgSeparator).foreground,
+            error: get(HlGroup::ErrorMsg).foreground,
+            warning: get(HlGroup::WarningMsg).foreground,
+            info: get(HlGroup::MoreMsg).foreground,
+            mode_normal: get(HlGroup::ModeMsg).foreground,
+            mode_insert: get(HlGroup::MoreMsg).foreground,
+            mode_visual: get(HlGroup::Visual).background,
+            mode_replace: get(HlGroup::WarningMsg).foreground,
+        };
+        self.set_styles(hl_defs);
+    }
+
+    fn set_styles(&self, hl_defs: &HlDefs) {
+        let fg = self.colors.fg.unwrap_or(hl_defs.default_fg);
+        let bg = self.colors.bg.unwrap_or(hl_defs.default_bg);
+        let mode_normal = self.colors.mode_normal.unwrap_or(fg);
+
+        let css = format!(
+            "{font_wild}
+
+            frame.message {{
+                padding: 4px 8px;
+                background: #{bg};
+                border: 1px solid #{border};
+            }}
+
+            frame.message > border {{
+                border: none;
+            }}
+
+            label {{
+                color: #{fg};
+                background: transparent;
+            }}
+
+            frame.message image, frame.toast image {{
+                color: #{fg};
+            }}
+
+            frame.message.error, frame.message.warning, frame.message.info {{
+                border-left-width: 4px;
+            }}
+
+            frame.message.error {{
+                border-left-color: #{error};
+            }}
+
+            frame.message.warning {{
+                border-left-color: #{warning};
+            }}
+
+            frame.message.info {{
+                border-left-color: #{info};
+            }}
+
+            frame.error image {{
+                color: #{error};
+            }}
+
+            frame.warning image {{
+                color: #{warning};
+            }}
+
+            frame.info image {{
+                color: #{info};
+            }}
+
+            frame.toast {{
+                padding: 4px 4px 4px 8px;
+                background: #{bg};
+                border: 1px solid #{error};
+                border-left-width: 4px;
+            }}
+
+            frame.toast > border {{
+                border: none;
+            }}
+
+            frame.toast button {{
+                padding: 0;
+                min-height: 0;
+                min-width: 0;
+                color: #{fg};
+            }}
+
+            label.search-count {{
+                padding: 0px 6px;
+                border-radius: 4px;
+                color: #{bg};
+                background: #{fg};
+            }}
+
+            label.search-count.stale {{
+                opacity: 0.5;
+            }}
+
+            label.ruler {{
+                opacity: 0.7;
+            }}
+
+            label.showmode {{
+                padding: 2px 8px;
+                border-radius: 4px;
+                font-weight: bold;
+                color: #{bg};
+                background: #{mode_normal};
+            }}
+
+            label.showmode.insert {{
+                background: #{mode_insert};
+            }}
+
+            label.showmode.visual {{
+                background: #{mode_visual};
+            }}
+
+            label.showmode.replace {{
+                background: #{mode_replace};
+            }}
+            ",
+            font_wild = self.font.as_wild_css(FontUnit::Point),
+            fg = fg.as_hex(),
+            bg = bg.as_hex(),
+            border = self.colors.border.unwrap_or(fg).as_hex(),
+            error = self.colors.error.unwrap_or(fg).as_hex(),
+            warning = self.colors.warning.unwrap_or(fg).as_hex(),
+            info = self.colors.info.unwrap_or(fg).as_hex(),
+            mode_normal = mode_normal.as_hex(),
+            mode_insert =
+                self.colors.mode_insert.unwrap_or(mode_normal).as_hex(),
+            mode_visual =
+                self.colors.mode_visual.unwrap_or(mode_normal).as_hex(),
+            mode_replace =
+                self.colors.mode_replace.unwrap_or(mode_normal).as_hex(),
+        );
+        CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
+            .unwrap();
+    }
+}
+
+/// Message kinds that are shown as toasts.
+const ERROR_KINDS: &[&str] = &["emsg", "echoerr", "lua_error", "rpc_error"];
+/// Style class and icon of the message kinds, for the kinds that have a
+/// style of their own. Plain `:echo`s are left as is.
+const KIND_STYLES: &[(&str, &str, &str)] = &[
+    ("emsg", "error", "dialog-error-symbolic"),
+    ("echoerr", "error", "dialog-error-symbolic"),
+    ("lua_error", "error", "dialog-error-symbolic"),
+    ("rpc_error", "error", "network-error-symbolic"),
+    ("shell_err", "error", "utilities-terminal-symbolic"),
+    ("wmsg", "warning", "dialog-warning-symbolic"),
+    ("bufwrite", "info", "document-save-symbolic"),
+    ("undo", "info", "edit-undo-symbolic"),
+    ("quickfix", "quickfix", "view-list-symbolic"),
+    ("list_cmd", "list", "view-list-symbolic"),
+    ("search_cmd", "search", "edit-find-symbolic"),
+    ("search_count", "search", "edit-find-symbolic"),
+    ("shell_out", "shell", "utilities-terminal-symbolic"),
+    ("shell_ret", "shell", "utilities-terminal-symbolic"),
+    ("return_prompt", "prompt", "dialog-question-symbolic"),
+];
+
+/// How long the toasts are shown.
+const TOAST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Gets the style class and icon name for message `kind`.
+fn kind_style(kind: &str) -> Option<(&'static str, &'static str)> {
+    KIND_STYLES
+        .iter()
+        .find(|(k, _, _)| *k == kind)
+        .map(|(_, class, icon)| (*class, *icon))
+}
+
+fn kind_icon(icon: &str) -> gtk::Image {
+    let image = gtk::Image::from_icon_name(Some(icon), gtk::IconSize::Menu);
+    image.set_valign(gtk::Align::Start);
+    image
+}
+
+fn content_markup(content: &[(u64, String)], hl_defs: &HlDefs) -> String {
+    content
+        .iter()
+        .map(|(hl_id, text)| {
+            let hl = hl_defs.get(hl_id).unwrap();
+            hl.pango_markup(
+                text,
+                &hl_defs.default_fg,
+                &hl_defs.default_bg,
+                &hl_defs.default_sp,
+            )
+        })
+        .collect()
+}
+
+/// Turns `msg_showmode`'s text (e.g. `-- INSERT --recording @q`) into a more
+/// compact form (e.g. `INSERT · recording @q`).
+fn showmode_text(text: &str) -> String {
+    text.split("--")
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// Gets the count from a `search_count` message, e.g. `3/17` from
+/// `/foo  [3/17]` or `>99/>99` from `W [>99/>99]`.
+fn search_count_text(text: &str) -> Option<&str> {
+    let text = text.trim_end().strip_suffix(']')?;
+    let start = text.rfind('[')?;
+    Some(&text[start + 1..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_showmode_text() {
+        assert_eq!(showmode_text("-- INSERT --"), "INSERT");
+        assert_eq!(
+            showmode_text("-- VISUAL LINE --recording @q"),
+            "VISUAL LINE · recording @q"
+        );
+        assert_eq!(showmode_text("recording @q"), "recording @q");
+        assert_eq!(showmode_text(""), "");
+    }
+
+    #[test]
+    fn test_kind_style() {
+        assert_eq!(
+            kind_style("emsg"),
+            Some(("error", "dialog-error-symbolic"))
+        );
+        assert_eq!(
+            kind_style("rpc_error"),
+            Some(("error", "network-error-symbolic"))
+        );
+        assert_eq!(
+            kind_style("wmsg"),
+            Some(("warning", "dialog-warning-symbolic"))
+        );
+        assert_eq!(
+            kind_style("search_cmd"),
+            Some(("search", "edit-find-symbolic"))
+        );
+        assert_eq!(kind_style("echo"), None);
+        assert_eq!(kind_style(""), None);
+    }
+
+    #[test]
+    fn test_search_count_text() {
+        assert_eq!(search_count_text("/foo  [3/17]"), Some("3/17"));
+        assert_eq!(search_count_text("W [>99/>99]"), Some(">99/>99"));
+        assert_eq!(search_count_text("?[a]  [1/2] "), Some("1/2"));
+        assert_eq!(search_count_text("foo"), None);
+    }
+}
@@ -47,17 +47,35 @@ macro_rules! upgrade_weak {
     };
 }
 
+mod actions;
 mod animation;
 mod cmdline;
-pub mod color;
-mod common;
+pub use gnvim_core::color;
+pub mod common;
+mod confirm;
+mod filetree;
+mod find;
 mod font;
 mod grid;
+mod im_indicator;
+mod inspector;
+mod messages;
+mod palette;
 mod popupmenu;
+mod session_picker;
+mod start_error;
 mod state;
 mod tabline;
+mod toolbar;
 #[allow(clippy::module_inception)]
 mod ui;
+mod view;
+mod welcome;
 mod wildmenu;
 mod window;
+#[cfg(test)]
+pub use self::grid::rows;
+pub use self::session_picker::pick_session;
+pub use self::start_error::show_start_error;
 pub use self::ui::UI;
+pub use self::view::{NeovimView, Nvim};
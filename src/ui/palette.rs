@@ -0,0 +1,440 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{gdk, pango};
+
+use log::error;
+
+use crate::files::{self, FileArg};
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::{gtk_recent_files, spawn_local};
+
+/// Maximum number of items shown.
+const MAX_ITEMS: usize = 50;
+/// Width of the palette, in pixels.
+const WIDTH: i32 = 500;
+/// Height of the list, in pixels.
+const HEIGHT: i32 = 300;
+
+const CSS: &str = "
+frame.palette {
+    background-color: @theme_bg_color;
+    border-radius: 6px;
+    padding: 6px;
+}
+";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    /// Ex command.
+    Command(String),
+    /// Application action, by name.
+    Action(String),
+    /// Recent file.
+    File(String),
+}
+
+impl Item {
+    fn label(&self) -> String {
+        match self {
+            Item::Command(cmd) => cmd.clone(),
+            Item::Action(name) => action_label(name),
+            Item::File(path) => path.clone(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Item::Command(_) => "Command",
+            Item::Action(_) => "Action",
+            Item::File(_) => "Recent file",
+        }
+    }
+}
+
+/// Fuzzy finder for ex commands, our actions and recent files, shown on top
+/// of the grid. The list is kept on our side, so the palette works while
+/// nvim is busy (e.g. waiting at a prompt).
+pub struct Palette {
+    frame: gtk::Frame,
+    entry: gtk::Entry,
+    list: gtk::ListBox,
+    scrolled: gtk::ScrolledWindow,
+    window: gtk::ApplicationWindow,
+    nvim: GioNeovim,
+
+    /// All items, for the current `show`.
+    items: RefCell<Vec<Item>>,
+    /// Items of the rows in `list`.
+    matches: RefCell<Vec<Item>>,
+    /// Ex commands, fetched from nvim when the palette is first shown.
+    commands: RefCell<Option<Vec<String>>>,
+    /// Widget that had the focus before the palette was shown.
+    prev_focus: RefCell<Option<gtk::Widget>>,
+}
+
+impl Palette {
+    /// Creates the (hidden) palette. Must be called before the window's
+    /// other key press handlers are connected, so we get the keypresses
+    /// while the palette is open.
+    pub fn new(
+        parent: &gtk::Overlay,
+        window: &gtk::ApplicationWindow,
+        nvim: GioNeovim,
+    ) -> Rc<Self> {
+        let css_provider = gtk::CssProvider::new();
+        if let Err(err) =
+            CssProviderExt::load_from_data(&css_provider, CSS.as_bytes())
+        {
+            error!("Failed to load palette css: {}", err);
+        }
+
+        let entry = gtk::Entry::new();
+        entry.set_placeholder_text(Some("Search, or :command"));
+
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Browse);
+        list.set_activate_on_single_click(true);
+
+        let scrolled = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scrolled.set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scrolled.set_size_request(-1, HEIGHT);
+        scrolled.add(&list);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_.pack_start(&entry, false, false, 0);
+        box_.pack_start(&scrolled, true, true, 0);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_halign(gtk::Align::Center);
+        frame.set_valign(gtk::Align::Start);
+        frame.set_margin_top(40);
+        frame.set_size_request(WIDTH, -1);
+        frame.set_no_show_all(true);
+        frame.style_context().add_class("palette");
+        frame.add(&box_);
+        parent.add_overlay(&frame);
+
+        add_css_provider!(&css_provider, frame);
+
+        let palette = Rc::new(Palette {
+            frame,
+            entry,
+            list,
+            scrolled,
+            window: window.clone(),
+            nvim,
+            items: RefCell::new(vec![]),
+            matches: RefCell::new(vec![]),
+            commands: RefCell::new(None),
+            prev_focus: RefCell::new(None),
+        });
+
+        let weak = Rc::downgrade(&palette);
+        palette.entry.connect_changed(move |_| {
+            let palette = upgrade_weak!(weak);
+            palette.filter();
+        });
+
+        let weak = Rc::downgrade(&palette);
+        palette.list.connect_row_activated(move |_, row| {
+            let palette = upgrade_weak!(weak);
+            palette.activate(row.index());
+        });
+
+        let weak = Rc::downgrade(&palette);
+        window.connect_key_press_event(move |window, e| {
+            let palette = upgrade_weak!(weak, Inhibit(false));
+            if !palette.frame.is_visible() {
+                return Inhibit(false);
+            }
+
+            let selected = palette
+                .list
+                .selected_row()
+                .map(|row| row.index())
+                .unwrap_or(0);
+
+            match e.keyval() {
+                gdk::keys::constants::Escape => palette.hide(),
+                gdk::keys::constants::Return
+                | gdk::keys::constants::KP_Enter => palette.activate(selected),
+                gdk::keys::constants::Up => palette.select(selected - 1),
+                gdk::keys::constants::Down => palette.select(selected + 1),
+                // The rest goes to the entry, and not to nvim.
+                _ => {
+                    window.propagate_key_event(e);
+                }
+            }
+
+            Inhibit(true)
+        });
+
+        palette
+    }
+
+    /// Shows the palette.
+    pub fn show(self: &Rc<Self>) {
+        let mut items = vec![];
+
+        if let Some(ref commands) = *self.commands.borrow() {
+            items.extend(commands.iter().cloned().map(Item::Command));
+        } else {
+            self.fetch_commands();
+        }
+
+        if let Some(app) = self.window.application() {
+            let mut actions = app
+                .list_actions()
+                .into_iter()
+                .map(|name| name.to_string())
+                .filter(|name| name != "command-palette")
+                .collect::<Vec<_>>();
+            actions.sort();
+            items.extend(actions.into_iter().map(Item::Action));
+        }
+
+        items.extend(gtk_recent_files().into_iter().map(Item::File));
+
+        self.items.replace(items);
+        self.entry.set_text("");
+        self.filter();
+
+        if !self.frame.is_visible() {
+            self.prev_focus.replace(self.window.focus());
+        }
+        self.frame.show_all();
+        self.entry.grab_focus();
+    }
+
+    fn hide(&self) {
+        self.frame.hide();
+        if let Some(focus) = self.prev_focus.take() {
+            focus.grab_focus();
+        }
+    }
+
+    /// Gets the ex commands from nvim, and adds them to the list.
+    fn fetch_commands(self: &Rc<Self>) {
+        let weak = Rc::downgrade(self);
+        let nvim = self.nvim.clone();
+        spawn_local(async move {
+            let res = nvim
+                .call_function(
+                    "getcompletion",
+                    vec!["".into(), "command".into()],
+                )
+                .await;
+            let commands = match res {
+                Ok(commands) => commands
+                    .as_array()
+                    .map(|commands| {
+                        commands
+                            .iter()
+                            .filter_map(|c| c.as_str().map(String::from))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default(),
+                Err(err) => {
+                    error!("Failed to get commands: {}", err);
+                    return;
+                }
+            };
+
+            let palette = upgrade_weak!(weak);
+            palette
+                .items
+                .borrow_mut()
+                .splice(0..0, commands.iter().cloned().map(Item::Command));
+            palette.commands.replace(Some(commands));
+
+            if palette.frame.is_visible() {
+                palette.filter();
+            }
+        });
+    }
+
+    /// Updates the list to the items matching the entry.
+    fn filter(&self) {
+        for row in self.list.children() {
+            self.list.remove(&row);
+        }
+
+        let query = self.entry.text();
+        let items = self.items.borrow();
+        let mut matches = items
+            .iter()
+            .filter_map(|item| {
+                fuzzy_score(&query, &item.label()).map(|score| (score, item))
+            })
+            .collect::<Vec<_>>();
+        // Stable, so the equal ones keep their order.
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        let matches = matches
+            .into_iter()
+            .take(MAX_ITEMS)
+            .map(|(_, item)| item.clone())
+            .collect::<Vec<_>>();
+
+        for item in matches.iter() {
+            let label = gtk::Label::new(Some(&item.label()));
+            label.set_xalign(0.0);
+            label.set_ellipsize(pango::EllipsizeMode::Start);
+
+            let kind = gtk::Label::new(Some(item.kind()));
+            kind.style_context().add_class("dim-label");
+
+            let box_ = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            box_.set_margin_start(6);
+            box_.set_margin_end(6);
+            box_.pack_start(&label, true, true, 0);
+            box_.pack_end(&kind, false, false, 0);
+
+            self.list.add(&box_);
+        }
+
+        self.list.show_all();
+        self.matches.replace(matches);
+        self.select(0);
+    }
+
+    fn select(&self, index: i32) {
+        if let Some(row) = self.list.row_at_index(index) {
+            self.list.select_row(Some(&row));
+
+            // Scroll the row into view.
+            let alloc = row.allocation();
+            self.scrolled.vadjustment().clamp_page(
+                f64::from(alloc.y),
+                f64::from(alloc.y + alloc.height),
+            );
+        }
+    }
+
+    /// Activates the item on row `index`. If the entry's text starts with
+    /// `:`, it is run as an ex command instead.
+    fn activate(&self, index: i32) {
+        let text = self.entry.text();
+        let item = match text.as_str().strip_prefix(':') {
+            Some(cmd) => Some(Item::Command(cmd.to_string())),
+            None => self.matches.borrow().get(index as usize).cloned(),
+        };
+        let item = match item {
+            Some(item) => item,
+            None => return,
+        };
+
+        self.hide();
+
+        if let Item::Action(ref name) = item {
+            if let Some(app) = self.window.application() {
+                app.activate_action(name, None);
+            }
+            return;
+        }
+
+        let nvim = self.nvim.clone();
+        spawn_local(async move {
+            let res = match item {
+                Item::Command(cmd) => nvim.command(&cmd).await,
+                Item::File(path) => {
+                    let file = FileArg {
+                        path,
+                        line: None,
+                        col: None,
+                    };
+                    files::open_file(&nvim, &file).await
+                }
+                Item::Action(_) => Ok(()),
+            };
+
+            if let Err(err) = res {
+                error!("Failed to run palette item: {}", err);
+            }
+        });
+    }
+}
+
+/// Label for action `name`, e.g. "Zoom in" for `zoom-in`.
+fn action_label(name: &str) -> String {
+    let name = name.replace('-', " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// Scores how well `text` matches `query`. All of the query's characters
+/// must be found in order, ignoring case. Consecutive characters and
+/// characters at the start of words score higher, and shorter texts win
+/// ties. Returns `None` if there's no match.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut text_chars = text.chars().enumerate().peekable();
+    let mut prev_match: Option<usize> = None;
+    let mut prev_char: Option<char> = None;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        loop {
+            let (i, c) = text_chars.next()?;
+            let before = prev_char.replace(c);
+            if !c.to_lowercase().eq(std::iter::once(q)) {
+                continue;
+            }
+
+            score += 1;
+            if prev_match.map(|p| p + 1 == i).unwrap_or(false) {
+                score += 5;
+            }
+            let word_start = match before {
+                None => true,
+                Some(b) => {
+                    !b.is_alphanumeric()
+                        || (b.is_lowercase() && c.is_uppercase())
+                }
+            };
+            if word_start {
+                score += 8;
+            }
+
+            prev_match = Some(i);
+            break;
+        }
+    }
+
+    Some(score * 100 - text.chars().count() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("", "write").is_some());
+        assert_eq!(fuzzy_score("wq", "write"), None);
+        assert_eq!(fuzzy_score("xyz", "write"), None);
+
+        // Consecutive matches win.
+        assert!(fuzzy_score("wri", "write") > fuzzy_score("wri", "wXrXi"));
+        // Word starts win.
+        assert!(fuzzy_score("zi", "Zoom in") > fuzzy_score("zi", "Zzzxiz"));
+        // Case doesn't matter.
+        assert!(fuzzy_score("NEW", "new-tab").is_some());
+        // Shorter wins ties.
+        assert!(fuzzy_score("w", "w") > fuzzy_score("w", "write"));
+    }
+
+    #[test]
+    fn test_action_label() {
+        assert_eq!(action_label("zoom-in"), "Zoom in");
+        assert_eq!(action_label("open-recent"), "Open recent");
+        assert_eq!(action_label(""), "");
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use gtk::{gdk_pixbuf, gio, glib};
+
+use crate::nvim_bridge::{CompletionItem, CompletionItemKind};
+use crate::ui::color::Color;
+
+macro_rules! icon {
+    ($file:expr, $color:expr, $size:expr) => {
+        format!(include_str!($file), $size, $size, $color,)
+    };
+}
+
+/// Cache for the `kind` icons, so we don't need to create an icon for each
+/// completion item.
+#[derive(Default)]
+pub struct Icons {
+    /// Icon color for normal items.
+    fg: Color,
+    /// Icon color for the selected item.
+    fg_sel: Color,
+    size: f64,
+
+    cache: HashMap<(CompletionItemKind, bool), gdk_pixbuf::Pixbuf>,
+}
+
+impl Icons {
+    pub fn set_colors(&mut self, fg: Color, fg_sel: Color) {
+        self.fg = fg;
+        self.fg_sel = fg_sel;
+        self.cache.clear();
+    }
+
+    pub fn set_size(&mut self, size: f64) {
+        self.size = size;
+        self.cache.clear();
+    }
+
+    /// Returns the icon for `kind`.
+    pub fn get(
+        &mut self,
+        kind: &CompletionItemKind,
+        selected: bool,
+    ) -> gdk_pixbuf::Pixbuf {
+        let color = if selected { &self.fg_sel } else { &self.fg };
+        let size = self.size;
+        self.cache
+            .entry((kind.clone(), selected))
+            .or_insert_with(|| get_icon_pixbuf(kind, color, size))
+            .clone()
+    }
+}
+
+/// Returns markup for the completion item's row. `show_info` adds the first
+/// line of the item's `info` below the word.
+pub fn item_markup(
+    item: &CompletionItem,
+    base: &str,
+    match_fg: Option<&Color>,
+    show_info: bool,
+) -> String {
+    let mut markup = word_markup(&item.word, base, match_fg);
+
+    if show_info && !item.info.is_empty() {
+        markup.push('\n');
+        markup.push_str(&glib::markup_escape_text(&shorten_info(&item.info)));
+    }
+
+    markup
+}
+
+/// Returns markup for `word`, where the characters matching `base` are
+/// highlighted.
+fn word_markup(word: &str, base: &str, match_fg: Option<&Color>) -> String {
+    let matches = fuzzy_match(word, base).unwrap_or_default();
+
+    let mut markup = String::new();
+    for (i, c) in word.chars().enumerate() {
+        let text = glib::markup_escape_text(&c.to_string());
+        if matches.contains(&i) {
+            let fg = match_fg
+                .map(|fg| format!(" foreground=\"#{}\"", fg.as_hex()))
+                .unwrap_or_default();
+            markup.push_str(&format!(
+                "<span weight=\"bold\"{}>{}</span>",
+                fg, text
+            ));
+        } else {
+            markup.push_str(&text);
+        }
+    }
+
+    markup
+}
+
+/// Returns (char) indices of the characters in `word` that match `base`,
+/// ignoring case. `None` is returned if `base` isn't a subsequence of `word`.
+fn fuzzy_match(word: &str, base: &str) -> Option<Vec<usize>> {
+    let mut word = word.chars().enumerate();
+
+    base.chars()
+        .map(|b| {
+            word.find(|(_, c)| c.to_lowercase().eq(b.to_lowercase()))
+                .map(|(i, _)| i)
+        })
+        .collect()
+}
+
+/// Returns first line of `info`.
+fn shorten_info(info: &str) -> String {
+    let lines = info.split('\n').collect::<Vec<&str>>();
+    let first_line = lines.get(0).unwrap();
+    first_line.to_string()
+}
+
+fn get_icon_pixbuf(
+    kind: &CompletionItemKind,
+    color: &Color,
+    size: f64,
+) -> gdk_pixbuf::Pixbuf {
+    let contents = get_icon_name_for_kind(kind, color, size);
+    let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from(
+        contents.as_bytes(),
+    ));
+
+    gdk_pixbuf::Pixbuf::from_stream(&stream, None::<&gio::Cancellable>).unwrap()
+}
+
+fn get_icon_name_for_kind(
+    kind: &CompletionItemKind,
+    color: &Color,
+    size: f64,
+) -> String {
+    let color = color.as_hex();
+
+    let size = size * 1.1;
+
+    use self::CompletionItemKind::*;
+    match kind {
+        Constructor => icon!("../../../assets/icons/box.svg", color, size),
+        Method => icon!("../../../assets/icons/box.svg", color, size),
+        Function => icon!("../../../assets/icons/box.svg", color, size),
+        Field => icon!("../../../assets/icons/chevrons-right.svg", color, size),
+        Event => icon!("../../../assets/icons/zap.svg", color, size),
+        Operator => icon!("../../../assets/icons/sliders.svg", color, size),
+        Variable => icon!("../../../assets/icons/disc.svg", color, size),
+        Class => icon!("../../../assets/icons/share-2.svg", color, size),
+        Interface => icon!("../../../assets/icons/book-open.svg", color, size),
+        Struct => icon!("../../../assets/icons/align-left.svg", color, size),
+        TypeParameter => icon!("../../../assets/icons/type.svg", color, size),
+        Module => icon!("../../../assets/icons/code.svg", color, size),
+        Property => icon!("../../../assets/icons/key.svg", color, size),
+        Unit => icon!("../../../assets/icons/compass.svg", color, size),
+        Constant => icon!("../../../assets/icons/shield.svg", color, size),
+        Value => icon!("../../../assets/icons/database.svg", color, size),
+        Enum => icon!("../../../assets/icons/database.svg", color, size),
+        EnumMember => icon!("../../../assets/icons/tag.svg", color, size),
+        Keyword => icon!("../../../assets/icons/link-2.svg", color, size),
+        Text => icon!("../../../assets/icons/at-sign.svg", color, size),
+        Color => icon!("../../../assets/icons/aperture.svg", color, size),
+        File => icon!("../../../assets/icons/file.svg", color, size),
+        Reference => icon!("../../../assets/icons/link.svg", color, size),
+        Snippet => icon!("../../../assets/icons/file-text.svg", color, size),
+        Folder => icon!("../../../assets/icons/folder.svg", color, size),
+
+        _ => icon!("../../../assets/icons/help-circle.svg", color, size),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("foobar", "fob"), Some(vec![0, 1, 3]));
+        assert_eq!(fuzzy_match("FooBar", "fb"), Some(vec![0, 3]));
+        assert_eq!(fuzzy_match("äbc", "bc"), Some(vec![1, 2]));
+        assert_eq!(fuzzy_match("foo", ""), Some(vec![]));
+        assert_eq!(fuzzy_match("foo", "fx"), None);
+    }
+
+    #[test]
+    fn test_word_markup() {
+        let fg = Color::from_hex_string(String::from("#ff0000")).unwrap();
+        assert_eq!(
+            word_markup("a<b", "ab", Some(&fg)),
+            "<span weight=\"bold\" foreground=\"#ff0000\">a</span>&lt;\
+             <span weight=\"bold\" foreground=\"#ff0000\">b</span>"
+        );
+        assert_eq!(word_markup("ab", "x", None), "ab");
+    }
+
+    #[test]
+    fn test_item_markup() {
+        let item = CompletionItem {
+            word: String::from("foo"),
+            kind: CompletionItemKind::Unknown,
+            kind_raw: String::new(),
+            menu: String::new(),
+            info: String::from("fn foo() -> &str\nDocs"),
+        };
+
+        assert_eq!(
+            item_markup(&item, "o", None, true),
+            "f<span weight=\"bold\">o</span>o\nfn foo() -&gt; &amp;str"
+        );
+        assert_eq!(
+            item_markup(&item, "o", None, false),
+            "f<span weight=\"bold\">o</span>o"
+        );
+    }
+}
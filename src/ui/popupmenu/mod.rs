@@ -1,9 +1,6 @@
-mod completion_item_widget;
-mod lazy_loader;
+mod completion_item;
 #[allow(clippy::module_inception)]
 mod popupmenu;
 
-use self::completion_item_widget::get_icon_pixbuf;
-use self::completion_item_widget::CompletionItemWidgetWrap;
-use self::lazy_loader::LazyLoader;
+use self::completion_item::{item_markup, Icons};
 pub use self::popupmenu::Popupmenu;
@@ -2,9 +2,10 @@ use std::cell::RefCell;
 use std::rc::Rc;
 
 use gtk::prelude::*;
-use gtk::{gdk, glib, pango};
+use gtk::{gdk, gdk_pixbuf, glib, pango};
 use log::error;
 
+use crate::config::PopupmenuConfig;
 use crate::nvim_bridge::CompletionItem;
 use crate::nvim_gio::GioNeovim;
 use crate::ui::color::{Highlight, HlDefs, HlGroup};
@@ -14,8 +15,13 @@ use crate::ui::common::{
 };
 use crate::ui::font::{Font, FontUnit};
 use crate::ui::grid::GridMetrics;
-use crate::ui::popupmenu::get_icon_pixbuf;
-use crate::ui::popupmenu::LazyLoader;
+use crate::ui::popupmenu::{item_markup, Icons};
+
+/// Columns of the list store.
+const COLUMN_ICON: u32 = 0;
+const COLUMN_MARKUP: u32 = 1;
+const COLUMN_MENU: u32 = 2;
+const COLUMN_MENU_VISIBLE: u32 = 3;
 
 /// Maximum height of completion menu.
 const MAX_HEIGHT: i32 = 500;
@@ -27,6 +33,7 @@ const DEFAULT_WIDTH_WITH_DETAILS: i32 = 660;
 pub struct PmenuColors {
     pub hl: Highlight,
     pub hl_sel: Highlight,
+    pub hl_match: Highlight,
 }
 
 struct State {
@@ -42,6 +49,9 @@ struct State {
     current_width: i32,
 
     width_no_details: i32,
+    /// Minimum width when the details view is not open. When `None`, the
+    /// width is always `width_no_details`.
+    min_width: Option<i32>,
     width_with_details: i32,
 }
 
@@ -60,6 +70,7 @@ impl State {
 
             current_width: DEFAULT_WIDTH_NO_DETAILS,
             width_no_details: DEFAULT_WIDTH_NO_DETAILS,
+            min_width: None,
             width_with_details: DEFAULT_WIDTH_WITH_DETAILS,
         }
     }
@@ -75,8 +86,17 @@ pub struct Popupmenu {
     scrolled_list: gtk::ScrolledWindow,
     /// Scrolled window that contains the info label for full info view.
     scrolled_info: gtk::ScrolledWindow,
-    /// List box that contains all the completion items.
-    list: gtk::ListBox,
+    /// Tree view that displays the completion items. Only the visible rows
+    /// are rendered, so huge completion sets don't stall the UI.
+    list: gtk::TreeView,
+    /// Model of the `list`.
+    store: gtk::ListStore,
+    /// The `list`'s only column.
+    column: gtk::TreeViewColumn,
+    /// Renderers of the `list` (icon, word and menu).
+    cell_icon: gtk::CellRendererPixbuf,
+    cell_word: gtk::CellRendererText,
+    cell_menu: gtk::CellRendererText,
     /// Style provider for all internal widgets.
     css_provider: gtk::CssProvider,
 
@@ -86,9 +106,20 @@ pub struct Popupmenu {
     info_label: gtk::Label,
     /// Flag telling if the menu label should be shown on inactive items too.
     show_menu_on_all_items: bool,
+    /// Flag telling if the menu label should be shown at all.
+    show_menu: bool,
+    /// Maximum number of visible items. When `None`, the list is limited by
+    /// `MAX_HEIGHT`.
+    max_items: Option<i32>,
 
     state: Rc<RefCell<State>>,
-    items: LazyLoader,
+    /// Current completion items.
+    items: Vec<CompletionItem>,
+    /// The word being completed.
+    base: String,
+    /// If the items have `kind` icons.
+    show_kind: bool,
+    icons: Icons,
 
     /// Our colors.
     colors: PmenuColors,
@@ -131,9 +162,36 @@ impl Popupmenu {
         scrolled_info
             .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
 
-        let list = gtk::ListBox::new();
-        list.set_valign(gtk::Align::Start);
-        list.set_selection_mode(gtk::SelectionMode::Single);
+        let store = gtk::ListStore::new(&[
+            gdk_pixbuf::Pixbuf::static_type(),
+            String::static_type(),
+            String::static_type(),
+            bool::static_type(),
+        ]);
+
+        let cell_icon = gtk::CellRendererPixbuf::new();
+        let cell_word = gtk::CellRendererText::new();
+        cell_word.set_ellipsize(pango::EllipsizeMode::End);
+        let cell_menu = gtk::CellRendererText::new();
+        cell_menu.set_ellipsize(pango::EllipsizeMode::End);
+        cell_menu.set_max_width_chars(30);
+        cell_menu.set_xalign(1.0);
+
+        let column = gtk::TreeViewColumn::new();
+        column.pack_start(&cell_icon, false);
+        column.add_attribute(&cell_icon, "pixbuf", COLUMN_ICON as i32);
+        column.pack_start(&cell_word, true);
+        column.add_attribute(&cell_word, "markup", COLUMN_MARKUP as i32);
+        column.pack_end(&cell_menu, false);
+        column.add_attribute(&cell_menu, "text", COLUMN_MENU as i32);
+        column.add_attribute(&cell_menu, "visible", COLUMN_MENU_VISIBLE as i32);
+
+        let list = gtk::TreeView::with_model(&store);
+        list.append_column(&column);
+        list.set_headers_visible(false);
+        list.set_enable_search(false);
+        list.set_activate_on_single_click(true);
+        list.selection().set_mode(gtk::SelectionMode::Single);
 
         let scrolled_list = gtk::ScrolledWindow::new(
             None::<&gtk::Adjustment>,
@@ -142,6 +200,8 @@ impl Popupmenu {
         scrolled_list.add(&list);
         scrolled_list
             .set_policy(gtk::PolicyType::Never, gtk::PolicyType::Automatic);
+        scrolled_list.set_valign(gtk::Align::Start);
+        scrolled_list.set_widget_name("list");
 
         let box_ = gtk::Box::new(gtk::Orientation::Horizontal, 0);
         box_.pack_start(&scrolled_list, true, true, 0);
@@ -155,21 +215,19 @@ impl Popupmenu {
             scrolled_info,
             list,
             scrolled_list,
-            box_,
-            // In gtk 3.18, the list box it self can't have borders,
-            // so we'll have to add the border to its parent (which is the
-            // viewport that scorlled window adds). This aint perfect,
-            // but I didn't any find better solutions.
-            scrolled_list.child().unwrap()
+            box_
         );
 
         let state = Rc::new(RefCell::new(State::new()));
 
         // When a row is activated (by mouse click), notify neovim to change
         // the selection to the activated row.
-        list.connect_row_activated(clone!(nvim, state => move |_, row| {
+        list.connect_row_activated(clone!(nvim, state => move |_, path, _| {
             let state = state.borrow_mut();
-            let new = row.index();
+            let new = match path.indices().first() {
+                Some(new) => *new,
+                None => return,
+            };
 
             let selected = state.selected;
 
@@ -219,22 +277,40 @@ impl Popupmenu {
         }));
 
         let layout_weak = layout.downgrade();
-        box_.connect_size_allocate(clone!(state, nvim, layout_weak, scrolled_info, scrolled_list => move |box_, alloc| {
+        box_.connect_size_allocate(clone!(state, nvim, layout_weak, scrolled_info, scrolled_list => move |box_, _| {
             let layout = upgrade_weak!(layout_weak);
             let state = state.borrow();
 
             if let Some(area) = state.available_size {
                 let pos = state.anchor;
 
+                // Place the popupmenu based on the size of its content, so
+                // it's only moved above the anchor if the content doesn't fit
+                // below it. The details view uses all the space it can get.
+                let (content_width, content_height) =
+                    if scrolled_info.is_visible() {
+                        (state.current_width, MAX_HEIGHT)
+                    } else {
+                        let width = match state.min_width {
+                            Some(min) => scrolled_list
+                                .preferred_width()
+                                .1
+                                .max(min)
+                                .min(state.current_width),
+                            None => state.current_width,
+                        };
+                        (width, scrolled_list.preferred_height().1)
+                    };
+
                 let (x, width) = get_preferred_horizontal_position(
                     &area,
                     &pos,
-                    state.current_width,
+                    content_width,
                 );
                 let (y, height) = get_preferred_vertical_position(
                     &area,
                     &pos,
-                    alloc.height.min(MAX_HEIGHT),
+                    content_height,
                 );
 
                 layout.move_(box_, x, y);
@@ -259,22 +335,16 @@ impl Popupmenu {
                 // sure our contents are aligned to the bottom so there is not
                 // cap between the achor and the content it self.
                 if y < pos.y {
+                    scrolled_list.set_valign(gtk::Align::End);
                     // Use get_child to get the viewport which is between
                     // the scrolled window and the actual widget that is
                     // inside it.
-                    scrolled_list
-                        .child()
-                        .unwrap()
-                        .set_valign(gtk::Align::End);
                     scrolled_info
                         .child()
                         .unwrap()
                         .set_valign(gtk::Align::End);
                 } else {
-                    scrolled_list
-                        .child()
-                        .unwrap()
-                        .set_valign(gtk::Align::Start);
+                    scrolled_list.set_valign(gtk::Align::Start);
                     scrolled_info
                         .child()
                         .unwrap()
@@ -289,12 +359,22 @@ impl Popupmenu {
         layout.hide();
 
         Popupmenu {
-            items: LazyLoader::new(list.clone(), css_provider.clone()),
+            items: vec![],
+            base: String::new(),
+            show_kind: false,
+            icons: Icons::default(),
             show_menu_on_all_items: false,
+            show_menu: true,
+            max_items: None,
             box_,
             layout,
             css_provider,
             list,
+            store,
+            column,
+            cell_icon,
+            cell_word,
+            cell_menu,
             scrolled_list,
             scrolled_info,
             info_label,
@@ -308,11 +388,57 @@ impl Popupmenu {
 
     pub fn set_show_menu_on_all_items(&mut self, b: bool) {
         self.show_menu_on_all_items = b;
+        self.update_rows();
+    }
+
+    /// Sets if the `menu` of the items is shown at all.
+    pub fn set_show_menu(&mut self, b: bool) {
+        self.show_menu = b;
+        self.update_rows();
+    }
+
+    /// Sets the maximum number of visible items. Zero removes the limit
+    /// (other than the maximum height).
+    pub fn set_max_items(&mut self, n: i32) {
+        self.max_items = if n > 0 { Some(n) } else { None };
+        self.update_list_height();
+    }
+
+    /// Sets the minimum width when the details view is not open. Zero makes
+    /// the width fixed (see `set_width`).
+    pub fn set_min_width(&mut self, w: i32) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.min_width = if w > 0 { Some(w) } else { None };
+        }
+        self.box_.queue_resize();
+    }
+
+    /// Applies the popupmenu's settings from the config file.
+    pub fn configure(&mut self, config: &PopupmenuConfig) {
+        if let Some(w) = config.width {
+            self.set_width(w);
+        }
+        if let Some(w) = config.width_details {
+            self.set_width_details(w);
+        }
+        if let Some(w) = config.min_width {
+            self.set_min_width(w);
+        }
+        if let Some(n) = config.max_items {
+            self.set_max_items(n);
+        }
+        if let Some(b) = config.show_menu {
+            self.set_show_menu(b);
+        }
+        if let Some(b) = config.show_menu_on_all_items {
+            self.set_show_menu_on_all_items(b);
+        }
     }
 
     #[allow(unused)]
     pub fn is_above_anchor(&self) -> bool {
-        self.scrolled_list.child().unwrap().valign() == gtk::Align::End
+        self.scrolled_list.valign() == gtk::Align::End
     }
 
     pub fn set_base_metrics(&self, metrics: GridMetrics) {
@@ -321,41 +447,27 @@ impl Popupmenu {
     }
 
     pub fn toggle_show_info(&mut self) {
-        {
-            let state = self.state.borrow();
-
-            self.info_shown = !self.info_shown;
-
-            let selected = state.selected as usize;
-            let info_shown = self.info_shown;
-            let info_label = self.info_label.clone();
-            self.items.once_loaded(Some(state.selected), move |items| {
-                if let Some(item) = items.get(selected) {
-                    item.info.set_visible(!info_shown);
-                    item.menu.set_visible(!info_shown);
-
-                    if item.item.info.is_empty() {
-                        item.info.set_visible(false);
-                    }
-
-                    info_label.set_visible(
-                        info_shown
-                            && item.item.menu.len() + item.item.info.len() > 0,
-                    );
-                }
-            });
-
-            if !self.info_shown {
-                let adj = self.scrolled_info.vadjustment();
-                adj.set_value(0.0);
-                // TODO(ville): There is a bug in GTK+ and some adjustment animations,
-                //              where the adjustment's value is set back to upper - page-size
-                //              if the user has "overshot" the scrolling. Work around this.
-            }
+        self.info_shown = !self.info_shown;
 
-            self.scrolled_info.set_visible(self.info_shown);
+        let selected = self.state.borrow().selected;
+        if let Some(item) = self.items.get(selected as usize) {
+            self.info_label.set_visible(
+                self.info_shown && item.menu.len() + item.info.len() > 0,
+            );
+        }
+        self.set_row(selected, true);
+        self.update_list_height();
+
+        if !self.info_shown {
+            let adj = self.scrolled_info.vadjustment();
+            adj.set_value(0.0);
+            // TODO(ville): There is a bug in GTK+ and some adjustment animations,
+            //              where the adjustment's value is set back to upper - page-size
+            //              if the user has "overshot" the scrolling. Work around this.
         }
 
+        self.scrolled_info.set_visible(self.info_shown);
+
         self.ensure_container_width();
     }
 
@@ -387,6 +499,12 @@ impl Popupmenu {
         self.ensure_container_width();
     }
 
+    /// Sets the popupmenu's transparency (see `:h 'pumblend'`).
+    pub fn set_blend(&self, blend: i64) {
+        let opacity = 1.0 - blend.clamp(0, 100) as f64 / 100.0;
+        self.box_.set_opacity(opacity);
+    }
+
     /// Hides the popupmenu.
     pub fn hide(&mut self) {
         self.layout.hide();
@@ -405,121 +523,188 @@ impl Popupmenu {
         state.anchor = rect;
     }
 
-    pub fn set_items(&mut self, items: Vec<CompletionItem>, hl_defs: &HlDefs) {
-        self.items.set_items(
-            items,
-            self.colors.hl.foreground.unwrap_or(hl_defs.default_fg),
-            self.font.height as f64,
-            self.show_menu_on_all_items,
-        );
+    /// Sets the completion items. Characters of the items matching `base`
+    /// (the word being completed) are highlighted.
+    pub fn set_items(&mut self, items: Vec<CompletionItem>, base: &str) {
+        self.state.borrow_mut().selected = -1;
+        self.base = base.to_string();
+        self.show_kind = items.iter().any(|item| !item.kind.is_unknown());
+        self.cell_icon.set_visible(self.show_kind);
+
+        let match_fg = self.colors.hl_match.foreground;
+
+        // Detach the model while filling it, so the list doesn't have to
+        // handle each insert separately.
+        self.list.set_model(None::<&gtk::ListStore>);
+        self.store.clear();
+        for item in items.iter() {
+            let icon = if self.show_kind {
+                Some(self.icons.get(&item.kind, false))
+            } else {
+                None
+            };
+
+            self.store.insert_with_values(
+                None,
+                &[
+                    (COLUMN_ICON, &icon),
+                    (
+                        COLUMN_MARKUP,
+                        &item_markup(item, base, match_fg.as_ref(), false),
+                    ),
+                    (COLUMN_MENU, &item.menu),
+                    (
+                        COLUMN_MENU_VISIBLE,
+                        &(self.show_menu && self.show_menu_on_all_items),
+                    ),
+                ],
+            );
+        }
+        self.list.set_model(Some(&self.store));
 
-        self.list.show_all();
+        self.items = items;
+        self.update_list_height();
     }
 
-    pub fn select(&mut self, item_num: i32, hl_defs: &HlDefs) {
-        let state = self.state.clone();
-        let scrolled_list = self.scrolled_list.clone();
-        let fg = self.colors.hl.foreground.unwrap_or(hl_defs.default_fg);
-        let fg_sel =
-            self.colors.hl_sel.foreground.unwrap_or(hl_defs.default_fg);
-        let font_height = self.font.height as f64;
-        let list = self.list.clone();
-        let info_label = self.info_label.clone();
-        let info_shown = self.info_shown;
-        let show_kind = self.items.get_show_kind();
-
-        let show_menu_on_all_items = self.show_menu_on_all_items;
-
-        self.items.once_loaded(Some(item_num), move |items| {
-            let mut state = state.borrow_mut();
+    pub fn select(&mut self, item_num: i32) {
+        let prev = self.state.borrow().selected;
+        self.set_row(prev, false);
 
-            if let Some(prev) = items.get(state.selected as usize) {
-                prev.info.set_visible(false);
-                prev.menu.set_visible(show_menu_on_all_items);
+        self.state.borrow_mut().selected = item_num;
 
-                if show_kind {
-                    // Update the `kind` icon with default fg color.
-                    let buf =
-                        get_icon_pixbuf(&prev.item.kind, &fg, font_height);
-                    prev.image.set_from_pixbuf(Some(&buf));
-                }
-            }
+        if item_num < 0 {
+            self.list.selection().unselect_all();
+            self.info_label.set_text("");
+            self.info_label.hide();
 
-            state.selected = item_num;
+            // If selection is removed, move the scrolled window to the top.
+            let adj = self.scrolled_list.vadjustment();
+            glib::idle_add_local(move || {
+                adj.set_value(0.0);
+                Continue(false)
+            });
 
-            if item_num < 0 {
-                list.unselect_all();
-                info_label.set_text("");
-                info_label.hide();
+            self.update_list_height();
+            return;
+        }
 
-                // If selection is removed, move the scrolled window to the top.
-                let adj = scrolled_list.vadjustment();
-                glib::idle_add_local(move || {
-                    adj.set_value(0.0);
-                    Continue(false)
-                });
+        self.set_row(item_num, true);
+
+        if let Some(item) = self.items.get(item_num as usize) {
+            let path = gtk::TreePath::from_indicesv(&[item_num]);
+            self.list.selection().select_path(&path);
+            self.list.scroll_to_cell(
+                Some(&path),
+                None::<&gtk::TreeViewColumn>,
+                false,
+                0.0,
+                0.0,
+            );
+
+            let newline = if !item.menu.is_empty() && !item.info.is_empty() {
+                "\n"
+            } else {
+                ""
+            };
+
+            self.info_label
+                .set_text(&format!("{}{}{}", item.menu, newline, item.info));
+
+            let has_info_content = item.menu.len() + item.info.len() > 0;
+            self.info_label
+                .set_visible(self.info_shown && has_info_content);
+        }
 
-                return;
-            }
+        self.update_list_height();
+    }
 
-            if let Some(item) = items.get(state.selected as usize) {
-                item.info.set_visible(!info_shown);
-                item.menu.set_visible(!info_shown);
+    /// Updates the row `i` of the list for its selection state. The selected
+    /// row shows the `menu` and the first line of `info` (unless they're
+    /// shown in the info label), and its `kind` icon is colored differently.
+    fn set_row(&mut self, i: i32, selected: bool) {
+        let item = match self.items.get(i as usize) {
+            Some(item) if i >= 0 => item,
+            _ => return,
+        };
+        let iter = match self.store.iter_nth_child(None, i) {
+            Some(iter) => iter,
+            None => return,
+        };
 
-                if item.item.info.is_empty() {
-                    item.info.set_visible(false);
-                }
+        let show_details = selected && !self.info_shown;
+        let markup = item_markup(
+            item,
+            &self.base,
+            self.colors.hl_match.foreground.as_ref(),
+            show_details,
+        );
+        let menu_visible =
+            self.show_menu && (show_details || self.show_menu_on_all_items);
+
+        self.store.set(
+            &iter,
+            &[
+                (COLUMN_MARKUP, &markup),
+                (COLUMN_MENU_VISIBLE, &menu_visible),
+            ],
+        );
 
-                item.row.grab_focus();
-                list.select_row(Some(&item.row));
-
-                {
-                    let id = Rc::new(RefCell::new(None));
-                    // Ensure that the row is in the view, but make sure first
-                    // that the row it self has allocated itself. It is possible
-                    // that when we selected the row and grabbed focus for it
-                    // the row it self isn't "ready" to grab focus yet. Hence
-                    // this signal handler here to ensure the row is in view.
-                    // NOTE(ville): According to some IRC discussions, this
-                    // hack wont work on GTK4. Prepare yourself!
-                    let list_weak = list.downgrade();
-                    let sig_id = item.row.connect_size_allocate(
-                        clone!(id, list_weak => move |row, _| {
-                            let list = upgrade_weak!(list_weak);
-                            ensure_row_visible(&list, row);
-
-                            let id = id.borrow_mut().take().unwrap();
-                            row.disconnect(id);
-                        }),
-                    );
-                    *id.borrow_mut() = Some(sig_id);
-                }
+        if self.show_kind {
+            let icon = self.icons.get(&item.kind, selected);
+            self.store.set(&iter, &[(COLUMN_ICON, &icon)]);
+        }
+    }
 
-                if show_kind {
-                    // Update the `kind` icon with "selected" fg color.
-                    let buf =
-                        get_icon_pixbuf(&item.item.kind, &fg_sel, font_height);
-                    item.image.set_from_pixbuf(Some(&buf));
-                }
+    /// Updates all the rows, after the settings have changed.
+    fn update_rows(&mut self) {
+        let selected = self.state.borrow().selected;
+        for i in 0..self.items.len() as i32 {
+            self.set_row(i, i == selected);
+        }
+        self.update_list_height();
+    }
 
-                let newline = if !item.item.menu.is_empty()
-                    && !item.item.info.is_empty()
-                {
-                    "\n"
-                } else {
-                    ""
-                };
+    /// Sets the list's height to fit its rows, up to `max_items` rows or
+    /// `MAX_HEIGHT`. All rows have the same height, except the selected one,
+    /// so only those two are measured.
+    fn update_list_height(&self) {
+        let separator = self
+            .list
+            .style_get_property("vertical-separator")
+            .get::<i32>()
+            .unwrap_or(0);
+        let row_height = |i: i32| {
+            self.store
+                .iter_nth_child(None, i)
+                .map(|iter| {
+                    self.column.cell_set_cell_data(
+                        &self.store,
+                        &iter,
+                        false,
+                        false,
+                    );
+                    self.column.cell_get_size(None).3 + separator
+                })
+                .unwrap_or(0)
+        };
 
-                info_label.set_text(&format!(
-                    "{}{}{}",
-                    item.item.menu, newline, item.item.info
-                ));
+        let count = self.items.len() as i32;
+        let selected = self.state.borrow().selected;
+        let (normal, extra) = if (0..count).contains(&selected) {
+            let normal = row_height(if selected == 0 { 1 } else { 0 });
+            (normal, row_height(selected) - normal)
+        } else {
+            (row_height(0), 0)
+        };
 
-                let has_info_content =
-                    item.item.menu.len() + item.item.info.len() > 0;
-                info_label.set_visible(info_shown && has_info_content);
-            }
-        });
+        let height = normal * count + extra;
+        let max_height = match self.max_items {
+            Some(n) => normal * n + extra,
+            None => MAX_HEIGHT,
+        };
+
+        self.scrolled_list
+            .set_min_content_height(height.min(max_height));
     }
 
     pub fn set_colors(&mut self, hl_defs: &HlDefs) {
@@ -532,13 +717,23 @@ impl Popupmenu {
                 .get_hl_group(&HlGroup::PmenuSel)
                 .cloned()
                 .unwrap_or_default(),
+            hl_match: hl_defs
+                .get_hl_group(&HlGroup::PmenuMatch)
+                .cloned()
+                .unwrap_or_default(),
         };
+        self.icons.set_colors(
+            self.colors.hl.foreground.unwrap_or(hl_defs.default_fg),
+            self.colors.hl_sel.foreground.unwrap_or(hl_defs.default_fg),
+        );
         self.set_styles(hl_defs);
     }
 
     pub fn set_line_space(&mut self, space: i64, hl_defs: &HlDefs) {
         self.line_space = space;
         self.set_styles(hl_defs);
+        self.set_cell_padding();
+        self.update_list_height();
 
         // Set line space to the info_label with pango attrs.
         let attrs = pango::AttrList::new();
@@ -548,28 +743,33 @@ impl Popupmenu {
         self.info_label.set_attributes(Some(&attrs));
     }
 
-    fn set_styles(&self, hl_defs: &HlDefs) {
+    /// Sets the padding of the list's cells, based on the font size and line
+    /// space.
+    fn set_cell_padding(&self) {
         let (above, below) = calc_line_space(self.line_space);
+        let ypad = (above + below).max(0) / 2;
+        let margin = (self.font.height / 3.0) as i32;
+
+        self.cell_icon.set_padding(margin, ypad);
+        self.cell_word.set_padding(5, ypad);
+        self.cell_menu.set_padding(margin, ypad);
+    }
 
+    fn set_styles(&self, hl_defs: &HlDefs) {
         let css = format!(
             "{font_wild}
 
-            grid, list, row, label {{
+            treeview, label {{
                 color: #{normal_fg};
                 background-color: {normal_bg};
                 outline: none;
             }}
 
-            #info-label, list {{
+            #info-label, #list {{
                 border: 1px solid #{normal_fg};
             }}
 
-            row {{
-                padding-top: {above}px;
-                padding-bottom: {below}px;
-            }}
-
-            row:selected, row:selected > grid, row:selected > grid > label {{
+            treeview:selected {{
                 color: #{selected_fg};
                 background-color: {selected_bg};
             }}
@@ -596,8 +796,6 @@ impl Popupmenu {
                 .foreground
                 .unwrap_or(hl_defs.default_fg)
                 .as_hex(),
-            above = above.max(0),
-            below = below.max(0),
         );
         CssProviderExt::load_from_data(&self.css_provider, css.as_bytes())
             .unwrap();
@@ -605,16 +803,9 @@ impl Popupmenu {
 
     pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
         self.font = font;
+        self.icons.set_size(self.font.height as f64);
         self.set_styles(hl_defs);
-    }
-}
-
-fn ensure_row_visible(list: &gtk::ListBox, row: &gtk::ListBoxRow) {
-    if let Some(adj) = list.adjustment() {
-        let alloc = row.allocation();
-        let y = alloc.y;
-        let height = alloc.height;
-
-        adj.clamp_page(y.into(), (y + height).into());
+        self.set_cell_padding();
+        self.update_list_height();
     }
 }
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use gtk::glib;
+use gtk::prelude::*;
+
+use crate::sessions::Session;
+
+/// Shows a dialog listing `sessions`, and returns the path to the selected
+/// session's socket. Returns `None` if the dialog was canceled.
+pub async fn pick_session(sessions: &[Session]) -> Option<PathBuf> {
+    let dialog = gtk::Dialog::with_buttons(
+        Some("Attach to Neovim"),
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel),
+            ("Attach", gtk::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_size(500, 300);
+    dialog.set_default_response(gtk::ResponseType::Accept);
+
+    let list = gtk::ListBox::new();
+    list.set_activate_on_single_click(false);
+
+    if sessions.is_empty() {
+        let label = gtk::Label::new(Some("No running Neovim sessions found"));
+        label.set_margin(12);
+        list.set_placeholder(Some(&label));
+        dialog.set_response_sensitive(gtk::ResponseType::Accept, false);
+    }
+
+    for session in sessions.iter() {
+        let file = if session.file.is_empty() {
+            "[No Name]"
+        } else {
+            &session.file
+        };
+
+        let label = gtk::Label::new(None);
+        label.set_markup(&format!(
+            "<b>{}</b>\n<small>{}</small>",
+            glib::markup_escape_text(file),
+            glib::markup_escape_text(&format!(
+                "{} ({})",
+                session.cwd,
+                session.path.display()
+            )),
+        ));
+        label.set_halign(gtk::Align::Start);
+        label.set_margin(6);
+        list.add(&label);
+    }
+
+    if let Some(row) = list.row_at_index(0) {
+        list.select_row(Some(&row));
+    }
+
+    list.connect_row_activated(clone!(dialog => move |_, _| {
+        dialog.response(gtk::ResponseType::Accept);
+    }));
+
+    let scroll = gtk::ScrolledWindow::new(
+        None::<&gtk::Adjustment>,
+        None::<&gtk::Adjustment>,
+    );
+    scroll.add(&list);
+    dialog.content_area().pack_start(&scroll, true, true, 0);
+    dialog.show_all();
+
+    let res = dialog.run_future().await;
+    let selected = list.selected_row().map(|row| row.index());
+    dialog.close();
+
+    match (res, selected) {
+        (gtk::ResponseType::Accept, Some(index)) => {
+            sessions.get(index as usize).map(|s| s.path.clone())
+        }
+        _ => None,
+    }
+}
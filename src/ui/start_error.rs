@@ -0,0 +1,82 @@
+use gtk::prelude::*;
+
+/// Formats `cmd` for showing it to the user, quoting the arguments that
+/// have whitespace in them.
+fn format_cmd(cmd: &[String]) -> String {
+    cmd.iter()
+        .map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                format!("'{}'", arg.replace('\'', "'\\''"))
+            } else {
+                arg.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Shows a dialog telling that nvim failed to start, with the command that
+/// was tried and what nvim wrote to its stderr.
+pub async fn show_start_error(cmd: &[String], error: &str, stderr: &str) {
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Close,
+        "Failed to start Neovim",
+    );
+    dialog.set_secondary_text(Some(&format!(
+        "{}\n\nCommand: {}\n\nThe Neovim binary can be changed with the \
+         --nvim flag, the GNVIM_NVIM environment variable or the path key \
+         in the [nvim] section of the config file.",
+        error,
+        format_cmd(cmd)
+    )));
+
+    let stderr = stderr.trim();
+    if !stderr.is_empty() {
+        let view = gtk::TextView::new();
+        view.set_editable(false);
+        view.set_monospace(true);
+        view.buffer().unwrap().set_text(stderr);
+
+        let scroll = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scroll.set_size_request(500, 200);
+        scroll.add(&view);
+
+        let expander = gtk::Expander::new(Some("Neovim's output"));
+        expander.set_expanded(true);
+        expander.add(&scroll);
+        if let Ok(area) = dialog.message_area().downcast::<gtk::Box>() {
+            area.pack_start(&expander, true, true, 0);
+        }
+    }
+
+    dialog.show_all();
+    dialog.run_future().await;
+    dialog.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_cmd() {
+        let cmd = vec![
+            String::from("nvim"),
+            String::from("--embed"),
+            String::from("--cmd"),
+            String::from("let g:foo = 'bar'"),
+        ];
+
+        assert_eq!(
+            format_cmd(&cmd),
+            r"nvim --embed --cmd 'let g:foo = '\''bar'\'''"
+        );
+        assert_eq!(format_cmd(&[String::new()]), "''");
+    }
+}
@@ -1,40 +1,111 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use gtk::glib;
 use gtk::prelude::*;
+use gtk::traits::SettingsExt;
+use gtk::{cairo, gdk, gio, glib};
 
 use log::{debug, error, warn};
 use nvim_rs::{Tabpage, Window as NvimWindow};
 
+use crate::config::{FontsConfig, ZenConfig};
+use crate::dbus;
 use crate::error::Error;
+use crate::logger;
 use crate::nvim_bridge::{
     CmdlineBlockAppend, CmdlineBlockShow, CmdlinePos, CmdlineShow,
     CmdlineSpecialChar, DefaultColorsSet, GnvimEvent, GridCursorGoto,
     GridLineSegment, GridResize, GridScroll, HlAttrDefine, HlGroupSet,
     ModeChange, ModeInfo, ModeInfoSet, MsgSetPos, Notify, OptionSet,
     PopupmenuShow, RedrawEvent, TablineUpdate, WindowExternalPos,
-    WindowFloatPos, WindowPos,
+    WindowExtmark, WindowFloatPos, WindowPos,
 };
 use crate::nvim_gio::GioNeovim;
+use crate::project;
+use crate::restart;
 use crate::ui::cmdline::Cmdline;
 use crate::ui::color::{HlDefs, HlGroup};
 use crate::ui::common::spawn_local;
+use crate::ui::filetree::FileTree;
 use crate::ui::font::Font;
-use crate::ui::grid::{Grid, GridMetrics};
+use crate::ui::grid::{
+    CellScale, CellWidth, CursorOverlay, Grid, GridMetrics, RenderOptions,
+    ScrollAnimation,
+};
+use crate::ui::im_indicator::ImIndicator;
+use crate::ui::inspector::{GridInfo, Inspector, Report};
+use crate::ui::messages::Messages;
 use crate::ui::popupmenu::Popupmenu;
 use crate::ui::tabline::Tabline;
 use crate::ui::window::{MsgWindow, Window};
 
+/// Minimum vertical padding (in pixels) in the zen mode.
+const ZEN_PADDING: i32 = 32;
+/// Time spent redrawing the grids at once, when they are redrawn in chunks
+/// (e.g. after a colorscheme change).
+const REDRAW_CHUNK: Duration = Duration::from_millis(4);
+
 pub(crate) type Windows = HashMap<i64, Window>;
 pub(crate) type Grids = HashMap<i64, Grid>;
 
+/// State to restore when leaving the zen mode.
+pub(crate) struct Zen {
+    /// If the window was decorated.
+    decorated: bool,
+    /// If the window's menubar was shown.
+    show_menubar: bool,
+    /// 'guifont', if the font size was changed.
+    guifont: Option<String>,
+}
+
+/// Project whose settings (`.gnvim.toml`) are applied, and the settings
+/// to restore when leaving the project.
+pub(crate) struct Project {
+    /// Path of the project's settings file.
+    path: PathBuf,
+    /// 'guifont', if the font size was changed.
+    guifont: Option<String>,
+    /// Grid padding, if it was changed.
+    grid_padding: Option<i32>,
+    /// If the dark theme was preferred, if the theme was changed.
+    prefer_dark_theme: Option<bool>,
+}
+
 pub(crate) struct ResizeOptions {
     pub font: Font,
     pub line_space: i64,
 }
 
+/// Fonts of the UI components, that are used instead of the grid's font.
+#[derive(Default)]
+pub(crate) struct ComponentFonts {
+    pub cmdline: Option<Font>,
+    pub popupmenu: Option<Font>,
+    pub tabline: Option<Font>,
+}
+
+impl ComponentFonts {
+    pub fn new(config: &FontsConfig) -> Self {
+        let parse = |font: &Option<String>| {
+            let font = font.as_ref()?;
+            let res = Font::from_guifont(font);
+            if res.is_err() {
+                error!("Invalid font: {}", font);
+            }
+            res.ok()
+        };
+
+        ComponentFonts {
+            cmdline: parse(&config.cmdline),
+            popupmenu: parse(&config.popupmenu),
+            tabline: parse(&config.tabline),
+        }
+    }
+}
+
 /// Internal structure for `UI` to work on.
 pub(crate) struct UIState {
     pub css_provider: gtk::CssProvider,
@@ -44,7 +115,7 @@ pub(crate) struct UIState {
     /// Container for floating windows.
     pub windows_float_container: gtk::Fixed,
     /// Container for the msg window/grid.
-    pub _msg_window_container: gtk::Fixed,
+    pub msg_window_container: gtk::Fixed,
     /// Window for our messages grid.
     pub msg_window: MsgWindow,
     /// All grids currently in the UI.
@@ -61,16 +132,29 @@ pub(crate) struct UIState {
 
     pub popupmenu: Popupmenu,
     pub cmdline: Cmdline,
+    /// Externalized messages.
+    pub messages: Messages,
+    pub im_indicator: ImIndicator,
     pub tabline: Tabline,
+    /// Toolbar from the config file, if it has any buttons.
+    pub toolbar: Option<gtk::Toolbar>,
+    /// File tree sidebar, if the view has one.
+    pub file_tree: Option<FileTree>,
 
     pub wildmenu_shown: bool,
 
     /// Overlay contains our grid(s) and popupmenu.
-    #[allow(unused)]
     pub overlay: gtk::Overlay,
+    /// Overlay on top of the grids, for the cursor moving between them.
+    pub cursor_overlay: CursorOverlay,
 
     /// Source id for delayed call to ui_try_resize.
     pub resize_source_id: Rc<RefCell<Option<glib::SourceId>>>,
+    /// Source id of the idle callback that redraws the grids in chunks.
+    pub redraw_source_id: Option<glib::SourceId>,
+    /// Source id of the idle callback that updates the input method's
+    /// cursor location.
+    pub im_location_source_id: Option<glib::SourceId>,
     /// Resize options that is some if a resize should be send to nvim on flush.
     pub resize_on_flush: Option<ResizeOptions>,
 
@@ -80,15 +164,51 @@ pub(crate) struct UIState {
 
     pub font: Font,
     pub line_space: i64,
+    /// How the width of the grids' cells is determined.
+    pub cell_width: CellWidth,
+    /// Scale of the grids' cells.
+    pub cell_scale: CellScale,
+    pub render_options: RenderOptions,
+    /// Antialiasing and hinting of the grids' text, if set in the config.
+    pub font_options: Option<cairo::FontOptions>,
+    /// Fonts that override `font` for some of the UI components.
+    pub component_fonts: ComponentFonts,
 
     pub enable_cursor_animations: bool,
-    pub grid_scroll_speed: i64,
+    /// If particles are emitted from the cursor when it moves.
+    pub enable_cursor_particles: bool,
+    /// If the grids have the debug overlay drawn on top of them.
+    pub debug_overlay: bool,
+    pub grid_scroll: ScrollAnimation,
+
+    /// Font for double width characters.
+    pub font_wide: Option<Font>,
+    /// If mouse move events should be sent to nvim.
+    pub mouse_move_event: Rc<Cell<bool>>,
+    /// If the window under the mouse pointer should become the current one.
+    pub mouse_focus: Rc<Cell<bool>>,
+
+    /// Padding around the grids, in pixels.
+    pub grid_padding: i32,
+    /// Set when in the zen mode.
+    pub zen: Option<Zen>,
+    pub zen_config: ZenConfig,
+    /// Set when nvim is in a project with its own settings.
+    pub project: Option<Project>,
+
+    /// Title set by nvim.
+    pub title: String,
+    /// Prefix for the window title, set with `SetTitlePrefix`.
+    pub title_prefix: String,
+
+    /// Debug window, created when it's first shown.
+    pub inspector: Option<Inspector>,
 }
 
 impl UIState {
     pub fn handle_notify(
         &mut self,
-        window: &gtk::ApplicationWindow,
+        window: &gtk::Window,
         notify: Notify,
         nvim: &GioNeovim,
     ) -> Result<(), Error> {
@@ -99,7 +219,7 @@ impl UIState {
                 })?;
             }
             Notify::GnvimEvent(event) => match event {
-                Ok(event) => self.handle_gnvim_event(&event, nvim),
+                Ok(event) => self.handle_gnvim_event(window, &event, nvim),
                 Err(err) => {
                     let nvim = nvim.clone();
                     let msg = format!(
@@ -118,8 +238,9 @@ impl UIState {
         Ok(())
     }
 
-    fn set_title(&mut self, window: &gtk::ApplicationWindow, title: &str) {
-        window.set_title(title);
+    fn set_title(&mut self, window: &gtk::Window, title: &str) {
+        self.title = title.to_string();
+        window.set_title(&format!("{}{}", self.title_prefix, self.title));
     }
 
     fn grid_cursor_goto(
@@ -129,32 +250,45 @@ impl UIState {
             row,
             col,
         }: GridCursorGoto,
-    ) {
+    ) -> Result<(), Error> {
         // Gird cursor goto sets the current cursor to grid_id,
         // so we'll need to handle that here...
         let grid = if grid_id != self.current_grid {
             // ...so if the grid_id is not same as the self tells us,
             // set the previous current grid to inactive self.
-            let grid = self.grids.get(&self.current_grid).unwrap();
-            grid.set_active(false);
+            let prev = self.grids.get(&self.current_grid).unwrap();
+            prev.set_active(false);
             self.current_grid = grid_id;
 
             // And set the new current grid to active.
             let grid = self.grids.get(&grid_id).unwrap();
             grid.set_active(true);
+
+            // Continue from the previous grid's cursor position, so the
+            // cursor animates from one window to another. On the way, it's
+            // drawn on top of all the grids, so the new grid doesn't clip
+            // it.
+            if let Some((x, y)) = prev
+                .cursor_position()
+                .and_then(|(x, y)| prev.translate_coordinates(grid, x, y))
+            {
+                grid.set_cursor_position(x, y);
+                grid.draw_cursor_to(&self.cursor_overlay);
+            }
+
             grid
         } else {
             self.grids.get(&grid_id).unwrap()
         };
 
         // And after all that, set the current grid's cursor position.
-        grid.cursor_goto(row, col);
+        grid.cursor_goto(row, col, &self.hl_defs)
     }
 
     fn grid_resize(
         &mut self,
         e: GridResize,
-        window: &gtk::ApplicationWindow,
+        window: &gtk::Window,
         nvim: &GioNeovim,
     ) -> Result<(), Error> {
         let win = window.window().unwrap();
@@ -184,18 +318,31 @@ impl UIState {
                 &window.window().unwrap(),
                 self.font.clone(),
                 self.line_space,
+                self.cell_width,
+                self.cell_scale,
+                self.render_options,
                 e.width as usize,
                 e.height as usize,
                 &self.hl_defs,
                 self.enable_cursor_animations,
-                self.grid_scroll_speed,
+                self.grid_scroll,
             )?;
 
             if let Some(ref mode) = self.current_mode {
                 grid.set_mode(mode);
             }
+            grid.enable_cursor_particles(self.enable_cursor_particles);
+            grid.enable_debug_overlay(self.debug_overlay);
+            grid.set_font_wide(self.font_wide.clone());
+            if self.font_options.is_some() {
+                grid.set_font_options(self.font_options.as_ref(), &win)?;
+            }
             grid.resize(&win, e.width, e.height, &self.hl_defs)?;
-            attach_grid_events(&grid, nvim.clone());
+            attach_grid_events(
+                &grid,
+                nvim.clone(),
+                self.mouse_move_event.clone(),
+            );
             self.grids.insert(e.grid, grid);
         }
 
@@ -256,7 +403,7 @@ impl UIState {
         }
 
         for grid in self.grids.values() {
-            grid.redraw(&self.hl_defs)?;
+            grid.redraw();
         }
 
         self.hl_changed = true;
@@ -278,6 +425,9 @@ impl UIState {
                 self.hl_defs.set_hl_group(HlGroup::PmenuSel, evt.hl_id);
                 self.hl_defs.set_hl_group(HlGroup::WildmenuSel, evt.hl_id)
             }
+            "PmenuMatch" => {
+                self.hl_defs.set_hl_group(HlGroup::PmenuMatch, evt.hl_id)
+            }
             "TabLine" => self.hl_defs.set_hl_group(HlGroup::Tabline, evt.hl_id),
             "TabLineSel" => {
                 self.hl_defs.set_hl_group(HlGroup::TablineSel, evt.hl_id);
@@ -290,6 +440,15 @@ impl UIState {
             "MsgSeparator" => {
                 self.hl_defs.set_hl_group(HlGroup::MsgSeparator, evt.hl_id)
             }
+            "ModeMsg" => self.hl_defs.set_hl_group(HlGroup::ModeMsg, evt.hl_id),
+            "MoreMsg" => self.hl_defs.set_hl_group(HlGroup::MoreMsg, evt.hl_id),
+            "WarningMsg" => {
+                self.hl_defs.set_hl_group(HlGroup::WarningMsg, evt.hl_id)
+            }
+            "ErrorMsg" => {
+                self.hl_defs.set_hl_group(HlGroup::ErrorMsg, evt.hl_id)
+            }
+            "Visual" => self.hl_defs.set_hl_group(HlGroup::Visual, evt.hl_id),
             _ => None,
         };
 
@@ -331,9 +490,32 @@ impl UIState {
 
                 self.resize_on_flush = Some(opts);
             }
+            OptionSet::GuiFontWide(font) => {
+                self.font_wide = if font.is_empty() {
+                    None
+                } else {
+                    Font::from_guifont(&font).ok()
+                };
+
+                for grid in self.grids.values() {
+                    grid.set_font_wide(self.font_wide.clone());
+                    grid.redraw();
+                }
+            }
+            OptionSet::PumBlend(blend) => {
+                self.popupmenu.set_blend(blend);
+            }
+            OptionSet::ShowTabline(show) => {
+                self.tabline.set_show_tabline(show);
+            }
+            OptionSet::MouseMoveEvent(enable) => {
+                self.mouse_move_event.set(enable);
+            }
+            OptionSet::MouseFocus(enable) => {
+                self.mouse_focus.set(enable);
+            }
             OptionSet::ExtTabline(enable) => {
-                let widget = self.tabline.get_widget();
-                widget_show(&widget, enable);
+                self.tabline.set_enabled(enable);
             }
             OptionSet::ExtCmdline(enable) => {
                 if !enable {
@@ -346,6 +528,12 @@ impl UIState {
                 // custom wildmenu. To "fix" this, the user needs to reopen the
                 // wildmenu/cmdline.
             }
+            OptionSet::ExtMessages(enable) => {
+                // Nvim draws the messages on the grid from now on.
+                if !enable {
+                    self.messages.hide_all();
+                }
+            }
             OptionSet::ExtPopupmenu(_enable) => {
                 // Nothing to do... If the popupmenu is active at this point,
                 // nvim seems continue send the ext popupmenu messages until
@@ -362,7 +550,9 @@ impl UIState {
         self.mode_infos = mode_info;
     }
 
-    fn mode_change(&mut self, ModeChange { index, .. }: ModeChange) {
+    fn mode_change(&mut self, ModeChange { name, index }: ModeChange) {
+        self.messages.set_mode(&name);
+
         let mode = self.mode_infos.get(index as usize).unwrap();
         self.current_mode = Some(mode.clone());
         // Broadcast the mode change to all grids.
@@ -379,15 +569,91 @@ impl UIState {
         }
     }
 
+    /// Sets the fonts of the popupmenu, cmdline and tabline: their own from
+    /// the config, or `font` (the grid's).
+    pub fn set_component_fonts(&mut self, font: &Font) {
+        let fonts = &self.component_fonts;
+        let font = |component: &Option<Font>| {
+            component.clone().unwrap_or_else(|| font.clone())
+        };
+        self.popupmenu
+            .set_font(font(&fonts.popupmenu), &self.hl_defs);
+        self.cmdline.set_font(font(&fonts.cmdline), &self.hl_defs);
+        self.tabline.set_font(font(&fonts.tabline), &self.hl_defs);
+    }
+
+    /// Tells the input method where the cursor is, and moves the input
+    /// method indicator below it. The input method belongs to the base grid,
+    /// so the cursor's cell is translated from the current grid (e.g. a
+    /// float) to it. Needs to be called once the grids are laid
+    /// out, as their positions (after scrolls, resizes, float moves) are
+    /// only known then.
+    pub fn update_im_cursor_location(&self) {
+        let (grid, base) =
+            match (self.grids.get(&self.current_grid), self.grids.get(&1)) {
+                (Some(grid), Some(base)) => (grid, base),
+                _ => return,
+            };
+
+        let (x, y, width, height) = grid.cursor_target_rect();
+        if let Some((x, y)) =
+            grid.translate_coordinates(base, f64::from(x), f64::from(y))
+        {
+            base.set_im_cursor_location(&gdk::Rectangle {
+                x: x as i32,
+                y: y as i32,
+                width,
+                height,
+            });
+        }
+
+        let below = grid.translate_coordinates_to_widget(
+            &self.im_indicator.widget(),
+            x,
+            y + height,
+        );
+        self.im_indicator
+            .update(base.im_context_id().as_deref(), below);
+    }
+
+    /// If any of the grids is being redrawn.
+    pub fn grids_redrawing(&self) -> bool {
+        self.grids.values().any(|grid| grid.is_redrawing())
+    }
+
+    /// Redraws the next chunk of the grids that are being redrawn. Returns
+    /// true if there is more to redraw.
+    pub fn redraw_grids(&mut self) -> bool {
+        let deadline = Instant::now() + REDRAW_CHUNK;
+
+        let mut redrawing = false;
+        for grid in self.grids.values() {
+            match grid.redraw_chunk(&self.hl_defs, deadline) {
+                Ok(more) => redrawing |= more,
+                Err(err) => {
+                    error!("Failed to redraw grid {}: {:?}", grid.id, err)
+                }
+            }
+        }
+
+        redrawing
+    }
+
     fn flush(
         &mut self,
         nvim: &GioNeovim,
-        window: &gtk::ApplicationWindow,
+        window: &gtk::Window,
     ) -> Result<(), Error> {
         for grid in self.grids.values() {
             grid.flush(&self.hl_defs)?;
         }
 
+        // Keep the grids centered, as the window or the font might have
+        // changed.
+        if self.zen.is_some() {
+            self.update_grid_padding();
+        }
+
         if let Some(opts) = self.resize_on_flush.take() {
             let win = window.window().unwrap();
             for grid in self.grids.values() {
@@ -402,8 +668,7 @@ impl UIState {
             let (cols, rows) = grid.calc_size();
 
             // Cancel any possible delayed call for ui_try_resize.
-            let mut id = self.resize_source_id.borrow_mut();
-            if let Some(id) = id.take() {
+            if let Some(id) = self.resize_source_id.borrow_mut().take() {
                 glib::source::source_remove(id);
             }
 
@@ -416,9 +681,9 @@ impl UIState {
                 }
             });
 
-            self.popupmenu.set_font(opts.font.clone(), &self.hl_defs);
-            self.cmdline.set_font(opts.font.clone(), &self.hl_defs);
-            self.tabline.set_font(opts.font.clone(), &self.hl_defs);
+            self.set_component_fonts(&opts.font);
+            self.messages.set_font(opts.font.clone(), &self.hl_defs);
+            self.im_indicator.set_font(opts.font.clone(), &self.hl_defs);
 
             self.cmdline.set_line_space(opts.line_space);
             self.popupmenu
@@ -431,6 +696,8 @@ impl UIState {
             self.tabline.set_colors(&self.hl_defs);
             self.cmdline.set_colors(&self.hl_defs);
             self.cmdline.wildmenu_set_colors(&self.hl_defs);
+            self.messages.set_colors(&self.hl_defs);
+            self.im_indicator.set_colors(&self.hl_defs);
 
             let msgsep = self
                 .hl_defs
@@ -473,9 +740,18 @@ impl UIState {
             self.wildmenu_shown = true;
             self.cmdline.wildmenu_show(&popupmenu.items)
         } else {
-            self.popupmenu.set_items(popupmenu.items, &self.hl_defs);
-
             let grid = self.grids.get(&self.current_grid).unwrap();
+
+            // The word being completed, for highlighting the matches.
+            let base = self
+                .grids
+                .get(&popupmenu.grid)
+                .and_then(|grid| {
+                    grid.text_to_cursor(popupmenu.row, popupmenu.col)
+                })
+                .unwrap_or_default();
+            self.popupmenu.set_items(popupmenu.items, &base);
+
             let mut rect = grid.get_rect_for_cell(popupmenu.row, popupmenu.col);
 
             if let Some(window) = self.windows.get(&popupmenu.grid) {
@@ -486,8 +762,7 @@ impl UIState {
             }
 
             self.popupmenu.set_anchor(rect);
-            self.popupmenu
-                .select(popupmenu.selected as i32, &self.hl_defs);
+            self.popupmenu.select(popupmenu.selected as i32);
 
             self.popupmenu.show();
         }
@@ -506,7 +781,7 @@ impl UIState {
         if self.wildmenu_shown {
             self.cmdline.wildmenu_select(selected as i32);
         } else {
-            self.popupmenu.select(selected as i32, &self.hl_defs);
+            self.popupmenu.select(selected as i32);
         }
     }
 
@@ -598,6 +873,7 @@ impl UIState {
     ) -> &mut Window {
         let grid = self.grids.get(&grid).unwrap();
         let css_provider = self.css_provider.clone();
+        let mouse_focus = self.mouse_focus.clone();
         self.windows
             .entry(grid.id)
             .and_modify(clone!(container => move |w| {
@@ -610,6 +886,8 @@ impl UIState {
                     container,
                     grid,
                     Some(css_provider),
+                    nvim,
+                    mouse_focus,
                 )
             })
     }
@@ -680,10 +958,10 @@ impl UIState {
     fn window_external_pos(
         &mut self,
         evt: WindowExternalPos,
-        window: &gtk::ApplicationWindow,
+        window: &gtk::Window,
         nvim: &GioNeovim,
     ) -> Result<(), Error> {
-        let parent_win = window.clone().upcast::<gtk::Window>();
+        let parent_win = window.clone();
         let grid_metrics = {
             let grid = self.grids.get(&evt.grid).unwrap();
             let grid_metrics = grid.get_grid_metrics();
@@ -723,6 +1001,15 @@ impl UIState {
         self.windows.get(&grid_id).unwrap().hide();
     }
 
+    /// Nothing is drawn at the `ui_watched` extmarks yet. This is where the
+    /// GUI side decorations (e.g. scrollbar marks) would get their positions.
+    fn window_extmark(&mut self, e: WindowExtmark) {
+        debug!(
+            "Extmark {}:{} at {}:{} on grid {}",
+            e.ns_id, e.mark_id, e.row, e.col, e.grid
+        );
+    }
+
     fn window_close(&mut self, grid_id: i64) {
         // Drop window.
         if self.windows.remove(&grid_id).is_none() {
@@ -734,10 +1021,240 @@ impl UIState {
         let base_grid = self.grids.get(&1).unwrap();
         let base_metrics = base_grid.get_grid_metrics();
         let grid = self.grids.get(&e.grid).unwrap();
+        grid.set_scroll_speed(self.grid_scroll.messages_speed);
         let h = base_metrics.height - e.row as f64 * base_metrics.cell_height;
         self.msg_window.set_pos(grid, e.row as f64, h, e.scrolled);
     }
 
+    /// Collects the state of the grids and their windows for the
+    /// inspector.
+    fn inspect(&self) -> Report {
+        let containers = [
+            ("windows", &self.windows_container),
+            ("floats", &self.windows_float_container),
+            ("messages", &self.msg_window_container),
+        ];
+
+        let mut grids = self
+            .grids
+            .values()
+            .map(|grid| {
+                let metrics = grid.get_grid_metrics();
+                let window = self.windows.get(&grid.id).map(|window| {
+                    let container = if window.is_external() {
+                        "external"
+                    } else {
+                        containers
+                            .iter()
+                            .find(|(_, fixed)| *fixed == window.container())
+                            .map(|(name, _)| *name)
+                            .unwrap_or("unknown")
+                    };
+
+                    (window.x, window.y, container)
+                });
+
+                GridInfo {
+                    id: grid.id,
+                    cols: metrics.cols,
+                    rows: metrics.rows,
+                    window,
+                    animations: grid.pending_animations(),
+                }
+            })
+            .collect::<Vec<_>>();
+        grids.sort_by_key(|grid| grid.id);
+
+        Report {
+            grids,
+            current_grid: self.current_grid,
+            hl_defs: self.hl_defs.len(),
+            containers: containers
+                .iter()
+                .map(|(name, fixed)| {
+                    (*name, fixed.allocated_width(), fixed.allocated_height())
+                })
+                .collect(),
+        }
+    }
+
+    /// Sets the padding (in pixels) around the grids.
+    pub fn set_grid_padding(&mut self, padding: i32) {
+        self.grid_padding = padding.max(0);
+        self.update_grid_padding();
+    }
+
+    /// Applies `grid_padding` to the grids. In the zen mode, the grids are
+    /// centered.
+    fn update_grid_padding(&self) {
+        let grid = self.grids.get(&1).unwrap();
+        let (x, y) = if self.zen.is_some() {
+            let metrics = grid.get_grid_metrics();
+            zen_padding(
+                self.overlay.allocated_width(),
+                self.zen_config.width as f64 * metrics.cell_width,
+                self.grid_padding,
+            )
+        } else {
+            (self.grid_padding, self.grid_padding)
+        };
+
+        grid.set_padding(x, y);
+
+        // Window grids are positioned relative to the base grid, so keep
+        // their containers aligned with it.
+        for container in &[
+            &self.windows_container,
+            &self.windows_float_container,
+            &self.msg_window_container,
+        ] {
+            container.set_margin_start(x);
+            container.set_margin_end(x);
+            container.set_margin_top(y);
+            container.set_margin_bottom(y);
+        }
+    }
+
+    /// Toggles the zen mode: hides the tabline, the toolbar, the menubar and
+    /// the window decorations, bumps the font size and centers the grids.
+    /// Leaving the zen mode restores the previous state.
+    fn toggle_zen(&mut self, window: &gtk::Window, nvim: &GioNeovim) {
+        let guifont = match self.zen.take() {
+            Some(zen) => {
+                window.set_decorated(zen.decorated);
+                if let Some(window) =
+                    window.downcast_ref::<gtk::ApplicationWindow>()
+                {
+                    window.set_show_menubar(zen.show_menubar);
+                }
+                self.tabline.set_hidden(false);
+                if let Some(ref toolbar) = self.toolbar {
+                    toolbar.show();
+                }
+                zen.guifont
+            }
+            None => {
+                let zen = Zen {
+                    decorated: window.is_decorated(),
+                    show_menubar: window
+                        .downcast_ref::<gtk::ApplicationWindow>()
+                        .map(|window| window.shows_menubar())
+                        .unwrap_or(false),
+                    guifont: Some(self.font.to_guifont())
+                        .filter(|_| self.zen_config.font_size != 0.0),
+                };
+                let guifont = zen.guifont.as_ref().map(|_| {
+                    self.font.resized(self.zen_config.font_size).to_guifont()
+                });
+
+                window.set_decorated(false);
+                if let Some(window) =
+                    window.downcast_ref::<gtk::ApplicationWindow>()
+                {
+                    window.set_show_menubar(false);
+                }
+                self.tabline.set_hidden(true);
+                if let Some(ref toolbar) = self.toolbar {
+                    toolbar.hide();
+                }
+                self.zen = Some(zen);
+                guifont
+            }
+        };
+
+        self.update_grid_padding();
+
+        if let Some(guifont) = guifont {
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) =
+                    nvim.set_option("guifont", guifont.into()).await
+                {
+                    error!("Failed to set guifont: {}", err);
+                }
+            });
+        }
+    }
+
+    /// Applies the settings of the project that `cwd` is in (if any), and
+    /// restores the ones that the previous project changed.
+    fn set_project(&mut self, cwd: &str, nvim: &GioNeovim) {
+        let loaded = match project::load(Path::new(cwd)) {
+            Some(Ok(loaded)) => Some(loaded),
+            Some(Err(err)) => {
+                error!("Failed to load project settings: {}", err);
+                None
+            }
+            None => None,
+        };
+
+        let path = loaded.as_ref().map(|(path, _)| path);
+        if self.project.as_ref().map(|project| &project.path) == path {
+            return;
+        }
+
+        let settings = gtk::Settings::default();
+        let mut guifont = None;
+
+        if let Some(project) = self.project.take() {
+            debug!("Leaving project {:?}", project.path);
+            guifont = project.guifont;
+            if let Some(padding) = project.grid_padding {
+                self.set_grid_padding(padding);
+            }
+            if let (Some(prefer_dark), Some(ref settings)) =
+                (project.prefer_dark_theme, &settings)
+            {
+                settings.set_gtk_application_prefer_dark_theme(prefer_dark);
+            }
+        }
+
+        if let Some((path, config)) = loaded {
+            debug!("Entering project {:?}", path);
+            let mut project = Project {
+                path,
+                guifont: None,
+                grid_padding: None,
+                prefer_dark_theme: None,
+            };
+
+            if let Some(size) = config.font_size {
+                // The font to restore, unless we're switching straight
+                // from another project.
+                project.guifont =
+                    guifont.clone().or_else(|| Some(self.font.to_guifont()));
+                guifont = Some(
+                    self.font.resized(size - self.font.height).to_guifont(),
+                );
+            }
+            if let Some(padding) = config.padding {
+                project.grid_padding = Some(self.grid_padding);
+                self.set_grid_padding(padding);
+            }
+            if let (Some(theme), Some(ref settings)) = (config.theme, &settings)
+            {
+                project.prefer_dark_theme =
+                    Some(settings.is_gtk_application_prefer_dark_theme());
+                settings.set_gtk_application_prefer_dark_theme(
+                    theme == project::Theme::Dark,
+                );
+            }
+
+            self.project = Some(project);
+        }
+
+        if let Some(guifont) = guifont {
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) =
+                    nvim.set_option("guifont", guifont.into()).await
+                {
+                    error!("Failed to set guifont: {}", err);
+                }
+            });
+        }
+    }
+
     fn enable_cursor_animations(&mut self, enable: bool) {
         self.enable_cursor_animations = enable;
         self.grids
@@ -745,9 +1262,23 @@ impl UIState {
             .for_each(|g| g.enable_cursor_animations(enable));
     }
 
+    fn enable_cursor_particles(&mut self, enable: bool) {
+        self.enable_cursor_particles = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_cursor_particles(enable));
+    }
+
+    fn enable_debug_overlay(&mut self, enable: bool) {
+        self.debug_overlay = enable;
+        self.grids
+            .values()
+            .for_each(|g| g.enable_debug_overlay(enable));
+    }
+
     fn handle_redraw_event(
         &mut self,
-        window: &gtk::ApplicationWindow,
+        window: &gtk::Window,
         event: RedrawEvent,
         nvim: &GioNeovim,
     ) -> Result<(), Error> {
@@ -759,7 +1290,7 @@ impl UIState {
                 evt.into_iter().try_for_each(|line| self.grid_line(line))?
             }
             RedrawEvent::GridCursorGoto(evt) => {
-                evt.into_iter().for_each(|e| self.grid_cursor_goto(e))
+                evt.into_iter().try_for_each(|e| self.grid_cursor_goto(e))?
             }
             RedrawEvent::GridResize(evt) => evt
                 .into_iter()
@@ -837,6 +1368,27 @@ impl UIState {
             RedrawEvent::WindowClose(evt) => {
                 evt.into_iter().for_each(|e| self.window_close(e));
             }
+            RedrawEvent::WindowExtmark(evt) => {
+                evt.into_iter().for_each(|e| self.window_extmark(e));
+            }
+            RedrawEvent::MsgShow(evt) => {
+                evt.into_iter()
+                    .for_each(|e| self.messages.show(e, &self.hl_defs));
+            }
+            RedrawEvent::MsgClear() => self.messages.clear(),
+            RedrawEvent::MsgShowMode(evt) => {
+                if let Some(content) = evt.last() {
+                    self.messages.set_showmode(content);
+                }
+            }
+            RedrawEvent::MsgRuler(evt) => {
+                if let Some(content) = evt.last() {
+                    self.messages.set_ruler(content, &self.hl_defs);
+                }
+            }
+            RedrawEvent::MsgHistoryShow(evt) => {
+                self.messages.history_show(evt, &self.hl_defs);
+            }
             RedrawEvent::MsgSetPos(evt) => {
                 evt.into_iter().for_each(|e| self.msg_set_pos(e));
             }
@@ -856,11 +1408,27 @@ impl UIState {
                 .await
             {
                 error!("Failed to set '{}' option: {}", opt, err);
+                return;
+            }
+
+            // Keep `g:gnvim_capabilities` up to date.
+            let cmd = format!(
+                "let g:gnvim_capabilities.{} = {}",
+                opt,
+                if enable { "v:true" } else { "v:false" }
+            );
+            if let Err(err) = nvim.command(&cmd).await {
+                error!("Failed to update capabilities: {}", err);
             }
         });
     }
 
-    fn handle_gnvim_event(&mut self, event: &GnvimEvent, nvim: &GioNeovim) {
+    pub fn handle_gnvim_event(
+        &mut self,
+        window: &gtk::Window,
+        event: &GnvimEvent,
+        nvim: &GioNeovim,
+    ) {
         match event {
             GnvimEvent::CompletionMenuToggleInfo => {
                 self.popupmenu.toggle_show_info()
@@ -871,12 +1439,151 @@ impl UIState {
             GnvimEvent::PopupmenuWidthDetails(width) => {
                 self.popupmenu.set_width_details(*width as i32);
             }
+            GnvimEvent::PopupmenuMinWidth(width) => {
+                self.popupmenu.set_min_width(*width as i32);
+            }
+            GnvimEvent::PopupmenuMaxItems(n) => {
+                self.popupmenu.set_max_items(*n as i32);
+            }
+            GnvimEvent::PopupmenuShowMenu(show) => {
+                self.popupmenu.set_show_menu(*show);
+            }
             GnvimEvent::PopupmenuShowMenuOnAllItems(should_show) => {
                 self.popupmenu.set_show_menu_on_all_items(*should_show);
             }
+            GnvimEvent::CmdlineHistory => self.cmdline.show_history(),
             GnvimEvent::EnableCursorAnimations(enable) => {
                 self.enable_cursor_animations(*enable);
             }
+            GnvimEvent::EnableCursorParticles(enable) => {
+                self.enable_cursor_particles(*enable);
+            }
+            GnvimEvent::EnableDebugOverlay(enable) => {
+                self.enable_debug_overlay(*enable);
+            }
+            GnvimEvent::SetLogLevel(level) => {
+                logger::set_level(*level);
+            }
+            GnvimEvent::SetFullscreen(fullscreen) => {
+                if *fullscreen {
+                    window.fullscreen();
+                } else {
+                    window.unfullscreen();
+                }
+            }
+            GnvimEvent::SetKeepAbove(keep_above) => {
+                let keep_above = keep_above.unwrap_or_else(|| {
+                    !window
+                        .window()
+                        .map(|w| w.state().contains(gdk::WindowState::ABOVE))
+                        .unwrap_or(false)
+                });
+                window.set_keep_above(keep_above);
+            }
+            GnvimEvent::SetSticky(sticky) => {
+                let sticky = sticky.unwrap_or_else(|| {
+                    !window
+                        .window()
+                        .map(|w| w.state().contains(gdk::WindowState::STICKY))
+                        .unwrap_or(false)
+                });
+                if sticky {
+                    window.stick();
+                } else {
+                    window.unstick();
+                }
+            }
+            GnvimEvent::SetProgress(progress) => {
+                if let Err(err) = dbus::set_launcher_progress(*progress) {
+                    error!("Failed to set progress: {:?}", err);
+                }
+            }
+            GnvimEvent::SetUrgent(urgent) => {
+                window.set_urgency_hint(*urgent);
+                if let Err(err) = dbus::set_launcher_urgent(*urgent) {
+                    error!("Failed to set urgent: {:?}", err);
+                }
+            }
+            GnvimEvent::SetOpacity(opacity) => {
+                window.set_opacity(opacity.clamp(0.0, 1.0));
+            }
+            GnvimEvent::SetPadding(padding) => {
+                self.set_grid_padding(*padding as i32);
+            }
+            GnvimEvent::ToggleFileTree(cwd) => {
+                if let Some(ref mut file_tree) = self.file_tree {
+                    file_tree.set_cwd(cwd);
+                    file_tree.toggle();
+                }
+            }
+            GnvimEvent::SetCwd(cwd) => {
+                if let Some(ref mut file_tree) = self.file_tree {
+                    file_tree.set_cwd(cwd);
+                }
+                self.set_project(cwd, nvim);
+            }
+            GnvimEvent::ToggleZen => {
+                self.toggle_zen(window, nvim);
+            }
+            GnvimEvent::Inspect => {
+                let report = self.inspect();
+                self.inspector
+                    .get_or_insert_with(|| Inspector::new(window))
+                    .show(&report);
+            }
+            GnvimEvent::Restart => {
+                let nvim = nvim.clone();
+                spawn_local(async move {
+                    if let Err(err) = restart::restart(&nvim).await {
+                        error!("Failed to restart: {}", err);
+                        if let Err(err) = nvim.err_writeln(&err).await {
+                            error!("Failed to write error: {}", err);
+                        }
+                    }
+                });
+            }
+            GnvimEvent::AddRecent(path) => {
+                let uri = gio::File::for_path(path).uri();
+                let added = gtk::RecentManager::default()
+                    .map(|manager| manager.add_item(&uri))
+                    .unwrap_or(false);
+                if !added {
+                    warn!("Failed to add '{}' to recent files", path);
+                }
+            }
+            GnvimEvent::SetTitlePrefix(prefix) => {
+                self.title_prefix = prefix.clone();
+                let title = self.title.clone();
+                self.set_title(window, &title);
+            }
+            GnvimEvent::ResizeWindow(width, height) => {
+                window.resize(*width, *height);
+            }
+            GnvimEvent::SetAccels(action, accels) => {
+                if let Some(app) = window.application() {
+                    let accels =
+                        accels.iter().map(String::as_str).collect::<Vec<_>>();
+                    app.set_accels_for_action(
+                        &format!("app.{}", action),
+                        &accels,
+                    );
+                }
+            }
+            GnvimEvent::SetOption(name, value) => {
+                let nvim = nvim.clone();
+                let (name, value) = (name.clone(), value.clone());
+                spawn_local(async move {
+                    if let Err(err) = nvim.set_option(&name, value).await {
+                        error!("Failed to set option '{}': {}", name, err);
+                    }
+                });
+            }
+            GnvimEvent::EnableShowMode(enable) => {
+                self.messages.enable_showmode(*enable);
+            }
+            GnvimEvent::EnableImIndicator(enable) => {
+                self.im_indicator.enable(*enable);
+            }
             GnvimEvent::EnableExtTabline(enable) => {
                 self.set_ui_option("ext_tabline".into(), *enable, nvim.clone());
             }
@@ -890,6 +1597,13 @@ impl UIState {
                     nvim.clone(),
                 );
             }
+            GnvimEvent::EnableExtMessages(enable) => {
+                self.set_ui_option(
+                    "ext_messages".into(),
+                    *enable,
+                    nvim.clone(),
+                );
+            }
             GnvimEvent::Unknown(msg) => {
                 debug!("Received unknown GnvimEvent: {}", msg);
             }
@@ -897,7 +1611,13 @@ impl UIState {
     }
 }
 
-pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
+/// Connects `grid`'s mouse events to nvim. Mouse move events are only sent
+/// when `mouse_move_event` is set (see `:h 'mousemoveevent'`).
+pub fn attach_grid_events(
+    grid: &Grid,
+    nvim: GioNeovim,
+    mouse_move_event: Rc<Cell<bool>>,
+) {
     let id = grid.id;
     // Mouse button press event.
     grid.connect_mouse_button_press_events(
@@ -935,23 +1655,48 @@ pub fn attach_grid_events(grid: &Grid, nvim: GioNeovim) {
         }),
     );
 
+    // Touch screen taps and long presses.
+    grid.connect_touch_click_events(clone!(nvim => move |button, row, col| {
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            let button = button.to_string();
+            for action in ["press", "release"].iter() {
+                nvim.input_mouse(&button, action, "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+            }
+        });
+    }));
+
+    // Mouse move events.
+    grid.connect_mouse_move_events(clone!(nvim => move |row, col| {
+        if !mouse_move_event.get() {
+            return Inhibit(false);
+        }
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            nvim.input_mouse("move", "", "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+        });
+
+        Inhibit(false)
+    }));
+
     // Scrolling events.
-    grid.connect_scroll_events(clone!(nvim => move |dir, row, col| {
+    grid.connect_scroll_events(clone!(nvim => move |dir, modifiers, row, col| {
         let nvim = nvim.clone();
         spawn_local(async move {
-            nvim.input_mouse("wheel", &dir.to_string(), "", id, row as i64, col as i64).await.expect("Couldn't send mouse input");
+            nvim.input_mouse("wheel", &dir.to_string(), &modifiers, id, row as i64, col as i64).await.expect("Couldn't send mouse input");
         });
 
         Inhibit(false)
     }));
 }
 
-fn widget_show(widget: &gtk::Widget, show: bool) {
-    if show {
-        widget.show();
-    } else {
-        widget.hide();
-    }
+/// Returns the horizontal and vertical padding (in pixels) that centers
+/// `text_width` wide grid in `width`, in the zen mode. The padding is at
+/// least `padding`.
+fn zen_padding(width: i32, text_width: f64, padding: i32) -> (i32, i32) {
+    let x = ((f64::from(width) - text_width) / 2.0).floor() as i32;
+    (x.max(padding), padding.max(ZEN_PADDING))
 }
 
 fn win_float_anchor_pos(
@@ -983,6 +1728,13 @@ mod tests {
     use crate::nvim_bridge::Anchor;
     use nvim_rs::Value;
 
+    #[test]
+    fn test_zen_padding() {
+        assert_eq!(zen_padding(1000, 800.0, 0), (100, ZEN_PADDING));
+        assert_eq!(zen_padding(1000, 999.0, 8), (8, ZEN_PADDING));
+        assert_eq!(zen_padding(500, 800.0, 40), (40, 40));
+    }
+
     #[test]
     fn test_float_anchor_pos() {
         struct Data {
@@ -1,15 +1,22 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
 use gtk::prelude::*;
-use gtk::{glib, pango};
+use gtk::{gdk, glib, pango};
 
-use nvim_rs::Tabpage;
+use nvim_rs::{Tabpage, Value};
+
+use log::error;
 
 use crate::nvim_gio::{GioNeovim, GioWriter};
 use crate::ui::color::{Color, HlDefs, HlGroup};
 use crate::ui::common::{calc_line_space, spawn_local};
 use crate::ui::font::{Font, FontUnit};
+use crate::ui::grid::{accumulate_scroll, ScrollDirection};
+
+/// Minimum width of a tab. Tabs that don't fit are moved to the overflow
+/// menu.
+const MIN_TAB_WIDTH: i32 = 100;
 
 #[derive(Default)]
 pub struct TablineColors {
@@ -21,19 +28,164 @@ pub struct TablineColors {
     pub sel_fg: Option<Color>,
 }
 
+/// Switches to the tab `page_num`.
+fn switch_tab(
+    nvim: &GioNeovim,
+    tabpage_data: &Rc<RefCell<Vec<Tabpage<GioWriter>>>>,
+    page_num: u32,
+) {
+    let tabpage_data = tabpage_data.clone();
+    let nvim = nvim.clone();
+    spawn_local(async move {
+        let pages = tabpage_data.borrow();
+        if let Some(page) = pages.get(page_num as usize) {
+            nvim.set_current_tabpage(page).await.unwrap();
+        } else {
+            println!("Failed to get tab page {}", page_num);
+        }
+    });
+}
+
+/// Returns the range of `tabs` to show, when `fit` of them fit, so that
+/// the `current` one is included.
+fn visible_tabs(tabs: usize, fit: usize, current: usize) -> (usize, usize) {
+    let fit = fit.min(tabs);
+    let start = (current + 1).saturating_sub(fit).min(tabs - fit);
+    (start, start + fit)
+}
+
+/// Shows only the tabs that fit to the notebook, and the rest in the
+/// overflow menu.
+fn layout_tabs(
+    notebook: &gtk::Notebook,
+    overflow_button: &gtk::MenuButton,
+    overflow_menu: &gtk::Menu,
+) {
+    let tabs = notebook.n_pages() as usize;
+    if tabs == 0 {
+        overflow_button.hide();
+        return;
+    }
+
+    let actions_width = notebook
+        .action_widget(gtk::PackType::End)
+        .map(|w| w.allocated_width())
+        .unwrap_or_default();
+    let fit = ((notebook.allocated_width() - actions_width) / MIN_TAB_WIDTH)
+        .max(1) as usize;
+    let current = notebook.current_page().unwrap_or_default() as usize;
+    let (start, end) = visible_tabs(tabs, fit, current);
+
+    let items = overflow_menu.children();
+    for i in 0..tabs {
+        let visible = i >= start && i < end;
+        if let Some(page) = notebook.nth_page(Some(i as u32)) {
+            page.set_visible(visible);
+        }
+        if let Some(item) = items.get(i) {
+            item.set_visible(!visible);
+        }
+    }
+
+    overflow_button.set_visible(end - start < tabs);
+}
+
+/// Returns the tooltip text of a tab.
+fn tooltip_text(path: &str, cwd: &str, windows: u64) -> String {
+    format!(
+        "{}\nDirectory: {}\n{} {}",
+        if path.is_empty() { "[No Name]" } else { path },
+        cwd,
+        windows,
+        if windows == 1 { "window" } else { "windows" },
+    )
+}
+
+/// Shows the details of `tab` in the tooltip of `widget`. The details are
+/// fetched once the tooltip is first needed.
+fn connect_tooltip(widget: &gtk::EventBox, nvim: &GioNeovim, tab: &Value) {
+    let text: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+    let fetching = Rc::new(Cell::new(false));
+    let nvim = nvim.clone();
+    let tab = tab.clone();
+
+    widget.set_has_tooltip(true);
+    widget.connect_query_tooltip(move |widget, _, _, _, tooltip| {
+        if let Some(ref text) = *text.borrow() {
+            tooltip.set_text(Some(text));
+            return true;
+        }
+
+        if fetching.replace(true) {
+            return false;
+        }
+
+        let widget = widget.clone();
+        let nvim = nvim.clone();
+        let tab = tab.clone();
+        let text = text.clone();
+        spawn_local(async move {
+            let details = match nvim
+                .call_function("gnvim#tabline#tooltip", vec![tab])
+                .await
+            {
+                Ok(details) => details,
+                Err(err) => {
+                    error!("Failed to get tab details: {}", err);
+                    return;
+                }
+            };
+
+            let get = |key: &str| {
+                details
+                    .as_map()
+                    .and_then(|m| {
+                        m.iter().find(|(k, _)| k.as_str() == Some(key))
+                    })
+                    .map(|(_, v)| v.clone())
+            };
+            let path = get("path");
+            let cwd = get("cwd");
+            text.replace(Some(tooltip_text(
+                path.as_ref().and_then(Value::as_str).unwrap_or_default(),
+                cwd.as_ref().and_then(Value::as_str).unwrap_or_default(),
+                get("windows").and_then(|v| v.as_u64()).unwrap_or_default(),
+            )));
+
+            widget.trigger_tooltip_query();
+        });
+
+        false
+    });
+}
+
 pub struct Tabline {
     notebook: gtk::Notebook,
     css_provider: gtk::CssProvider,
     switch_tab_signal: glib::SignalHandlerId,
+    /// Button for the menu of the tabs that don't fit.
+    overflow_button: gtk::MenuButton,
+    /// Menu of the tabs, of which the ones that don't fit are visible.
+    overflow_menu: gtk::Menu,
 
     tabpage_data: Rc<RefCell<Vec<Tabpage<GioWriter>>>>,
 
+    nvim: GioNeovim,
+
     /// Our colors.
     colors: TablineColors,
     /// Our font.
     font: Font,
 
     line_space: i64,
+
+    /// When to show the tabline (see `:h 'showtabline'`).
+    show_tabline: Cell<i64>,
+    /// If the tabline is externalized (`ext_tabline`).
+    enabled: Cell<bool>,
+    /// If the tabline is hidden regardless of the above (e.g. in the zen
+    /// mode).
+    hidden: Cell<bool>,
 }
 
 impl Tabline {
@@ -44,20 +196,98 @@ impl Tabline {
         let css_provider = gtk::CssProvider::new();
         add_css_provider!(&css_provider, notebook);
 
+        let overflow_menu = gtk::Menu::new();
+        let overflow_button = gtk::MenuButton::new();
+        overflow_button.set_popup(Some(&overflow_menu));
+        overflow_button.set_relief(gtk::ReliefStyle::None);
+        overflow_button.set_can_focus(false);
+        overflow_button.set_tooltip_text(Some("More tabs"));
+        overflow_button.set_no_show_all(true);
+
+        let new_tab_button = gtk::Button::from_icon_name(
+            Some("list-add-symbolic"),
+            gtk::IconSize::Button,
+        );
+        new_tab_button.set_relief(gtk::ReliefStyle::None);
+        new_tab_button.set_can_focus(false);
+        new_tab_button.set_tooltip_text(Some("New tab"));
+        new_tab_button.connect_clicked(clone!(nvim => move |_| {
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.command("tabnew").await {
+                    error!("Failed to open a new tab: {}", err);
+                }
+            });
+        }));
+
+        let actions = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        actions.pack_start(&overflow_button, false, false, 0);
+        actions.pack_start(&new_tab_button, false, false, 0);
+        actions.show_all();
+        notebook.set_action_widget(&actions, gtk::PackType::End);
+
+        add_css_provider!(&css_provider, overflow_button, new_tab_button);
+
         let tabpage_data = Rc::new(RefCell::new(vec![]));
         let switch_tab_signal = notebook.connect_switch_page(
             clone!(tabpage_data, nvim => move |_, _, page_num| {
-                let tabpage_data = tabpage_data.clone();
-                let nvim = nvim.clone();
-                spawn_local(async move {
-                    let pages = tabpage_data.borrow();
-                    if let Some(page) = pages.get(page_num as usize) {
-                        nvim.set_current_tabpage(page)
-                            .await
-                            .unwrap();
-                    } else {
-                        println!("Failed to get tab page {}", page_num);
-                    }
+                switch_tab(&nvim, &tabpage_data, page_num);
+            }),
+        );
+
+        // Switch tabs with the mouse wheel.
+        notebook.add_events(
+            gdk::EventMask::SCROLL_MASK | gdk::EventMask::SMOOTH_SCROLL_MASK,
+        );
+        let scroll_delta = RefCell::new((0.0, 0.0));
+        notebook.connect_scroll_event(clone!(nvim => move |_, e| {
+            let dir = match e.direction() {
+                gdk::ScrollDirection::Smooth => {
+                    let (dx, dy) = e.scroll_deltas().unwrap_or_default();
+                    accumulate_scroll(&scroll_delta, dx, dy)
+                }
+                gdk::ScrollDirection::Up => Some(ScrollDirection::Up),
+                gdk::ScrollDirection::Down => Some(ScrollDirection::Down),
+                gdk::ScrollDirection::Left => Some(ScrollDirection::Left),
+                gdk::ScrollDirection::Right => Some(ScrollDirection::Right),
+                _ => None,
+            };
+
+            let cmd = match dir {
+                Some(ScrollDirection::Up) | Some(ScrollDirection::Left) => {
+                    "tabprevious"
+                }
+                Some(ScrollDirection::Down) | Some(ScrollDirection::Right) => {
+                    "tabnext"
+                }
+                None => return Inhibit(true),
+            };
+
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.command(cmd).await {
+                    error!("Failed to switch tab: {}", err);
+                }
+            });
+
+            Inhibit(true)
+        }));
+
+        // Move the tabs that don't fit to the overflow menu when our size
+        // changes.
+        let width = Cell::new(0);
+        notebook.connect_size_allocate(
+            clone!(overflow_button, overflow_menu => move |notebook, alloc| {
+                if width.replace(alloc.width) == alloc.width {
+                    return;
+                }
+
+                let notebook = notebook.clone();
+                let overflow_button = overflow_button.clone();
+                let overflow_menu = overflow_menu.clone();
+                glib::idle_add_local(move || {
+                    layout_tabs(&notebook, &overflow_button, &overflow_menu);
+                    Continue(false)
                 });
             }),
         );
@@ -66,10 +296,16 @@ impl Tabline {
             notebook,
             css_provider,
             switch_tab_signal,
+            overflow_button,
+            overflow_menu,
             tabpage_data,
+            nvim,
             colors: TablineColors::default(),
             font: Font::default(),
             line_space: 0,
+            show_tabline: Cell::new(1),
+            enabled: Cell::new(true),
+            hidden: Cell::new(false),
         }
     }
 
@@ -86,15 +322,10 @@ impl Tabline {
         for child in self.notebook.children() {
             self.notebook.remove(&child);
         }
-        glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
-
-        if tabs.len() < 2 {
-            self.notebook.hide();
-            return;
+        for child in self.overflow_menu.children() {
+            self.overflow_menu.remove(&child);
         }
 
-        glib::signal_handler_block(&self.notebook, &self.switch_tab_signal);
-
         let mut page = 0;
         for (i, tab) in tabs.iter().enumerate() {
             let tab_label = gtk::Label::new(Some(tab.1.as_str()));
@@ -102,19 +333,56 @@ impl Tabline {
             tab_label.set_ellipsize(pango::EllipsizeMode::End);
             add_css_provider!(&self.css_provider, tab_label);
 
+            // Event box for catching the middle clicks, which close the tab.
+            let event_box = gtk::EventBox::new();
+            event_box.set_visible_window(false);
+            event_box.set_hexpand(true);
+            event_box.add(&tab_label);
+            let nvim = self.nvim.clone();
+            event_box.connect_button_press_event(move |_, e| {
+                if e.button() != 2 {
+                    return Inhibit(false);
+                }
+
+                let nvim = nvim.clone();
+                spawn_local(async move {
+                    let cmd = format!("tabclose {}", i + 1);
+                    if let Err(err) = nvim.command(&cmd).await {
+                        error!("Failed to close tab: {}", err);
+                    }
+                });
+
+                Inhibit(true)
+            });
+
+            connect_tooltip(&event_box, &self.nvim, tab.0.get_value());
+
             self.notebook.append_page(
                 &gtk::Box::new(gtk::Orientation::Vertical, 0),
-                Some(&tab_label),
+                Some(&event_box),
             );
 
+            let item = gtk::MenuItem::with_label(tab.1.as_str());
+            let tabpage_data = self.tabpage_data.clone();
+            let nvim = self.nvim.clone();
+            item.connect_activate(move |_| {
+                switch_tab(&nvim, &tabpage_data, i as u32);
+            });
+            self.overflow_menu.append(&item);
+
             if tab.0.get_value() == current.get_value() {
                 page = i;
             }
         }
 
         self.notebook.show_all();
+        self.overflow_menu.show_all();
+        self.notebook.set_visible(self.should_show(tabs.len()));
 
         self.notebook.set_current_page(Some(page as u32));
+        layout_tabs(&self.notebook, &self.overflow_button, &self.overflow_menu);
+
+        self.format_labels(tabs.iter().map(|t| t.0.get_value().clone()));
 
         self.tabpage_data
             .replace(tabs.into_iter().map(|t| t.0).collect());
@@ -122,6 +390,90 @@ impl Tabline {
         glib::signal_handler_unblock(&self.notebook, &self.switch_tab_signal);
     }
 
+    /// Replaces the labels of `tabs` with the ones formatted by
+    /// `g:gnvim_tabline_format`, if it's set.
+    fn format_labels(&self, tabs: impl Iterator<Item = Value>) {
+        let tabs = Value::Array(tabs.collect());
+        let nvim = self.nvim.clone();
+        let notebook = self.notebook.clone();
+        let overflow_menu = self.overflow_menu.clone();
+        spawn_local(async move {
+            let labels = match nvim
+                .call_function("gnvim#tabline#labels", vec![tabs])
+                .await
+            {
+                Ok(Value::Array(labels)) => labels,
+                Ok(_) => return,
+                Err(err) => {
+                    error!("Failed to format tab labels: {}", err);
+                    return;
+                }
+            };
+
+            // Tabs have changed in the meantime.
+            if labels.len() != notebook.n_pages() as usize {
+                return;
+            }
+
+            let items = overflow_menu.children();
+            for (i, label) in labels.iter().enumerate() {
+                let label = match label.as_str() {
+                    Some(label) => label,
+                    None => continue,
+                };
+
+                if let Some(tab_label) = notebook
+                    .nth_page(Some(i as u32))
+                    .and_then(|page| notebook.tab_label(&page))
+                    .and_then(|w| w.downcast::<gtk::Bin>().ok())
+                    .and_then(|event_box| event_box.child())
+                    .and_then(|w| w.downcast::<gtk::Label>().ok())
+                {
+                    tab_label.set_text(label);
+                }
+
+                if let Some(item) =
+                    items.get(i).and_then(|w| w.downcast_ref::<gtk::MenuItem>())
+                {
+                    item.set_label(label);
+                }
+            }
+        });
+    }
+
+    /// Sets when the tabline is shown (see `:h 'showtabline'`).
+    pub fn set_show_tabline(&self, show: i64) {
+        self.show_tabline.set(show);
+        let tabs = self.tabpage_data.borrow().len();
+        self.notebook.set_visible(self.should_show(tabs));
+    }
+
+    /// Sets if the tabline is externalized. If not, we're never shown.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+        let tabs = self.tabpage_data.borrow().len();
+        self.notebook.set_visible(self.should_show(tabs));
+    }
+
+    /// Hides the tabline, regardless of 'showtabline'.
+    pub fn set_hidden(&self, hidden: bool) {
+        self.hidden.set(hidden);
+        let tabs = self.tabpage_data.borrow().len();
+        self.notebook.set_visible(self.should_show(tabs));
+    }
+
+    fn should_show(&self, tabs: usize) -> bool {
+        if !self.enabled.get() || self.hidden.get() {
+            return false;
+        }
+
+        match self.show_tabline.get() {
+            0 => false,
+            1 => tabs > 1,
+            _ => tabs > 0,
+        }
+    }
+
     pub fn set_font(&mut self, font: Font, hl_defs: &HlDefs) {
         self.font = font;
         self.set_styles(hl_defs);
@@ -202,6 +554,17 @@ impl Tabline {
             tab:hover {{
                 box-shadow: inset 73px 0px 0px -70px #{selected_fg};
             }}
+            button {{
+                color: #{normal_fg};
+                background: #{normal_bg};
+                border: none;
+                border-radius: 0px;
+                box-shadow: none;
+            }}
+            button:hover {{
+                color: #{selected_fg};
+                background: #{selected_bg};
+            }}
             ",
             font_wild = self.font.as_wild_css(FontUnit::Point),
             normal_fg = self.colors.fg.unwrap_or(hl_defs.default_fg).as_hex(),
@@ -218,3 +581,29 @@ impl Tabline {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tooltip_text() {
+        assert_eq!(
+            tooltip_text("/tmp/foo.rs", "/tmp", 1),
+            "/tmp/foo.rs\nDirectory: /tmp\n1 window"
+        );
+        assert_eq!(
+            tooltip_text("", "/tmp", 3),
+            "[No Name]\nDirectory: /tmp\n3 windows"
+        );
+    }
+
+    #[test]
+    fn test_visible_tabs() {
+        assert_eq!(visible_tabs(3, 5, 2), (0, 3));
+        assert_eq!(visible_tabs(10, 4, 0), (0, 4));
+        assert_eq!(visible_tabs(10, 4, 3), (0, 4));
+        assert_eq!(visible_tabs(10, 4, 5), (2, 6));
+        assert_eq!(visible_tabs(10, 4, 9), (6, 10));
+    }
+}
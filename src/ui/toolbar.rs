@@ -0,0 +1,47 @@
+use gtk::prelude::*;
+
+use log::error;
+
+use crate::config::ToolbarButton;
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::spawn_local;
+
+/// Creates the toolbar with `buttons` from the config file. Returns `None`
+/// if there are no buttons.
+pub fn new(
+    buttons: &[ToolbarButton],
+    nvim: &GioNeovim,
+) -> Option<gtk::Toolbar> {
+    if buttons.is_empty() {
+        return None;
+    }
+
+    let toolbar = gtk::Toolbar::new();
+    toolbar.set_style(gtk::ToolbarStyle::Icons);
+
+    for button in buttons.iter() {
+        let icon = gtk::Image::from_icon_name(
+            Some(&button.icon),
+            gtk::IconSize::LargeToolbar,
+        );
+        let item = gtk::ToolButton::new(Some(&icon), Some(&button.label));
+        item.set_tooltip_text(Some(&button.label));
+        // Don't steal the focus from the grid.
+        item.set_focus_on_click(false);
+
+        let command = button.command.clone();
+        item.connect_clicked(clone!(nvim => move |_| {
+            let nvim = nvim.clone();
+            let command = command.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.command(&command).await {
+                    error!("Failed to run '{}': {}", command, err);
+                }
+            });
+        }));
+
+        toolbar.insert(&item, -1);
+    }
+
+    Some(toolbar)
+}
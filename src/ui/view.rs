@@ -0,0 +1,1088 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time;
+
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+
+use futures::channel::{mpsc, oneshot};
+use futures::StreamExt;
+use log::{debug, error};
+use nvim_rs::Value;
+use structopt::StructOpt;
+
+use crate::app;
+use crate::args;
+use crate::config::Config;
+use crate::error::Error;
+use crate::nvim_bridge::{commands, Message, NvimBridge, Request};
+use crate::nvim_gio::{self, GioNeovim};
+use crate::ui::actions;
+use crate::ui::animation::Easing;
+use crate::ui::cmdline::Cmdline;
+use crate::ui::color::{Highlight, HlDefs};
+use crate::ui::common::{self, spawn_local};
+use crate::ui::filetree::FileTree;
+use crate::ui::font::{self, Font};
+use crate::ui::grid::{
+    CellScale, CellWidth, CursorOverlay, GlyphOverflow, Grid, RenderOptions,
+    ScrollAnimation,
+};
+use crate::ui::im_indicator::ImIndicator;
+use crate::ui::messages::Messages;
+use crate::ui::popupmenu::Popupmenu;
+use crate::ui::state::{attach_grid_events, ComponentFonts, UIState, Windows};
+use crate::ui::tabline::Tabline;
+use crate::ui::window::MsgWindow;
+
+/// Editor pane of one nvim: the tabline, the grids, popupmenu, cmdline and
+/// messages. The view doesn't need anything from gnvim's own window, so it
+/// can be put into any gtk window. Widgets can be shown on top of the grids
+/// by adding them to `overlay()`.
+pub struct NeovimView {
+    /// Top level widget of the view.
+    widget: gtk::Box,
+    /// Window that the view is in.
+    window: gtk::Window,
+    /// Neovim instance.
+    nvim: GioNeovim,
+    /// Channel to receive event from nvim.
+    rx: glib::Receiver<Message>,
+    /// Our internal state, containing basically everything we manipulate
+    /// when we receive an event from nvim.
+    state: Rc<RefCell<UIState>>,
+    /// Handles most of the keyboard input.
+    im_context: gtk::IMMulticontext,
+    /// Keyboard input on its way to nvim.
+    input: InputBuffer,
+    /// Called when nvim exits.
+    on_close: Option<Box<dyn Fn()>>,
+}
+
+/// Nvim that a `NeovimView` edits with.
+pub enum Nvim {
+    /// Starts nvim (see `--nvim` and the config file's `[nvim]` section)
+    /// embedded, with these arguments.
+    Spawn(Vec<String>),
+    /// Attaches to the nvim that listens on this socket.
+    Attach(PathBuf),
+}
+
+impl NeovimView {
+    /// Creates a view for `nvim`, to `window`. Nvim is started (or attached
+    /// to) here, and our UI is attached to it. The view is set up from the
+    /// config file, like gnvim's own. Call `start` once the window is shown.
+    pub async fn new(window: &gtk::Window, nvim: Nvim) -> Result<Self, Error> {
+        let config = Config::load();
+        let mut args = args::Args::from_iter(&["gnvim"]);
+
+        let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let bridge = NvimBridge::new(tx.clone());
+        let nvim = match nvim {
+            Nvim::Spawn(nvim_args) => {
+                args.nvim_args = nvim_args;
+                let cmd = args.nvim_cmd(&config);
+                let (nvim, _) = nvim_gio::new_child(
+                    bridge,
+                    cmd.iter().map(OsStr::new).collect(),
+                    tx,
+                )?;
+                nvim
+            }
+            Nvim::Attach(path) => {
+                let conn = nvim_gio::connect_socket(&path)?;
+                let nvim = nvim_gio::new_socket(bridge, conn, tx)?;
+
+                // The nvim wasn't started by us, so our runtime isn't loaded.
+                nvim.command(&args.nvim_runtime_cmd()).await?;
+                nvim
+            }
+        };
+
+        app::setup_nvim(&nvim, &args, &config, true)
+            .await
+            .map_err(Error::Setup)?;
+        let grid_scroll_speed = app::grid_scroll_speed(&nvim).await;
+
+        Self::with_nvim(window, rx, nvim, grid_scroll_speed, &config)
+    }
+
+    /// Creates new view, for an nvim that is already set up.
+    ///
+    /// * `window` - Window for the view. Realized, for the grids' surfaces.
+    /// * `rx` - Channel to receive nvim UI events.
+    /// * `nvim` - Neovim instance to use. Should be the same that is the source
+    ///            of `rx` events.
+    /// * `config` - Our config file.
+    pub(crate) fn with_nvim(
+        window: &gtk::Window,
+        rx: glib::Receiver<Message>,
+        nvim: GioNeovim,
+        grid_scroll_speed: i64,
+        config: &Config,
+    ) -> Result<Self, Error> {
+        // Realize window resources.
+        window.realize();
+
+        // Takes the keyboard focus, for the keyboard input.
+        let widget = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        widget.set_can_focus(true);
+
+        let tabline = Tabline::new(nvim.clone());
+        widget.pack_start(&tabline.get_widget(), false, false, 0);
+
+        // Our root widget for all grids/windows.
+        let overlay = gtk::Overlay::new();
+        widget.pack_start(&overlay, true, true, 0);
+
+        // Create hl defs and initialize 0th element because we'll need to have
+        // something that is accessible for the default grid that we're gonna
+        // make next.
+        let mut hl_defs = HlDefs::default();
+        hl_defs.insert(0, Highlight::default());
+        hl_defs.altfont = config.fonts.altfont.as_ref().and_then(|font| {
+            let res = Font::from_guifont(font);
+            if res.is_err() {
+                error!("Invalid font: {}", font);
+            }
+            res.ok().map(|font| font.family().to_string())
+        });
+
+        let render_options = RenderOptions {
+            disable_ligatures: config.fonts.disable_ligatures,
+            disable_hex_boxes: config.fonts.disable_hex_boxes,
+            glyph_overflow: config
+                .grid_glyph_overflow
+                .as_ref()
+                .and_then(|value| {
+                    let overflow = GlyphOverflow::parse(value);
+                    if overflow.is_none() {
+                        error!("Invalid glyph overflow: {}", value);
+                    }
+                    overflow
+                })
+                .unwrap_or_default(),
+        };
+
+        let font = Font::from_guifont("Monospace:h12").unwrap();
+        let line_space = 0;
+
+        let grid_scroll = ScrollAnimation {
+            speed: grid_scroll_speed,
+            mouse_speed: config.scroll.mouse_speed,
+            messages_speed: config.scroll.messages_speed,
+            easing: config
+                .scroll
+                .easing
+                .as_ref()
+                .and_then(|name| {
+                    let easing = Easing::parse(name);
+                    if easing.is_none() {
+                        error!("Invalid scroll easing: {}", name);
+                    }
+                    easing
+                })
+                .unwrap_or_default(),
+            proportional: config.scroll.proportional,
+        };
+
+        let cell_width = config
+            .grid_cell_width
+            .as_ref()
+            .and_then(|width| {
+                let cell_width = CellWidth::parse(width);
+                if cell_width.is_none() {
+                    error!("Invalid cell width: {}", width);
+                }
+                cell_width
+            })
+            .unwrap_or_default();
+        let cell_scale = CellScale::from_percents(
+            config.grid_cell_width_percent,
+            config.grid_cell_height_percent,
+        );
+
+        // Create default grid.
+        let grid = Grid::new(
+            1,
+            &window.window().unwrap(),
+            font.clone(),
+            line_space,
+            cell_width,
+            cell_scale,
+            render_options,
+            80,
+            30,
+            &hl_defs,
+            true,
+            grid_scroll,
+        )?;
+        let font_options = font::font_options(&config.fonts);
+        if font_options.is_some() {
+            grid.set_font_options(
+                font_options.as_ref(),
+                &window.window().unwrap(),
+            )?;
+        }
+        // Mark the default grid as active at the beginning.
+        grid.set_active(true);
+        overlay.add(&grid.widget());
+
+        let windows_container = gtk::Fixed::new();
+        windows_container.set_widget_name("windows-contianer");
+        let windows_float_container = gtk::Fixed::new();
+        windows_float_container.set_widget_name("windows-contianer-float");
+        let msg_window_container = gtk::Fixed::new();
+        msg_window_container.set_widget_name("message-grid-contianer");
+        overlay.add_overlay(&windows_container);
+        overlay.add_overlay(&msg_window_container);
+        overlay.add_overlay(&windows_float_container);
+        let cursor_overlay = CursorOverlay::new();
+        overlay.add_overlay(&cursor_overlay.widget());
+
+        let css_provider = gtk::CssProvider::new();
+        let msg_window =
+            MsgWindow::new(msg_window_container.clone(), css_provider.clone());
+
+        overlay.set_overlay_pass_through(&windows_container, true);
+        overlay.set_overlay_pass_through(&windows_float_container, true);
+        overlay.set_overlay_pass_through(&msg_window_container, true);
+        overlay.set_overlay_pass_through(&cursor_overlay.widget(), true);
+
+        // When resizing our window (main grid), we'll have to tell neovim to
+        // resize it self also. The notify to nvim is send with a small delay,
+        // so we don't spam it multiple times a second. source_id is used to
+        // track the function timeout. This timeout might be canceled in
+        // redraw even handler if we receive a message that changes the size
+        // of the main grid.
+        let source_id = Rc::new(RefCell::new(None));
+        grid.connect_da_resize(clone!(nvim, source_id => move |rows, cols| {
+
+            // Set timeout to notify nvim about the new size.
+            let new = glib::timeout_add_local(time::Duration::from_millis(30), clone!(nvim, source_id => move || {
+                let nvim = nvim.clone();
+                spawn_local(async move {
+                    if let Err(err) = nvim.ui_try_resize(cols as i64, rows as i64).await {
+                        error!("Error: failed to resize nvim when grid size changed ({:?})", err);
+                    }
+                });
+
+                // Set the source_id to none, so we don't accidentally remove
+                // it since it used at this point.
+                source_id.borrow_mut().take();
+
+                Continue(false)
+            }));
+
+            let mut source_id = source_id.borrow_mut();
+            // If we have earlier timeout, remove it.
+            if let Some(old) = source_id.take() {
+                glib::source::source_remove(old);
+            }
+
+            *source_id = Some(new);
+
+            false
+        }));
+
+        let mouse_move_event = Rc::new(Cell::new(false));
+        attach_grid_events(&grid, nvim.clone(), mouse_move_event.clone());
+
+        // IMMulticontext is used to handle most of the inputs.
+        let input = InputBuffer::new(nvim.clone());
+        let im_context = gtk::IMMulticontext::new();
+        im_context.set_use_preedit(false);
+        im_context.connect_commit(clone!(input => move |_, text| {
+            input.commit(text);
+        }));
+
+        let mut cmdline = Cmdline::new(&overlay, nvim.clone());
+        cmdline.set_floating(config.cmdline_floating);
+        cmdline.set_wildmenu_horizontal(config.wildmenu_horizontal);
+        let messages = Messages::new(&overlay, nvim.clone());
+        let mut im_indicator = ImIndicator::new(&overlay);
+        im_indicator.enable(!config.disable_im_indicator);
+
+        let mut grids = HashMap::new();
+        grids.insert(1, grid);
+
+        add_css_provider!(&css_provider, window);
+
+        let mut popupmenu = Popupmenu::new(&overlay, nvim.clone());
+        popupmenu.configure(&config.popupmenu);
+
+        let view = NeovimView {
+            widget,
+            window: window.clone(),
+            rx,
+            im_context,
+            input,
+            on_close: None,
+            state: Rc::new(RefCell::new(UIState {
+                css_provider,
+                windows: Windows::new(),
+                windows_container,
+                msg_window_container,
+                msg_window,
+                windows_float_container,
+                grids,
+                mode_infos: vec![],
+                current_grid: 1,
+                wildmenu_shown: false,
+                popupmenu,
+                cmdline,
+                messages,
+                im_indicator,
+                overlay,
+                cursor_overlay,
+                tabline,
+                toolbar: None,
+                file_tree: None,
+                resize_source_id: source_id,
+                redraw_source_id: None,
+                im_location_source_id: None,
+                hl_defs,
+                resize_on_flush: None,
+                hl_changed: false,
+                font,
+                line_space,
+                cell_width,
+                cell_scale,
+                render_options,
+                font_options,
+                component_fonts: ComponentFonts::new(&config.fonts),
+                current_mode: None,
+                enable_cursor_animations: true,
+                enable_cursor_particles: false,
+                debug_overlay: false,
+                grid_scroll,
+                font_wide: None,
+                mouse_move_event,
+                mouse_focus: Rc::new(Cell::new(false)),
+                grid_padding: 0,
+                zen: None,
+                project: None,
+                zen_config: config.zen.clone(),
+                title: String::new(),
+                title_prefix: String::new(),
+                inspector: None,
+            })),
+            nvim,
+        };
+
+        {
+            let mut state = view.state.borrow_mut();
+            state.set_grid_padding(config.grid_padding);
+            // Until 'guifont' is set, the components get their own fonts
+            // from the config, or the default one.
+            let font = state.font.clone();
+            state.set_component_fonts(&font);
+        }
+
+        Ok(view)
+    }
+
+    pub fn widget(&self) -> gtk::Widget {
+        self.widget.clone().upcast()
+    }
+
+    /// Keyboard input on its way to nvim, for input from elsewhere (e.g.
+    /// pastes) to go in order with it.
+    pub fn input(&self) -> InputBuffer {
+        self.input.clone()
+    }
+
+    /// Overlay on top of the grids.
+    pub fn overlay(&self) -> gtk::Overlay {
+        self.state.borrow().overlay.clone()
+    }
+
+    /// Sets `f` to be called when nvim exits, e.g. for closing the window.
+    pub fn connect_close<F: Fn() + 'static>(&mut self, f: F) {
+        self.on_close = Some(Box::new(f));
+    }
+
+    /// Sets the toolbar that is hidden in the zen mode.
+    pub fn set_toolbar(&self, toolbar: Option<gtk::Toolbar>) {
+        self.state.borrow_mut().toolbar = toolbar;
+    }
+
+    /// Sets the file tree that follows nvim's current directory.
+    pub fn set_file_tree(&self, file_tree: FileTree) {
+        self.state.borrow_mut().file_tree = Some(file_tree);
+    }
+
+    /// Starts to listen events from `rx` (e.g. from nvim) and processing
+    /// those, and the keyboard input of the view. Should be called once the
+    /// window is shown. Takes the keyboard focus.
+    pub fn start(self) {
+        let NeovimView {
+            widget,
+            rx,
+            state,
+            window,
+            nvim,
+            im_context,
+            input,
+            on_close,
+        } = self;
+
+        {
+            let mut state = state.borrow_mut();
+            state.grids.get_mut(&1).unwrap().set_im_context(&im_context);
+            state.cmdline.hide();
+            // Shown once we know the tabs and 'showtabline'.
+            state.tabline.get_widget().hide();
+        }
+
+        let (layout_group, cmdline) = {
+            let state = state.borrow();
+            state.im_indicator.connect_im_context(&im_context);
+            (
+                state.im_indicator.layout_group(),
+                state.cmdline.focus_widget(),
+            )
+        };
+        connect_input(&widget, &im_context, &input, layout_group);
+        connect_focus(
+            &widget,
+            &[widget.clone().upcast(), cmdline],
+            &nvim,
+            &im_context,
+        );
+        connect_grid_focus(&widget, &state);
+        widget.grab_focus();
+
+        rx.attach(None, move |message| {
+            match message {
+                // Handle a notify.
+                Message::Notify(notify) => {
+                    let res = state
+                        .borrow_mut()
+                        .handle_notify(&window, notify, &nvim);
+                    if let Err(err) = res {
+                        error!("Failed to handle a notify: {:?}", err);
+                    }
+
+                    schedule_grid_redraw(&state);
+                    schedule_im_cursor_location(&state);
+                }
+                // Handle a request.
+                Message::Request(tx, request) => {
+                    let mut state = state.borrow_mut();
+                    handle_request(request, &mut state, &window, &nvim, tx);
+                }
+                // Handle close.
+                Message::Close => {
+                    if let Some(ref on_close) = on_close {
+                        on_close();
+                    }
+                    return Continue(false);
+                }
+            }
+
+            Continue(true)
+        });
+    }
+}
+
+/// Redraws the grids that are being redrawn (see `Grid::redraw`) in
+/// chunks when idle, so that input and drawing are handled in between.
+fn schedule_grid_redraw(state: &Rc<RefCell<UIState>>) {
+    let mut state_ref = state.borrow_mut();
+    if state_ref.redraw_source_id.is_some() || !state_ref.grids_redrawing() {
+        return;
+    }
+
+    let weak = Rc::downgrade(state);
+    state_ref.redraw_source_id = Some(glib::idle_add_local(move || {
+        let state = upgrade_weak!(weak, Continue(false));
+        let mut state = state.borrow_mut();
+
+        let redrawing = state.redraw_grids();
+        if !redrawing {
+            state.redraw_source_id = None;
+        }
+
+        Continue(redrawing)
+    }));
+}
+
+/// Updates the input method's cursor location when idle, after GTK has
+/// laid out the grids.
+fn schedule_im_cursor_location(state: &Rc<RefCell<UIState>>) {
+    let mut state_ref = state.borrow_mut();
+    if state_ref.im_location_source_id.is_some() {
+        return;
+    }
+
+    let weak = Rc::downgrade(state);
+    state_ref.im_location_source_id = Some(glib::idle_add_local(move || {
+        let state = upgrade_weak!(weak, Continue(false));
+        let mut state = state.borrow_mut();
+
+        state.update_im_cursor_location();
+        state.im_location_source_id = None;
+
+        Continue(false)
+    }));
+}
+
+/// Input for nvim, in the order it's sent.
+enum Input {
+    /// Keys in nvim's key notation, for `nvim_input`.
+    Keys(String),
+    /// Text for `nvim_paste`.
+    Paste(String),
+}
+
+/// Keyboard input that is sent to nvim in one `nvim_input` call once the key
+/// events that are already queued are handled, so that fast typing and key
+/// repeat don't cost a call per key. The input from the input method and from
+/// the keys goes through the same buffer, and the same channel with pastes,
+/// so it stays in order.
+#[derive(Clone)]
+pub struct InputBuffer {
+    pending: Rc<RefCell<String>>,
+    tx: mpsc::UnboundedSender<Input>,
+}
+
+impl InputBuffer {
+    fn new(nvim: GioNeovim) -> Self {
+        let (tx, mut rx) = mpsc::unbounded();
+        spawn_local(async move {
+            while let Some(input) = rx.next().await {
+                match input {
+                    Input::Keys(keys) => {
+                        nvim.input(&keys).await.expect("Couldn't send input");
+                    }
+                    Input::Paste(text) => {
+                        if let Err(err) = common::paste(&nvim, &text).await {
+                            error!("Failed to paste: {}", err);
+                        }
+                    }
+                }
+            }
+        });
+
+        InputBuffer {
+            pending: Rc::new(RefCell::new(String::new())),
+            tx,
+        }
+    }
+
+    /// Queues keys in nvim's key notation.
+    fn push(&self, keys: &str) {
+        let mut pending = self.pending.borrow_mut();
+        if pending.is_empty() {
+            let buffer = self.clone();
+            glib::idle_add_local_once(move || buffer.flush());
+        }
+
+        pending.push_str(keys);
+    }
+
+    /// Queues text from the input method, which is pasted if it looks like a
+    /// paste, and typed otherwise.
+    fn commit(&self, text: &str) {
+        if is_paste(text) {
+            self.paste(text.to_string());
+        } else {
+            // "<" needs to be escaped for nvim.input()
+            self.push(&text.replace('<', "<lt>"));
+        }
+    }
+
+    /// Queues `text` to be pasted, after the keys that are already queued.
+    pub fn paste(&self, text: String) {
+        self.flush();
+        self.send(Input::Paste(text));
+    }
+
+    fn flush(&self) {
+        let keys = self.pending.take();
+        if !keys.is_empty() {
+            self.send(Input::Keys(keys));
+        }
+    }
+
+    fn send(&self, input: Input) {
+        if self.tx.unbounded_send(input).is_err() {
+            error!("Failed to send input, the input channel is closed");
+        }
+    }
+}
+
+/// If the input method's commit `text` looks like a paste, i.e. it has a
+/// line break. E.g. clipboard managers and xdotool commit whole texts at
+/// once. Input methods commit long phrases too, so the length doesn't tell.
+fn is_paste(text: &str) -> bool {
+    text.contains(['\n', '\r'])
+}
+
+/// Sends the keyboard input of `widget` (which has the focus) to nvim
+/// (through `input`). The keyboard layout group of the key presses is stored
+/// to `layout_group`.
+fn connect_input(
+    widget: &gtk::Box,
+    im_context: &gtk::IMMulticontext,
+    input: &InputBuffer,
+    layout_group: Rc<Cell<u8>>,
+) {
+    widget.connect_key_press_event(
+        clone!(im_context, input => move |widget, e| {
+            layout_group.set(e.group());
+
+            if im_context.filter_keypress(e) {
+                Inhibit(true)
+            } else if widget
+                .toplevel()
+                .and_then(|w| w.downcast::<gtk::Window>().ok())
+                .and_then(|window| window.application())
+                .is_some_and(|app| actions::activate_latin_accel(&app, e))
+            {
+                // Key was an accelerator for one of our actions (the window
+                // has already tried the rest).
+                Inhibit(true)
+            } else {
+                if let Some(keys) = event_to_nvim_input(e) {
+                    input.push(&keys);
+                    return Inhibit(true);
+                } else {
+                    debug!(
+                        "Failed to turn input event into nvim key (keyval: {})",
+                        e.keyval()
+                    )
+                }
+
+                Inhibit(false)
+            }
+        }),
+    );
+
+    widget.connect_key_release_event(clone!(im_context => move |_, e| {
+        im_context.filter_keypress(e);
+
+        Inhibit(false)
+    }));
+}
+
+/// Tells nvim and the input method when the keyboard focus enters or leaves
+/// the view, i.e. any of `focusables` (`widget`, and the widgets that take
+/// the focus for a while, like the cmdline). If the focus is lost to nowhere
+/// (e.g. the cmdline was hidden), `widget` takes it back.
+fn connect_focus(
+    widget: &gtk::Box,
+    focusables: &[gtk::Widget],
+    nvim: &GioNeovim,
+    im_context: &gtk::IMMulticontext,
+) {
+    let focused = Rc::new(Cell::new(false));
+    let focusables = Rc::new(focusables.to_vec());
+
+    // Checked once the focus has settled, so that moving the focus between
+    // our widgets isn't a focus change for nvim.
+    let check = clone!(widget, focusables, nvim, im_context => move || {
+        let has_focus = focusables.iter().any(|w| w.has_focus());
+
+        let window = widget
+            .toplevel()
+            .and_then(|w| w.downcast::<gtk::Window>().ok());
+        if !has_focus
+            && focused.get()
+            && window.is_some_and(|w| w.is_active() && w.focus().is_none())
+        {
+            widget.grab_focus();
+            return Continue(false);
+        }
+
+        if has_focus == focused.replace(has_focus) {
+            return Continue(false);
+        }
+
+        let event = if has_focus {
+            im_context.focus_in();
+            "FocusGained"
+        } else {
+            im_context.focus_out();
+            "FocusLost"
+        };
+
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            let cmd = format!(
+                "if exists('#{event}') | doautocmd {event} | endif",
+                event = event
+            );
+            if let Err(err) = nvim.command(&cmd).await {
+                error!("Failed to issue {} autocmd: {:?}", event, err)
+            }
+        });
+
+        Continue(false)
+    });
+    let check = Rc::new(check);
+
+    for focusable in focusables.iter() {
+        focusable.connect_focus_in_event(clone!(check => move |_, _| {
+            glib::idle_add_local(clone!(check => move || check()));
+            Inhibit(false)
+        }));
+        focusable.connect_focus_out_event(clone!(check => move |_, _| {
+            glib::idle_add_local(clone!(check => move || check()));
+            Inhibit(false)
+        }));
+    }
+}
+
+/// Gives the keyboard focus to `widget` when a grid is clicked.
+fn connect_grid_focus(widget: &gtk::Box, state: &Rc<RefCell<UIState>>) {
+    let weak = Rc::downgrade(state);
+    widget.connect_button_press_event(move |widget, e| {
+        let state = upgrade_weak!(weak, Inhibit(false));
+        let mut event = gdk::Event::clone(e);
+        let clicked = match gtk::event_widget(&mut event) {
+            Some(clicked) => clicked,
+            None => return Inhibit(false),
+        };
+
+        let state = state.borrow();
+        let on_grid = state.grids.values().any(|grid| {
+            let grid = grid.widget();
+            clicked == grid || clicked.is_ancestor(&grid)
+        });
+        if on_grid {
+            widget.grab_focus();
+        }
+
+        Inhibit(false)
+    });
+}
+
+/// Handles `request`. The response is sent to `tx`, possibly after this
+/// function has returned (e.g. for dialogs).
+fn handle_request(
+    request: Request,
+    state: &mut UIState,
+    window: &gtk::Window,
+    nvim: &GioNeovim,
+    tx: oneshot::Sender<Result<Value, Value>>,
+) {
+    match request {
+        Request::GetMetrics => {
+            let grid = state.grids.get(&1).unwrap();
+            let metrics = grid.get_grid_metrics();
+            respond(
+                tx,
+                Ok(Value::Map(vec![
+                    ("rows".into(), metrics.rows.into()),
+                    ("cols".into(), metrics.cols.into()),
+                    ("cell_width".into(), metrics.cell_width.into()),
+                    ("cell_height".into(), metrics.cell_height.into()),
+                    ("width".into(), metrics.width.into()),
+                    ("height".into(), metrics.height.into()),
+                    ("line_space".into(), state.line_space.into()),
+                ])),
+            );
+        }
+        Request::GetVersion => respond(tx, Ok(args::version().into())),
+        Request::GetCapabilities => {
+            let requests = Request::NAMES.iter().map(|name| (*name).into());
+            respond(
+                tx,
+                Ok(Value::Map(vec![(
+                    "requests".into(),
+                    Value::Array(requests.collect()),
+                )])),
+            );
+        }
+        Request::ClipboardGet(register) => {
+            let clipboard = match clipboard_for_register(&register) {
+                Some(clipboard) => clipboard,
+                None => {
+                    let err = format!("Invalid register: {}", register);
+                    return respond(tx, Err(err.into()));
+                }
+            };
+
+            clipboard.request_text(move |_, text| {
+                let text = text.unwrap_or_default();
+                // Text ending with a newline is linewise.
+                let (text, regtype) = match text.strip_suffix('\n') {
+                    Some(text) => (text, "V"),
+                    None => (text, "v"),
+                };
+
+                let lines = text.split('\n').map(Value::from).collect();
+                respond(
+                    tx,
+                    Ok(Value::Array(vec![Value::Array(lines), regtype.into()])),
+                );
+            });
+        }
+        Request::ClipboardSet(register, lines, regtype) => {
+            match clipboard_for_register(&register) {
+                Some(clipboard) => {
+                    let mut text = lines.join("\n");
+                    if regtype == "V" {
+                        text.push('\n');
+                    }
+                    clipboard.set_text(&text);
+                    respond(tx, Ok(Value::Nil));
+                }
+                None => {
+                    let err = format!("Invalid register: {}", register);
+                    respond(tx, Err(err.into()));
+                }
+            }
+        }
+        Request::ListFonts => {
+            let fonts = font::monospace_families().into_iter();
+            respond(tx, Ok(Value::Array(fonts.map(Value::from).collect())));
+        }
+        Request::CompleteCommand(line) => {
+            let candidates = commands::complete(&line).into_iter();
+            respond(
+                tx,
+                Ok(Value::Array(candidates.map(Value::from).collect())),
+            );
+        }
+        Request::Event(event) => {
+            state.handle_gnvim_event(window, &event, nvim);
+            respond(tx, Ok(Value::Nil));
+        }
+        Request::FileDialog(save) => {
+            let (title, action) = if save {
+                ("Save File", gtk::FileChooserAction::Save)
+            } else {
+                ("Open File", gtk::FileChooserAction::Open)
+            };
+
+            let dialog = gtk::FileChooserNative::new(
+                Some(title),
+                Some(window),
+                action,
+                None,
+                None,
+            );
+            dialog.set_do_overwrite_confirmation(true);
+
+            spawn_local(async move {
+                let res = dialog.run_future().await;
+                let path = dialog
+                    .filename()
+                    .filter(|_| res == gtk::ResponseType::Accept)
+                    .map(|path| path.to_string_lossy().into_owned().into());
+
+                respond(tx, Ok(path.unwrap_or(Value::Nil)));
+            });
+        }
+    }
+}
+
+fn respond(
+    tx: oneshot::Sender<Result<Value, Value>>,
+    res: Result<Value, Value>,
+) {
+    if tx.send(res).is_err() {
+        error!("Failed to respond to a request");
+    }
+}
+
+fn clipboard_for_register(register: &str) -> Option<gtk::Clipboard> {
+    let selection = match register {
+        "+" => gdk::SELECTION_CLIPBOARD,
+        "*" => gdk::SELECTION_PRIMARY,
+        _ => return None,
+    };
+
+    Some(gtk::Clipboard::get(&selection))
+}
+
+fn keyname_to_nvim_key(s: &str) -> Option<&str> {
+    // Originally sourced from python-gui.
+    match s {
+        "asciicircum" => Some("^"), // fix #137
+        "slash" => Some("/"),
+        "backslash" => Some("\\"),
+        "dead_circumflex" => Some("^"),
+        "at" => Some("@"),
+        "numbersign" => Some("#"),
+        "dollar" => Some("$"),
+        "percent" => Some("%"),
+        "ampersand" => Some("&"),
+        "asterisk" => Some("*"),
+        "parenleft" => Some("("),
+        "parenright" => Some(")"),
+        "underscore" => Some("_"),
+        "plus" => Some("+"),
+        "minus" => Some("-"),
+        "bracketleft" => Some("["),
+        "bracketright" => Some("]"),
+        "braceleft" => Some("{"),
+        "braceright" => Some("}"),
+        "dead_diaeresis" => Some("\""),
+        "dead_acute" => Some("\'"),
+        "less" => Some("<"),
+        "greater" => Some(">"),
+        "comma" => Some(","),
+        "period" => Some("."),
+        "space" => Some("Space"),
+        "BackSpace" => Some("BS"),
+        "Insert" => Some("Insert"),
+        "Return" => Some("CR"),
+        "Escape" => Some("Esc"),
+        "Delete" => Some("Del"),
+        "Page_Up" => Some("PageUp"),
+        "Page_Down" => Some("PageDown"),
+        "Enter" => Some("CR"),
+        "ISO_Left_Tab" => Some("Tab"),
+        "Tab" => Some("Tab"),
+        "Up" => Some("Up"),
+        "Down" => Some("Down"),
+        "Left" => Some("Left"),
+        "Right" => Some("Right"),
+        "Home" => Some("Home"),
+        "End" => Some("End"),
+        "F1" => Some("F1"),
+        "F2" => Some("F2"),
+        "F3" => Some("F3"),
+        "F4" => Some("F4"),
+        "F5" => Some("F5"),
+        "F6" => Some("F6"),
+        "F7" => Some("F7"),
+        "F8" => Some("F8"),
+        "F9" => Some("F9"),
+        "F10" => Some("F10"),
+        "F11" => Some("F11"),
+        "F12" => Some("F12"),
+        _ => None,
+    }
+}
+
+fn event_to_nvim_input(e: &gdk::EventKey) -> Option<String> {
+    let keyval = e.keyval();
+    let state = e.state();
+
+    // Modifiers that the keyboard layout used to produce the key (e.g. shift
+    // for `#`, or AltGr for `@`).
+    let consumed = gdk::Display::default()
+        .and_then(|display| gdk::Keymap::for_display(&display))
+        .and_then(|keymap| {
+            keymap.translate_keyboard_state(
+                u32::from(e.hardware_keycode()),
+                state,
+                i32::from(e.group()),
+            )
+        })
+        .map(|(_, _, _, consumed)| consumed)
+        .unwrap_or_else(gdk::ModifierType::empty);
+
+    let key = match keyval.to_unicode().filter(|c| !c.is_control() && *c != ' ')
+    {
+        Some(c) => c.to_string(),
+        None => keyname_to_nvim_key(keyval.name()?.as_str())?.to_string(),
+    };
+
+    Some(nvim_input(&key, state, consumed))
+}
+
+/// Turns `key` (a character, or a key name from `keyname_to_nvim_key`) into
+/// nvim's key notation. The modifiers in `consumed` are already encoded in a
+/// character, so they're left out, except for the shift of a letter with
+/// ctrl, as ctrl chords are case-insensitive in nvim.
+fn nvim_input(
+    key: &str,
+    state: gdk::ModifierType,
+    consumed: gdk::ModifierType,
+) -> String {
+    let mut chars = key.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    };
+
+    let mods = state - consumed;
+    let ctrl = mods.contains(gdk::ModifierType::CONTROL_MASK);
+    let alt = mods.contains(gdk::ModifierType::MOD1_MASK);
+    let shift = match c {
+        Some(c) => {
+            mods.contains(gdk::ModifierType::SHIFT_MASK)
+                || (ctrl && c.is_uppercase())
+        }
+        // E.g. shift+tab gives ISO_Left_Tab, with the shift consumed.
+        None => state.contains(gdk::ModifierType::SHIFT_MASK),
+    };
+
+    let key = if key == "<" { "lt" } else { key };
+    if c.is_some() && !shift && !ctrl && !alt {
+        return if key == "lt" {
+            String::from("<lt>")
+        } else {
+            key.to_string()
+        };
+    }
+
+    // In the same order as nvim's `keytrans()`.
+    let mut input = String::from("");
+    if alt {
+        input.push_str("A-");
+    }
+    if ctrl {
+        input.push_str("C-");
+    }
+    if shift {
+        input.push_str("S-");
+    }
+    input.push_str(key);
+
+    format!("<{}>", input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_paste() {
+        assert!(!is_paste("a"));
+        assert!(!is_paste("你好"));
+        assert!(is_paste("foo\nbar"));
+        assert!(is_paste("foo\r"));
+        assert!(!is_paste("fn main() {}; fn foo() {}"));
+        assert!(!is_paste("今日はとても良い天気ですね、散歩に行きましょう"));
+    }
+
+    #[test]
+    fn test_nvim_input() {
+        let none = gdk::ModifierType::empty();
+        let shift = gdk::ModifierType::SHIFT_MASK;
+        let ctrl = gdk::ModifierType::CONTROL_MASK;
+        let alt = gdk::ModifierType::MOD1_MASK;
+        let altgr = gdk::ModifierType::MOD5_MASK;
+
+        // Shifted symbols.
+        assert_eq!(nvim_input("#", shift, shift), "#");
+        assert_eq!(nvim_input("#", shift | alt, shift), "<A-#>");
+        assert_eq!(nvim_input("<", shift, shift), "<lt>");
+        assert_eq!(nvim_input("<", shift | ctrl, shift), "<C-lt>");
+
+        // AltGr, also when it comes as ctrl+alt.
+        assert_eq!(nvim_input("@", altgr, altgr), "@");
+        assert_eq!(nvim_input("€", ctrl | alt, ctrl | alt), "€");
+        assert_eq!(nvim_input("@", altgr | alt, altgr), "<A-@>");
+
+        // Ctrl chords.
+        assert_eq!(nvim_input("a", ctrl, none), "<C-a>");
+        assert_eq!(nvim_input("A", shift | ctrl, shift), "<C-S-A>");
+        assert_eq!(nvim_input("A", shift | alt, shift), "<A-A>");
+
+        // Named keys.
+        assert_eq!(nvim_input("Tab", shift, shift), "<S-Tab>");
+        assert_eq!(nvim_input("Space", none, none), "<Space>");
+        assert_eq!(nvim_input("Up", shift | ctrl, none), "<C-S-Up>");
+    }
+}
@@ -0,0 +1,304 @@
+use std::cell::Cell;
+use std::path::Path;
+use std::rc::Rc;
+
+use gtk::prelude::*;
+use gtk::{glib, pango};
+
+use log::error;
+
+use crate::files::{self, FileArg};
+use crate::nvim_gio::GioNeovim;
+use crate::ui::common::{gtk_recent_files, spawn_local};
+
+/// Maximum number of recent files shown.
+const MAX_RECENT: usize = 8;
+
+const CSS: &str = "
+frame.welcome {
+    background-color: @theme_bg_color;
+    border-radius: 6px;
+    padding: 12px;
+}
+frame.welcome button.recent label {
+    font-family: monospace;
+}
+";
+
+/// Start screen, shown on top of the grid when we're started without any
+/// files. Hidden on the first keypress.
+pub struct Welcome {
+    frame: gtk::Frame,
+    /// Container for the recent files' title and buttons.
+    recent: gtk::Box,
+    /// Set on the first keypress, so we're not shown after that.
+    dismissed: Rc<Cell<bool>>,
+    nvim: GioNeovim,
+}
+
+impl Welcome {
+    /// Creates the (hidden) start screen. Must be called before the
+    /// window's other key press handlers are connected, so we see every
+    /// keypress.
+    pub fn new(
+        parent: &gtk::Overlay,
+        window: &gtk::ApplicationWindow,
+        nvim: GioNeovim,
+    ) -> Self {
+        let css_provider = gtk::CssProvider::new();
+        if let Err(err) =
+            CssProviderExt::load_from_data(&css_provider, CSS.as_bytes())
+        {
+            error!("Failed to load welcome screen css: {}", err);
+        }
+
+        let new_file = gtk::Button::with_mnemonic("_New File");
+        let open_folder = gtk::Button::with_mnemonic("Open _Folder…");
+        let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        buttons.set_halign(gtk::Align::Center);
+        buttons.pack_start(&new_file, false, false, 0);
+        buttons.pack_start(&open_folder, false, false, 0);
+
+        let title = gtk::Label::new(None);
+        title.set_markup("<b>Recent Files</b>");
+        title.set_xalign(0.0);
+
+        let recent = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        recent.pack_start(&title, false, false, 6);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        box_.pack_start(&recent, false, false, 0);
+        box_.pack_start(&buttons, false, false, 6);
+
+        let frame = gtk::Frame::new(None);
+        frame.set_halign(gtk::Align::Center);
+        frame.set_valign(gtk::Align::Center);
+        frame.set_no_show_all(true);
+        frame.style_context().add_class("welcome");
+        frame.add(&box_);
+        parent.add_overlay(&frame);
+
+        add_css_provider!(&css_provider, frame);
+
+        new_file.connect_clicked(clone!(frame, nvim => move |_| {
+            frame.hide();
+
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if let Err(err) = nvim.command("enew").await {
+                    error!("Failed to create a new file: {}", err);
+                }
+            });
+        }));
+
+        open_folder.connect_clicked(clone!(frame, nvim, window => move |_| {
+            let dialog = gtk::FileChooserNative::new(
+                Some("Open Folder"),
+                Some(&window),
+                gtk::FileChooserAction::SelectFolder,
+                None,
+                None,
+            );
+
+            let frame = frame.clone();
+            let nvim = nvim.clone();
+            spawn_local(async move {
+                if dialog.run_future().await != gtk::ResponseType::Accept {
+                    return;
+                }
+
+                let path = match dialog.filename() {
+                    Some(path) => path,
+                    None => return,
+                };
+
+                frame.hide();
+
+                let res = nvim
+                    .call_function(
+                        "fnameescape",
+                        vec![path.to_string_lossy().as_ref().into()],
+                    )
+                    .await;
+                let res = match res {
+                    Ok(path) => {
+                        let path = path.as_str().unwrap_or_default();
+                        nvim.command(&format!("cd {}", path)).await
+                    }
+                    Err(err) => Err(err),
+                };
+
+                if let Err(err) = res {
+                    error!("Failed to open folder: {}", err);
+                }
+            });
+        }));
+
+        let dismissed = Rc::new(Cell::new(false));
+        window.connect_key_press_event(
+            clone!(frame, dismissed => move |_, _| {
+                dismissed.set(true);
+                frame.hide();
+                Inhibit(false)
+            }),
+        );
+
+        Welcome {
+            frame,
+            recent,
+            dismissed,
+            nvim,
+        }
+    }
+
+    /// Fills the recent files from nvim's `v:oldfiles` and gtk's recent
+    /// files, and shows the start screen.
+    pub fn show(&self) {
+        let frame = self.frame.clone();
+        let recent = self.recent.clone();
+        let dismissed = self.dismissed.clone();
+        let nvim = self.nvim.clone();
+
+        spawn_local(async move {
+            let oldfiles = match nvim.get_vvar("oldfiles").await {
+                Ok(files) => files
+                    .as_array()
+                    .map(|files| {
+                        files
+                            .iter()
+                            .filter_map(|f| f.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                Err(err) => {
+                    error!("Failed to get oldfiles: {}", err);
+                    vec![]
+                }
+            };
+
+            let gtk_recent = gtk_recent_files();
+
+            let home = glib::home_dir();
+            let files = recent_files(&oldfiles, &gtk_recent, |path| {
+                Path::new(path).is_file()
+            });
+
+            let has_files = !files.is_empty();
+            for path in files {
+                let label = gtk::Label::new(Some(&display_path(
+                    &path,
+                    &home.to_string_lossy(),
+                )));
+                label.set_xalign(0.0);
+                label.set_ellipsize(pango::EllipsizeMode::Start);
+                label.set_max_width_chars(60);
+
+                let button = gtk::Button::new();
+                button.set_relief(gtk::ReliefStyle::None);
+                button.style_context().add_class("recent");
+                button.set_tooltip_text(Some(&path));
+                button.add(&label);
+
+                button.connect_clicked(clone!(frame, nvim => move |_| {
+                    frame.hide();
+
+                    let file = FileArg {
+                        path: path.clone(),
+                        line: None,
+                        col: None,
+                    };
+                    let nvim = nvim.clone();
+                    spawn_local(async move {
+                        if let Err(err) = files::open_file(&nvim, &file).await
+                        {
+                            error!("Failed to open '{}': {}", file.path, err);
+                        }
+                    });
+                }));
+
+                recent.pack_start(&button, false, false, 0);
+            }
+
+            // The user was quicker than us.
+            if dismissed.get() {
+                return;
+            }
+
+            frame.show_all();
+            // Without any recent files, only the buttons are shown.
+            recent.set_visible(has_files);
+        });
+    }
+}
+
+/// Merges nvim's `oldfiles` and gtk's `gtk_recent` files, in that order,
+/// without duplicates. Files for which `exists` returns false are skipped.
+fn recent_files<F>(
+    oldfiles: &[String],
+    gtk_recent: &[String],
+    exists: F,
+) -> Vec<String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut files: Vec<String> = vec![];
+
+    for file in oldfiles.iter().chain(gtk_recent.iter()) {
+        if files.len() == MAX_RECENT {
+            break;
+        }
+
+        // Terminal buffers can't be reopened.
+        if file.starts_with("term://") || files.contains(file) || !exists(file)
+        {
+            continue;
+        }
+
+        files.push(file.clone());
+    }
+
+    files
+}
+
+/// Shortens `path` under `home` to start with `~`.
+fn display_path(path: &str, home: &str) -> String {
+    match path.strip_prefix(home) {
+        Some(rest) if !home.is_empty() && rest.starts_with('/') => {
+            format!("~{}", rest)
+        }
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_recent_files() {
+        let oldfiles = strings(&["/a", "term:///bin/sh", "/b", "/gone"]);
+        let gtk_recent = strings(&["/c", "/a"]);
+
+        assert_eq!(
+            recent_files(&oldfiles, &gtk_recent, |f| f != "/gone"),
+            strings(&["/a", "/b", "/c"])
+        );
+
+        let many = (0..20).map(|i| format!("/{}", i)).collect::<Vec<_>>();
+        assert_eq!(recent_files(&many, &[], |_| true).len(), MAX_RECENT);
+    }
+
+    #[test]
+    fn test_display_path() {
+        assert_eq!(display_path("/home/me/foo.rs", "/home/me"), "~/foo.rs");
+        assert_eq!(
+            display_path("/home/meh/foo.rs", "/home/me"),
+            "/home/meh/foo.rs"
+        );
+        assert_eq!(display_path("/tmp/foo.rs", ""), "/tmp/foo.rs");
+    }
+}
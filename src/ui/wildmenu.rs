@@ -1,3 +1,4 @@
+use gtk::glib;
 use gtk::prelude::*;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -18,7 +19,14 @@ struct State {
 pub struct Wildmenu {
     css_provider: gtk::CssProvider,
     frame: gtk::Frame,
+    /// Vertical list of the items.
     list: gtk::ListBox,
+    scrolled_list: gtk::ScrolledWindow,
+    /// Single row (horizontal) bar of the items.
+    bar: gtk::FlowBox,
+    scrolled_bar: gtk::ScrolledWindow,
+    /// If the items are shown in the `bar`, instead of the `list`.
+    horizontal: bool,
 
     state: Rc<RefCell<State>>,
 }
@@ -40,7 +48,24 @@ impl Wildmenu {
             .set_policy(gtk::PolicyType::Automatic, gtk::PolicyType::Automatic);
         scrolledwindow.add(&list);
 
-        frame.add(&scrolledwindow);
+        let bar = gtk::FlowBox::new();
+        bar.set_selection_mode(gtk::SelectionMode::Single);
+        bar.set_activate_on_single_click(true);
+        bar.set_homogeneous(false);
+
+        let scrolled_bar = gtk::ScrolledWindow::new(
+            None::<&gtk::Adjustment>,
+            None::<&gtk::Adjustment>,
+        );
+        scrolled_bar
+            .set_policy(gtk::PolicyType::External, gtk::PolicyType::Never);
+        scrolled_bar.add(&bar);
+        scrolled_bar.set_no_show_all(true);
+
+        let box_ = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        box_.pack_start(&scrolledwindow, true, true, 0);
+        box_.pack_start(&scrolled_bar, true, true, 0);
+        frame.add(&box_);
 
         let frame_weak = frame.downgrade();
         // Make sure our container grows to certain height.
@@ -62,30 +87,22 @@ impl Wildmenu {
         let state = Rc::new(RefCell::new(State::default()));
 
         // If user selects some row with a mouse, notify nvim about it.
-        list.connect_row_activated(clone!(state => move |_, row| {
-            let prev = state.borrow().selected;
-            let new = row.index();
-
-            let op = if new > prev { "<Tab>" } else { "<S-Tab>" };
-
-            for _ in 0..(new - prev).abs() {
-                // NOTE(ville): nvim doesn't like single input with many
-                //              tabs in it, so we'll have to send each
-                //              individually.
-                let nvim = nvim.clone();
-                spawn_local(async move {
-                    nvim.input(op)
-                        .await
-                        .unwrap();
-                })
-            }
+        list.connect_row_activated(clone!(state, nvim => move |_, row| {
+            select_with_tabs(&nvim, &state, row.index());
+        }));
+        bar.connect_child_activated(clone!(state => move |_, child| {
+            select_with_tabs(&nvim, &state, child.index());
         }));
 
-        add_css_provider!(&css_provider, list, frame);
+        add_css_provider!(&css_provider, list, frame, bar);
 
         Wildmenu {
             css_provider,
             list,
+            scrolled_list: scrolledwindow,
+            bar,
+            scrolled_bar,
+            horizontal: false,
             frame,
 
             state,
@@ -104,11 +121,27 @@ impl Wildmenu {
         self.frame.hide();
     }
 
+    /// Sets if the items are shown in a single row, instead of a list.
+    pub fn set_horizontal(&mut self, horizontal: bool) {
+        self.clear();
+        self.horizontal = horizontal;
+
+        self.scrolled_list.set_no_show_all(horizontal);
+        self.scrolled_list.set_visible(!horizontal);
+        self.scrolled_bar.set_no_show_all(!horizontal);
+        self.scrolled_bar.set_visible(horizontal);
+    }
+
     pub fn clear(&mut self) {
         let mut children = self.list.children();
         while let Some(item) = children.pop() {
             self.list.remove(&item);
         }
+
+        let mut children = self.bar.children();
+        while let Some(item) = children.pop() {
+            self.bar.remove(&item);
+        }
     }
 
     pub fn set_items(&mut self, items: &[nvim_bridge::CompletionItem]) {
@@ -118,21 +151,54 @@ impl Wildmenu {
             let label = gtk::Label::new(Some(item.word.as_str()));
             label.set_halign(gtk::Align::Start);
 
-            let row = gtk::ListBoxRow::new();
-            row.add(&label);
+            if self.horizontal {
+                let child = gtk::FlowBoxChild::new();
+                child.add(&label);
 
-            add_css_provider!(&self.css_provider, row, label);
+                add_css_provider!(&self.css_provider, child, label);
 
-            self.list.add(&row);
+                self.bar.add(&child);
+            } else {
+                let row = gtk::ListBoxRow::new();
+                row.add(&label);
+
+                add_css_provider!(&self.css_provider, row, label);
+
+                self.list.add(&row);
+            }
         }
 
+        // Keep all the items on one row.
+        let count = (items.len() as u32).max(1);
+        self.bar.set_min_children_per_line(count);
+        self.bar.set_max_children_per_line(count);
+
         self.list.show_all();
+        self.bar.show_all();
     }
 
     pub fn select(&mut self, item_num: i32) {
         self.state.borrow_mut().selected = item_num;
 
-        if item_num < 0 {
+        if self.horizontal {
+            if item_num < 0 {
+                self.bar.unselect_all();
+            } else if let Some(child) = self.bar.child_at_index(item_num) {
+                self.bar.select_child(&child);
+
+                // Scroll the selected item into view, once it has been
+                // allocated.
+                let adj = self.scrolled_bar.hadjustment();
+                glib::idle_add_local(move || {
+                    let alloc = child.allocation();
+                    adj.clamp_page(
+                        alloc.x as f64,
+                        (alloc.x + alloc.width) as f64,
+                    );
+                    Continue(false)
+                });
+            }
+        } else if item_num < 0 {
             self.list.unselect_all();
         } else if let Some(row) = self.list.row_at_index(item_num) {
             self.list.select_row(Some(&row));
@@ -161,14 +227,19 @@ impl Wildmenu {
                 border: none;
             }}
 
-            row {{
+            row, flowbox, flowboxchild {{
                 padding: 6px;
                 color: #{fg};
                 background-color: #{bg};
                 outline: none;
             }}
 
-            row:selected, row:selected > label {{
+            flowbox {{
+                padding: 0;
+            }}
+
+            row:selected, row:selected > label,
+            flowboxchild:selected, flowboxchild:selected > label {{
                 color: #{sel_fg};
                 background: #{sel_bg};
             }}",
@@ -181,3 +252,20 @@ impl Wildmenu {
             .unwrap();
     }
 }
+
+/// Tells nvim to select item `new`, by sending tabs to it.
+fn select_with_tabs(nvim: &GioNeovim, state: &RefCell<State>, new: i32) {
+    let prev = state.borrow().selected;
+
+    let op = if new > prev { "<Tab>" } else { "<S-Tab>" };
+
+    for _ in 0..(new - prev).abs() {
+        // NOTE(ville): nvim doesn't like single input with many
+        //              tabs in it, so we'll have to send each
+        //              individually.
+        let nvim = nvim.clone();
+        spawn_local(async move {
+            nvim.input(op).await.unwrap();
+        })
+    }
+}
@@ -1,8 +1,14 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk::glib;
 use gtk::prelude::*;
 
+use log::error;
 use nvim_rs::Window as NvimWindow;
 
-use crate::nvim_gio::GioWriter;
+use crate::nvim_gio::{GioNeovim, GioWriter};
+use crate::ui::common::spawn_local;
 use crate::ui::grid::Grid;
 
 pub struct MsgWindow {
@@ -78,14 +84,21 @@ pub struct Window {
     /// Currently shown grid's id.
     pub grid_id: i64,
     pub nvim_win: NvimWindow<GioWriter>,
+
+    /// Grid's enter event handler, for `'mousefocus'`.
+    enter_handler: Option<glib::SignalHandlerId>,
 }
 
 impl Window {
+    /// Creates new window for `grid`. When `mouse_focus` is set, the window
+    /// is made the current window once the mouse pointer enters it.
     pub fn new(
         win: NvimWindow<GioWriter>,
         fixed: gtk::Fixed,
         grid: &Grid,
         css_provider: Option<gtk::CssProvider>,
+        nvim: &GioNeovim,
+        mouse_focus: Rc<Cell<bool>>,
     ) -> Self {
         let frame = gtk::Frame::new(None);
         fixed.put(&frame, 0, 0);
@@ -93,6 +106,23 @@ impl Window {
         let widget = grid.widget();
         frame.add(&widget);
 
+        let value = win.get_value().clone();
+        let enter_handler = grid.connect_enter_events(clone!(nvim => move || {
+            if !mouse_focus.get() {
+                return;
+            }
+
+            let nvim = nvim.clone();
+            let value = value.clone();
+            spawn_local(async move {
+                if let Err(err) =
+                    nvim.call_function("gnvim#mouse_focus", vec![value]).await
+                {
+                    error!("Failed to focus window: {}", err);
+                }
+            });
+        }));
+
         if let Some(css_provider) = css_provider {
             add_css_provider!(&css_provider, frame);
         }
@@ -105,6 +135,7 @@ impl Window {
             nvim_win: win,
             x: 0.0,
             y: 0.0,
+            enter_handler: Some(enter_handler),
         }
     }
 
@@ -159,6 +190,16 @@ impl Window {
             .set_size_request(w.ceil() as i32, h.ceil() as i32);
     }
 
+    /// Container that the window is in. External windows are still
+    /// attached to their container, but aren't drawn in it.
+    pub fn container(&self) -> &gtk::Fixed {
+        &self.fixed
+    }
+
+    pub fn is_external(&self) -> bool {
+        self.external_win.is_some()
+    }
+
     pub fn show(&self) {
         self.frame.show_all();
     }
@@ -171,6 +212,11 @@ impl Window {
 impl Drop for Window {
     fn drop(&mut self) {
         if let Some(child) = self.frame.child() {
+            // The grid might outlive us.
+            if let Some(id) = self.enter_handler.take() {
+                child.disconnect(id);
+            }
+
             // We don't want to destroy the child widget, so just remove the child from our
             // container.
             self.frame.remove(&child);
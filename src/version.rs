@@ -0,0 +1,125 @@
+//! Version report (`--version`), with the versions of the libraries and
+//! Neovim that gnvim runs with, so issue reports have them in one place.
+
+use std::process::Command;
+
+use gtk::{cairo, pango};
+
+use crate::args::{self, Args};
+use crate::config::Config;
+use crate::nvim_bridge::NvimVersion;
+
+/// Version of the nvim started with `cmd`, from `nvim --api-info`.
+fn nvim_version(cmd: &[String]) -> Result<NvimVersion, String> {
+    let (bin, args) = cmd.split_first().ok_or("No command")?;
+    let output = Command::new(bin)
+        .args(args)
+        .arg("--api-info")
+        .output()
+        .map_err(|err| err.to_string())?;
+
+    if !output.status.success() {
+        return Err(format!("Exited with {}", output.status));
+    }
+
+    let metadata = rmpv::decode::read_value(&mut output.stdout.as_slice())
+        .map_err(|err| err.to_string())?;
+    NvimVersion::parse(&metadata)
+}
+
+fn format_report(
+    version: &str,
+    git_hash: &str,
+    libs: &[(&str, String)],
+    nvim_cmd: &[String],
+    nvim: Result<NvimVersion, String>,
+) -> String {
+    let mut report = format!("gnvim {} ({})\n", version, git_hash);
+
+    for (name, version) in libs.iter() {
+        report.push_str(&format!("{} {}\n", name, version));
+    }
+
+    let nvim_cmd = nvim_cmd.join(" ");
+    report.push_str(&match nvim {
+        Ok(nvim) => format!(
+            "Neovim {}, api level {} ({})\n",
+            nvim, nvim.api_level, nvim_cmd
+        ),
+        Err(err) => format!("Neovim: failed to run '{}': {}\n", nvim_cmd, err),
+    });
+
+    report
+}
+
+/// Version report of gnvim, the libraries it uses and the Neovim it would
+/// start.
+pub fn report(args: &Args, config: &Config) -> String {
+    let libs = [
+        (
+            "GTK",
+            format!(
+                "{}.{}.{}",
+                gtk::major_version(),
+                gtk::minor_version(),
+                gtk::micro_version()
+            ),
+        ),
+        ("Cairo", cairo::version_string().to_string()),
+        (
+            "Pango",
+            pango::version_string()
+                .map(|version| version.to_string())
+                .unwrap_or_default(),
+        ),
+    ];
+
+    let nvim_cmd = args.nvim_bin_cmd(config);
+    let nvim = nvim_version(&nvim_cmd);
+
+    format_report(args::version(), args::git_hash(), &libs, &nvim_cmd, nvim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_report() {
+        let libs = [
+            ("GTK", String::from("3.24.33")),
+            ("Cairo", String::from("1.16.0")),
+        ];
+        let nvim_cmd = vec![String::from("nvim")];
+
+        assert_eq!(
+            format_report(
+                "v0.1.6",
+                "abc1234",
+                &libs,
+                &nvim_cmd,
+                Ok(NvimVersion {
+                    major: 0,
+                    minor: 9,
+                    patch: 5,
+                    api_level: 11,
+                }),
+            ),
+            "gnvim v0.1.6 (abc1234)\n\
+             GTK 3.24.33\n\
+             Cairo 1.16.0\n\
+             Neovim 0.9.5, api level 11 (nvim)\n"
+        );
+
+        assert!(format_report(
+            "v0.1.6",
+            "abc1234",
+            &[],
+            &nvim_cmd,
+            Err(String::from("No such file or directory")),
+        )
+        .ends_with(
+            "Neovim: failed to run 'nvim': No such file or directory\n"
+        ));
+    }
+}